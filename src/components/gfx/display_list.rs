@@ -20,24 +20,38 @@ use text::TextRun;
 
 use extra::arc::Arc;
 use geom::{Point2D, Rect, Size2D, SideOffsets2D};
+use servo_msg::compositor_msg::{LayerId, OverlayLayerId, PageLayerId, ScrollLayerId};
 use servo_net::image::base::Image;
 use servo_util::geometry::Au;
 use servo_util::range::Range;
 use std::cast::transmute_region;
+use std::util;
 use std::vec::VecIterator;
 use style::computed_values::border_style;
 
 pub struct DisplayListCollection<E> {
-    lists: ~[DisplayList<E>]
+    lists: ~[DisplayList<E>],
+
+    /// The contents of the scrolling boxes painted by these lists, which are rendered into layers
+    /// of their own. Nested scrolling boxes are hoisted here too, so that every layer can be found
+    /// from the page's collection. Once `assign_layers` has run, the overlays are here as well,
+    /// and the layers are in compositing order.
+    scroll_layers: ~[ScrollLayer<E>],
 }
 
 impl<E> DisplayListCollection<E> {
     pub fn new() -> DisplayListCollection<E> {
         DisplayListCollection {
-            lists: ~[]
+            lists: ~[],
+            scroll_layers: ~[],
         }
     }
 
+    /// Returns the scrolling box layer with the given ID, if there is one.
+    pub fn scroll_layer<'a>(&'a self, id: LayerId) -> Option<&'a ScrollLayer<E>> {
+        self.scroll_layers.iter().find(|layer| layer.id == id)
+    }
+
     pub fn iter<'a>(&'a self) -> DisplayListIterator<'a,E> {
         ParentDisplayListIterator(self.lists.iter())
     }
@@ -46,6 +60,31 @@ impl<E> DisplayListCollection<E> {
         self.lists.push(list);
     }
 
+    /// Sorts the lists from `start` onwards, which were built for a box that paints atomically,
+    /// into CSS 2.1 Appendix E order, and moves them to `level` of the enclosing stacking context.
+    ///
+    /// If the box does not establish a stacking context of its own, the lists of its positioned
+    /// descendants belong to the enclosing stacking context, so they keep their levels and are
+    /// left after the box's own lists.
+    pub fn flatten_into_level(&mut self, start: uint, level: StackingLevel, stacking_context: bool) {
+        let mut lists = ~[];
+        while self.lists.len() > start {
+            lists.push(self.lists.pop());
+        }
+        lists.reverse();
+
+        let (mut own_lists, descendant_lists) = lists.partition(|list| {
+            stacking_context || !list.level.is_positioned()
+        });
+        own_lists.sort_by(|a, b| a.level.paint_order().cmp(&b.level.paint_order()));
+        for list in own_lists.mut_iter() {
+            list.level = level;
+        }
+
+        self.lists.push_all_move(own_lists);
+        self.lists.push_all_move(descendant_lists);
+    }
+
     pub fn draw_lists_into_context(&self, render_context: &mut RenderContext) {
         for list in self.lists.iter() {
             list.draw_into_context(render_context);
@@ -63,9 +102,259 @@ impl<E> DisplayListCollection<E> {
     }
 }
 
+impl<E:Clone> DisplayListCollection<E> {
+    /// Puts the layers of the scrolling boxes into compositing order, which is the order that
+    /// their boxes paint in (CSS 2.1 Appendix E), and moves whatever paints above a scrolling box
+    /// and overlaps it into an overlay layer that is composited above the box's layer. Compositing
+    /// the layers over each other then gives the same picture as painting everything in order.
+    ///
+    /// Afterwards every layer comes after its parent, and layers with the same parent are in
+    /// compositing order, from the bottom up.
+    pub fn assign_layers(&mut self) {
+        let mut unassigned = util::replace(&mut self.scroll_layers, ~[]);
+        let lists = util::replace(&mut self.lists, ~[]);
+        self.lists = self.assign_child_layers(PageLayerId, lists, &mut unassigned);
+
+        // Layers whose boxes didn't leave a placeholder go on top, as before.
+        self.scroll_layers.push_all_move(unassigned);
+    }
+
+    /// Splits `lists`, the lists of the layer `parent`, into what stays in that layer and the
+    /// overlays above its scrolling boxes. Adds the layers above `parent` to `scroll_layers` in
+    /// compositing order, each followed by the layers above it, and returns what stays.
+    fn assign_child_layers(&mut self,
+                           parent: LayerId,
+                           lists: ~[DisplayList<E>],
+                           unassigned: &mut ~[ScrollLayer<E>])
+                           -> ~[DisplayList<E>] {
+        let mut splitter = LayerSplitter {
+            parent: parent,
+            covered: ~[],
+            overlay: None,
+            layers: ~[],
+        };
+        let lists: ~[DisplayList<E>] = lists.move_iter().map(|list| {
+            DisplayList {
+                list: splitter.split_items(list.list, &mut ~[]),
+                level: list.level,
+            }
+        }).collect();
+        splitter.finish_overlay();
+
+        for layer in splitter.layers.move_iter() {
+            match layer {
+                OverlayChildLayer(overlay) => self.scroll_layers.push(overlay),
+                ScrollChildLayer(id) => {
+                    let mut layer = match unassigned.iter().position(|layer| layer.id == id) {
+                        Some(i) => unassigned.remove(i),
+                        None => continue,
+                    };
+                    let lists = util::replace(&mut layer.display_list_collection.lists, ~[]);
+                    let index = self.scroll_layers.len();
+                    self.scroll_layers.push(layer);
+                    let lists = self.assign_child_layers(id, lists, unassigned);
+                    self.scroll_layers[index].display_list_collection.lists = lists;
+                }
+            }
+        }
+        lists
+    }
+}
+
+/// A layer composited above another one, in the order that `assign_layers` found them.
+enum ChildLayer<E> {
+    /// The layer of a scrolling box.
+    ScrollChildLayer(LayerId),
+    /// An overlay.
+    OverlayChildLayer(ScrollLayer<E>),
+}
+
+/// Splits the items of a layer into those that stay in it and the overlays above its scrolling
+/// boxes, going through them in painting order.
+struct LayerSplitter<E> {
+    /// The layer being split.
+    parent: LayerId,
+
+    /// The areas covered by the layers above `parent` found so far. Whatever paints after them
+    /// and overlaps them has to go into an overlay.
+    covered: ~[Rect<Au>],
+
+    /// The overlay being gathered above the last scrolling box: its ID, its items and their
+    /// bounds.
+    overlay: Option<(LayerId, ~[DisplayItem<E>], Option<Rect<Au>>)>,
+
+    /// The layers above `parent`, in compositing order.
+    layers: ~[ChildLayer<E>],
+}
+
+impl<E:Clone> LayerSplitter<E> {
+    /// Returns the items of `items` that stay in the layer, moving the others into the overlay.
+    /// `clips` holds the bounds of the clip items around `items` that clip, and their extra data.
+    fn split_items(&mut self, items: ~[DisplayItem<E>], clips: &mut ~[(Rect<Au>, E)])
+                   -> ~[DisplayItem<E>] {
+        let mut kept = ~[];
+        for item in items.move_iter() {
+            match item {
+                ClipDisplayItemClass(mut clip) => {
+                    let child_list = util::replace(&mut clip.child_list, ~[]);
+                    if clip.need_clip {
+                        clips.push((clip.base.bounds, clip.base.extra.clone()));
+                    }
+                    clip.child_list = self.split_items(child_list, clips);
+                    if clip.need_clip {
+                        clips.pop();
+                    }
+                    kept.push(ClipDisplayItemClass(clip))
+                }
+                ScrollLayerDisplayItemClass(placeholder) => {
+                    self.finish_overlay();
+                    let overlay_id = match placeholder.layer {
+                        ScrollLayerId(id) => OverlayLayerId(id),
+                        PageLayerId | OverlayLayerId(..) => {
+                            fail!("placeholder for a layer that isn't a scrolling box's")
+                        }
+                    };
+                    self.layers.push(ScrollChildLayer(placeholder.layer));
+                    self.covered.push(placeholder.base.bounds);
+                    self.overlay = Some((overlay_id, ~[], None));
+                    kept.push(ScrollLayerDisplayItemClass(placeholder))
+                }
+                item => {
+                    let bounds = item.bounds();
+                    if !self.covered.iter().any(|rect| rect.intersects(&bounds)) {
+                        kept.push(item);
+                        continue
+                    }
+
+                    // The item keeps the clips around it in the overlay.
+                    let mut item = item;
+                    for &(clip_bounds, ref extra) in clips.rev_iter() {
+                        item = ClipDisplayItemClass(~ClipDisplayItem {
+                            base: BaseDisplayItem {
+                                bounds: clip_bounds,
+                                extra: extra.clone(),
+                            },
+                            child_list: ~[item],
+                            need_clip: true,
+                        });
+                    }
+                    self.covered.push(bounds);
+                    match self.overlay {
+                        Some((_, ref mut items, ref mut overlay_bounds)) => {
+                            items.push(item);
+                            *overlay_bounds = match *overlay_bounds {
+                                Some(overlay_bounds) => Some(overlay_bounds.union(&bounds)),
+                                None => Some(bounds),
+                            };
+                        }
+                        None => fail!("covered area without a scrolling box"),
+                    }
+                }
+            }
+        }
+        kept
+    }
+
+    /// Adds the overlay being gathered to the layers, if anything went into it. Its items are
+    /// moved into its own coordinates.
+    fn finish_overlay(&mut self) {
+        match self.overlay.take() {
+            Some((id, mut items, Some(bounds))) => {
+                let delta = Point2D(Au(0), Au(0)) - bounds.origin;
+                for item in items.mut_iter() {
+                    item.translate(&delta);
+                }
+                let mut display_list_collection = DisplayListCollection::new();
+                display_list_collection.add_list(DisplayList {
+                    list: items,
+                    level: ContentStackingLevel,
+                });
+                self.layers.push(OverlayChildLayer(ScrollLayer {
+                    id: id,
+                    parent: self.parent,
+                    clip_rect: bounds,
+                    size: bounds.size,
+                    scroll_offset: Point2D(Au(0), Au(0)),
+                    display_list_collection: display_list_collection,
+                }))
+            }
+            Some((_, _, None)) | None => {}
+        }
+    }
+}
+
+/// The display lists of the contents of a box with `overflow: scroll` or `auto`. The compositor
+/// gives them a layer of their own, so that they can scroll independently of the page. The layer
+/// is transparent: the box's background is painted in the parent layer and shows through it.
+///
+/// Overlays, which hold what paints above the contents of a scrolling box and overlaps them, are
+/// layers of this kind too. They are exactly as big as their contents, so they can't scroll.
+pub struct ScrollLayer<E> {
+    /// The layer of the box's contents, or of the overlay.
+    id: LayerId,
+
+    /// The layer that the box itself, with its background and borders, is painted in.
+    parent: LayerId,
+
+    /// The padding box of the box, or the area of the overlay, in the coordinates of the parent
+    /// layer.
+    clip_rect: Rect<Au>,
+
+    /// The size of the contents, which are laid out from the top left corner of the padding box.
+    size: Size2D<Au>,
+
+    /// How far the contents are scrolled.
+    scroll_offset: Point2D<Au>,
+
+    /// The contents, in the coordinates of this layer.
+    display_list_collection: DisplayListCollection<E>,
+}
+
+/// Where a display list paints within its stacking context. The levels follow the painting order
+/// of CSS 2.1 Appendix E.
+#[deriving(Clone, Eq)]
+pub enum StackingLevel {
+    /// The background and borders of the box that establishes the stacking context.
+    BackgroundAndBordersStackingLevel,
+    /// The backgrounds and borders of in-flow, non-positioned, block-level descendants.
+    BlockBackgroundsAndBordersStackingLevel,
+    /// Non-positioned floats, each painted atomically.
+    FloatStackingLevel,
+    /// Inline content, such as text and replaced elements.
+    ContentStackingLevel,
+    /// Positioned descendants and child stacking contexts with the given z-index. Positioned
+    /// descendants with `z-index: auto` use zero.
+    PositionedStackingLevel(i32),
+}
+
+impl StackingLevel {
+    pub fn is_positioned(&self) -> bool {
+        match *self {
+            PositionedStackingLevel(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns a key that sorts the levels into painting order. Negative z-indices paint just
+    /// after the stacking context's own background; the rest paint after the inline content.
+    fn paint_order(&self) -> (i32, i32) {
+        match *self {
+            BackgroundAndBordersStackingLevel => (0, 0),
+            PositionedStackingLevel(z_index) if z_index < 0 => (1, z_index),
+            BlockBackgroundsAndBordersStackingLevel => (2, 0),
+            FloatStackingLevel => (3, 0),
+            ContentStackingLevel => (4, 0),
+            PositionedStackingLevel(z_index) => (5, z_index),
+        }
+    }
+}
+
 /// A list of rendering operations to be performed.
 pub struct DisplayList<E> {
-    list: ~[DisplayItem<E>]
+    list: ~[DisplayItem<E>],
+
+    /// Where this list paints within its stacking context.
+    level: StackingLevel,
 }
 
 pub enum DisplayListIterator<'a,E> {
@@ -86,8 +375,14 @@ impl<'a,E> Iterator<&'a DisplayList<E>> for DisplayListIterator<'a,E> {
 impl<E> DisplayList<E> {
     /// Creates a new display list.
     pub fn new() -> DisplayList<E> {
+        DisplayList::new_at_level(BlockBackgroundsAndBordersStackingLevel)
+    }
+
+    /// Creates a new display list that paints at the given level of its stacking context.
+    pub fn new_at_level(level: StackingLevel) -> DisplayList<E> {
         DisplayList {
-            list: ~[]
+            list: ~[],
+            level: level,
         }
     }
 
@@ -127,10 +422,12 @@ pub enum DisplayItem<E> {
     TextDisplayItemClass(~TextDisplayItem<E>),
     ImageDisplayItemClass(~ImageDisplayItem<E>),
     BorderDisplayItemClass(~BorderDisplayItem<E>),
-    ClipDisplayItemClass(~ClipDisplayItem<E>)
+    ClipDisplayItemClass(~ClipDisplayItem<E>),
+    ScrollLayerDisplayItemClass(~ScrollLayerDisplayItem<E>),
 }
 
 /// Information common to all display items.
+#[deriving(Clone)]
 pub struct BaseDisplayItem<E> {
     /// The boundaries of the display item.
     ///
@@ -158,6 +455,9 @@ pub struct TextDisplayItem<E> {
     /// The range of text within the text run.
     range: Range,
 
+    /// The space that justification adds after each word separator in the range.
+    extra_word_spacing: Au,
+
     /// The color of the text.
     text_color: Color,
 
@@ -190,6 +490,10 @@ bitfield!(TextDisplayItemFlags, override_line_through, set_override_line_through
 pub struct ImageDisplayItem<E> {
     base: BaseDisplayItem<E>,
     image: Arc<~Image>,
+
+    /// The size to draw the image at. If this is smaller than the bounds, the image is tiled
+    /// across them, starting from their origin.
+    stretch_size: Size2D<Au>,
 }
 
 /// Renders a border.
@@ -212,6 +516,16 @@ pub struct ClipDisplayItem<E> {
     need_clip: bool
 }
 
+/// Marks where the contents of a scrolling box paint among the items of the layer that the box is
+/// in. The contents are painted in a layer of their own, so this item draws nothing.
+pub struct ScrollLayerDisplayItem<E> {
+    /// The bounds are those of the padding box of the scrolling box.
+    base: BaseDisplayItem<E>,
+
+    /// The layer of the box's contents.
+    layer: LayerId,
+}
+
 pub enum DisplayItemIterator<'a,E> {
     EmptyDisplayItemIterator,
     ParentDisplayItemIterator(VecIterator<'a,DisplayItem<E>>),
@@ -252,20 +566,39 @@ impl<E> DisplayItem<E> {
 
                 // FIXME(pcwalton): Allocating? Why?
                 let text_run = text.text_run.get();
-                let font = render_context.font_ctx.get_font_by_descriptor(&text_run.font_descriptor).unwrap();
-
-                let font_metrics = font.borrow().with(|font| {
-                    font.metrics.clone()
-                });
+                let font_metrics = &text_run.font_metrics;
                 let origin = text.base.bounds.origin;
-                let baseline_origin = Point2D(origin.x, origin.y + font_metrics.ascent);
-                font.borrow().with_mut(|font| {
-                    font.draw_text_into_context(render_context,
-                                                text.text_run.get(),
-                                                &text.range,
-                                                baseline_origin,
-                                                text.text_color);
-                });
+                let ascent = text_run.metrics_for_range(&text.range).ascent;
+                let baseline_origin = Point2D(origin.x, origin.y + ascent);
+
+                // Draw the parts of the run that fell back to other fonts with those fonts. In
+                // right-to-left runs, they go from right to left.
+                let mut font_origin = baseline_origin;
+                if text_run.is_rtl {
+                    let advance = text_run.justified_advance_for_range(&text.range,
+                                                                       text.extra_word_spacing);
+                    font_origin = Point2D(font_origin.x + advance, font_origin.y);
+                }
+                for &(font_index, ref range) in text_run.font_ranges_for_range(&text.range).iter() {
+                    let descriptor = &text_run.font_descriptors[font_index];
+                    let font = render_context.font_ctx.get_font_by_descriptor(descriptor).unwrap();
+                    let advance = text_run.justified_advance_for_range(range,
+                                                                       text.extra_word_spacing);
+                    if text_run.is_rtl {
+                        font_origin = Point2D(font_origin.x - advance, font_origin.y);
+                    }
+                    font.borrow().with_mut(|font| {
+                        font.draw_text_into_context(render_context,
+                                                    text.text_run.get(),
+                                                    range,
+                                                    font_origin,
+                                                    text.text_color,
+                                                    text.extra_word_spacing);
+                    });
+                    if !text_run.is_rtl {
+                        font_origin = Point2D(font_origin.x + advance, font_origin.y);
+                    }
+                }
                 let width = text.base.bounds.size.width;
                 let underline_size = font_metrics.underline_size;
                 let underline_offset = font_metrics.underline_offset;
@@ -294,7 +627,9 @@ impl<E> DisplayItem<E> {
             ImageDisplayItemClass(ref image_item) => {
                 debug!("Drawing image at {:?}.", image_item.base.bounds);
 
-                render_context.draw_image(image_item.base.bounds, image_item.image.clone())
+                render_context.draw_image(image_item.base.bounds,
+                                          image_item.stretch_size,
+                                          image_item.image.clone())
             }
 
             BorderDisplayItemClass(ref border) => {
@@ -303,6 +638,8 @@ impl<E> DisplayItem<E> {
                                            border.color,
                                            border.style)
             }
+
+            ScrollLayerDisplayItemClass(_) => {}
         }
     }
 
@@ -315,7 +652,28 @@ impl<E> DisplayItem<E> {
                 ImageDisplayItemClass(ref image_item) => transmute_region(&image_item.base),
                 BorderDisplayItemClass(ref border) => transmute_region(&border.base),
                 ClipDisplayItemClass(ref clip) => transmute_region(&clip.base),
+                ScrollLayerDisplayItemClass(ref placeholder) => {
+                    transmute_region(&placeholder.base)
+                }
+            }
+        }
+    }
+
+    /// Moves this display item and its children by `delta`.
+    fn translate(&mut self, delta: &Point2D<Au>) {
+        let bounds = self.bounds().translate(delta);
+        match *self {
+            SolidColorDisplayItemClass(ref mut solid_color) => solid_color.base.bounds = bounds,
+            TextDisplayItemClass(ref mut text) => text.base.bounds = bounds,
+            ImageDisplayItemClass(ref mut image_item) => image_item.base.bounds = bounds,
+            BorderDisplayItemClass(ref mut border) => border.base.bounds = bounds,
+            ClipDisplayItemClass(ref mut clip) => {
+                clip.base.bounds = bounds;
+                for item in clip.child_list.mut_iter() {
+                    item.translate(delta);
+                }
             }
+            ScrollLayerDisplayItemClass(ref mut placeholder) => placeholder.base.bounds = bounds,
         }
     }
 
@@ -329,7 +687,8 @@ impl<E> DisplayItem<E> {
             SolidColorDisplayItemClass(..) |
             TextDisplayItemClass(..) |
             ImageDisplayItemClass(..) |
-            BorderDisplayItemClass(..) => EmptyDisplayItemIterator,
+            BorderDisplayItemClass(..) |
+            ScrollLayerDisplayItemClass(..) => EmptyDisplayItemIterator,
         }
     }
 
@@ -351,8 +710,232 @@ impl<E> DisplayItem<E> {
             ImageDisplayItemClass(_) => "Image",
             BorderDisplayItemClass(_) => "Border",
             ClipDisplayItemClass(_) => "Clip",
+            ScrollLayerDisplayItemClass(_) => "ScrollLayer",
         };
         format!("{} @ {:?}", class, self.base().bounds)
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::{BackgroundAndBordersStackingLevel, BaseDisplayItem};
+    use super::{BlockBackgroundsAndBordersStackingLevel, ClipDisplayItem, ClipDisplayItemClass};
+    use super::{ContentStackingLevel, DisplayItem, DisplayList, DisplayListCollection};
+    use super::{FloatStackingLevel, PositionedStackingLevel, ScrollLayer, ScrollLayerDisplayItem};
+    use super::{ScrollLayerDisplayItemClass, SolidColorDisplayItem, SolidColorDisplayItemClass};
+    use super::{StackingLevel};
+
+    use color::rgb;
+    use geom::{Point2D, Rect, Size2D};
+    use servo_msg::compositor_msg::{LayerId, OverlayLayerId, PageLayerId, ScrollLayerId};
+    use servo_util::geometry::Au;
+
+    fn collection(levels: &[StackingLevel]) -> DisplayListCollection<()> {
+        let mut collection = DisplayListCollection::new();
+        for level in levels.iter() {
+            collection.add_list(DisplayList::new_at_level(*level));
+        }
+        collection
+    }
+
+    fn levels(collection: &DisplayListCollection<()>) -> ~[StackingLevel] {
+        collection.lists.iter().map(|list| list.level).collect()
+    }
+
+    #[test]
+    fn test_paint_order() {
+        let mut levels = ~[
+            PositionedStackingLevel(2),
+            ContentStackingLevel,
+            PositionedStackingLevel(-1),
+            FloatStackingLevel,
+            PositionedStackingLevel(0),
+            BlockBackgroundsAndBordersStackingLevel,
+            PositionedStackingLevel(-3),
+            BackgroundAndBordersStackingLevel,
+        ];
+        levels.sort_by(|a, b| a.paint_order().cmp(&b.paint_order()));
+        assert_eq!(levels, ~[
+            BackgroundAndBordersStackingLevel,
+            PositionedStackingLevel(-3),
+            PositionedStackingLevel(-1),
+            BlockBackgroundsAndBordersStackingLevel,
+            FloatStackingLevel,
+            ContentStackingLevel,
+            PositionedStackingLevel(0),
+            PositionedStackingLevel(2),
+        ]);
+    }
+
+    #[test]
+    fn test_flatten_stacking_context() {
+        let mut lists = collection([
+            ContentStackingLevel,
+            BackgroundAndBordersStackingLevel,
+            ContentStackingLevel,
+            PositionedStackingLevel(-1),
+        ]);
+        lists.flatten_into_level(1, PositionedStackingLevel(3), true);
+        assert_eq!(levels(&lists), ~[
+            ContentStackingLevel,
+            PositionedStackingLevel(3),
+            PositionedStackingLevel(3),
+            PositionedStackingLevel(3),
+        ]);
+    }
+
+    #[test]
+    fn test_flatten_without_stacking_context() {
+        // The positioned descendants belong to the enclosing stacking context, so they keep their
+        // levels and follow the lists of the box itself.
+        let mut lists = collection([
+            BackgroundAndBordersStackingLevel,
+            PositionedStackingLevel(-1),
+            ContentStackingLevel,
+            PositionedStackingLevel(2),
+        ]);
+        lists.flatten_into_level(0, FloatStackingLevel, false);
+        assert_eq!(levels(&lists), ~[
+            FloatStackingLevel,
+            FloatStackingLevel,
+            PositionedStackingLevel(-1),
+            PositionedStackingLevel(2),
+        ]);
+    }
+
+    fn rect(x: int, y: int, width: int, height: int) -> Rect<Au> {
+        Rect(Point2D(Au::from_px(x), Au::from_px(y)),
+             Size2D(Au::from_px(width), Au::from_px(height)))
+    }
+
+    fn solid_color(bounds: Rect<Au>) -> DisplayItem<()> {
+        SolidColorDisplayItemClass(~SolidColorDisplayItem {
+            base: BaseDisplayItem {
+                bounds: bounds,
+                extra: (),
+            },
+            color: rgb(0, 0, 0),
+        })
+    }
+
+    fn clip(bounds: Rect<Au>, child_list: ~[DisplayItem<()>]) -> DisplayItem<()> {
+        ClipDisplayItemClass(~ClipDisplayItem {
+            base: BaseDisplayItem {
+                bounds: bounds,
+                extra: (),
+            },
+            child_list: child_list,
+            need_clip: true,
+        })
+    }
+
+    fn placeholder(layer: LayerId, bounds: Rect<Au>) -> DisplayItem<()> {
+        ScrollLayerDisplayItemClass(~ScrollLayerDisplayItem {
+            base: BaseDisplayItem {
+                bounds: bounds,
+                extra: (),
+            },
+            layer: layer,
+        })
+    }
+
+    fn scroll_layer(id: LayerId, parent: LayerId, clip_rect: Rect<Au>, items: ~[DisplayItem<()>])
+                    -> ScrollLayer<()> {
+        let mut display_list_collection = DisplayListCollection::new();
+        display_list_collection.add_list(DisplayList {
+            list: items,
+            level: ContentStackingLevel,
+        });
+        ScrollLayer {
+            id: id,
+            parent: parent,
+            clip_rect: clip_rect,
+            size: clip_rect.size,
+            scroll_offset: Point2D(Au(0), Au(0)),
+            display_list_collection: display_list_collection,
+        }
+    }
+
+    fn bounds(items: &[DisplayItem<()>]) -> ~[Rect<Au>] {
+        items.iter().map(|item| item.bounds()).collect()
+    }
+
+    #[test]
+    fn test_assign_layers() {
+        // A box scrolls inside a clipping box. After the scrolling box, the clipping box paints an
+        // item that overlaps it and another one that doesn't, and the page paints one more item.
+        let mut lists = DisplayListCollection::new();
+        lists.add_list(DisplayList {
+            list: ~[
+                clip(rect(0, 0, 100, 100), ~[
+                    solid_color(rect(0, 0, 100, 100)),
+                    placeholder(ScrollLayerId(1), rect(10, 10, 50, 50)),
+                    solid_color(rect(5, 5, 20, 20)),
+                    solid_color(rect(80, 80, 10, 10)),
+                ]),
+                solid_color(rect(20, 20, 100, 10)),
+            ],
+            level: ContentStackingLevel,
+        });
+        lists.scroll_layers = ~[
+            scroll_layer(ScrollLayerId(2), ScrollLayerId(1), rect(0, 0, 10, 10), ~[]),
+            scroll_layer(ScrollLayerId(1), PageLayerId, rect(10, 10, 50, 50), ~[
+                placeholder(ScrollLayerId(2), rect(0, 0, 10, 10)),
+            ]),
+        ];
+        lists.assign_layers();
+
+        // Parents come before their children, and the overlay above the scrolling box comes after
+        // it and the box nested inside it.
+        let ids: ~[LayerId] = lists.scroll_layers.iter().map(|layer| layer.id).collect();
+        assert_eq!(ids, ~[ScrollLayerId(1), ScrollLayerId(2), OverlayLayerId(1)]);
+
+        // The page keeps what paints below the scrolling box or doesn't overlap anything above it.
+        let page = &lists.lists[0].list;
+        assert_eq!(bounds(*page), ~[rect(0, 0, 100, 100)]);
+        match page[0] {
+            ClipDisplayItemClass(ref clip) => {
+                assert_eq!(bounds(clip.child_list),
+                           ~[rect(0, 0, 100, 100), rect(10, 10, 50, 50), rect(80, 80, 10, 10)]);
+            }
+            _ => fail!("the clip item left the page"),
+        }
+
+        // The overlay holds the items that overlap the scrolling box, or each other, in its own
+        // coordinates. The item from inside the clipping box is still clipped by it.
+        let overlay = &lists.scroll_layers[2];
+        assert_eq!(overlay.parent, PageLayerId);
+        assert_eq!(overlay.clip_rect, rect(5, 5, 115, 25));
+        assert_eq!(overlay.size, overlay.clip_rect.size);
+        let overlay_items = &overlay.display_list_collection.lists[0].list;
+        assert_eq!(bounds(*overlay_items), ~[rect(-5, -5, 100, 100), rect(15, 15, 100, 10)]);
+        match overlay_items[0] {
+            ClipDisplayItemClass(ref clip) => {
+                assert_eq!(bounds(clip.child_list), ~[rect(0, 0, 20, 20)]);
+            }
+            _ => fail!("the overlaid item lost its clip"),
+        }
+    }
+
+    #[test]
+    fn test_assign_layers_without_overlap() {
+        // Nothing paints over the scrolling box, so there is no overlay.
+        let mut lists = DisplayListCollection::new();
+        lists.add_list(DisplayList {
+            list: ~[
+                solid_color(rect(0, 0, 10, 10)),
+                placeholder(ScrollLayerId(1), rect(10, 10, 50, 50)),
+                solid_color(rect(60, 60, 10, 10)),
+            ],
+            level: ContentStackingLevel,
+        });
+        lists.scroll_layers = ~[
+            scroll_layer(ScrollLayerId(1), PageLayerId, rect(10, 10, 50, 50), ~[]),
+        ];
+        lists.assign_layers();
+
+        assert_eq!(lists.scroll_layers.len(), 1);
+        assert_eq!(bounds(lists.lists[0].list),
+                   ~[rect(0, 0, 10, 10), rect(10, 10, 50, 50), rect(60, 60, 10, 10)]);
+    }
+}
@@ -14,7 +14,7 @@ use std::rc::Rc;
 use std::cell::RefCell;
 use servo_util::cache::{Cache, HashCache};
 use servo_util::range::Range;
-use style::computed_values::{text_decoration, font_weight, font_style};
+use style::computed_values::{text_decoration, font_weight, font_style, word_break};
 
 use color::Color;
 use font_context::FontContext;
@@ -25,6 +25,7 @@ use render_context::RenderContext;
 use text::glyph::{GlyphStore, GlyphIndex};
 use text::shaping::ShaperMethods;
 use text::{Shaper, TextRun};
+use text::text_run::is_word_separator;
 
 // FontHandle encapsulates access to the platform's font API,
 // e.g. quartz, FreeType. It provides access to metrics and tables
@@ -162,13 +163,14 @@ impl FontGroup {
         self.fonts = ~[];
     }
 
-    pub fn create_textrun(&self, text: ~str, decoration: text_decoration::T) -> TextRun {
+    pub fn create_textrun(&self,
+                          text: ~str,
+                          decoration: text_decoration::T,
+                          word_break: word_break::T,
+                          is_rtl: bool)
+                          -> TextRun {
         assert!(self.fonts.len() > 0);
-
-        // TODO(Issue #177): Actually fall back through the FontGroup when a font is unsuitable.
-        self.fonts[0].borrow().with_mut(|font| {
-            TextRun::new(font, text.clone(), decoration)
-        })
+        TextRun::new(self.fonts, text, decoration, word_break, is_rtl)
     }
 }
 
@@ -211,7 +213,7 @@ pub struct Font {
     style: UsedFontStyle,
     metrics: FontMetrics,
     backend: BackendType,
-    shape_cache: HashCache<~str, Arc<GlyphStore>>,
+    shape_cache: HashCache<(~str, bool), Arc<GlyphStore>>,
     glyph_advance_cache: HashCache<u32, FractionalPixel>,
 }
 
@@ -342,7 +344,8 @@ impl Font {
                               run: &~TextRun,
                               range: &Range,
                               baseline_origin: Point2D<Au>,
-                              color: Color) {
+                              color: Color,
+                              extra_word_spacing: Au) {
         use std::libc::types::common::c99::{uint16_t, uint32_t};
         use azure::{struct__AzDrawOptions,
                     struct__AzGlyph,
@@ -361,25 +364,54 @@ impl Font {
             fields: 0x0200 as uint16_t
         };
 
+        // Glyphs are laid out in logical order. Right-to-left runs are then mirrored within the
+        // width of the range, so that the first character ends up on the right.
+        let rtl_width = if run.is_rtl {
+            run.justified_advance_for_range(range, extra_word_spacing)
+        } else {
+            Au(0)
+        };
+
         let mut origin = baseline_origin.clone();
         let mut azglyphs = ~[];
         azglyphs.reserve(range.length());
 
+        let has_spacing = run.letter_spacing != Au(0) || run.word_spacing != Au(0) ||
+            extra_word_spacing != Au(0);
+
         for (glyphs, _offset, slice_range) in run.iter_slices_for_range(range) {
-            for (_i, glyph) in glyphs.iter_glyphs_for_char_range(&slice_range) {
-                let glyph_advance = glyph.advance();
-                let glyph_offset = glyph.offset().unwrap_or(Au::zero_point());
-
-                let azglyph = struct__AzGlyph {
-                    mIndex: glyph.index() as uint32_t,
-                    mPosition: struct__AzPoint {
-                        x: (origin.x + glyph_offset.x).to_nearest_px() as AzFloat,
-                        y: (origin.y + glyph_offset.y).to_nearest_px() as AzFloat
+            for char_i in slice_range.eachi() {
+                for (_i, glyph) in glyphs.iter_glyphs_for_char_index(char_i) {
+                    let glyph_advance = glyph.advance();
+                    let glyph_offset = glyph.offset().unwrap_or(Au::zero_point());
+                    let glyph_x = if run.is_rtl {
+                        baseline_origin.x + rtl_width - (origin.x - baseline_origin.x) -
+                            glyph_advance
+                    } else {
+                        origin.x
+                    };
+
+                    let azglyph = struct__AzGlyph {
+                        mIndex: glyph.index() as uint32_t,
+                        mPosition: struct__AzPoint {
+                            x: (glyph_x + glyph_offset.x).to_nearest_px() as AzFloat,
+                            y: (origin.y + glyph_offset.y).to_nearest_px() as AzFloat
+                        }
+                    };
+                    origin = Point2D(origin.x + glyph_advance, origin.y);
+                    azglyphs.push(azglyph)
+                }
+
+                // Letter and word spacing go after the last glyph of each character, and so does
+                // the space that justification adds to word separators.
+                if has_spacing {
+                    let mut spacing = run.spacing_after_char(glyphs, char_i);
+                    if glyphs.char_is_space(char_i) {
+                        spacing = spacing + extra_word_spacing
                     }
-                };
-                origin = Point2D(origin.x + glyph_advance, origin.y);
-                azglyphs.push(azglyph)
-            };
+                    origin = Point2D(origin.x + spacing, origin.y);
+                }
+            }
         }
 
         let azglyph_buf_len = azglyphs.len();
@@ -424,13 +456,19 @@ impl Font {
         RunMetrics::new(advance, self.metrics.ascent, self.metrics.descent)
     }
 
-    pub fn shape_text(&mut self, text: ~str, is_whitespace: bool) -> Arc<GlyphStore> {
+    pub fn shape_text(&mut self, text: ~str, is_whitespace: bool, is_rtl: bool)
+                      -> Arc<GlyphStore> {
 
         //FIXME (ksh8281)
         self.make_shaper();
-        self.shape_cache.find_or_create(&text, |txt| {
-            let mut glyphs = GlyphStore::new(text.char_len(), is_whitespace);
-            self.shaper.get_ref().shape_text(*txt, &mut glyphs);
+        self.shape_cache.find_or_create(&(text, is_rtl), |&(ref txt, is_rtl)| {
+            let mut glyphs = GlyphStore::new(txt.char_len(), is_whitespace);
+            self.shaper.get_ref().shape_text(*txt, is_rtl, &mut glyphs);
+            for (i, c) in txt.chars().enumerate() {
+                if is_word_separator(c) {
+                    glyphs.set_char_is_space(i)
+                }
+            }
             Arc::new(glyphs)
         })
     }
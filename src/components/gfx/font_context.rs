@@ -158,39 +158,44 @@ impl FontContext {
             }
         }
 
-        if fonts.len() == 0 {
-            let last_resort = FontList::get_last_resort_font_families();
-            for family in last_resort.iter() {
-                let font_desc = match self.font_list {
-                    Some(ref mut font_list) => {
-                        let font_desc = {
-                            let font_entry = font_list.find_font_in_family(family, style);
-                            match font_entry {
-                                Some(v) => {
-                                    let font_id =
-                                        SelectorPlatformIdentifier(v.handle.face_identifier());
-                                    Some(FontDescriptor::new((*style).clone(), font_id))
-                                },
-                                None => {
-                                    None
-                                }
+        // The last resort fonts go at the end of every group, so that characters missing from the
+        // fonts that the style asks for can fall back to them.
+        let last_resort = FontList::get_last_resort_font_families();
+        for family in last_resort.iter() {
+            let font_desc = match self.font_list {
+                Some(ref mut font_list) => {
+                    let font_desc = {
+                        let font_entry = font_list.find_font_in_family(family, style);
+                        match font_entry {
+                            Some(v) => {
+                                let font_id =
+                                    SelectorPlatformIdentifier(v.handle.face_identifier());
+                                Some(FontDescriptor::new((*style).clone(), font_id))
+                            },
+                            None => {
+                                None
                             }
-                        };
-                        font_desc
-                    },
-                    None => {
-                        None
-                    }
-                };
+                        }
+                    };
+                    font_desc
+                },
+                None => {
+                    None
+                }
+            };
 
-                match font_desc {
-                    Some(ref fd) => {
+            match font_desc {
+                Some(ref fd) => {
+                    let in_group = fonts.iter().any(|font| {
+                        font.borrow().with(|font| font.get_descriptor() == *fd)
+                    });
+                    if !in_group {
                         let instance = self.get_font_by_descriptor(fd);
                         instance.map(|font| fonts.push(font.clone()));
-                    },
-                    None => { }
-                };
-            }
+                    }
+                },
+                None => { }
+            };
         }
         assert!(fonts.len() > 0);
         // TODO(Issue #179): Split FontStyle into specified and used styles
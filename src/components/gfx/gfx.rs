@@ -46,6 +46,7 @@ mod render_context;
 // Rendering
 pub mod color;
 pub mod display_list;
+pub mod print;
 pub mod render_task;
 
 // Fonts
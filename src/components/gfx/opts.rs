@@ -8,6 +8,7 @@
 use azure::azure_hl::{BackendType, CairoBackend, CoreGraphicsBackend};
 use azure::azure_hl::{CoreGraphicsAcceleratedBackend, Direct2DBackend, SkiaBackend};
 use extra::getopts::groups;
+use geom::size::Size2D;
 use std::num;
 use std::rt;
 
@@ -44,10 +45,34 @@ pub struct Opts {
     exit_after_load: bool,
 
     output_file: Option<~str>,
+
+    /// The directory in which to persist the HTTP cache (`--http-cache-dir`). If absent, the
+    /// cache is kept in memory only.
+    http_cache_dir: Option<~str>,
+
+    /// `Some` with a path to lay the page out with print media and write it out paginated once it
+    /// has loaded (`--print`). Paths ending in `.pdf` produce a single multi-page PDF; anything
+    /// else produces one PNG per page, numbered from 1 (`out.png` becomes `out-1.png`, ...).
+    print_output: Option<~str>,
+
+    /// The size of each printed page in pixels (`--page-size`). Defaults to US Letter at 96 DPI.
+    page_size: Size2D<uint>,
+
     headless: bool,
     hard_fail: bool,
 }
 
+/// Parses a page size of the form `WIDTHxHEIGHT`, in pixels.
+fn parse_page_size(page_size: &str) -> Option<Size2D<uint>> {
+    let dimensions: ~[Option<uint>] = page_size.split('x').map(|dimension| {
+        from_str(dimension)
+    }).collect();
+    match dimensions.as_slice() {
+        [Some(width), Some(height)] if width > 0 && height > 0 => Some(Size2D(width, height)),
+        _ => None,
+    }
+}
+
 fn print_usage(app: &str, opts: &[groups::OptGroup]) {
     let message = format!("Usage: {} [ options ... ] [URL]\n\twhere options include", app);
     println(groups::usage(message, opts));
@@ -67,6 +92,9 @@ pub fn from_cmdline_args(args: &[~str]) -> Opts {
         groups::optflag("x", "exit", "Exit after load flag"),
         groups::optopt("y", "layout-threads", "Number of threads to use for layout", "1"),
         groups::optflag("z", "headless", "Headless mode"),
+        groups::optopt("", "http-cache-dir", "Directory in which to persist the HTTP cache", "cache/"),
+        groups::optopt("", "print", "Print the page to a PDF, or to one PNG per page", "output.pdf"),
+        groups::optopt("", "page-size", "Size of printed pages in pixels", "816x1056"),
         groups::optflag("f", "hard-fail", "Exit on task failure instead of displaying about:failure"),
         groups::optflag("h", "help", "Print this message")
     ];
@@ -131,6 +159,17 @@ pub fn from_cmdline_args(args: &[~str]) -> Opts {
         None => num::max(rt::default_sched_threads() * 3 / 4, 1),
     };
 
+    let page_size = match opt_match.opt_str("page-size") {
+        Some(page_size_str) => {
+            match parse_page_size(page_size_str) {
+                Some(page_size) => page_size,
+                None => fail!(format!("invalid page size `{}`; it must be of the form \
+                                       WIDTHxHEIGHT", page_size_str)),
+            }
+        }
+        None => Size2D(816, 1056),
+    };
+
     Opts {
         urls: urls,
         render_backend: render_backend,
@@ -141,7 +180,20 @@ pub fn from_cmdline_args(args: &[~str]) -> Opts {
         layout_threads: layout_threads,
         exit_after_load: opt_match.opt_present("x"),
         output_file: opt_match.opt_str("o"),
+        http_cache_dir: opt_match.opt_str("http-cache-dir"),
+        print_output: opt_match.opt_str("print"),
+        page_size: page_size,
         headless: opt_match.opt_present("z"),
         hard_fail: opt_match.opt_present("f"),
     }
 }
+
+#[test]
+fn test_parse_page_size() {
+    assert_eq!(parse_page_size("816x1056"), Some(Size2D(816, 1056)));
+    assert_eq!(parse_page_size("816"), None);
+    assert_eq!(parse_page_size("816x"), None);
+    assert_eq!(parse_page_size("0x1056"), None);
+    assert_eq!(parse_page_size("axb"), None);
+    assert_eq!(parse_page_size("1x2x3"), None);
+}
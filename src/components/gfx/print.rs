@@ -0,0 +1,287 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Renders a paginated display list to PNG images or to a PDF document.
+
+use display_list::DisplayListCollection;
+use font_context::{FontContext, FontContextInfo};
+use opts::Opts;
+use render_context::RenderContext;
+
+use azure::azure_hl::{B8G8R8A8, Color, DrawTarget};
+use azure::AzFloat;
+use geom::matrix2d::Matrix2D;
+use geom::point::Point2D;
+use geom::rect::Rect;
+use geom::size::Size2D;
+use png;
+use servo_msg::compositor_msg::{LayerId, PageLayerId};
+use servo_util::geometry::Au;
+use servo_util::time::ProfilerChan;
+use std::io;
+use std::io::File;
+use std::path::Path;
+use std::vec;
+
+/// The rendered pixels of one page, as tightly packed 8-bit RGB.
+pub struct Page {
+    pixels: ~[u8],
+}
+
+/// Draws the layers above the layer `parent` in compositing order: the contents of its scrolling
+/// boxes, unscrolled and clipped to their boxes since a printed page cannot scroll, and the
+/// overlays above them. `origin` is the offset of the parent layer from the top left corner of the
+/// page being drawn.
+fn draw_scroll_layers<T>(ctx: &mut RenderContext,
+                         display_list_collection: &DisplayListCollection<T>,
+                         parent: LayerId,
+                         origin: Point2D<Au>) {
+    for layer in display_list_collection.scroll_layers.iter().filter(|layer| layer.parent == parent) {
+        // The clip is in the coordinates of the parent layer, which the current transform maps.
+        ctx.draw_push_clip(&layer.clip_rect);
+
+        let layer_origin = origin + layer.clip_rect.origin;
+        set_translation(ctx, layer_origin);
+        layer.display_list_collection.draw_lists_into_context(ctx);
+        draw_scroll_layers(ctx, display_list_collection, layer.id, layer_origin);
+        set_translation(ctx, origin);
+
+        ctx.draw_pop_clip();
+    }
+}
+
+fn set_translation(ctx: &mut RenderContext, origin: Point2D<Au>) {
+    let matrix: Matrix2D<AzFloat> = Matrix2D::identity();
+    let matrix = matrix.translate(origin.x.to_nearest_px() as AzFloat,
+                                  origin.y.to_nearest_px() as AzFloat);
+    ctx.draw_target.set_transform(&matrix);
+}
+
+/// Renders one page per entry in `page_starts`, which holds the vertical offset of the top of each
+/// page in document coordinates. Content below the start of the following page is clipped out so
+/// that it is only drawn once, at the top of the next page.
+pub fn render_pages<T>(display_list_collection: &DisplayListCollection<T>,
+                       page_starts: &[Au],
+                       page_size: Size2D<uint>,
+                       background_color: Color,
+                       opts: &Opts,
+                       profiler_chan: ProfilerChan)
+                       -> ~[Page] {
+    let mut font_ctx = ~FontContext::new(FontContextInfo {
+        backend: opts.render_backend.clone(),
+        needs_font_list: false,
+        profiler_chan: profiler_chan,
+    });
+
+    let page_width = Au::from_px(page_size.width as int);
+    let page_height = Au::from_px(page_size.height as int);
+    let mut pages = ~[];
+    for (i, &page_start) in page_starts.iter().enumerate() {
+        let page_end = if i + 1 < page_starts.len() {
+            page_starts[i + 1]
+        } else {
+            page_start + page_height
+        };
+
+        let draw_target = DrawTarget::new(opts.render_backend,
+                                          Size2D(page_size.width as i32, page_size.height as i32),
+                                          B8G8R8A8);
+        {
+            let mut ctx = RenderContext {
+                draw_target: &draw_target,
+                font_ctx: &mut font_ctx,
+                opts: opts,
+                page_rect: Rect(Point2D(0.0, page_start.to_nearest_px() as f32),
+                                Size2D(page_size.width as f32, page_size.height as f32)),
+                screen_rect: Rect(Point2D(0u, 0u), page_size),
+            };
+
+            let matrix: Matrix2D<AzFloat> = Matrix2D::identity();
+            let matrix = matrix.translate(0.0, -page_start.to_nearest_px() as AzFloat);
+            ctx.draw_target.set_transform(&matrix);
+
+            let page_rect = Rect(Point2D(Au(0), page_start), Size2D(page_width, page_height));
+            ctx.draw_solid_color(&page_rect, background_color);
+
+            let content_rect = Rect(Point2D(Au(0), page_start),
+                                    Size2D(page_width, page_end - page_start));
+            ctx.draw_push_clip(&content_rect);
+            display_list_collection.draw_lists_into_context(&mut ctx);
+            draw_scroll_layers(&mut ctx,
+                               display_list_collection,
+                               PageLayerId,
+                               Point2D(Au(0), -page_start));
+            ctx.draw_pop_clip();
+            ctx.draw_target.flush();
+        }
+
+        // The draw target is premultiplied BGRA; the background is opaque, so the alpha channel
+        // can simply be dropped.
+        let mut pixels = vec::with_capacity(page_size.width * page_size.height * 3);
+        draw_target.snapshot().get_data_surface().with_data(|data| {
+            for bgra in data.chunks(4) {
+                pixels.push(bgra[2]);
+                pixels.push(bgra[1]);
+                pixels.push(bgra[0]);
+            }
+        });
+        pages.push(Page {
+            pixels: pixels,
+        });
+    }
+    pages
+}
+
+/// Writes the pages out: as a single PDF if `path` ends in `.pdf`, otherwise as one PNG per page.
+pub fn write_pages(path: &str, pages: &[Page], page_size: Size2D<uint>) {
+    if path.ends_with(".pdf") {
+        let _guard = io::ignore_io_error();
+        match File::create(&Path::new(path)) {
+            Some(ref mut file) => file.write(pdf_document(pages, page_size)),
+            None => error!("print: couldn't write {:s}", path),
+        }
+        return
+    }
+
+    for (i, page) in pages.iter().enumerate() {
+        let page_path = numbered_path(path, i + 1);
+        let image = png::Image {
+            width: page_size.width as u32,
+            height: page_size.height as u32,
+            color_type: png::RGB8,
+            pixels: page.pixels.clone(),
+        };
+        if png::store_png(&image, &Path::new(page_path.clone())).is_err() {
+            error!("print: couldn't write {:s}", page_path);
+        }
+    }
+}
+
+/// The name of the PNG for page `number`: `out.png` becomes `out-1.png`, `out` becomes `out-1`.
+fn numbered_path(path: &str, number: uint) -> ~str {
+    let extension_start = match path.rfind('.') {
+        Some(i) if path.slice_from(i).find('/').is_none() => i,
+        _ => path.len(),
+    };
+    format!("{:s}-{:u}{:s}", path.slice_to(extension_start), number,
+            path.slice_from(extension_start))
+}
+
+/// Builds a PDF with one page per image. Each page is an uncompressed RGB image XObject scaled to
+/// cover the page; pixels are mapped to points at 96 DPI, as CSS does.
+///
+/// FIXME: Compress the image streams with FlateDecode.
+fn pdf_document(pages: &[Page], page_size: Size2D<uint>) -> ~[u8] {
+    let mut writer = PdfWriter {
+        bytes: ~[],
+        offsets: ~[],
+    };
+    let width_pt = page_size.width as f64 * 0.75;
+    let height_pt = page_size.height as f64 * 0.75;
+
+    writer.push_str("%PDF-1.4\n");
+
+    // Objects 1 and 2 are the catalog and the page tree; each page then takes three objects: the
+    // page itself, its image and its content stream.
+    let kids: ~[~str] = range(0, pages.len()).map(|i| format!("{:u} 0 R", 3 + i * 3)).collect();
+    writer.object("<< /Type /Catalog /Pages 2 0 R >>");
+    writer.object(format!("<< /Type /Pages /Kids [{:s}] /Count {:u} >>",
+                          kids.connect(" "),
+                          pages.len()));
+
+    for (i, page) in pages.iter().enumerate() {
+        let page_object = 3 + i * 3;
+        writer.object(format!("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                               /Resources << /XObject << /Page {:u} 0 R >> >> \
+                               /Contents {:u} 0 R >>",
+                              width_pt, height_pt, page_object + 1, page_object + 2));
+        writer.stream(format!("<< /Type /XObject /Subtype /Image /Width {:u} /Height {:u} \
+                               /ColorSpace /DeviceRGB /BitsPerComponent 8 /Length {:u} >>",
+                              page_size.width, page_size.height, page.pixels.len()),
+                      page.pixels);
+        let contents = format!("q {} 0 0 {} 0 0 cm /Page Do Q", width_pt, height_pt);
+        writer.stream(format!("<< /Length {:u} >>", contents.len()), contents.as_bytes());
+    }
+
+    writer.finish()
+}
+
+struct PdfWriter {
+    bytes: ~[u8],
+    /// The byte offset of each object, for the cross-reference table.
+    offsets: ~[uint],
+}
+
+impl PdfWriter {
+    fn push_str(&mut self, string: &str) {
+        self.bytes.push_all(string.as_bytes());
+    }
+
+    fn begin_object(&mut self) {
+        self.offsets.push(self.bytes.len());
+        self.push_str(format!("{:u} 0 obj\n", self.offsets.len()));
+    }
+
+    fn object(&mut self, dictionary: &str) {
+        self.begin_object();
+        self.push_str(dictionary);
+        self.push_str("\nendobj\n");
+    }
+
+    fn stream(&mut self, dictionary: &str, data: &[u8]) {
+        self.begin_object();
+        self.push_str(dictionary);
+        self.push_str("\nstream\n");
+        self.bytes.push_all(data);
+        self.push_str("\nendstream\nendobj\n");
+    }
+
+    fn finish(mut self) -> ~[u8] {
+        let xref_offset = self.bytes.len();
+        self.push_str(format!("xref\n0 {:u}\n0000000000 65535 f \n", self.offsets.len() + 1));
+        for i in range(0, self.offsets.len()) {
+            let offset = self.offsets[i];
+            self.push_str(format!("{:010u} 00000 n \n", offset));
+        }
+        self.push_str(format!("trailer\n<< /Size {:u} /Root 1 0 R >>\nstartxref\n{:u}\n%%EOF\n",
+                              self.offsets.len() + 1,
+                              xref_offset));
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Page, numbered_path, pdf_document};
+    use geom::size::Size2D;
+    use std::str;
+
+    #[test]
+    fn test_numbered_path() {
+        assert_eq!(numbered_path("out.png", 1), ~"out-1.png");
+        assert_eq!(numbered_path("dir.d/out", 12), ~"dir.d/out-12");
+    }
+
+    #[test]
+    fn test_pdf_document() {
+        // ASCII pixel values, so that the document can be inspected as a string.
+        let pages = ~[
+            Page { pixels: ~[65u8, ..12] },
+            Page { pixels: ~[66u8, ..12] },
+        ];
+        let bytes = pdf_document(pages, Size2D(2u, 2u));
+        let text = str::from_utf8(bytes);
+        assert!(text.starts_with("%PDF-1.4\n"));
+        assert!(text.contains("/Kids [3 0 R 6 0 R] /Count 2"));
+        assert!(text.contains("xref\n0 9\n"));
+        assert!(text.ends_with("%%EOF\n"));
+
+        // Every cross-reference entry must point at the start of its object.
+        let xref = text.slice_from(text.find_str("xref\n").unwrap());
+        for (i, line) in xref.lines().skip(3).take(8).enumerate() {
+            let offset: uint = from_str(line.slice_to(10)).unwrap();
+            assert!(text.slice_from(offset).starts_with(format!("{:u} 0 obj", i + 1)));
+        }
+    }
+}
@@ -84,7 +84,13 @@ impl<'a> RenderContext<'a>  {
         self.draw_target.pop_clip();
     }    
 
-    pub fn draw_image(&self, bounds: Rect<Au>, image: Arc<~Image>) {
+    /// Draws `image` at the size `stretch_size`, tiled across `bounds`. Only the tiles that fall
+    /// within this context's page rect are drawn, so tiny tiles on a huge box stay cheap.
+    pub fn draw_image(&self, bounds: Rect<Au>, stretch_size: Size2D<Au>, image: Arc<~Image>) {
+        if stretch_size.width <= Au(0) || stretch_size.height <= Au(0) {
+            return
+        }
+
         let image = image.get();
         let size = Size2D(image.width as i32, image.height as i32);
         let pixel_width = match image.color_type {
@@ -97,22 +103,43 @@ impl<'a> RenderContext<'a>  {
 
         self.draw_target.make_current();
         let draw_target_ref = &self.draw_target;
-        let azure_surface = draw_target_ref.create_source_surface_from_data(image.pixels, size,
-                                                                            stride as i32, B8G8R8A8);
+        let azure_surface = draw_target_ref.create_source_surface_from_data(image.pixels,
+                                                                            size,
+                                                                            stride as i32,
+                                                                            B8G8R8A8);
         let source_rect = Rect(Point2D(0 as AzFloat, 0 as AzFloat),
                                Size2D(image.width as AzFloat, image.height as AzFloat));
-        let dest_rect = bounds.to_azure_rect();
-        let draw_surface_options = DrawSurfaceOptions(Linear, true);
-        let draw_options = DrawOptions(1.0f64 as AzFloat, 0);
-        draw_target_ref.draw_surface(azure_surface,
-                                     dest_rect,
-                                     source_rect,
-                                     draw_surface_options,
-                                     draw_options);
+
+        // Skip the rows and columns of tiles before the page rect, and stop after it.
+        let page_rect = Rect(Point2D(Au::from_frac_px(self.page_rect.origin.x as f64),
+                                     Au::from_frac_px(self.page_rect.origin.y as f64)),
+                             Size2D(Au::from_frac_px(self.page_rect.size.width as f64),
+                                    Au::from_frac_px(self.page_rect.size.height as f64)));
+        let (start_x, end_x) = tile_range(bounds.origin.x, bounds.size.width, stretch_size.width,
+                                          page_rect.origin.x, page_rect.size.width);
+        let (start_y, end_y) = tile_range(bounds.origin.y, bounds.size.height, stretch_size.height,
+                                          page_rect.origin.y, page_rect.size.height);
+
+        let mut y = start_y;
+        while y < end_y {
+            let mut x = start_x;
+            while x < end_x {
+                let dest_rect = Rect(Point2D(x, y), stretch_size).to_azure_rect();
+                let draw_surface_options = DrawSurfaceOptions(Linear, true);
+                let draw_options = DrawOptions(1.0f64 as AzFloat, 0);
+                draw_target_ref.draw_surface(azure_surface.clone(),
+                                             dest_rect,
+                                             source_rect,
+                                             draw_surface_options,
+                                             draw_options);
+                x = x + stretch_size.width;
+            }
+            y = y + stretch_size.height;
+        }
     }
 
-    pub fn clear(&self) {
-        let pattern = ColorPattern(Color(1.0, 1.0, 1.0, 1.0));
+    pub fn clear(&self, color: Color) {
+        let pattern = ColorPattern(color);
         let rect = Rect(Point2D(self.page_rect.origin.x as AzFloat,
                                 self.page_rect.origin.y as AzFloat),
                         Size2D(self.screen_rect.size.width as AzFloat,
@@ -255,6 +282,18 @@ impl to_float for u8 {
     }
 }
 
+/// Returns the range along one axis to draw tiles of size `tile_size` over, for tiles that start
+/// at `origin` and cover `size`, to fill the part of them within `clip_origin` and `clip_size`.
+/// The start is on a tile boundary.
+fn tile_range(origin: Au, size: Au, tile_size: Au, clip_origin: Au, clip_size: Au) -> (Au, Au) {
+    let end = Au::min(origin + size, clip_origin + clip_size);
+    if clip_origin <= origin {
+        return (origin, end)
+    }
+    let skipped_tiles = (clip_origin - origin) / tile_size;
+    (origin + tile_size * skipped_tiles, end)
+}
+
 trait ToAzureRect {
     fn to_azure_rect(&self) -> Rect<AzFloat>;
 }
@@ -280,3 +319,14 @@ impl ToSideOffsetsPx for SideOffsets2D<Au> {
                            self.left.to_nearest_px() as AzFloat)
     }
 }
+
+#[test]
+fn test_tile_range() {
+    // Tiles before the clip are skipped, and the range stops at the end of the clip.
+    assert_eq!(tile_range(Au(0), Au(1000), Au(10), Au(495), Au(100)), (Au(490), Au(595)));
+    // The range doesn't start before the tiles or go past them.
+    assert_eq!(tile_range(Au(100), Au(50), Au(10), Au(0), Au(1000)), (Au(100), Au(150)));
+    // Tiles entirely outside the clip give an empty range.
+    let (start, end) = tile_range(Au(0), Au(100), Au(10), Au(500), Au(100));
+    assert!(start >= end);
+}
@@ -7,15 +7,18 @@
 use azure::azure_hl::{B8G8R8A8, Color, DrawTarget, StolenGLResources};
 use azure::AzFloat;
 use geom::matrix2d::Matrix2D;
+use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
 use layers::platform::surface::{NativePaintingGraphicsContext, NativeSurface};
 use layers::platform::surface::{NativeSurfaceMethods};
 use layers;
-use servo_msg::compositor_msg::{Epoch, IdleRenderState, LayerBuffer, LayerBufferSet};
-use servo_msg::compositor_msg::{RenderListener, RenderingRenderState};
+use servo_msg::compositor_msg::{Epoch, IdleRenderState, LayerBuffer, LayerBufferSet, LayerId};
+use servo_msg::compositor_msg::{PageLayerId, RenderListener, RenderingRenderState};
+use servo_msg::compositor_msg::{OverlayLayerId, ScrollLayerId, ScrollLayerMetadata};
 use servo_msg::constellation_msg::{ConstellationChan, PipelineId, RendererReadyMsg};
 use servo_msg::platform::surface::NativeSurfaceAzureMethods;
+use servo_util::geometry::Au;
 use servo_util::time::{ProfilerChan, profile};
 use servo_util::time;
 use servo_util::task::spawn_named;
@@ -35,9 +38,32 @@ pub struct RenderLayer<T> {
     color: Color
 }
 
+impl<T> RenderLayer<T> {
+    /// Describes the layers of the scrolling boxes on the page to the compositor.
+    fn scroll_layer_metadata(&self) -> ~[ScrollLayerMetadata] {
+        fn to_px(length: Au) -> uint {
+            if length < Au(0) { 0 } else { length.to_nearest_px() as uint }
+        }
+
+        self.display_list_collection.get().scroll_layers.iter().map(|layer| {
+            ScrollLayerMetadata {
+                id: layer.id,
+                parent: layer.parent,
+                clip_rect: Rect(Point2D(to_px(layer.clip_rect.origin.x),
+                                        to_px(layer.clip_rect.origin.y)),
+                                Size2D(to_px(layer.clip_rect.size.width),
+                                       to_px(layer.clip_rect.size.height))),
+                page_size: Size2D(to_px(layer.size.width), to_px(layer.size.height)),
+                scroll_offset: Point2D(layer.scroll_offset.x.to_nearest_px() as f32,
+                                       layer.scroll_offset.y.to_nearest_px() as f32),
+            }
+        }).collect()
+    }
+}
+
 pub enum Msg<T> {
     RenderMsg(RenderLayer<T>),
-    ReRenderMsg(~[BufferRequest], f32, Epoch),
+    ReRenderMsg(LayerId, ~[BufferRequest], f32, Epoch),
     UnusedBufferMsg(~[~LayerBuffer]),
     PaintPermissionGranted,
     PaintPermissionRevoked,
@@ -203,15 +229,16 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
                     if self.paint_permission {
                         self.epoch.next();
                         self.compositor.set_layer_page_size_and_color(self.id, render_layer.size, self.epoch, render_layer.color);
+                        self.compositor.set_scroll_layers(self.id, render_layer.scroll_layer_metadata(), self.epoch);
                     } else {
                         debug!("render_task: render ready msg");
                         self.constellation_chan.send(RendererReadyMsg(self.id));
                     }
                     self.render_layer = Some(render_layer);
                 }
-                ReRenderMsg(tiles, scale, epoch) => {
+                ReRenderMsg(layer_id, tiles, scale, epoch) => {
                     if self.epoch == epoch {
-                        self.render(tiles, scale);
+                        self.render(layer_id, tiles, scale);
                     } else {
                         debug!("renderer epoch mismatch: {:?} != {:?}", self.epoch, epoch);
                     }
@@ -228,6 +255,7 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
                         Some(ref render_layer) => {
                             self.epoch.next();
                             self.compositor.set_layer_page_size_and_color(self.id, render_layer.size, self.epoch, render_layer.color);
+                            self.compositor.set_scroll_layers(self.id, render_layer.scroll_layer_metadata(), self.epoch);
                         }
                         None => {}
                     }
@@ -244,7 +272,7 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
         }
     }
 
-    fn render(&mut self, tiles: ~[BufferRequest], scale: f32) {
+    fn render(&mut self, layer_id: LayerId, tiles: ~[BufferRequest], scale: f32) {
         let render_layer;
         match self.render_layer {
             Some(ref r_layer) => {
@@ -253,6 +281,20 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
             _ => return, // nothing to do
         }
 
+        // The layers of scrolling boxes are cleared to transparent, so that whatever was painted
+        // behind the boxes shows through where their contents don't paint.
+        let (display_list_collection, clear_color) = match layer_id {
+            PageLayerId => (render_layer.display_list_collection.get(), Color(1.0, 1.0, 1.0, 1.0)),
+            ScrollLayerId(..) | OverlayLayerId(..) => {
+                match render_layer.display_list_collection.get().scroll_layer(layer_id) {
+                    Some(scroll_layer) => {
+                        (&scroll_layer.display_list_collection, Color(0.0, 0.0, 0.0, 0.0))
+                    }
+                    None => return, // The layer is gone.
+                }
+            }
+        };
+
         self.compositor.set_render_state(RenderingRenderState);
         time::profile(time::RenderingCategory, self.profiler_chan.clone(), || {
             // FIXME: Try not to create a new array here.
@@ -301,11 +343,11 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
                         ctx.draw_target.set_transform(&matrix);
                         
                         // Clear the buffer.
-                        ctx.clear();
+                        ctx.clear(clear_color);
                         
                         // Draw the display list.
                         profile(time::RenderingDrawingCategory, self.profiler_chan.clone(), || {
-                            render_layer.display_list_collection.get().draw_lists_into_context(&mut ctx);
+                            display_list_collection.draw_lists_into_context(&mut ctx);
                             ctx.draw_target.flush();
                         });
                     }
@@ -386,7 +428,7 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
 
             debug!("render_task: returning surface");
             if self.paint_permission {
-                self.compositor.paint(self.id, layer_buffer_set, self.epoch);
+                self.compositor.paint(self.id, layer_id, layer_buffer_set, self.epoch);
             } else {
                 debug!("render_task: RendererReadyMsg send");
                 self.constellation_chan.send(RendererReadyMsg(self.id));
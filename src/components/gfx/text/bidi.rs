@@ -0,0 +1,402 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Embedding levels of bidirectional text, following the Unicode Bidirectional Algorithm
+//! (UAX #9):
+//!
+//!     http://www.unicode.org/reports/tr9/
+//!
+//! Explicit embeddings and overrides are supported, but isolates are not. As with line breaking,
+//! the character classification covers the commonly used scripts rather than the whole Unicode
+//! database.
+
+use std::cmp;
+use std::vec;
+
+/// An embedding level. Even levels are left-to-right and odd levels are right-to-left.
+pub type BidiLevel = u8;
+
+pub static LEFT_TO_RIGHT_EMBEDDING: char = '\u202a';
+pub static RIGHT_TO_LEFT_EMBEDDING: char = '\u202b';
+pub static POP_DIRECTIONAL_FORMATTING: char = '\u202c';
+pub static LEFT_TO_RIGHT_OVERRIDE: char = '\u202d';
+pub static RIGHT_TO_LEFT_OVERRIDE: char = '\u202e';
+
+/// The deepest embedding level that explicit embeddings can reach (rule X1).
+static MAX_DEPTH: BidiLevel = 61;
+
+/// The bidirectional character types of UAX #9, section 3.2, less the isolates.
+#[deriving(Eq, Clone)]
+enum BidiClass {
+    L, R, AL, EN, ES, ET, AN, CS, NSM, BN, B, S, WS, ON, LRE, LRO, RLE, RLO, PDF,
+}
+
+/// Returns true if `level` is a right-to-left level.
+pub fn is_rtl(level: BidiLevel) -> bool {
+    level % 2 == 1
+}
+
+/// Returns the embedding level of each character of `text`, which is one paragraph with the
+/// embedding level `paragraph_level`. Explicit formatting characters get the level of the
+/// character before them.
+pub fn resolve_levels(text: &str, paragraph_level: BidiLevel) -> ~[BidiLevel] {
+    let original_classes: ~[BidiClass] = text.chars().map(bidi_class).collect();
+    let mut classes = original_classes.clone();
+    let mut levels = vec::from_elem(classes.len(), paragraph_level);
+
+    // X1 to X8: Apply explicit embeddings and overrides. Each entry of the stack is a level and
+    // the class that an override forces characters to, if any.
+    let mut stack = ~[(paragraph_level, None)];
+    let mut overflow_count = 0u;
+    let mut removed = vec::from_elem(classes.len(), false);
+    for i in range(0, classes.len()) {
+        let (level, override_class) = stack[stack.len() - 1];
+        levels[i] = level;
+        match classes[i] {
+            RLE | LRE | RLO | LRO => {
+                let next_level = match classes[i] {
+                    RLE | RLO => (level + 1) | 1,
+                    _ => (level + 2) & !1,
+                };
+                let next_override = match classes[i] {
+                    RLO => Some(R),
+                    LRO => Some(L),
+                    _ => None,
+                };
+                if next_level <= MAX_DEPTH && overflow_count == 0 {
+                    stack.push((next_level, next_override));
+                } else {
+                    overflow_count += 1;
+                }
+                removed[i] = true;
+            }
+            PDF => {
+                if overflow_count > 0 {
+                    overflow_count -= 1;
+                } else if stack.len() > 1 {
+                    stack.pop();
+                }
+                removed[i] = true;
+            }
+            B => {
+                levels[i] = paragraph_level;
+                stack.truncate(1);
+                overflow_count = 0;
+            }
+            BN => removed[i] = true,
+            _ => {
+                for &class in override_class.iter() {
+                    classes[i] = class;
+                }
+            }
+        }
+    }
+
+    // X9: Ignore the explicit formatting characters from here on.
+    let kept: ~[uint] = range(0, classes.len()).filter(|&i| !removed[i]).collect();
+
+    // X10: Resolve each run of characters at the same level separately.
+    let mut run_start = 0;
+    while run_start < kept.len() {
+        let level = levels[kept[run_start]];
+        let mut run_end = run_start + 1;
+        while run_end < kept.len() && levels[kept[run_end]] == level {
+            run_end += 1;
+        }
+
+        let previous_level = if run_start == 0 {
+            paragraph_level
+        } else {
+            levels[kept[run_start - 1]]
+        };
+        let next_level = if run_end == kept.len() {
+            paragraph_level
+        } else {
+            levels[kept[run_end]]
+        };
+        let sos = direction_of_level(cmp::max(previous_level, level));
+        let eos = direction_of_level(cmp::max(next_level, level));
+
+        let run = kept.slice(run_start, run_end);
+        resolve_weak_types(classes.mut_slice_from(0), run, sos);
+        resolve_neutral_types(classes.mut_slice_from(0), run, sos, eos, level);
+        resolve_implicit_levels(classes, levels.mut_slice_from(0), run);
+        run_start = run_end;
+    }
+
+    // Give the explicit formatting characters the level of the character before them.
+    for i in range(0, classes.len()) {
+        if removed[i] {
+            levels[i] = if i == 0 { paragraph_level } else { levels[i - 1] };
+        }
+    }
+
+    // L1: Reset separators, and any whitespace before them or at the end of the paragraph, to
+    // the paragraph level. The original classes are used, since overrides do not apply here.
+    let mut trailing_whitespace = true;
+    for i in range(0, classes.len()).invert() {
+        match original_classes[i] {
+            B | S => {
+                levels[i] = paragraph_level;
+                trailing_whitespace = true;
+            }
+            WS | BN | LRE | RLE | LRO | RLO | PDF if trailing_whitespace => {
+                levels[i] = paragraph_level;
+            }
+            _ => trailing_whitespace = false,
+        }
+    }
+    levels
+}
+
+/// Returns the indices of items with the given embedding levels in visual order, from left to
+/// right (rule L2).
+pub fn visual_order(levels: &[BidiLevel]) -> ~[uint] {
+    let mut order: ~[uint] = range(0, levels.len()).collect();
+    let highest_level = match levels.iter().max() {
+        None => return order,
+        Some(&level) => level,
+    };
+    let lowest_odd_level = match levels.iter().filter(|&&level| is_rtl(level)).min() {
+        None => return order,
+        Some(&level) => level,
+    };
+
+    // From the highest level down to the lowest odd level, reverse every run of items at that
+    // level or higher.
+    let mut level = highest_level;
+    while level >= lowest_odd_level {
+        let mut i = 0;
+        while i < order.len() {
+            if levels[order[i]] < level {
+                i += 1;
+                continue
+            }
+            let start = i;
+            while i < order.len() && levels[order[i]] >= level {
+                i += 1;
+            }
+            order.mut_slice(start, i).reverse();
+        }
+        level -= 1;
+    }
+    order
+}
+
+fn direction_of_level(level: BidiLevel) -> BidiClass {
+    if is_rtl(level) { R } else { L }
+}
+
+/// Applies rules W1 to W7 to the characters at the indices `run`.
+fn resolve_weak_types(classes: &mut [BidiClass], run: &[uint], sos: BidiClass) {
+    // W1: Nonspacing marks take the class of the character before them.
+    let mut previous = sos;
+    for &i in run.iter() {
+        if classes[i] == NSM {
+            classes[i] = previous;
+        }
+        previous = classes[i];
+    }
+
+    // W2, W3: European numbers after Arabic letters are Arabic numbers, and Arabic letters are
+    // right-to-left.
+    let mut last_strong = sos;
+    for &i in run.iter() {
+        match classes[i] {
+            L | R | AL => last_strong = classes[i],
+            EN if last_strong == AL => classes[i] = AN,
+            _ => {}
+        }
+    }
+    for &i in run.iter() {
+        if classes[i] == AL {
+            classes[i] = R;
+        }
+    }
+
+    // W4: A single separator between two numbers of the same kind joins them.
+    for j in range(1, cmp::max(run.len(), 1) - 1) {
+        let (before, after) = (classes[run[j - 1]], classes[run[j + 1]]);
+        match classes[run[j]] {
+            ES if before == EN && after == EN => classes[run[j]] = EN,
+            CS if before == EN && after == EN => classes[run[j]] = EN,
+            CS if before == AN && after == AN => classes[run[j]] = AN,
+            _ => {}
+        }
+    }
+
+    // W5: Terminators next to European numbers are European numbers.
+    let mut j = 0;
+    while j < run.len() {
+        if classes[run[j]] != ET {
+            j += 1;
+            continue
+        }
+        let start = j;
+        while j < run.len() && classes[run[j]] == ET {
+            j += 1;
+        }
+        let touches_number = (start > 0 && classes[run[start - 1]] == EN) ||
+            (j < run.len() && classes[run[j]] == EN);
+        if touches_number {
+            for k in range(start, j) {
+                classes[run[k]] = EN;
+            }
+        }
+    }
+
+    // W6: Remaining separators and terminators are neutral.
+    for &i in run.iter() {
+        match classes[i] {
+            ES | ET | CS => classes[i] = ON,
+            _ => {}
+        }
+    }
+
+    // W7: European numbers after left-to-right text are left-to-right.
+    let mut last_strong = sos;
+    for &i in run.iter() {
+        match classes[i] {
+            L | R => last_strong = classes[i],
+            EN if last_strong == L => classes[i] = L,
+            _ => {}
+        }
+    }
+}
+
+/// Applies rules N1 and N2 to the characters at the indices `run`, which are at `level`.
+fn resolve_neutral_types(classes: &mut [BidiClass],
+                         run: &[uint],
+                         sos: BidiClass,
+                         eos: BidiClass,
+                         level: BidiLevel) {
+    fn is_neutral(class: BidiClass) -> bool {
+        match class {
+            B | S | WS | ON => true,
+            _ => false,
+        }
+    }
+    // Numbers count as right-to-left text here.
+    fn strong_direction(class: BidiClass) -> BidiClass {
+        match class {
+            EN | AN => R,
+            _ => class,
+        }
+    }
+
+    let mut j = 0;
+    while j < run.len() {
+        if !is_neutral(classes[run[j]]) {
+            j += 1;
+            continue
+        }
+        let start = j;
+        while j < run.len() && is_neutral(classes[run[j]]) {
+            j += 1;
+        }
+        let before = if start == 0 { sos } else { strong_direction(classes[run[start - 1]]) };
+        let after = if j == run.len() { eos } else { strong_direction(classes[run[j]]) };
+
+        // N1: Neutrals between text of the same direction take that direction. N2: Others take
+        // the embedding direction.
+        let class = if before == after { before } else { direction_of_level(level) };
+        for k in range(start, j) {
+            classes[run[k]] = class;
+        }
+    }
+}
+
+/// Applies rules I1 and I2 to the characters at the indices `run`.
+fn resolve_implicit_levels(classes: &[BidiClass], levels: &mut [BidiLevel], run: &[uint]) {
+    for &i in run.iter() {
+        let level = levels[i];
+        levels[i] = match (is_rtl(level), classes[i]) {
+            (false, R) => level + 1,
+            (false, AN) | (false, EN) => level + 2,
+            (true, L) | (true, AN) | (true, EN) => level + 1,
+            _ => level,
+        };
+    }
+}
+
+/// Returns the bidirectional character type of `c`.
+fn bidi_class(c: char) -> BidiClass {
+    match c {
+        '\u202a' => LRE,
+        '\u202b' => RLE,
+        '\u202c' => PDF,
+        '\u202d' => LRO,
+        '\u202e' => RLO,
+        '\n' | '\r' | '\x1c' .. '\x1e' | '\x85' | '\u2029' => B,
+        '\t' | '\x0b' | '\x1f' => S,
+        ' ' | '\x0c' | '\u1680' | '\u2000' .. '\u200a' | '\u2028' | '\u205f' | '\u3000' => WS,
+        '\x00' .. '\x08' | '\x0e' .. '\x1b' | '\x7f' .. '\x84' | '\x86' .. '\x9f' | '\xad' |
+        '\u180e' | '\u200b' .. '\u200d' | '\u2060' .. '\u2064' | '\ufeff' => BN,
+        '\u200e' => L,
+        '\u200f' => R,
+        '0' .. '9' | '\xb2' | '\xb3' | '\xb9' | '\u06f0' .. '\u06f9' | '\u2070' |
+        '\u2074' .. '\u2079' | '\u2080' .. '\u2089' | '\u2488' .. '\u249b' |
+        '\uff10' .. '\uff19' => EN,
+        '+' | '-' | '\u207a' | '\u207b' | '\u208a' | '\u208b' | '\u2212' | '\ufb29' | '\ufe62' |
+        '\ufe63' | '\uff0b' | '\uff0d' => ES,
+        '#' .. '%' | '\xa2' .. '\xa5' | '\xb0' | '\xb1' | '\u058f' | '\u0609' | '\u060a' |
+        '\u066a' | '\u09f2' | '\u09f3' | '\u2030' .. '\u2034' | '\u20a0' .. '\u20cf' | '\u2213' |
+        '\ufe5f' | '\ufe69' | '\ufe6a' | '\uff03' .. '\uff05' | '\uffe0' | '\uffe1' | '\uffe5' |
+        '\uffe6' => ET,
+        '\u0600' .. '\u0605' | '\u0660' .. '\u0669' | '\u066b' | '\u066c' | '\u06dd' => AN,
+        ',' | '.' | '/' | ':' | '\xa0' | '\u060c' | '\u202f' | '\u2044' | '\ufe50' | '\ufe52' |
+        '\ufe55' | '\uff0c' .. '\uff0f' | '\uff1a' => CS,
+        '\u0300' .. '\u036f' | '\u0483' .. '\u0489' | '\u0591' .. '\u05bd' | '\u05bf' | '\u05c1' |
+        '\u05c2' | '\u05c4' | '\u05c5' | '\u05c7' | '\u0610' .. '\u061a' | '\u064b' .. '\u065f' |
+        '\u0670' | '\u06d6' .. '\u06dc' | '\u06df' .. '\u06e4' | '\u06e7' | '\u06e8' |
+        '\u06ea' .. '\u06ed' | '\u0711' | '\u0730' .. '\u074a' | '\u07a6' .. '\u07b0' |
+        '\u0900' .. '\u0902' | '\u093c' | '\u0941' .. '\u0948' | '\u094d' | '\u0e31' |
+        '\u0e34' .. '\u0e3a' | '\u0e47' .. '\u0e4e' | '\u20d0' .. '\u20f0' | '\u302a' .. '\u302d' |
+        '\u3099' | '\u309a' | '\ufb1e' | '\ufe00' .. '\ufe0f' | '\ufe20' .. '\ufe2f' => NSM,
+        '\u0590' .. '\u05ff' | '\u07c0' .. '\u085f' | '\ufb1d' .. '\ufb4f' |
+        '\U00010800' .. '\U00010fff' | '\U0001e800' .. '\U0001edff' => R,
+        '\u0608' | '\u060b' | '\u060d' | '\u061b' .. '\u064a' | '\u066d' .. '\u06d5' |
+        '\u06e5' | '\u06e6' | '\u06ee' | '\u06ef' | '\u06fa' .. '\u07bf' | '\u0860' .. '\u08ff' |
+        '\ufb50' .. '\ufd3d' | '\ufd40' .. '\ufdff' | '\ufe70' .. '\ufefe' |
+        '\U0001ee00' .. '\U0001eeff' => AL,
+        '!' | '"' | '&' .. '*' | ';' .. '@' | '[' .. '`' | '{' .. '~' | '\xa1' |
+        '\xa6' .. '\xa9' | '\xab' | '\xac' | '\xae' | '\xaf' | '\xb4' | '\xb6' .. '\xb8' |
+        '\xbb' .. '\xbf' | '\xd7' | '\xf7' | '\u2010' .. '\u2027' | '\u2035' .. '\u2043' |
+        '\u2045' .. '\u205e' | '\u2190' .. '\u2211' | '\u2214' .. '\u2335' | '\u2500' .. '\u27ff' |
+        '\u2e00' .. '\u2e7f' | '\u3001' .. '\u3004' | '\u3008' .. '\u3020' | '\u3030' |
+        '\ufd3e' | '\ufd3f' | '\ufe10' .. '\ufe19' | '\ufe30' .. '\ufe4f' | '\ufe51' | '\ufe54' |
+        '\ufe56' .. '\ufe5e' | '\ufe60' | '\ufe61' | '\ufe64' .. '\ufe66' | '\ufe68' | '\ufe6b' |
+        '\uff01' | '\uff02' | '\uff06' .. '\uff0a' | '\uff1b' .. '\uff20' | '\uff3b' .. '\uff40' |
+        '\uff5b' .. '\uff65' | '\ufff9' .. '\ufffd' => ON,
+        _ => L,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve_levels, visual_order};
+
+    #[test]
+    fn test_resolve_levels() {
+        assert_eq!(resolve_levels("abc", 0), ~[0, 0, 0]);
+        assert_eq!(resolve_levels("אבג", 0), ~[1, 1, 1]);
+        assert_eq!(resolve_levels("ab אב cd", 0), ~[0, 0, 0, 1, 1, 0, 0, 0]);
+        assert_eq!(resolve_levels("ab אב cd", 1), ~[2, 2, 1, 1, 1, 1, 2, 2]);
+        assert_eq!(resolve_levels("ا 12", 1), ~[1, 1, 2, 2]);
+        assert_eq!(resolve_levels("אב 12.5!", 0), ~[1, 1, 1, 2, 2, 2, 2, 0]);
+    }
+
+    #[test]
+    fn test_resolve_levels_with_embeddings() {
+        assert_eq!(resolve_levels("a\u202ebc\u202cd", 0), ~[0, 0, 1, 1, 1, 0]);
+        assert_eq!(resolve_levels("a\u202bb\u202c", 0), ~[0, 0, 2, 0]);
+    }
+
+    #[test]
+    fn test_visual_order() {
+        assert_eq!(visual_order([0, 0, 1, 1, 0]), ~[0, 1, 3, 2, 4]);
+        assert_eq!(visual_order([1, 1, 2, 2]), ~[2, 3, 1, 0]);
+        assert_eq!(visual_order([0, 0, 0]), ~[0, 1, 2]);
+    }
+}
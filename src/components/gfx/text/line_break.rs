@@ -0,0 +1,340 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Line break opportunities, following the Unicode Line Breaking Algorithm (UAX #14):
+//!
+//!     http://www.unicode.org/reports/tr14/
+//!
+//! The character classification covers the commonly used scripts rather than the whole Unicode
+//! database. Complex-context scripts (Thai, Lao and so on) need a dictionary to find word
+//! boundaries and are treated as alphabetic for now.
+
+use std::vec;
+use style::computed_values::word_break;
+
+/// The line breaking classes of UAX #14, § 5.1. Classes that rule LB1 resolves to others (AI, CJ,
+/// SA, SG and XX) are not produced by `line_break_class`.
+#[deriving(Eq, Clone)]
+enum LineBreakClass {
+    BK, CR, LF, NL, SP, ZW, WJ, GL, CM, ZWJ,
+    OP, CL, CP, QU, EX, IS, SY, NS, BA, BB, HY, B2, IN, NU, PR, PO,
+    AL, HL, ID, JL, JV, JT, H2, H3, RI,
+}
+
+/// Returns one entry per character of `text`, which is true if a line may break before that
+/// character. Mandatory breaks (after newlines) are reported as opportunities too; the caller is
+/// expected to handle them itself.
+pub fn break_opportunities(text: &str, word_break: word_break::T) -> ~[bool] {
+    let mut opportunities = vec::with_capacity(text.char_len());
+
+    // The class of the previous character, after combining marks have been attached to their
+    // base characters (LB9).
+    let mut before = None;
+    // The class of the last character that was not a space, for rules LB8 and LB14-LB17.
+    let mut before_spaces = SP;
+    // Whether the previous character was a zero width joiner (LB8a).
+    let mut after_zwj = false;
+    // The number of regional indicators ending at the previous character (LB30a).
+    let mut regional_indicators = 0u;
+
+    for c in text.chars() {
+        let class = resolve_class(line_break_class(c), word_break);
+        let a = match before {
+            None => {
+                // LB2: Never break at the start of text. LB10: treat a leading combining mark as
+                // alphabetic.
+                opportunities.push(false);
+                let class = if class == CM || class == ZWJ { AL } else { class };
+                before = Some(class);
+                before_spaces = class;
+                after_zwj = c == '\u200d';
+                regional_indicators = if class == RI { 1 } else { 0 };
+                continue
+            }
+            Some(a) => a,
+        };
+
+        // LB9: Attach combining marks to the character before them.
+        if (class == CM || class == ZWJ) && !is_hard_break_or_space(a) {
+            opportunities.push(false);
+            after_zwj = class == ZWJ;
+            continue
+        }
+        // LB10: Treat any remaining combining mark as alphabetic.
+        let b = if class == CM || class == ZWJ { AL } else { class };
+
+        opportunities.push(can_break_between(a, b, before_spaces, after_zwj, regional_indicators));
+
+        before = Some(b);
+        if b != SP {
+            before_spaces = b;
+        }
+        after_zwj = class == ZWJ;
+        regional_indicators = if b == RI { regional_indicators + 1 } else { 0 };
+    }
+    opportunities
+}
+
+fn is_hard_break_or_space(class: LineBreakClass) -> bool {
+    match class {
+        BK | CR | LF | NL | SP | ZW => true,
+        _ => false,
+    }
+}
+
+fn is_alphabetic(class: LineBreakClass) -> bool {
+    class == AL || class == HL
+}
+
+fn is_hangul(class: LineBreakClass) -> bool {
+    match class {
+        JL | JV | JT | H2 | H3 => true,
+        _ => false,
+    }
+}
+
+/// Applies rules LB4 to LB31 to the pair of classes `a` and `b`. `before_spaces` is the class of
+/// the last character before any spaces ending at `a` (which is `a` itself if it is not a space).
+fn can_break_between(a: LineBreakClass,
+                     b: LineBreakClass,
+                     before_spaces: LineBreakClass,
+                     after_zwj: bool,
+                     regional_indicators: uint)
+                     -> bool {
+    match (a, b) {
+        // LB4, LB5: Always break after hard line breaks, but treat CR LF as one.
+        (BK, _) => true,
+        (CR, LF) => false,
+        (CR, _) | (LF, _) | (NL, _) => true,
+        // LB6: Do not break before hard line breaks.
+        (_, BK) | (_, CR) | (_, LF) | (_, NL) => false,
+        // LB7: Do not break before spaces or zero width space.
+        (_, SP) | (_, ZW) => false,
+        // LB8: Break before any character following a zero width space, even after spaces.
+        _ if before_spaces == ZW => true,
+        // LB8a: Do not break after a zero width joiner.
+        _ if after_zwj => false,
+        // LB11: Do not break before or after word joiners.
+        (WJ, _) | (_, WJ) => false,
+        // LB12, LB12a: Do not break after non-breaking characters, nor before them except after
+        // spaces and hyphens.
+        (GL, _) => false,
+        (SP, GL) | (BA, GL) | (HY, GL) => true,
+        (_, GL) => false,
+        // LB13: Do not break before closing punctuation, exclamations or separators.
+        (_, CL) | (_, CP) | (_, EX) | (_, IS) | (_, SY) => false,
+        // LB14 to LB17: Do not break after opening punctuation, and between some pairs of
+        // punctuation, even with spaces in between.
+        _ if before_spaces == OP => false,
+        (_, OP) if before_spaces == QU => false,
+        (_, NS) if before_spaces == CL || before_spaces == CP => false,
+        (_, B2) if before_spaces == B2 => false,
+        // LB18: Break after spaces.
+        (SP, _) => true,
+        // LB19: Do not break before or after quotation marks.
+        (QU, _) | (_, QU) => false,
+        // LB21: Do not break before hyphens, small kana and other non-starters, nor after
+        // characters that attach to what follows.
+        (_, BA) | (_, HY) | (_, NS) | (BB, _) => false,
+        // LB21b: Do not break between a solidus and Hebrew letters.
+        (SY, HL) => false,
+        // LB22: Do not break before ellipses.
+        (_, IN) => false,
+        // LB23, LB23a, LB24: Do not break between letters and numbers, nor within prefixed or
+        // postfixed numbers and ideographs.
+        _ if is_alphabetic(a) && b == NU => false,
+        _ if a == NU && is_alphabetic(b) => false,
+        (PR, ID) | (ID, PO) => false,
+        _ if (a == PR || a == PO) && is_alphabetic(b) => false,
+        _ if is_alphabetic(a) && (b == PR || b == PO) => false,
+        // LB25: Do not break within numbers, like "$(12.35)" or "2,1%".
+        (CL, PO) | (CP, PO) | (CL, PR) | (CP, PR) | (NU, PO) | (NU, PR) |
+        (PO, OP) | (PO, NU) | (PR, OP) | (PR, NU) |
+        (HY, NU) | (IS, NU) | (NU, NU) | (SY, NU) => false,
+        // LB26, LB27: Do not break within Korean syllables.
+        (JL, JL) | (JL, JV) | (JL, H2) | (JL, H3) => false,
+        (JV, JV) | (JV, JT) | (H2, JV) | (H2, JT) => false,
+        (JT, JT) | (H3, JT) => false,
+        _ if is_hangul(a) && b == PO => false,
+        _ if a == PR && is_hangul(b) => false,
+        // LB28: Do not break between alphabetic characters.
+        _ if is_alphabetic(a) && is_alphabetic(b) => false,
+        // LB29: Do not break between numeric punctuation and letters, as in "e.g.".
+        _ if a == IS && is_alphabetic(b) => false,
+        // LB30: Do not break between letters or numbers and parentheses.
+        _ if (is_alphabetic(a) || a == NU) && b == OP => false,
+        _ if a == CP && (is_alphabetic(b) || b == NU) => false,
+        // LB30a: Break between pairs of regional indicators, but not within them.
+        (RI, RI) => regional_indicators % 2 == 0,
+        // LB31: Break everywhere else.
+        _ => true,
+    }
+}
+
+/// Tailors a class for `word-break` (CSS Text Level 3 § 5.2).
+fn resolve_class(class: LineBreakClass, word_break: word_break::T) -> LineBreakClass {
+    match word_break {
+        word_break::normal => class,
+        // Letters and digits break like ideographs.
+        word_break::break_all => match class {
+            AL | HL | NU => ID,
+            _ => class,
+        },
+        // Ideographs and Korean syllables hold together like letters.
+        word_break::keep_all => match class {
+            ID | JL | JV | JT | H2 | H3 => AL,
+            _ => class,
+        },
+    }
+}
+
+/// Returns true if `c` is a combining mark or a joiner, which belongs to the grapheme cluster of
+/// the character before it.
+pub fn is_combining_mark(c: char) -> bool {
+    match line_break_class(c) {
+        CM | ZWJ => true,
+        _ => false,
+    }
+}
+
+/// Returns the line breaking class of `c`, with rule LB1 applied: ambiguous, unknown and
+/// complex-context characters are alphabetic, and conditional Japanese starters are
+/// non-starters.
+fn line_break_class(c: char) -> LineBreakClass {
+    match c {
+        '\t' => BA,
+        '\n' => LF,
+        '\x0b' | '\x0c' => BK,
+        '\r' => CR,
+        '\x00' .. '\x08' | '\x0e' .. '\x1f' | '\x7f' .. '\x84' | '\x86' .. '\x9f' => CM,
+        ' ' => SP,
+        '!' | '?' => EX,
+        '"' | '\'' => QU,
+        '$' | '+' | '\\' => PR,
+        '%' => PO,
+        '(' | '[' | '{' => OP,
+        ')' | ']' => CP,
+        '}' => CL,
+        ',' | '.' | ':' | ';' => IS,
+        '-' => HY,
+        '/' => SY,
+        '0' .. '9' => NU,
+        '|' => BA,
+        '\x85' => NL,
+        '\xa0' => GL,
+        '\xa1' | '\xbf' => OP,
+        '\xa2' | '\xb0' => PO,
+        '\xa3' .. '\xa5' | '\xb1' => PR,
+        '\xab' | '\xbb' => QU,
+        '\xad' => BA,
+        '\xb4' => BB,
+        '\u0300' .. '\u036f' | '\u0483' .. '\u0489' | '\u0591' .. '\u05bd' |
+        '\u0610' .. '\u061a' | '\u064b' .. '\u065f' | '\u0900' .. '\u0903' |
+        '\u093a' .. '\u094f' | '\u1ab0' .. '\u1aff' | '\u1dc0' .. '\u1dff' |
+        '\u200c' | '\u20d0' .. '\u20ff' | '\u3099' | '\u309a' | '\ufe00' .. '\ufe0f' |
+        '\ufe20' .. '\ufe2f' => CM,
+        '\u05d0' .. '\u05ea' | '\u05f0' .. '\u05f2' | '\ufb1d' .. '\ufb4f' => HL,
+        '\u0660' .. '\u0669' | '\u06f0' .. '\u06f9' | '\u0966' .. '\u096f' => NU,
+        '\u1100' .. '\u115f' | '\ua960' .. '\ua97f' => JL,
+        '\u1160' .. '\u11a7' | '\ud7b0' .. '\ud7c6' => JV,
+        '\u11a8' .. '\u11ff' | '\ud7cb' .. '\ud7fb' => JT,
+        '\uac00' .. '\ud7a3' => {
+            // Syllables with no final consonant are LV; the rest are LVT.
+            if (c as u32 - 0xac00) % 28 == 0 { H2 } else { H3 }
+        }
+        '\u2000' .. '\u2006' | '\u2008' .. '\u200a' | '\u2010' | '\u2012' | '\u2013' => BA,
+        '\u2007' | '\u2011' | '\u202f' => GL,
+        '\u200b' => ZW,
+        '\u200d' => ZWJ,
+        '\u2014' => B2,
+        '\u2018' | '\u2019' | '\u201b' .. '\u201f' | '\u2039' | '\u203a' => QU,
+        '\u201a' | '\u201e' => OP,
+        '\u2024' .. '\u2026' => IN,
+        '\u2030' .. '\u2037' | '\u2103' | '\u2109' => PO,
+        '\u2044' => IS,
+        '\u2060' | '\ufeff' => WJ,
+        '\u20a0' .. '\u20cf' | '\u2116' => PR,
+        '\u3000' => BA,
+        '\u3001' | '\u3002' | '\ufe50' | '\ufe52' | '\uff0c' | '\uff0e' | '\uff61' |
+        '\uff64' => CL,
+        '\u3008' | '\u300a' | '\u300c' | '\u300e' | '\u3010' | '\u3014' | '\u3016' |
+        '\u3018' | '\u301a' | '\u301d' | '\uff08' | '\uff3b' | '\uff5b' | '\uff5f' |
+        '\uff62' => OP,
+        '\u3009' | '\u300b' | '\u300d' | '\u300f' | '\u3011' | '\u3015' | '\u3017' |
+        '\u3019' | '\u301b' | '\u301e' | '\u301f' | '\uff3d' | '\uff5d' | '\uff60' |
+        '\uff63' => CL,
+        '\uff09' => CP,
+        '\uff01' | '\uff1f' => EX,
+        '\uff1a' | '\uff1b' => NS,
+        '\uff04' | '\uffe1' | '\uffe5' | '\uffe6' => PR,
+        '\uff05' | '\uffe0' => PO,
+        // Small kana, the prolonged sound mark and iteration marks (CJ and NS).
+        '\u3005' | '\u303b' | '\u3041' | '\u3043' | '\u3045' | '\u3047' | '\u3049' |
+        '\u3063' | '\u3083' | '\u3085' | '\u3087' | '\u308e' | '\u3095' | '\u3096' |
+        '\u309b' .. '\u309e' | '\u30a0' | '\u30a1' | '\u30a3' | '\u30a5' | '\u30a7' |
+        '\u30a9' | '\u30c3' | '\u30e3' | '\u30e5' | '\u30e7' | '\u30ee' | '\u30f5' |
+        '\u30f6' | '\u30fb' .. '\u30fe' | '\u31f0' .. '\u31ff' | '\uff67' .. '\uff70' |
+        '\uff9e' | '\uff9f' => NS,
+        '\u2e80' .. '\u2fff' | '\u3003' | '\u3004' | '\u3006' | '\u3007' | '\u3012' |
+        '\u3013' | '\u3020' .. '\u303a' | '\u303c' .. '\u303f' | '\u3040' .. '\u30ff' |
+        '\u3100' .. '\u31ef' | '\u3200' .. '\u4dbf' | '\u4e00' .. '\u9fff' |
+        '\ua000' .. '\ua4cf' | '\uf900' .. '\ufaff' | '\ufe30' .. '\ufe4f' |
+        '\uff00' .. '\uff60' | '\uffe2' .. '\uffe4' | '\U0001f000' .. '\U0001f1e5' |
+        '\U0001f200' .. '\U0001f64f' | '\U0001f680' .. '\U0001f6ff' |
+        '\U00020000' .. '\U0003fffd' => ID,
+        '\U0001f1e6' .. '\U0001f1ff' => RI,
+        _ => AL,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::break_opportunities;
+    use style::computed_values::word_break;
+
+    /// Returns the pieces of `text` between its break opportunities.
+    fn pieces(text: &str, word_break: word_break::T) -> ~[~str] {
+        let mut pieces = ~[];
+        let mut piece = ~"";
+        let opportunities = break_opportunities(text, word_break);
+        for (c, &can_break) in text.chars().zip(opportunities.iter()) {
+            if can_break {
+                pieces.push(piece);
+                piece = ~"";
+            }
+            piece.push_char(c);
+        }
+        pieces.push(piece);
+        pieces
+    }
+
+    #[test]
+    fn test_latin() {
+        assert_eq!(pieces("Hello, world!", word_break::normal), ~[~"Hello, ", ~"world!"]);
+        assert_eq!(pieces("well-known (e.g. $12.50)", word_break::normal),
+                   ~[~"well-", ~"known ", ~"(e.g. ", ~"$12.50)"]);
+        assert_eq!(pieces("a\u00a0b c", word_break::normal), ~[~"a\u00a0b ", ~"c"]);
+    }
+
+    #[test]
+    fn test_url() {
+        assert_eq!(pieces("http://example.com/a/b", word_break::normal),
+                   ~[~"http://", ~"example.com/", ~"a/", ~"b"]);
+    }
+
+    #[test]
+    fn test_cjk() {
+        assert_eq!(pieces("日本語。", word_break::normal), ~[~"日", ~"本", ~"語。"]);
+        assert_eq!(pieces("ちょっと", word_break::normal), ~[~"ちょっ", ~"と"]);
+        assert_eq!(pieces("「日本」", word_break::normal), ~[~"「日", ~"本」"]);
+        assert_eq!(pieces("한국어 텍스트", word_break::normal),
+                   ~[~"한", ~"국", ~"어 ", ~"텍", ~"스", ~"트"]);
+    }
+
+    #[test]
+    fn test_word_break() {
+        assert_eq!(pieces("abc", word_break::break_all), ~[~"a", ~"b", ~"c"]);
+        assert_eq!(pieces("한국어 텍스트", word_break::keep_all), ~[~"한국어 ", ~"텍스트"]);
+    }
+}
@@ -11,8 +11,10 @@ servo.rc. This is not ideal and may be changed in the future. */
 pub use text::shaping::Shaper;
 pub use text::text_run::TextRun;
 
+pub mod bidi;
 pub mod glyph;
 #[path="shaping/mod.rs"] pub mod shaping;
+pub mod line_break;
 pub mod text_run;
 pub mod util;
 
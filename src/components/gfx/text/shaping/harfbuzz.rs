@@ -11,13 +11,14 @@ use text::shaping::ShaperMethods;
 use text::util::{float_to_fixed, fixed_to_float};
 
 use geom::Point2D;
-use harfbuzz::{HB_MEMORY_MODE_READONLY, HB_DIRECTION_LTR};
+use harfbuzz::{HB_MEMORY_MODE_READONLY, HB_DIRECTION_LTR, HB_DIRECTION_RTL};
 use harfbuzz::{hb_blob_create, hb_face_create_for_tables};
 use harfbuzz::{hb_blob_t};
 use harfbuzz::{hb_bool_t};
 use harfbuzz::{hb_buffer_add_utf8};
 use harfbuzz::{hb_buffer_destroy};
 use harfbuzz::{hb_buffer_get_glyph_positions};
+use harfbuzz::{hb_buffer_reverse};
 use harfbuzz::{hb_buffer_set_direction};
 use harfbuzz::{hb_face_destroy};
 use harfbuzz::{hb_face_t, hb_font_t};
@@ -201,10 +202,11 @@ impl Shaper {
 impl ShaperMethods for Shaper {
     /// Calculate the layout metrics associated with the given text when rendered in a specific
     /// font.
-    fn shape_text(&self, text: &str, glyphs: &mut GlyphStore) {
+    fn shape_text(&self, text: &str, is_rtl: bool, glyphs: &mut GlyphStore) {
         unsafe {
             let hb_buffer: *hb_buffer_t = hb_buffer_create();
-            hb_buffer_set_direction(hb_buffer, HB_DIRECTION_LTR);
+            let direction = if is_rtl { HB_DIRECTION_RTL } else { HB_DIRECTION_LTR };
+            hb_buffer_set_direction(hb_buffer, direction);
 
             // Using as_imm_buf because it never does a copy - we don't need the trailing null
             hb_buffer_add_utf8(hb_buffer,
@@ -214,6 +216,12 @@ impl ShaperMethods for Shaper {
                                text.len() as c_int);
 
             hb_shape(self.hb_font, hb_buffer, null(), 0);
+
+            // Right-to-left text comes back in visual order, but glyph stores are kept in
+            // logical order.
+            if is_rtl {
+                hb_buffer_reverse(hb_buffer);
+            }
             self.save_glyph_results(text, glyphs, hb_buffer);
             hb_buffer_destroy(hb_buffer);
         }
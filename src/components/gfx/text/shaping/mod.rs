@@ -14,6 +14,6 @@ pub use Shaper = text::shaping::harfbuzz::Shaper;
 pub mod harfbuzz;
 
 pub trait ShaperMethods {
-    fn shape_text(&self, text: &str, glyphs: &mut GlyphStore);
+    fn shape_text(&self, text: &str, is_rtl: bool, glyphs: &mut GlyphStore);
 }
 
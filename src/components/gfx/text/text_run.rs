@@ -6,19 +6,41 @@ use extra::arc::Arc;
 use font::{Font, FontDescriptor, RunMetrics, FontStyle, FontMetrics};
 use servo_util::geometry::Au;
 use servo_util::range::Range;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::vec;
 use std::vec::VecIterator;
-use style::computed_values::text_decoration;
+use style::computed_values::{text_decoration, word_break};
 use text::glyph::GlyphStore;
+use text::line_break;
 
 /// A text run.
 #[deriving(Clone)]
 pub struct TextRun {
     text: Arc<~str>,
+    /// The primary font of the run, which its metrics come from.
     font_descriptor: FontDescriptor,
     font_metrics: FontMetrics,
     font_style: FontStyle,
     decoration: text_decoration::T,
     glyphs: Arc<~[Arc<GlyphStore>]>,
+    /// The fonts that the glyphs were shaped with. The first is the primary font; the others are
+    /// the fonts that characters missing from it fell back to.
+    font_descriptors: ~[FontDescriptor],
+    /// For each glyph store, the index into `font_descriptors` of the font it was shaped with.
+    glyph_store_fonts: Arc<~[uint]>,
+    /// For each glyph store, whether a line may break before it. A store also starts wherever the
+    /// font changes, which is not a break opportunity. This lives on the run because the shape
+    /// cache shares glyph stores between runs.
+    glyph_store_breaks: Arc<~[bool]>,
+    /// The metrics of each of `font_descriptors`.
+    all_font_metrics: ~[FontMetrics],
+    /// The extra space after each character, from `letter-spacing`.
+    letter_spacing: Au,
+    /// The extra space after each word separator, from `word-spacing`.
+    word_spacing: Au,
+    /// Whether the run is right-to-left text, whose glyphs are drawn from right to left.
+    is_rtl: bool,
 }
 
 pub struct SliceIterator<'a> {
@@ -96,16 +118,35 @@ impl<'a> Iterator<Range> for LineIterator<'a> {
 }
 
 impl<'a> TextRun {
-    pub fn new(font: &mut Font, text: ~str, decoration: text_decoration::T) -> TextRun {
-        let glyphs = TextRun::break_and_shape(font, text);
+    /// Creates a text run, shaping each grapheme cluster with the first of `fonts` that has glyphs
+    /// for it.
+    pub fn new(fonts: &[Rc<RefCell<Font>>],
+               text: ~str,
+               decoration: text_decoration::T,
+               word_break: word_break::T,
+               is_rtl: bool)
+               -> TextRun {
+        assert!(fonts.len() > 0);
+        let (glyphs, glyph_store_fonts, glyph_store_breaks) =
+            TextRun::break_and_shape(fonts, text, word_break, is_rtl);
 
+        let font_descriptors = fonts.map(|font| font.borrow().with(|font| font.get_descriptor()));
+        let all_font_metrics = fonts.map(|font| font.borrow().with(|font| font.metrics.clone()));
+        let font_style = fonts[0].borrow().with(|font| font.style.clone());
         let run = TextRun {
             text: Arc::new(text),
-            font_style: font.style.clone(),
-            font_metrics: font.metrics.clone(),
-            font_descriptor: font.get_descriptor(),
+            font_style: font_style,
+            font_metrics: all_font_metrics[0].clone(),
+            font_descriptor: font_descriptors[0].clone(),
             decoration: decoration,
             glyphs: Arc::new(glyphs),
+            font_descriptors: font_descriptors,
+            glyph_store_fonts: Arc::new(glyph_store_fonts),
+            glyph_store_breaks: Arc::new(glyph_store_breaks),
+            all_font_metrics: all_font_metrics,
+            letter_spacing: Au(0),
+            word_spacing: Au(0),
+            is_rtl: is_rtl,
         };
         return run;
     }
@@ -113,59 +154,77 @@ impl<'a> TextRun {
     pub fn teardown(&self) {
     }
 
-    pub fn break_and_shape(font: &mut Font, text: &str) -> ~[Arc<GlyphStore>] {
-        // TODO(Issue #230): do a better job. See Gecko's LineBreaker.
+    /// Divides `text` into slices and shapes each of them. Returns the glyph stores, the index into
+    /// `fonts` of the font that each was shaped with, and whether a line may break before each.
+    pub fn break_and_shape(fonts: &[Rc<RefCell<Font>>],
+                           text: &str,
+                           word_break: word_break::T,
+                           is_rtl: bool)
+                           -> (~[Arc<GlyphStore>], ~[uint], ~[bool]) {
+        // Slices end wherever a line may break, wherever whitespace starts or ends so that
+        // whitespace can be trimmed at the ends of lines, and wherever the font changes.
+        let break_opportunities = line_break::break_opportunities(text, word_break);
+        let font_indices = font_indices_for_text(text, fonts.len(), |font_index, c| {
+            fonts[font_index].borrow().with(|font| font.glyph_index(c).is_some())
+        });
 
         let mut glyphs = ~[];
+        let mut glyph_store_fonts = ~[];
+        let mut glyph_store_breaks = ~[];
+        let mut cur_slice_can_break_before = true;
         let mut byte_i = 0u;
+        let mut char_i = 0u;
         let mut cur_slice_is_whitespace = false;
+        let mut cur_font_index = 0u;
         let mut byte_last_boundary = 0;
         while byte_i < text.len() {
             let range = text.char_range_at(byte_i);
             let ch = range.ch;
             let next = range.next;
 
-            // Slices alternate between whitespace and non-whitespace,
-            // representing line break opportunities.
-            let can_break_before = if cur_slice_is_whitespace {
-                match ch {
-                    ' ' | '\t' | '\n' => false,
-                    _ => {
-                        cur_slice_is_whitespace = false;
-                        true
-                    }
-                }
-            } else {
-                match ch {
-                    ' ' | '\t' | '\n' => {
-                        cur_slice_is_whitespace = true;
-                        true
-                    },
-                    _ => false
-                }
+            let is_whitespace = match ch {
+                ' ' | '\t' | '\n' => true,
+                _ => false,
             };
+            let font_index = font_indices[char_i];
+            let can_break_before = is_whitespace != cur_slice_is_whitespace ||
+                break_opportunities[char_i];
 
             // Create a glyph store for this slice if it's nonempty.
-            if can_break_before && byte_i > byte_last_boundary {
+            if (can_break_before || font_index != cur_font_index) && byte_i > byte_last_boundary {
                 let slice = text.slice(byte_last_boundary, byte_i).to_owned();
-                debug!("creating glyph store for slice {} (ws? {}), {} - {} in run {}",
-                        slice, !cur_slice_is_whitespace, byte_last_boundary, byte_i, text);
-                glyphs.push(font.shape_text(slice, !cur_slice_is_whitespace));
+                debug!("creating glyph store for slice {} (ws? {}, font {}), {} - {} in run {}",
+                        slice, cur_slice_is_whitespace, cur_font_index, byte_last_boundary, byte_i,
+                        text);
+                glyphs.push(fonts[cur_font_index].borrow().with_mut(|font| {
+                    font.shape_text(slice.clone(), cur_slice_is_whitespace, is_rtl)
+                }));
+                glyph_store_fonts.push(cur_font_index);
+                glyph_store_breaks.push(cur_slice_can_break_before);
+                cur_slice_can_break_before = can_break_before;
                 byte_last_boundary = byte_i;
             }
 
+            cur_slice_is_whitespace = is_whitespace;
+            cur_font_index = font_index;
             byte_i = next;
+            char_i += 1;
         }
 
         // Create a glyph store for the final slice if it's nonempty.
         if byte_i > byte_last_boundary {
             let slice = text.slice_from(byte_last_boundary).to_owned();
-            debug!("creating glyph store for final slice {} (ws? {}), {} - {} in run {}",
-                slice, cur_slice_is_whitespace, byte_last_boundary, text.len(), text);
-            glyphs.push(font.shape_text(slice, cur_slice_is_whitespace));
+            debug!("creating glyph store for final slice {} (ws? {}, font {}), {} - {} in run {}",
+                slice, cur_slice_is_whitespace, cur_font_index, byte_last_boundary, text.len(),
+                text);
+            glyphs.push(fonts[cur_font_index].borrow().with_mut(|font| {
+                font.shape_text(slice.clone(), cur_slice_is_whitespace, is_rtl)
+            }));
+            glyph_store_fonts.push(cur_font_index);
+            glyph_store_breaks.push(cur_slice_can_break_before);
         }
 
-        glyphs
+        (glyphs, glyph_store_fonts, glyph_store_breaks)
     }
 
     pub fn char_len(&self) -> uint {
@@ -186,35 +245,133 @@ impl<'a> TextRun {
     }
 
     pub fn metrics_for_range(&self, range: &Range) -> RunMetrics {
-        // TODO(Issue #199): alter advance direction for RTL
-        // TODO(Issue #98): using inter-char and inter-word spacing settings  when measuring text
-        let mut advance = Au(0);
+        let mut advance = self.spacing_for_range(range);
         for (glyphs, _offset, slice_range) in self.iter_slices_for_range(range) {
             for (_i, glyph) in glyphs.iter_glyphs_for_char_range(&slice_range) {
                 advance = advance + glyph.advance();
             }
         }
-        RunMetrics::new(advance, self.font_metrics.ascent, self.font_metrics.descent)
+        let (ascent, descent) = self.ascent_and_descent_for_range(range);
+        RunMetrics::new(advance, ascent, descent)
+    }
+
+    /// Returns the advance of `range` once justification has added `extra_word_spacing` after each
+    /// of its word separators.
+    pub fn justified_advance_for_range(&self, range: &Range, extra_word_spacing: Au) -> Au {
+        let advance = self.metrics_for_range(range).advance_width;
+        if extra_word_spacing == Au(0) {
+            return advance
+        }
+        advance + extra_word_spacing.scale_by(self.word_separator_count_for_range(range) as f64)
     }
 
-    pub fn metrics_for_slice(&self, glyphs: &GlyphStore, slice_range: &Range) -> RunMetrics {
-        let mut advance = Au(0);
-        for (_i, glyph) in glyphs.iter_glyphs_for_char_range(slice_range) {
-            advance = advance + glyph.advance();
+    /// Returns the largest ascent and descent of the primary font and the fonts that the
+    /// characters in `range` fell back to, so that glyphs from taller fonts fit in the line.
+    pub fn ascent_and_descent_for_range(&self, range: &Range) -> (Au, Au) {
+        let mut ascent = self.font_metrics.ascent;
+        let mut descent = self.font_metrics.descent;
+        for &(font_index, _) in self.font_ranges_for_range(range).iter() {
+            let metrics = &self.all_font_metrics[font_index];
+            ascent = Au::max(ascent, metrics.ascent);
+            descent = Au::max(descent, metrics.descent);
         }
-        RunMetrics::new(advance, self.font_metrics.ascent, self.font_metrics.descent)
+        (ascent, descent)
     }
+
+    /// Returns the space that letter and word spacing add after character `i` of the glyph store
+    /// `glyphs`.
+    pub fn spacing_after_char(&self, glyphs: &GlyphStore, i: uint) -> Au {
+        if glyphs.char_is_space(i) {
+            self.letter_spacing + self.word_spacing
+        } else {
+            self.letter_spacing
+        }
+    }
+
+    /// Returns the space that letter and word spacing add to the characters in `range`.
+    pub fn spacing_for_range(&self, range: &Range) -> Au {
+        if self.letter_spacing == Au(0) && self.word_spacing == Au(0) {
+            return Au(0)
+        }
+        self.iter_slices_for_range(range).fold(Au(0), |sum, (glyphs, _, slice_range)| {
+            sum + self.spacing_for_slice(glyphs, &slice_range)
+        })
+    }
+
+    /// Like `spacing_for_range`, for the part `slice_range` of the glyph store `glyphs`.
+    fn spacing_for_slice(&self, glyphs: &GlyphStore, slice_range: &Range) -> Au {
+        if self.letter_spacing == Au(0) && self.word_spacing == Au(0) {
+            return Au(0)
+        }
+        slice_range.eachi().fold(Au(0), |sum, i| sum + self.spacing_after_char(glyphs, i))
+    }
+
+    /// Returns the number of word separators in `range`, which are where justification adds
+    /// space.
+    pub fn word_separator_count_for_range(&self, range: &Range) -> uint {
+        self.iter_slices_for_range(range).fold(0, |count, (glyphs, _, slice_range)| {
+            count + slice_range.eachi().count(|i| glyphs.char_is_space(i))
+        })
+    }
+
+    /// Returns the number of word separators at the end of `range`, after its last other
+    /// character.
+    pub fn trailing_word_separator_count_for_range(&self, range: &Range) -> uint {
+        let mut count = 0;
+        for (glyphs, _, slice_range) in self.iter_slices_for_range(range) {
+            for i in slice_range.eachi() {
+                count = if glyphs.char_is_space(i) { count + 1 } else { 0 }
+            }
+        }
+        count
+    }
+
+    /// Returns the number of whitespace characters at the end of `range`, after its last other
+    /// character.
+    pub fn trailing_whitespace_count_for_range(&self, range: &Range) -> uint {
+        let mut count = 0;
+        for (glyphs, _, slice_range) in self.iter_slices_for_range(range) {
+            count = if glyphs.is_whitespace() { count + slice_range.length() } else { 0 }
+        }
+        count
+    }
+
     pub fn min_width_for_range(&self, range: &Range) -> Au {
         let mut max_piece_width = Au(0);
         debug!("iterating outer range {:?}", range);
-        for (_, offset, slice_range) in self.iter_slices_for_range(range) {
-            debug!("iterated on {:?}[{:?}]", offset, slice_range);
-            let metrics = self.metrics_for_range(&slice_range);
+        for &(ref piece, _) in self.break_pieces_for_range(range).iter() {
+            debug!("iterated on {:?}", piece);
+            let metrics = self.metrics_for_range(piece);
             max_piece_width = Au::max(max_piece_width, metrics.advance_width);
         }
         max_piece_width
     }
 
+    /// Divides `range` into the pieces between the glyph store boundaries where a line may break.
+    /// Returns each piece with whether it is whitespace.
+    pub fn break_pieces_for_range(&self, range: &Range) -> ~[(Range, bool)] {
+        let mut pieces: ~[(Range, bool)] = ~[];
+        let mut offset = 0;
+        let glyph_store_breaks = self.glyph_store_breaks.get();
+        for (glyphs, &can_break_before) in self.glyphs.get().iter().zip(glyph_store_breaks.iter()) {
+            let char_range = range.intersect(&Range::new(offset, glyphs.char_len()));
+            offset += glyphs.char_len();
+            if char_range.is_empty() {
+                continue
+            }
+
+            if can_break_before || pieces.is_empty() {
+                pieces.push((char_range, glyphs.is_whitespace()));
+            } else {
+                let last = pieces.len() - 1;
+                match pieces[last] {
+                    (ref mut piece, _) => piece.extend_by(char_range.length() as int),
+                }
+            }
+        }
+        pieces
+    }
+
     pub fn iter_slices_for_range(&'a self, range: &Range) -> SliceIterator<'a> {
         SliceIterator {
             glyph_iter: self.glyphs.get().iter(),
@@ -223,6 +380,37 @@ impl<'a> TextRun {
         }
     }
 
+    /// Divides `range` into the ranges that were shaped with the same font. Returns the index into
+    /// `font_descriptors` of the font of each range, in order.
+    pub fn font_ranges_for_range(&self, range: &Range) -> ~[(uint, Range)] {
+        let mut font_ranges = ~[];
+        let mut current_font = None;
+        let mut current_range = Range::empty();
+        let mut offset = 0;
+        let glyph_store_fonts = self.glyph_store_fonts.get();
+        for (glyphs, &font_index) in self.glyphs.get().iter().zip(glyph_store_fonts.iter()) {
+            let char_range = range.intersect(&Range::new(offset, glyphs.char_len()));
+            offset += glyphs.char_len();
+            if char_range.is_empty() {
+                continue
+            }
+
+            if current_font == Some(font_index) {
+                current_range.extend_by(char_range.length() as int);
+            } else {
+                for &font in current_font.iter() {
+                    font_ranges.push((font, current_range));
+                }
+                current_font = Some(font_index);
+                current_range = char_range;
+            }
+        }
+        for &font in current_font.iter() {
+            font_ranges.push((font, current_range));
+        }
+        font_ranges
+    }
+
     pub fn iter_natural_lines_for_range(&'a self, range: &Range) -> LineIterator<'a> {
         LineIterator {
             range:  *range,
@@ -231,3 +419,249 @@ impl<'a> TextRun {
         }
     }
 }
+
+/// Returns, for each character of `text`, the index of the font to shape it with. `has_glyph`
+/// says whether the font with the given index, of `font_count`, has a glyph for a character.
+/// Combining marks stay in the font of their base character, and whitespace stays in the font of
+/// the text before it.
+fn font_indices_for_text(text: &str, font_count: uint, has_glyph: |uint, char| -> bool)
+                         -> ~[uint] {
+    let chars: ~[char] = text.chars().collect();
+    let mut font_indices = vec::with_capacity(chars.len());
+    let mut cur_font_index = 0u;
+    let mut cluster_start = 0;
+    while cluster_start < chars.len() {
+        let mut cluster_end = cluster_start + 1;
+        while cluster_end < chars.len() && line_break::is_combining_mark(chars[cluster_end]) {
+            cluster_end += 1;
+        }
+
+        let cluster = chars.slice(cluster_start, cluster_end);
+        cur_font_index = match cluster[0] {
+            ' ' | '\t' | '\n' => cur_font_index,
+            _ => font_index_for_cluster(cluster, font_count, |i, c| has_glyph(i, c)),
+        };
+        for _ in cluster.iter() {
+            font_indices.push(cur_font_index);
+        }
+        cluster_start = cluster_end;
+    }
+    font_indices
+}
+
+/// Returns the index of the first font that has glyphs for all the characters of `cluster`. If
+/// there is none, returns that of the first font with a glyph for its base character, or of the
+/// first font if none has one either.
+fn font_index_for_cluster(cluster: &[char], font_count: uint, has_glyph: |uint, char| -> bool)
+                          -> uint {
+    let mut base_font_index = None;
+    for font_index in range(0, font_count) {
+        if cluster.iter().all(|&c| has_glyph(font_index, c)) {
+            return font_index
+        }
+        if base_font_index.is_none() && has_glyph(font_index, cluster[0]) {
+            base_font_index = Some(font_index);
+        }
+    }
+    base_font_index.unwrap_or(0)
+}
+
+/// The characters that `word-spacing` applies to (CSS 2.1 § 16.4). Shaping marks them in the
+/// glyph stores of a run.
+pub fn is_word_separator(c: char) -> bool {
+    c == ' ' || c == '\u00a0'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TextRun, font_indices_for_text, is_word_separator};
+    use extra::arc::Arc;
+    use font::{FontDescriptor, FontMetrics, FontStyle, SelectorPlatformIdentifier};
+    use servo_util::geometry::Au;
+    use servo_util::range::Range;
+    use style::computed_values::{font_style, font_weight, text_decoration};
+    use text::glyph::{GlyphData, GlyphStore};
+
+    /// Returns a glyph store for `text` in which every character has one glyph, 10 units wide.
+    fn glyph_store(text: &str) -> Arc<GlyphStore> {
+        let is_whitespace = text.chars().all(|c| c == ' ');
+        let mut glyphs = GlyphStore::new(text.char_len(), is_whitespace);
+        for (i, c) in text.chars().enumerate() {
+            glyphs.add_glyph_for_char_index(i, &GlyphData::new(i as u32, Au(10), None, false,
+                                                               true, true));
+            if is_word_separator(c) {
+                glyphs.set_char_is_space(i)
+            }
+        }
+        glyphs.finalize_changes();
+        Arc::new(glyphs)
+    }
+
+    fn font_metrics(ascent: Au, descent: Au) -> FontMetrics {
+        FontMetrics {
+            underline_size: Au(0),
+            underline_offset: Au(0),
+            strikeout_size: Au(0),
+            strikeout_offset: Au(0),
+            leading: Au(0),
+            x_height: Au(0),
+            em_size: ascent + descent,
+            ascent: ascent,
+            descent: descent,
+            max_advance: Au(10),
+        }
+    }
+
+    /// Returns a run of `texts`, each shaped into its own glyph store with the primary font.
+    fn text_run(texts: &[&str], letter_spacing: Au, word_spacing: Au) -> TextRun {
+        let fonts = texts.map(|_| 0u);
+        let breaks = texts.map(|_| true);
+        text_run_in_fonts(texts, fonts, breaks, ~[font_metrics(Au(12), Au(4))], letter_spacing,
+                          word_spacing)
+    }
+
+    /// Returns a run of `texts`, each shaped into its own glyph store with the font of the same
+    /// index in `fonts`, which have the metrics `all_font_metrics`. A line may break before the
+    /// stores for which `breaks` is true.
+    fn text_run_in_fonts(texts: &[&str],
+                         fonts: ~[uint],
+                         breaks: ~[bool],
+                         all_font_metrics: ~[FontMetrics],
+                         letter_spacing: Au,
+                         word_spacing: Au)
+                         -> TextRun {
+        let style = FontStyle {
+            pt_size: 16.0,
+            weight: font_weight::Weight400,
+            style: font_style::normal,
+            families: ~[~"serif"],
+        };
+        let descriptor = FontDescriptor::new(style, SelectorPlatformIdentifier(~"test"));
+        TextRun {
+            text: Arc::new(texts.concat()),
+            font_descriptor: descriptor.clone(),
+            font_metrics: all_font_metrics[0].clone(),
+            font_style: descriptor.style.clone(),
+            decoration: text_decoration::none,
+            glyphs: Arc::new(texts.iter().map(|text| glyph_store(*text)).collect()),
+            font_descriptors: all_font_metrics.map(|_| descriptor.clone()),
+            glyph_store_fonts: Arc::new(fonts),
+            glyph_store_breaks: Arc::new(breaks),
+            all_font_metrics: all_font_metrics,
+            letter_spacing: letter_spacing,
+            word_spacing: word_spacing,
+            is_rtl: false,
+        }
+    }
+
+    #[test]
+    fn test_spacing_for_range() {
+        let run = text_run(["ab ", "c d"], Au(1), Au(5));
+        assert_eq!(run.spacing_for_range(&Range::new(0, 6)), Au(6 + 2 * 5));
+        assert_eq!(run.spacing_for_range(&Range::new(1, 3)), Au(3 + 5));
+        assert_eq!(run.metrics_for_range(&Range::new(0, 2)).advance_width, Au(22));
+
+        let unspaced = text_run(["ab ", "c d"], Au(0), Au(0));
+        assert_eq!(unspaced.spacing_for_range(&Range::new(0, 6)), Au(0));
+        assert_eq!(run.justified_advance_for_range(&Range::new(0, 6), Au(3)), Au(76 + 2 * 3));
+    }
+
+    #[test]
+    fn test_word_separator_counts() {
+        let run = text_run(["a b ", " ", "c  "], Au(0), Au(0));
+        assert_eq!(run.word_separator_count_for_range(&Range::new(0, 8)), 5);
+        assert_eq!(run.word_separator_count_for_range(&Range::new(1, 2)), 1);
+        assert_eq!(run.trailing_word_separator_count_for_range(&Range::new(0, 8)), 2);
+        assert_eq!(run.trailing_word_separator_count_for_range(&Range::new(0, 5)), 2);
+        assert_eq!(run.trailing_word_separator_count_for_range(&Range::new(0, 3)), 0);
+        assert_eq!(run.trailing_word_separator_count_for_range(&Range::new(3, 2)), 2);
+    }
+
+    #[test]
+    fn test_trailing_whitespace_count() {
+        let run = text_run(["ab", "  ", "c", " "], Au(0), Au(0));
+        assert_eq!(run.trailing_whitespace_count_for_range(&Range::new(0, 6)), 1);
+        assert_eq!(run.trailing_whitespace_count_for_range(&Range::new(0, 4)), 2);
+        assert_eq!(run.trailing_whitespace_count_for_range(&Range::new(1, 2)), 1);
+        assert_eq!(run.trailing_whitespace_count_for_range(&Range::new(0, 5)), 0);
+        assert_eq!(run.trailing_whitespace_count_for_range(&Range::new(2, 2)), 2);
+    }
+
+    /// Returns the font indices that `font_indices_for_text` picks for `text` from fonts that
+    /// have glyphs for the characters in `font_chars`.
+    fn font_indices(text: &str, font_chars: &[&str]) -> ~[uint] {
+        font_indices_for_text(text, font_chars.len(), |font_index, c| {
+            font_chars[font_index].contains_char(c)
+        })
+    }
+
+    #[test]
+    fn test_font_indices_for_text() {
+        let fonts = ["ab ", "\u0301a", "\u4e2d"];
+        assert_eq!(font_indices("ab", fonts), ~[0, 0]);
+        assert_eq!(font_indices("a\u4e2db", fonts), ~[0, 2, 0]);
+        // Characters that no font has go to the primary font.
+        assert_eq!(font_indices("a?", fonts), ~[0, 0]);
+        // Whitespace stays in the font of the text before it.
+        assert_eq!(font_indices("\u4e2d a", fonts), ~[2, 2, 0]);
+        assert_eq!(font_indices(" a", fonts), ~[0, 0]);
+        // Grapheme clusters go to the first font with all their characters, or else to the font
+        // of their base character.
+        assert_eq!(font_indices("ba\u0301b", fonts), ~[0, 1, 1, 0]);
+        assert_eq!(font_indices("b\u0301", fonts), ~[0, 0]);
+        assert_eq!(font_indices("\u4e2d\u0301\u200d", fonts), ~[2, 2, 2]);
+    }
+
+    /// Returns the font index, start and length of each font range of `range` in `run`.
+    fn font_ranges(run: &TextRun, begin: uint, length: uint) -> ~[(uint, uint, uint)] {
+        run.font_ranges_for_range(&Range::new(begin, length)).map(|&(font_index, ref range)| {
+            (font_index, range.begin(), range.length())
+        })
+    }
+
+    #[test]
+    fn test_font_ranges_for_range() {
+        let metrics = ~[font_metrics(Au(12), Au(4)), font_metrics(Au(20), Au(2))];
+        let breaks = ~[true, true, true, true];
+        let run = text_run_in_fonts(["ab", "cd", "e", "fg"], ~[0, 1, 1, 0], breaks, metrics, Au(0),
+                                    Au(0));
+        assert_eq!(font_ranges(&run, 0, 7), ~[(0, 0, 2), (1, 2, 3), (0, 5, 2)]);
+        assert_eq!(font_ranges(&run, 1, 3), ~[(0, 1, 1), (1, 2, 2)]);
+        assert_eq!(font_ranges(&run, 3, 2), ~[(1, 3, 2)]);
+        assert_eq!(font_ranges(&run, 3, 0), ~[]);
+    }
+
+    #[test]
+    fn test_metrics_cover_fallback_fonts() {
+        let metrics = ~[font_metrics(Au(12), Au(4)), font_metrics(Au(20), Au(2))];
+        let run = text_run_in_fonts(["ab", "cd"], ~[0, 1], ~[true, true], metrics, Au(0), Au(0));
+        let primary = run.metrics_for_range(&Range::new(0, 2));
+        assert_eq!((primary.ascent, primary.descent), (Au(12), Au(4)));
+        let mixed = run.metrics_for_range(&Range::new(1, 2));
+        assert_eq!((mixed.ascent, mixed.descent), (Au(20), Au(4)));
+        assert_eq!(mixed.bounding_box.size.height, Au(24));
+        // The line box keeps the strut of the primary font.
+        let fallback = run.metrics_for_range(&Range::new(2, 2));
+        assert_eq!((fallback.ascent, fallback.descent), (Au(20), Au(4)));
+    }
+
+    /// Returns the start, length and whitespace flag of each break piece of `range` in `run`.
+    fn break_pieces(run: &TextRun, begin: uint, length: uint) -> ~[(uint, uint, bool)] {
+        run.break_pieces_for_range(&Range::new(begin, length)).map(|&(ref range, is_whitespace)| {
+            (range.begin(), range.length(), is_whitespace)
+        })
+    }
+
+    #[test]
+    fn test_mixed_font_word_does_not_break() {
+        // "ab\u4e2dcd ef", where the ideograph falls back to another font in the middle of a word.
+        let metrics = ~[font_metrics(Au(12), Au(4)), font_metrics(Au(20), Au(2))];
+        let run = text_run_in_fonts(["ab", "\u4e2d", "cd", " ", "ef"], ~[0, 1, 0, 0, 0],
+                                    ~[true, false, false, true, true], metrics, Au(0), Au(0));
+        assert_eq!(break_pieces(&run, 0, 8), ~[(0, 5, false), (5, 1, true), (6, 2, false)]);
+        assert_eq!(break_pieces(&run, 1, 3), ~[(1, 3, false)]);
+        assert_eq!(break_pieces(&run, 3, 4), ~[(3, 2, false), (5, 1, true), (6, 1, false)]);
+        assert_eq!(run.min_width_for_range(&Range::new(0, 8)), Au(50));
+        assert_eq!(run.min_width_for_range(&Range::new(5, 3)), Au(20));
+    }
+}
@@ -50,7 +50,22 @@ pub fn transform_text(text: &str, mode: CompressionMode, incoming_whitespace: bo
 
         CompressWhitespace | CompressWhitespaceNewline => {
             let mut in_whitespace: bool = incoming_whitespace;
+            let mut new_line_index = 0;
             for ch in text.chars() {
+                if ch == '\n' && mode == CompressWhitespace {
+                    // Newlines are kept as forced line breaks, and the spaces around them are
+                    // removed.
+                    if in_whitespace && out_str.ends_with(" ") {
+                        out_str.pop_char();
+                        new_line_index -= 1;
+                    }
+                    new_line_pos.push(new_line_index);
+                    new_line_index = 0;
+                    out_str.push_char(ch);
+                    in_whitespace = true;
+                    continue
+                }
+
                 // TODO: discard newlines between CJK chars
                 let mut next_in_whitespace: bool = is_in_whitespace(ch, mode);
                 
@@ -62,6 +77,7 @@ pub fn transform_text(text: &str, mode: CompressionMode, incoming_whitespace: bo
                     } else {
                         // TODO: record kept char
                         out_str.push_char(ch);
+                        new_line_index += 1;
                     }
                 } else { /* next_in_whitespace; possibly add a space char */
                     if in_whitespace {
@@ -69,6 +85,7 @@ pub fn transform_text(text: &str, mode: CompressionMode, incoming_whitespace: bo
                     } else {
                         // TODO: record kept char
                         out_str.push_char(' ');
+                        new_line_index += 1;
                     }
                 }
                 // save whitespace context for next char
@@ -184,7 +201,6 @@ fn test_transform_discard_newline() {
     }
 }
 
-/* FIXME: Fix and re-enable
 #[test]
 fn test_transform_compress_whitespace() {
     let  test_strs : ~[~str] = ~[~"  foo bar",
@@ -195,11 +211,11 @@ fn test_transform_compress_whitespace() {
                                  ~"foo bar baz",
                                  ~"foobarbaz\n\n"];
 
-    let oracle_strs : ~[~str] = ~[~" foo bar",
+    let oracle_strs : ~[~str] = ~[~"foo bar",
                                  ~"foo bar ",
-                                 ~"foo\n bar",
-                                 ~"foo \nbar",
-                                 ~" foo bar \nbaz",
+                                 ~"foo\nbar",
+                                 ~"foo\nbar",
+                                 ~"foo bar\nbaz",
                                  ~"foo bar baz",
                                  ~"foobarbaz\n\n"];
 
@@ -213,6 +229,17 @@ fn test_transform_compress_whitespace() {
     }
 }
 
+#[test]
+fn test_transform_compress_whitespace_new_line_pos() {
+    let mut new_line_pos = ~[];
+    let (trimmed_str, out) = transform_text("a  b \n  cd\n", CompressWhitespace, false,
+                                            &mut new_line_pos);
+    assert_eq!(trimmed_str, ~"a b\ncd\n");
+    assert_eq!(new_line_pos, ~[3, 2]);
+    assert!(out);
+}
+
+/* FIXME: Fix and re-enable
 #[test]
 fn test_transform_compress_whitespace_newline() {
     let  test_strs : ~[~str] = ~[~"  foo bar",
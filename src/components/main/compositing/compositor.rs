@@ -32,6 +32,7 @@ use layers::scene::Scene;
 use opengles::gl2;
 use png;
 use servo_msg::compositor_msg::{Blank, Epoch, FinishedLoading, IdleRenderState, LayerBufferSet, ReadyState, RenderState};
+use servo_msg::compositor_msg::{LayerId, ScrollLayerMetadata};
 use servo_msg::constellation_msg::{ConstellationChan, ExitMsg, NavigateMsg, ResizedWindowMsg, LoadUrlMsg, PipelineId};
 use servo_msg::constellation_msg;
 use servo_util::time::{profile, ProfilerChan, Timer};
@@ -264,12 +265,16 @@ impl IOCompositor {
                     self.set_layer_clip_rect(id, new_rect);
                 }
 
+                (Some(SetScrollLayers(id, layers, epoch)), false) => {
+                    self.set_scroll_layers(id, layers, epoch);
+                }
+
                 (Some(DeleteLayer(id)), _) => {
                     self.delete_layer(id);
                 }
 
-                (Some(Paint(id, new_layer_buffer_set, epoch)), false) => {
-                    self.paint(id, new_layer_buffer_set, epoch);
+                (Some(Paint(id, layer_id, new_layer_buffer_set, epoch)), false) => {
+                    self.paint(id, layer_id, new_layer_buffer_set, epoch);
                 }
 
                 (Some(InvalidateRect(id, rect)), false) => {
@@ -280,6 +285,10 @@ impl IOCompositor {
                     self.scroll_fragment_to_point(id, point);
                 }
 
+                (Some(ScrollLayerToPoint(id, layer_id, point)), false) => {
+                    self.scroll_layer_to_point(id, layer_id, point);
+                }
+
                 (Some(LoadComplete(..)), false) => {
                     self.load_complete = true;
                 }
@@ -410,6 +419,23 @@ impl IOCompositor {
         }
     }
 
+    fn set_scroll_layers(&mut self,
+                         id: PipelineId,
+                         layers: ~[ScrollLayerMetadata],
+                         epoch: Epoch) {
+        let ask: bool = match self.compositor_layer {
+            Some(ref mut layer) => {
+                layer.set_scroll_layers(&self.graphics_context, id, layers, epoch)
+            }
+            None => false,
+        };
+
+        if ask {
+            self.recomposite = true;
+            self.ask_for_tiles();
+        }
+    }
+
     fn delete_layer(&mut self, id: PipelineId) {
         let ask: bool = match self.compositor_layer {
             Some(ref mut layer) => {
@@ -428,6 +454,7 @@ impl IOCompositor {
 
     fn paint(&mut self,
              id: PipelineId,
+             layer_id: LayerId,
              new_layer_buffer_set: ~LayerBufferSet,
              epoch: Epoch) {
         debug!("osmain: received new frame");
@@ -440,6 +467,7 @@ impl IOCompositor {
             Some(ref mut layer) => {
                 assert!(layer.add_buffers(&self.graphics_context,
                                           id,
+                                          layer_id,
                                           new_layer_buffer_set,
                                           epoch).is_none());
                 self.recomposite = true;
@@ -495,6 +523,18 @@ impl IOCompositor {
         }
     }
 
+    fn scroll_layer_to_point(&mut self, id: PipelineId, layer_id: LayerId, point: Point2D<f32>) {
+        let ask: bool = match self.compositor_layer {
+            Some(ref mut layer) => layer.scroll_layer_to_point(id, layer_id, point),
+            None => false,
+        };
+
+        if ask {
+            self.recomposite = true;
+            self.ask_for_tiles();
+        }
+    }
+
     fn handle_window_message(&mut self, event: WindowEvent) {
         match event {
             IdleWindowEvent => {}
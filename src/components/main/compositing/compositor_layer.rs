@@ -18,14 +18,16 @@ use layers::platform::surface::{NativeCompositingGraphicsContext, NativeSurfaceM
 use layers::texturegl::{Texture, TextureTarget};
 #[cfg(target_os="macos")] use layers::texturegl::TextureTargetRectangle;
 use pipeline::CompositionPipeline;
-use script::dom::event::{ClickEvent, MouseDownEvent, MouseMoveEvent, MouseUpEvent};
+use script::dom::event::{ClickEvent, MouseDownEvent, MouseMoveEvent, MouseUpEvent, ScrollEvent};
 use script::script_task::SendEventMsg;
-use servo_msg::compositor_msg::{LayerBuffer, LayerBufferSet, Epoch, Tile};
+use servo_msg::compositor_msg::{LayerBuffer, LayerBufferSet, Epoch, Tile, LayerId, PageLayerId};
+use servo_msg::compositor_msg::{OverlayLayerId, ScrollLayerId, ScrollLayerMetadata};
 use servo_msg::constellation_msg::PipelineId;
 use windowing::{MouseWindowEvent, MouseWindowClickEvent, MouseWindowMouseDownEvent};
 use windowing::{MouseWindowMouseUpEvent};
 use azure::azure_hl::Color;
 use gfx;
+use std::util;
 
 #[cfg(not(target_os="macos"))]
 use layers::texturegl::TextureTarget2D;
@@ -37,6 +39,10 @@ pub struct CompositorLayer {
     /// This layer's pipeline. BufferRequests and mouse events will be sent through this.
     pipeline: CompositionPipeline,
 
+    /// Which of the pipeline's layers this is: its page, or the contents of one of the page's
+    /// scrolling boxes.
+    id: LayerId,
+
     /// The size of the underlying page in page coordinates. This is an option
     /// because we may not know the size of the page until layout is finished completely.
     /// if we have no size yet, the layer is hidden until a size message is recieved.
@@ -113,6 +119,7 @@ impl CompositorLayer {
                -> CompositorLayer {
         CompositorLayer {
             pipeline: pipeline,
+            id: PageLayerId,
             page_size: page_size,
             scroll_offset: Point2D(0f32, 0f32),
             children: ~[],
@@ -171,12 +178,13 @@ impl CompositorLayer {
 
     // Move the layer by as relative specified amount in page coordinates. Does not change
     // the position of the layer relative to its parent. This also takes in a cursor position
-    // to see if the mouse is over child layers first. If a layer successfully scrolled, returns
-    // true; otherwise returns false, so a parent layer can scroll instead.
+    // to see if the mouse is over child layers first, trying the topmost ones first. If a layer
+    // successfully scrolled, returns true; otherwise returns false, so a parent layer can scroll
+    // instead.
     pub fn scroll(&mut self, delta: Point2D<f32>, cursor: Point2D<f32>, window_size: Size2D<f32>)
                   -> bool {
         let cursor = cursor - self.scroll_offset;
-        for child in self.children.mut_iter().filter(|x| !x.child.hidden) {
+        for child in self.children.mut_rev_iter().filter(|x| !x.child.hidden) {
             match child.container.scissor {
                 None => {
                     error!("CompositorLayer: unable to perform cursor hit test for layer");
@@ -216,6 +224,17 @@ impl CompositorLayer {
                 self.root_layer.common.set_transform(identity().translate(self.scroll_offset.x,
                                                                           self.scroll_offset.y,
                                                                           0.0));
+
+                // Let script know how far the box has scrolled, so that it can report it.
+                match self.id {
+                    ScrollLayerId(..) => {
+                        let offset = Point2D(-self.scroll_offset.x, -self.scroll_offset.y);
+                        let message = ScrollEvent(self.id, offset);
+                        self.pipeline.script_chan.send(SendEventMsg(self.pipeline.id.clone(),
+                                                                    message));
+                    }
+                    PageLayerId | OverlayLayerId(..) => {}
+                }
                 true
             }
             FixedPosition => false, // Ignore this scroll event.
@@ -225,9 +244,12 @@ impl CompositorLayer {
     // Takes in a MouseWindowEvent, determines if it should be passed to children, and 
     // sends the event off to the appropriate pipeline. NB: the cursor position is in
     // page coordinates.
+    //
+    // Events over the layers of scrolling boxes go to the page in its own coordinates; layout
+    // knows how far each box is scrolled when it looks for the node under the cursor.
     pub fn send_mouse_event(&self, event: MouseWindowEvent, cursor: Point2D<f32>) {
         let cursor = cursor - self.scroll_offset;
-        for child in self.children.iter().filter(|&x| !x.child.hidden) {
+        for child in self.children.iter().filter(|&x| !x.child.hidden && x.child.id == PageLayerId) {
             match child.container.scissor {
                 None => {
                     error!("CompositorLayer: unable to perform cursor hit test for layer");
@@ -280,7 +302,7 @@ impl CompositorLayer {
                 self.pipeline.render_chan.send(UnusedBufferMsg(unused));
             }
             if !request.is_empty() { // ask for tiles
-                self.pipeline.render_chan.try_send(ReRenderMsg(request, scale, self.epoch));
+                self.pipeline.render_chan.try_send(ReRenderMsg(self.id, request, scale, self.epoch));
             }
         }
         if redisplay {
@@ -320,7 +342,8 @@ impl CompositorLayer {
     // If the layer is hidden and has a defined page size, unhide it.
     // This method returns false if the specified layer is not found.
     pub fn set_clipping_rect(&mut self, pipeline_id: PipelineId, new_rect: Rect<f32>) -> bool {
-        match self.children.iter().position(|x| pipeline_id == x.child.pipeline.id) {
+        match self.children.iter().position(|x| pipeline_id == x.child.pipeline.id &&
+                                                x.child.id == PageLayerId) {
             Some(i) => {
                 let child_node = &mut self.children[i];
                 let con = child_node.container;
@@ -360,7 +383,7 @@ impl CompositorLayer {
     // If the layer is hidden and has a defined clipping rect, unhide it.
     // This method returns false if the specified layer is not found.
     pub fn resize(&mut self, pipeline_id: PipelineId, new_size: Size2D<f32>, window_size: Size2D<f32>, epoch: Epoch) -> bool {
-        if self.pipeline.id == pipeline_id {
+        if self.pipeline.id == pipeline_id && self.id == PageLayerId {
             self.epoch = epoch;
             self.page_size = Some(new_size);
             match self.quadtree {
@@ -417,6 +440,182 @@ impl CompositorLayer {
         }
     }
 
+    // Replaces the layers of the scrolling boxes and overlays on the page of the given pipeline
+    // with the ones described, in which parents come before their children and children come in
+    // compositing order. Layers of boxes that are still
+    // there keep their tiles and scroll offsets. This method returns false if the page layer of
+    // the pipeline is not found.
+    pub fn set_scroll_layers(&mut self,
+                             graphics_context: &NativeCompositingGraphicsContext,
+                             pipeline_id: PipelineId,
+                             layers: &[ScrollLayerMetadata],
+                             epoch: Epoch)
+                             -> bool {
+        if self.pipeline.id != pipeline_id || self.id != PageLayerId {
+            // ID does not match ours, so recurse on descendents (including hidden children).
+            return self.children.mut_iter().map(|x| &mut x.child).any(|x| {
+                x.set_scroll_layers(graphics_context, pipeline_id, layers, epoch)
+            })
+        }
+
+        let mut old_children = self.take_scroll_layers();
+        for metadata in layers.iter() {
+            let clip_rect = Rect(Point2D(metadata.clip_rect.origin.x as f32,
+                                         metadata.clip_rect.origin.y as f32),
+                                 Size2D(metadata.clip_rect.size.width as f32,
+                                        metadata.clip_rect.size.height as f32));
+            let page_size = Size2D(metadata.page_size.width as f32,
+                                   metadata.page_size.height as f32);
+
+            // The point of the contents that should be at the top left corner of the box.
+            let mut origin = metadata.scroll_offset;
+            let mut child_node = match old_children.iter().position(|x| x.child.id == metadata.id) {
+                Some(i) => {
+                    let child_node = old_children.remove(i);
+                    origin = Point2D(0f32, 0f32) - child_node.child.scroll_offset;
+                    child_node
+                }
+                None => {
+                    let (tile_size, max_mem) = self.tile_size_and_max_mem();
+                    let mut child = ~CompositorLayer::new(self.pipeline.clone(),
+                                                          None,
+                                                          tile_size,
+                                                          max_mem,
+                                                          self.cpu_painting);
+                    child.id = metadata.id;
+                    child.unrendered_color = self.unrendered_color;
+                    let container = @mut ContainerLayer();
+                    container.add_child_start(ContainerLayerKind(child.root_layer));
+                    CompositorLayerChild {
+                        child: child,
+                        container: container,
+                    }
+                }
+            };
+
+            child_node.container.scissor = Some(clip_rect);
+            child_node.container.common.set_transform(identity().translate(clip_rect.origin.x,
+                                                                           clip_rect.origin.y,
+                                                                           0.0));
+            {
+                let child = &mut child_node.child;
+                child.epoch = epoch;
+                child.page_size = Some(page_size);
+                match child.quadtree {
+                    Tree(ref mut quadtree) => {
+                        child.pipeline.render_chan.send(UnusedBufferMsg(quadtree.resize(page_size.width as uint,
+                                                                                        page_size.height as uint)));
+                    }
+                    NoTree(tile_size, max_mem) => {
+                        child.quadtree = Tree(Quadtree::new(Size2D(page_size.width as uint,
+                                                                   page_size.height as uint),
+                                                            tile_size,
+                                                            max_mem))
+                    }
+                }
+
+                // Bring the scroll offset within the new bounds.
+                child.move(origin, clip_rect.size);
+                child.hidden = false;
+            }
+
+            match self.find_layer(metadata.parent) {
+                Some(parent) => parent.children.push(child_node),
+                None => old_children.push(child_node),
+            }
+        }
+
+        // Whatever is left belongs to boxes that no longer scroll.
+        for old_child in old_children.mut_iter() {
+            old_child.child.clear_all();
+        }
+
+        self.set_occlusions();
+        self.build_scroll_layer_trees(graphics_context);
+        true
+    }
+
+    // Detaches the layers of the scrolling boxes below this layer that belong to its own
+    // pipeline.
+    fn take_scroll_layers(&mut self) -> ~[CompositorLayerChild] {
+        let pipeline_id = self.pipeline.id.clone();
+        let children = util::replace(&mut self.children, ~[]);
+        let (mut scroll_children, children) = children.partition(|x| {
+            x.child.pipeline.id == pipeline_id && x.child.id != PageLayerId
+        });
+        self.children = children;
+
+        let mut nested_children = ~[];
+        for scroll_child in scroll_children.mut_iter() {
+            nested_children.push_all_move(scroll_child.child.take_scroll_layers());
+        }
+        scroll_children.push_all_move(nested_children);
+        scroll_children
+    }
+
+    // Finds the layer with the given ID among this layer and the scroll layers of its pipeline
+    // below it.
+    fn find_layer<'a>(&'a mut self, id: LayerId) -> Option<&'a mut CompositorLayer> {
+        if self.id == id {
+            return Some(self)
+        }
+        let pipeline_id = self.pipeline.id.clone();
+        for child in self.children.mut_iter() {
+            if child.child.pipeline.id == pipeline_id {
+                match child.child.find_layer(id) {
+                    Some(layer) => return Some(layer),
+                    None => {}
+                }
+            }
+        }
+        None
+    }
+
+    // Rebuilds the layer trees of this layer and the scroll layers of its pipeline below it, so
+    // that they pick up their new children.
+    fn build_scroll_layer_trees(&mut self, graphics_context: &NativeCompositingGraphicsContext) {
+        match self.quadtree {
+            NoTree(..) => return,
+            Tree(..) => self.build_layer_tree(graphics_context),
+        }
+        let pipeline_id = self.pipeline.id.clone();
+        for child in self.children.mut_iter() {
+            if child.child.pipeline.id == pipeline_id {
+                child.child.build_scroll_layer_trees(graphics_context);
+            }
+        }
+    }
+
+    // Scrolls the layer of a scrolling box so that the given point of its contents is at its top
+    // left corner. This method returns false if the layer is not found.
+    pub fn scroll_layer_to_point(&mut self,
+                                 pipeline_id: PipelineId,
+                                 layer_id: LayerId,
+                                 point: Point2D<f32>)
+                                 -> bool {
+        for child in self.children.mut_iter() {
+            if child.child.pipeline.id == pipeline_id && child.child.id == layer_id {
+                match child.container.scissor {
+                    Some(rect) => {
+                        child.child.move(point, rect.size);
+                    }
+                    None => {} // Nothing to do
+                }
+                return true
+            }
+        }
+        self.children.mut_iter().map(|x| &mut x.child).any(|x| {
+            x.scroll_layer_to_point(pipeline_id, layer_id, point)
+        })
+    }
+
+    fn tile_size_and_max_mem(&self) -> (uint, Option<uint>) {
+        match self.quadtree {
+            Tree(ref quadtree) => (quadtree.max_tile_size, quadtree.max_mem),
+            NoTree(tile_size, max_mem) => (tile_size, max_mem),
+        }
+    }
+
     // Returns whether the layer should be vertically flipped.
     #[cfg(target_os="macos")]
     fn texture_flip_and_target(cpu_painting: bool, size: Size2D<uint>) -> (Flip, TextureTarget) {
@@ -447,7 +646,8 @@ impl CompositorLayer {
     
     // A helper method to resize sublayers.
     fn resize_helper(&mut self, pipeline_id: PipelineId, new_size: Size2D<f32>, epoch: Epoch) -> bool {
-        let found = match self.children.iter().position(|x| pipeline_id == x.child.pipeline.id) {
+        let found = match self.children.iter().position(|x| pipeline_id == x.child.pipeline.id &&
+                                                            x.child.id == PageLayerId) {
             Some(i) => {
                 let child_node = &mut self.children[i];
                 let child = &mut child_node.child;
@@ -587,10 +787,11 @@ impl CompositorLayer {
     pub fn add_buffers(&mut self,
                        graphics_context: &NativeCompositingGraphicsContext,
                        pipeline_id: PipelineId,
+                       layer_id: LayerId,
                        mut new_buffers: ~LayerBufferSet,
                        epoch: Epoch)
                        -> Option<~LayerBufferSet> {
-        if self.pipeline.id == pipeline_id {
+        if self.pipeline.id == pipeline_id && self.id == layer_id {
             if self.epoch != epoch {
                 debug!("compositor epoch mismatch: {:?} != {:?}, id: {:?}",
                        self.epoch,
@@ -626,6 +827,7 @@ impl CompositorLayer {
         for child_layer in self.children.mut_iter() {
             match child_layer.child.add_buffers(graphics_context,
                                                 pipeline_id,
+                                                layer_id,
                                                 new_buffers,
                                                 epoch) {
                 None => return None,
@@ -642,7 +844,8 @@ impl CompositorLayer {
                   graphics_context: &NativeCompositingGraphicsContext,
                   pipeline_id: PipelineId)
                   -> bool {
-        match self.children.iter().position(|x| x.child.pipeline.id == pipeline_id) {
+        match self.children.iter().position(|x| x.child.pipeline.id == pipeline_id &&
+                                                x.child.id == PageLayerId) {
             Some(i) => {
                 let mut child = self.children.remove(i);
                 match self.quadtree {
@@ -671,7 +874,7 @@ impl CompositorLayer {
     }
 
     pub fn invalidate_rect(&mut self, pipeline_id: PipelineId, rect: Rect<f32>) -> bool {
-        if self.pipeline.id == pipeline_id {
+        if self.pipeline.id == pipeline_id && self.id == PageLayerId {
             let quadtree = match self.quadtree {
                 NoTree(..) => return true, // Nothing to do
                 Tree(ref mut quadtree) => quadtree,
@@ -686,12 +889,13 @@ impl CompositorLayer {
     
     // Recursively sets occluded portions of quadtrees to Hidden, so that they do not ask for
     // tile requests. If layers are moved, resized, or deleted, these portions may be updated.
+    // The layers of scrolling boxes are transparent, so they don't occlude anything.
     fn set_occlusions(&mut self) {
         let quadtree = match self.quadtree {
             NoTree(..) => return, // Cannot calculate occlusions
             Tree(ref mut quadtree) => quadtree,
         };
-        for child in self.children.iter().filter(|x| !x.child.hidden) {
+        for child in self.children.iter().filter(|x| !x.child.hidden && x.child.id == PageLayerId) {
             match child.container.scissor {
                 None => {} // Nothing to do
                 Some(rect) => {
@@ -15,7 +15,7 @@ use geom::size::Size2D;
 use gfx::opts::Opts;
 use layers::platform::surface::{NativeCompositingGraphicsContext, NativeGraphicsMetadata};
 use servo_msg::compositor_msg::{Epoch, RenderListener, LayerBufferSet, RenderState, ReadyState};
-use servo_msg::compositor_msg::{ScriptListener, Tile};
+use servo_msg::compositor_msg::{ScriptListener, Tile, LayerId, ScrollLayerMetadata};
 use servo_msg::constellation_msg::{ConstellationChan, PipelineId};
 use servo_util::time::ProfilerChan;
 use std::comm::{Chan, SharedChan, Port};
@@ -54,6 +54,10 @@ impl ScriptListener for CompositorChan {
 	    self.chan.send(ScrollFragmentPoint(id, point));
     }
 
+    fn scroll_layer_to_point(&self, id: PipelineId, layer_id: LayerId, point: Point2D<f32>) {
+        self.chan.send(ScrollLayerToPoint(id, layer_id, point));
+    }
+
     fn close(&self) {
         let (port, chan) = Chan::new();
         self.chan.send(Exit(chan));
@@ -70,8 +74,8 @@ impl RenderListener for CompositorChan {
         port.recv()
     }
 
-    fn paint(&self, id: PipelineId, layer_buffer_set: ~LayerBufferSet, epoch: Epoch) {
-        self.chan.send(Paint(id, layer_buffer_set, epoch))
+    fn paint(&self, id: PipelineId, layer_id: LayerId, layer_buffer_set: ~LayerBufferSet, epoch: Epoch) {
+        self.chan.send(Paint(id, layer_id, layer_buffer_set, epoch))
     }
 
     fn new_layer(&self, id: PipelineId, page_size: Size2D<uint>) {
@@ -84,6 +88,10 @@ impl RenderListener for CompositorChan {
         self.chan.send(SetLayerPageSize(id, Size2D(width as f32, height as f32), epoch))
     }
 
+    fn set_scroll_layers(&self, id: PipelineId, layers: ~[ScrollLayerMetadata], epoch: Epoch) {
+        self.chan.send(SetScrollLayers(id, layers, epoch))
+    }
+
     fn set_layer_clip_rect(&self, id: PipelineId, new_rect: Rect<uint>) {
         let new_rect = Rect(Point2D(new_rect.origin.x as f32,
                                     new_rect.origin.y as f32),
@@ -135,6 +143,8 @@ pub enum Msg {
     NewLayer(PipelineId, Size2D<f32>),
     /// Alerts the compositor that the specified layer's page has changed size.
     SetLayerPageSize(PipelineId, Size2D<f32>, Epoch),
+    /// Alerts the compositor to the layers of the scrolling boxes on the specified page.
+    SetScrollLayers(PipelineId, ~[ScrollLayerMetadata], Epoch),
     /// Alerts the compositor that the specified layer's clipping rect has changed.
     SetLayerClipRect(PipelineId, Rect<f32>),
     /// Alerts the compositor that the specified layer has been deleted.
@@ -143,8 +153,10 @@ pub enum Msg {
     InvalidateRect(PipelineId, Rect<uint>),
     /// Scroll a page in a window
     ScrollFragmentPoint(PipelineId, Point2D<f32>),
+    /// Scroll the layer of a scrolling box
+    ScrollLayerToPoint(PipelineId, LayerId, Point2D<f32>),
     /// Requests that the compositor paint the given layer buffer set for the given page size.
-    Paint(PipelineId, ~LayerBufferSet, Epoch),
+    Paint(PipelineId, LayerId, ~LayerBufferSet, Epoch),
     /// Alerts the compositor to the current status of page loading.
     ChangeReadyState(ReadyState),
     /// Alerts the compositor to the current status of rendering.
@@ -63,7 +63,8 @@ impl NullCompositor {
 
                 NewLayer(..) | SetLayerPageSize(..) | SetLayerClipRect(..) | DeleteLayer(..) |
                 Paint(..) | InvalidateRect(..) | ChangeReadyState(..) | ChangeRenderState(..)|
-                ScrollFragmentPoint(..) | SetUnRenderedColor(..) | LoadComplete(..)
+                ScrollFragmentPoint(..) | SetUnRenderedColor(..) | LoadComplete(..) |
+                SetScrollLayers(..) | ScrollLayerToPoint(..)
                     => ()
             }
         }
@@ -9,6 +9,7 @@ use geom::rect::Rect;
 use geom::size::Size2D;
 use gfx::opts::Opts;
 use pipeline::{Pipeline, CompositionPipeline};
+use script::layout_interface::PrintMsg;
 use script::script_task::{ResizeMsg, ResizeInactiveMsg};
 use servo_msg::constellation_msg::{ConstellationChan, ExitMsg, FailureMsg, FrameRectMsg};
 use servo_msg::constellation_msg::{IFrameSandboxState, IFrameUnsandboxed, InitLoadUrlMsg};
@@ -22,6 +23,7 @@ use servo_net::resource_task;
 use servo_util::time::ProfilerChan;
 use servo_util::url::parse_url;
 use servo_util::task::spawn_named;
+use servo_util::visited::VisitedUrls;
 use std::hashmap::{HashMap, HashSet};
 use std::util::replace;
 
@@ -188,6 +190,8 @@ struct NavigationContext {
     previous: ~[@mut FrameTree],
     next: ~[@mut FrameTree],
     current: Option<@mut FrameTree>,
+    /// Every URL loaded so far, shared with the layout tasks for `:visited`.
+    visited_urls: VisitedUrls,
 }
 
 impl NavigationContext {
@@ -196,6 +200,7 @@ impl NavigationContext {
             previous: ~[],
             next: ~[],
             current: None,
+            visited_urls: VisitedUrls::new(),
         }
     }
 
@@ -217,6 +222,11 @@ impl NavigationContext {
     /// Loads a new set of page frames, returning all evicted frame trees
     pub fn load(&mut self, frame_tree: @mut FrameTree) -> ~[@mut FrameTree] {
         debug!("navigating to {:?}", frame_tree.pipeline.id);
+        for frame in frame_tree.iter() {
+            for url in frame.pipeline.url.iter() {
+                self.visited_urls.add(url)
+            }
+        }
         let evicted = replace(&mut self.next, ~[]);
         if self.current.is_some() {
             self.previous.push(self.current.take_unwrap());
@@ -351,6 +361,11 @@ impl Constellation {
             LoadCompleteMsg(pipeline_id, url) => {
                 debug!("constellation got load complete message");
                 self.compositor_chan.send(LoadComplete(pipeline_id, url));
+                if self.opts.print_output.is_some() && self.handle_print(pipeline_id) {
+                    debug!("constellation exiting after printing");
+                    self.handle_exit();
+                    return false;
+                }
             }
             // Handle a forward or back request
             NavigateMsg(direction) => {
@@ -379,6 +394,18 @@ impl Constellation {
         self.compositor_chan.send(ShutdownComplete);
     }
 
+    /// Prints the page once the top-level document has loaded. Returns true if anything was printed.
+    fn handle_print(&self, pipeline_id: PipelineId) -> bool {
+        let pipeline = match self.pipelines.find(&pipeline_id) {
+            Some(&pipeline) if pipeline.subpage_id.is_none() => pipeline,
+            _ => return false,
+        };
+        let (response_port, response_chan) = Chan::new();
+        pipeline.layout_chan.send(PrintMsg(response_chan));
+        response_port.recv();
+        true
+    }
+
     fn handle_failure_msg(&mut self, pipeline_id: PipelineId, subpage_id: Option<SubpageId>) {
         let new_id = self.get_next_pipeline_id();
         let pipeline = @mut Pipeline::create(new_id,
@@ -386,6 +413,7 @@ impl Constellation {
                                              self.chan.clone(),
                                              self.compositor_chan.clone(),
                                              self.image_cache_task.clone(),
+                                             self.navigation_context.visited_urls.clone(),
                                              self.resource_task.clone(),
                                              self.profiler_chan.clone(),
                                              self.window_size,
@@ -408,6 +436,7 @@ impl Constellation {
                                              self.chan.clone(),
                                              self.compositor_chan.clone(),
                                              self.image_cache_task.clone(),
+                                             self.navigation_context.visited_urls.clone(),
                                              self.resource_task.clone(),
                                              self.profiler_chan.clone(),
                                              self.window_size,
@@ -534,6 +563,7 @@ impl Constellation {
                                   self.chan.clone(),
                                   self.compositor_chan.clone(),
                                   self.image_cache_task.clone(),
+                                  self.navigation_context.visited_urls.clone(),
                                   self.profiler_chan.clone(),
                                   self.opts.clone(),
                                   source_pipeline)
@@ -545,6 +575,7 @@ impl Constellation {
                              self.chan.clone(),
                              self.compositor_chan.clone(),
                              self.image_cache_task.clone(),
+                             self.navigation_context.visited_urls.clone(),
                              self.resource_task.clone(),
                              self.profiler_chan.clone(),
                              self.window_size,
@@ -599,6 +630,7 @@ impl Constellation {
                                              self.chan.clone(),
                                              self.compositor_chan.clone(),
                                              self.image_cache_task.clone(),
+                                             self.navigation_context.visited_urls.clone(),
                                              self.resource_task.clone(),
                                              self.profiler_chan.clone(),
                                              self.window_size,
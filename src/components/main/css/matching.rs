@@ -6,6 +6,7 @@
 
 use css::node_style::StyledNode;
 use layout::extra::LayoutAuxMethods;
+use layout::incremental::RestyleDamage;
 use layout::incremental;
 use layout::util::LayoutDataAccess;
 use layout::wrapper::LayoutNode;
@@ -14,7 +15,7 @@ use extra::arc::Arc;
 use script::layout_interface::LayoutChan;
 use servo_util::smallvec::SmallVec;
 use style::{TNode, Stylist, cascade};
-use style::{Before, After};
+use style::{Before, After, FirstLine, FirstLetter};
 
 pub trait MatchMethods {
     fn match_node(&self, stylist: &Stylist);
@@ -59,6 +60,18 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
                                                      &mut layout_data
                                                          .data
                                                          .after_applicable_declarations);
+                stylist.push_applicable_declarations(self,
+                                                     None,
+                                                     Some(FirstLine),
+                                                     &mut layout_data
+                                                         .data
+                                                         .first_line_applicable_declarations);
+                stylist.push_applicable_declarations(self,
+                                                     None,
+                                                     Some(FirstLetter),
+                                                     &mut layout_data
+                                                         .data
+                                                         .first_letter_applicable_declarations);
             }
             None => fail!("no layout data")
         }
@@ -85,33 +98,13 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
 
     unsafe fn cascade_node(&self, parent: Option<LayoutNode>) {
         macro_rules! cascade_node(
-            ($applicable_declarations: ident, $style: ident) => {{
-                // Get our parent's style. This must be unsafe so that we don't touch the parent's
-                // borrow flags.
-                //
-                // FIXME(pcwalton): Isolate this unsafety into the `wrapper` module to allow
-                // enforced safe, race-free access to the parent style.
-                let parent_style = match parent {
-                    None => None,
-                    Some(parent_node) => {
-                        let parent_layout_data = parent_node.borrow_layout_data_unchecked();
-                        match *parent_layout_data {
-                            None => fail!("no parent data?!"),
-                            Some(ref parent_layout_data) => {
-                                match parent_layout_data.data.style {
-                                    None => fail!("parent hasn't been styled yet?!"),
-                                    Some(ref style) => Some(style.get()),
-                                }
-                            }
-                        }
-                    }
-                };
-
+            ($applicable_declarations: ident, $style: ident, $parent_style: expr,
+             $add_damage: expr) => {{
                 let computed_values = {
                     let layout_data_ref = self.borrow_layout_data();
                     let layout_data = layout_data_ref.get().as_ref().unwrap();
                     Arc::new(cascade(layout_data.data.$applicable_declarations.as_slice(),
-                                     parent_style))
+                                     $parent_style))
                 };
 
                 let mut layout_data_ref = self.mutate_layout_data();
@@ -122,8 +115,14 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
                         match *style {
                             None => (),
                             Some(ref previous_style) => {
-                                layout_data.data.restyle_damage = Some(incremental::compute_damage(
-                                    previous_style.get(), computed_values.get()).to_int())
+                                let mut damage = incremental::compute_damage(
+                                    previous_style.get(), computed_values.get());
+                                if $add_damage {
+                                    for &old_damage in layout_data.data.restyle_damage.iter() {
+                                        damage = damage.union(RestyleDamage::from_int(old_damage))
+                                    }
+                                }
+                                layout_data.data.restyle_damage = Some(damage.to_int())
                             }
                         }
                         *style = Some(computed_values)
@@ -132,25 +131,53 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
             }}
         );
 
-        {
-            let before_len = {
-                let layout_data_ref = self.borrow_layout_data();
-                layout_data_ref.get().as_ref().unwrap().data.before_applicable_declarations.len()
-            };
-            if before_len > 0 {
-                cascade_node!(before_applicable_declarations, before_style);
-            }
-        }
-        cascade_node!(applicable_declarations, style);
-        {
-            let after_len = {
-                let layout_data_ref = self.borrow_layout_data();
-                layout_data_ref.get().as_ref().unwrap().data.after_applicable_declarations.len()
-            };
-            if after_len > 0 {
-                cascade_node!(after_applicable_declarations, after_style);
+        // Get our parent's style. This must be unsafe so that we don't touch the parent's
+        // borrow flags.
+        //
+        // FIXME(pcwalton): Isolate this unsafety into the `wrapper` module to allow
+        // enforced safe, race-free access to the parent style.
+        let parent_style = match parent {
+            None => None,
+            Some(parent_node) => {
+                let parent_layout_data = parent_node.borrow_layout_data_unchecked();
+                match *parent_layout_data {
+                    None => fail!("no parent data?!"),
+                    Some(ref parent_layout_data) => {
+                        match parent_layout_data.data.style {
+                            None => fail!("parent hasn't been styled yet?!"),
+                            Some(ref style) => Some(style.get()),
+                        }
+                    }
+                }
             }
-        }
+        };
+        cascade_node!(applicable_declarations, style, parent_style, false);
+
+        // Pseudo-elements inherit from this node. Changes to their styles add to the damage of
+        // the node.
+        //
+        // FIXME: `::first-letter` should inherit from `::first-line` when both apply.
+        let style = {
+            let layout_data_ref = self.borrow_layout_data();
+            layout_data_ref.get().as_ref().unwrap().data.style.clone().unwrap()
+        };
+        macro_rules! cascade_pseudo_element(
+            ($applicable_declarations: ident, $style: ident) => {{
+                let len = {
+                    let layout_data_ref = self.borrow_layout_data();
+                    layout_data_ref.get().as_ref().unwrap().data.$applicable_declarations.len()
+                };
+                if len > 0 {
+                    cascade_node!($applicable_declarations, $style, Some(style.get()), true);
+                } else {
+                    self.mutate_layout_data().get().get_mut_ref().data.$style = None
+                }
+            }}
+        );
+        cascade_pseudo_element!(before_applicable_declarations, before_style);
+        cascade_pseudo_element!(after_applicable_declarations, after_style);
+        cascade_pseudo_element!(first_line_applicable_declarations, first_line_style);
+        cascade_pseudo_element!(first_letter_applicable_declarations, first_letter_style);
     }
 }
 
@@ -9,11 +9,12 @@ use layout::incremental::RestyleDamage;
 use layout::wrapper::ThreadSafeLayoutNode;
 
 use extra::arc::Arc;
-use style::ComputedValues;
+use style::{ComputedValues, PseudoElement};
 
 /// Node mixin providing `style` method that returns a `NodeStyle`
 pub trait StyledNode {
     fn style<'a>(&'a self) -> &'a Arc<ComputedValues>;
+    fn pseudo_style<'a>(&'a self, pseudo: PseudoElement) -> Option<&'a Arc<ComputedValues>>;
     fn restyle_damage(&self) -> RestyleDamage;
 }
 
@@ -23,6 +24,11 @@ impl<'ln> StyledNode for ThreadSafeLayoutNode<'ln> {
         self.get_css_select_results()
     }
 
+    #[inline]
+    fn pseudo_style<'a>(&'a self, pseudo: PseudoElement) -> Option<&'a Arc<ComputedValues>> {
+        self.get_pseudo_css_select_results(pseudo)
+    }
+
     fn restyle_damage(&self) -> RestyleDamage {
         self.get_restyle_damage()
     }
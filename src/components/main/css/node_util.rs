@@ -8,10 +8,12 @@ use layout::wrapper::{TLayoutNode, ThreadSafeLayoutNode};
 
 use extra::arc::Arc;
 use std::cast;
-use style::ComputedValues;
+use style::{After, Before, ComputedValues, FirstLetter, FirstLine, PseudoElement};
 
 pub trait NodeUtil {
     fn get_css_select_results<'a>(&'a self) -> &'a Arc<ComputedValues>;
+    fn get_pseudo_css_select_results<'a>(&'a self, pseudo: PseudoElement)
+                                         -> Option<&'a Arc<ComputedValues>>;
     fn have_css_select_results(self) -> bool;
 
     fn get_restyle_damage(self) -> RestyleDamage;
@@ -35,6 +37,24 @@ impl<'ln> NodeUtil for ThreadSafeLayoutNode<'ln> {
         }
     }
 
+    /// Returns the style results for the given pseudo-element of the node, or `None` if no rules
+    /// apply to it.
+    #[inline]
+    fn get_pseudo_css_select_results<'a>(&'a self, pseudo: PseudoElement)
+                                         -> Option<&'a Arc<ComputedValues>> {
+        unsafe {
+            let layout_data_ref = self.borrow_layout_data();
+            let data = &layout_data_ref.get().as_ref().unwrap().data;
+            let style = match pseudo {
+                Before => &data.before_style,
+                After => &data.after_style,
+                FirstLine => &data.first_line_style,
+                FirstLetter => &data.first_letter_style,
+            };
+            style.as_ref().map(|style| cast::transmute_region(style))
+        }
+    }
+
     /// Does this node have a computed style yet?
     fn have_css_select_results(self) -> bool {
         let layout_data_ref = self.borrow_layout_data();
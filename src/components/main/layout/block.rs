@@ -10,15 +10,18 @@ use layout::context::LayoutContext;
 use layout::display_list_builder::{DisplayListBuilder, ExtraDisplayListData};
 use layout::float_context::{FloatContext, PlacementInfo, Invalid, FloatType};
 use layout::flow::{BaseFlow, BlockFlowClass, FlowClass, Flow, ImmutableFlowUtils};
+use layout::flow::{InlineFlowClass, TableRowFlowClass, TableRowGroupFlowClass};
 use layout::flow;
 use layout::model::{MaybeAuto, Specified, Auto, specified_or_none, specified};
 use layout::wrapper::ThreadSafeLayoutNode;
 
 use std::cell::RefCell;
 use geom::{Point2D, Rect, SideOffsets2D, Size2D};
-use gfx::display_list::{DisplayList, DisplayListCollection};
+use gfx::display_list::{BackgroundAndBordersStackingLevel, DisplayList, DisplayListCollection};
+use gfx::display_list::{FloatStackingLevel, PositionedStackingLevel, StackingLevel};
 use servo_util::geometry::Au;
 use servo_util::geometry;
+use style::computed_values::{direction, position, z_index};
 
 /// Information specific to floated blocks.
 pub struct FloatedBlockInfo {
@@ -79,6 +82,21 @@ impl BlockFlow {
         }
     }
 
+    /// Creates a block flow for `node` around the given box. Table flows use this to wrap either
+    /// the node's own box or an anonymous one.
+    pub fn from_node_and_box(constructor: &mut FlowConstructor,
+                             node: ThreadSafeLayoutNode,
+                             box_: Box)
+                             -> BlockFlow {
+        BlockFlow {
+            base: BaseFlow::new(constructor.next_flow_id(), node),
+            box_: Some(box_),
+            is_root: false,
+            is_fixed: false,
+            float: None
+        }
+    }
+
     pub fn float_from_node(constructor: &mut FlowConstructor,
                            node: ThreadSafeLayoutNode,
                            float_type: FloatType)
@@ -92,6 +110,22 @@ impl BlockFlow {
         }
     }
 
+    /// Creates a floated block flow for `node` around the given box. Floated `::first-letter`
+    /// pseudo-elements use this.
+    pub fn float_from_node_and_box(constructor: &mut FlowConstructor,
+                                   node: ThreadSafeLayoutNode,
+                                   box_: Box,
+                                   float_type: FloatType)
+                                   -> BlockFlow {
+        BlockFlow {
+            base: BaseFlow::new(constructor.next_flow_id(), node),
+            box_: Some(box_),
+            is_root: false,
+            is_fixed: false,
+            float: Some(~FloatedBlockInfo::new(float_type))
+        }
+    }
+
     pub fn new_root(base: BaseFlow) -> BlockFlow {
         BlockFlow {
             base: base,
@@ -116,6 +150,84 @@ impl BlockFlow {
         self.float.is_some()
     }
 
+    /// Returns true if this block is positioned, which makes it the containing block of its
+    /// absolutely positioned descendants (CSS 2.1 § 10.1). The root flow stands for the initial
+    /// containing block.
+    pub fn is_positioned(&self) -> bool {
+        if self.is_root || self.is_fixed {
+            return true
+        }
+        match self.box_ {
+            Some(ref box_) => box_.style().Box.position != position::static_,
+            None => false,
+        }
+    }
+
+    /// Returns true if this block establishes a stacking context: the root and positioned blocks
+    /// with an integer `z-index` do (CSS 2.1 § 9.9.1).
+    pub fn establishes_stacking_context(&self) -> bool {
+        if self.is_root {
+            return true
+        }
+        match self.box_ {
+            Some(ref box_) if self.is_positioned() => box_.style().Box.z_index != z_index::Auto,
+            _ => false,
+        }
+    }
+
+    /// Returns true if the contents of this block scroll in a layer of their own (CSS 2.1 § 11.1.1).
+    /// The root scrolls with the window instead.
+    pub fn is_scroll_container(&self) -> bool {
+        match self.box_ {
+            Some(ref box_) if !self.is_root => box_.is_scroll_container(),
+            _ => false,
+        }
+    }
+
+    /// The size of the scrollable area of a scroll container: its padding box, extended to take
+    /// in the overflow of its children. The area is measured from the top left corner of the
+    /// padding box, which is the origin of the layer that the children are painted in.
+    pub fn scroll_layer_size(&self) -> Size2D<Au> {
+        let padding_box = self.absolute_containing_block(Au(0));
+        let mut width = padding_box.size.width;
+        let mut height = padding_box.size.height;
+        for kid in self.base.child_iter() {
+            let overflow = flow::base(kid).overflow;
+            width = Au::max(width, overflow.origin.x + overflow.size.width - padding_box.origin.x);
+            height = Au::max(height, overflow.origin.y + overflow.size.height - padding_box.origin.y);
+        }
+        Size2D(width, height)
+    }
+
+    /// Places the children of a scroll container in the coordinates of its layer.
+    fn position_children_in_scroll_layer(&mut self) {
+        let layer_origin = self.absolute_containing_block(Au(0)).origin;
+        for child in self.base.child_iter() {
+            let child_base = flow::mut_base(child);
+            child_base.abs_position = child_base.position.origin - layer_origin;
+        }
+    }
+
+    /// Returns the level of the enclosing stacking context that this block paints at as a whole,
+    /// if it paints atomically. Positioned blocks and floats do; blocks in the normal flow are
+    /// painted in pieces along with their stacking context (CSS 2.1 Appendix E).
+    pub fn stacking_level(&self) -> Option<StackingLevel> {
+        if self.is_positioned() {
+            let z_index = match self.box_ {
+                Some(ref box_) => match box_.style().Box.z_index {
+                    z_index::Integer(z_index) => z_index,
+                    z_index::Auto => 0,
+                },
+                None => 0,
+            };
+            Some(PositionedStackingLevel(z_index))
+        } else if self.is_float() {
+            Some(FloatStackingLevel)
+        } else {
+            None
+        }
+    }
+
     pub fn teardown(&mut self) {
         for box_ in self.box_.iter() {
             box_.teardown();
@@ -148,6 +260,11 @@ impl BlockFlow {
             }
         };
 
+        let is_rtl = match self.box_ {
+            Some(ref box_) => box_.style().Box.direction == direction::rtl,
+            None => false,
+        };
+
         // Invariant: left_margin_Au + width_Au + right_margin_Au == available_width
         let (left_margin_Au, width_Au, right_margin_Au) = match (left_margin, width, right_margin) {
             // If all have a computed value other than 'auto', the system is
@@ -155,7 +272,8 @@ impl BlockFlow {
             // If direction is ltr, ignore the specified right margin and
             // solve for it.
             // If it is rtl, ignore the specified left margin.
-            // FIXME(eatkinson): this assumes the direction is ltr
+            (Specified(_margin_l), Specified(width), Specified(margin_r)) if is_rtl =>
+                (available_width - (width + margin_r), width, margin_r),
             (Specified(margin_l), Specified(width), Specified(_margin_r)) =>
                 (margin_l, width, available_width - (margin_l + width )),
 
@@ -247,6 +365,77 @@ impl BlockFlow {
         return (width, margin_left, margin_right);
     }
 
+    // CSS Section 10.3.7 and 10.3.8. Returns (content width, left margin, right margin).
+    // `containing_width` is the width of the padding box of the containing block.
+    //
+    // FIXME: The static position isn't known yet, so shrink-to-fit widths of boxes whose `left`
+    // and `right` are both `auto` are computed as if it were at the left of the containing block.
+    fn compute_absolute_margins(&self, box_: &Box, containing_width: Au) -> (Au, Au, Au) {
+        let style = box_.style();
+        let left = MaybeAuto::from_style(style.PositionOffsets.left, containing_width);
+        let right = MaybeAuto::from_style(style.PositionOffsets.right, containing_width);
+        let margin_left = MaybeAuto::from_style(style.Margin.margin_left, containing_width);
+        let margin_right = MaybeAuto::from_style(style.Margin.margin_right, containing_width);
+
+        // Replaced boxes always have a width, which `Box::assign_width` has already computed.
+        let width = if box_.is_replaced() {
+            Specified(box_.content_width())
+        } else {
+            MaybeAuto::from_style(style.Box.width, containing_width)
+        };
+
+        // The sum of the left, margins, width and right.
+        let available_width = containing_width - box_.border.get().left -
+            box_.border.get().right - box_.padding.get().left - box_.padding.get().right;
+
+        match (left, width, right) {
+            (Specified(left), Specified(width), Specified(right)) => {
+                let margins = available_width - left - width - right;
+                match (margin_left, margin_right) {
+                    // Auto margins center the box, unless that would make the left one negative.
+                    (Auto, Auto) if margins < Au(0) => (width, Au(0), margins),
+                    (Auto, Auto) => {
+                        let margin_left = margins.scale_by(0.5);
+                        (width, margin_left, margins - margin_left)
+                    }
+                    (Auto, Specified(margin_right)) => {
+                        (width, margins - margin_right, margin_right)
+                    }
+                    (Specified(margin_left), Auto) => (width, margin_left, margins - margin_left),
+
+                    // Over-constrained: `right` is ignored, or `left` if the direction is rtl.
+                    // Either way the margins stand.
+                    (Specified(margin_left), Specified(margin_right)) => {
+                        (width, margin_left, margin_right)
+                    }
+                }
+            }
+            _ => {
+                let margin_left = margin_left.specified_or_zero();
+                let margin_right = margin_right.specified_or_zero();
+                let width = match (left, width, right) {
+                    (_, Specified(width), _) => width,
+                    (Specified(left), Auto, Specified(right)) => {
+                        available_width - left - right - margin_left - margin_right
+                    }
+                    (_, Auto, _) => {
+                        // The minimum and preferred widths include the margins, borders and
+                        // padding.
+                        let noncontent_width = margin_left + margin_right +
+                            containing_width - available_width;
+                        let shrink_to_fit = geometry::min(self.base.pref_width,
+                                                          geometry::max(self.base.min_width,
+                                                                        containing_width -
+                                                                        left.specified_or_zero() -
+                                                                        right.specified_or_zero()));
+                        geometry::max(shrink_to_fit - noncontent_width, Au(0))
+                    }
+                };
+                (width, margin_left, margin_right)
+            }
+        }
+    }
+
     // inline(always) because this is only ever called by in-order or non-in-order top-level
     // methods
     #[inline(always)]
@@ -285,6 +474,10 @@ impl BlockFlow {
             // last_child.floats_out -> self.floats_out (done at the end of this method)
             float_ctx = self.base.floats_in.translate(Point2D(-left_offset, -top_offset));
             for kid in self.base.child_iter() {
+                // Floats don't affect absolutely positioned kids, which the traversal lays out.
+                if kid.is_absolutely_positioned() {
+                    continue
+                }
                 flow::mut_base(kid).floats_in = float_ctx.clone();
                 kid.assign_height_inorder(ctx);
                 float_ctx = flow::mut_base(kid).floats_out.clone();
@@ -306,12 +499,15 @@ impl BlockFlow {
         let mut top_margin_collapsible = false;
         let mut bottom_margin_collapsible = false;
         let mut first_in_flow = true;
+        // Absolutely positioned boxes establish block formatting contexts, so their margins don't
+        // collapse with those of their children (CSS 2.1 § 8.3.1).
+        let can_collapse = !self.is_root && !self.is_absolutely_positioned();
         for box_ in self.box_.iter() {
-            if !self.is_root && box_.border.get().top == Au(0) && box_.padding.get().top == Au(0) {
+            if can_collapse && box_.border.get().top == Au(0) && box_.padding.get().top == Au(0) {
                 collapsible = box_.margin.get().top;
                 top_margin_collapsible = true;
             }
-            if !self.is_root && box_.border.get().bottom == Au(0) &&
+            if can_collapse && box_.border.get().bottom == Au(0) &&
                     box_.padding.get().bottom == Au(0) {
                 bottom_margin_collapsible = true;
             }
@@ -321,6 +517,14 @@ impl BlockFlow {
 
         // At this point, cur_y is at the content edge of the flow's box_
         for kid in self.base.child_iter() {
+            // Absolutely positioned kids take up no room. Their static position is where they
+            // would have been in the flow; they are positioned once the height of their containing
+            // block is known.
+            if kid.is_absolutely_positioned() {
+                flow::mut_base(kid).position.origin.y = cur_y;
+                continue
+            }
+
             // At this point, cur_y is at bottom margin edge of previous kid
             kid.collapse_margins(top_margin_collapsible,
                                  &mut first_in_flow,
@@ -438,6 +642,12 @@ impl BlockFlow {
         } else {
             self.base.floats_out = self.base.floats_in.clone();
         }
+
+        // Absolutely positioned blocks only get their final height from their own containing
+        // block.
+        if self.is_positioned() && !self.is_absolutely_positioned() {
+            self.position_absolute_descendants(screen_height)
+        }
     }
 
     fn assign_height_float_inorder(&mut self) {
@@ -484,6 +694,9 @@ impl BlockFlow {
         if has_inorder_children {
             let mut float_ctx = FloatContext::new(self.float.get_ref().floated_children);
             for kid in self.base.child_iter() {
+                if kid.is_absolutely_positioned() {
+                    continue
+                }
                 flow::mut_base(kid).floats_in = float_ctx.clone();
                 kid.assign_height_inorder(ctx);
                 float_ctx = flow::mut_base(kid).floats_out.clone();
@@ -498,9 +711,12 @@ impl BlockFlow {
         }
 
         for kid in self.base.child_iter() {
+            let is_absolutely_positioned = kid.is_absolutely_positioned();
             let child_base = flow::mut_base(kid);
             child_base.position.origin.y = cur_y;
-            cur_y = cur_y + child_base.position.size.height;
+            if !is_absolutely_positioned {
+                cur_y = cur_y + child_base.position.size.height;
+            }
         }
 
         let mut height = cur_y - top_offset;
@@ -526,6 +742,321 @@ impl BlockFlow {
         box_.position.set(position);
     }
 
+    /// Determines the width of this block and its box, and the positions and widths of its
+    /// children. If `width_override` is given, it is used as the content width instead of the one
+    /// computed from the style; table flows use this to impose the widths that table layout
+    /// computed.
+    pub fn assign_widths_block_base(&mut self,
+                                    ctx: &mut LayoutContext,
+                                    width_override: Option<Au>) {
+        debug!("assign_widths({}): assigning width for flow {}",
+               if self.is_float() {
+                   "float"
+               } else {
+                   "block"
+               },
+               self.base.id);
+
+        if self.is_root {
+            debug!("Setting root position");
+            self.base.position.origin = Au::zero_point();
+            self.base.position.size.width = ctx.screen_size.width;
+            self.base.floats_in = FloatContext::new(self.base.num_floats);
+            self.base.flags_info.flags.set_inorder(false);
+        }
+
+        // The position was set to the containing block by the flow's parent.
+        let mut remaining_width = self.base.position.size.width;
+        let mut x_offset = Au::new(0);
+
+        if self.is_float() {
+            self.float.get_mut_ref().containing_width = remaining_width;
+
+            // Parent usually sets this, but floats are never inorder
+            self.base.flags_info.flags.set_inorder(false);
+        }
+
+        for box_ in self.box_.iter() {
+            let style = box_.style();
+
+            // The text alignment of a block flow is the text alignment of its box's style.
+            self.base.flags_info.flags.set_text_align(style.Text.text_align);
+
+            box_.assign_width(remaining_width);
+            // Can compute padding here since we know containing block width.
+            box_.compute_padding(style, remaining_width);
+
+            // Margins are 0 right now so base.noncontent_width() is just borders + padding.
+            let available_width = remaining_width - box_.noncontent_width();
+
+            // Top and bottom margins for blocks are 0 if auto.
+            let margin_top = MaybeAuto::from_style(style.Margin.margin_top,
+                                                   remaining_width).specified_or_zero();
+            let margin_bottom = MaybeAuto::from_style(style.Margin.margin_bottom,
+                                                      remaining_width).specified_or_zero();
+
+            let (width, margin_left, margin_right) = match width_override {
+                Some(width) => {
+                    self.compute_horiz(Specified(width),
+                                       MaybeAuto::from_style(style.Margin.margin_left,
+                                                             remaining_width),
+                                       MaybeAuto::from_style(style.Margin.margin_right,
+                                                             remaining_width),
+                                       available_width)
+                }
+                None if self.is_float() => self.compute_float_margins(box_, remaining_width),
+                None if self.is_absolutely_positioned() => {
+                    self.compute_absolute_margins(box_, remaining_width)
+                }
+                None => self.compute_block_margins(box_, remaining_width, available_width),
+            };
+
+            box_.margin.set(SideOffsets2D::new(margin_top,
+                                               margin_right,
+                                               margin_bottom,
+                                               margin_left));
+
+            let screen_size = ctx.screen_size;
+            let (x, w) = box_.get_x_coord_and_new_width_if_fixed(screen_size.width,
+                                                                 screen_size.height,
+                                                                 width,
+                                                                 box_.offset(),
+                                                                 self.is_fixed);
+
+            x_offset = x;
+            remaining_width = w;
+
+            // The associated box is the border box of this flow.
+            let mut position_ref = box_.position.borrow_mut();
+            if self.is_fixed {
+                position_ref.get().origin.x = x_offset + box_.margin.get().left;
+                x_offset = x_offset + box_.padding.get().left;
+            } else {
+                position_ref.get().origin.x = box_.margin.get().left;
+            }
+            let padding_and_borders = box_.padding.get().left + box_.padding.get().right +
+                box_.border.get().left + box_.border.get().right;
+            position_ref.get().size.width = remaining_width + padding_and_borders;
+        }
+
+        if self.is_float() {
+            self.base.position.size.width = remaining_width;
+        }
+        if self.is_absolutely_positioned() {
+            // The flow of an absolutely positioned box is its margin box.
+            for box_ in self.box_.iter() {
+                self.base.position.size.width = box_.position.get().size.width +
+                    box_.margin.get().left + box_.margin.get().right;
+            }
+            self.base.flags_info.flags.set_inorder(false);
+        }
+
+        let has_inorder_children = if self.is_float() {
+            self.base.num_floats > 0
+        } else {
+            self.base.flags_info.flags.inorder() || self.base.num_floats > 0
+        };
+
+        // FIXME(ksh8281): avoid copy
+        let flags_info = self.base.flags_info.clone();
+        for kid in self.base.child_iter() {
+            assert!(kid.starts_block_flow() || kid.starts_inline_flow());
+
+            // The width of absolutely positioned kids comes from their containing block.
+            let is_absolutely_positioned = kid.is_absolutely_positioned();
+            let child_base = flow::mut_base(kid);
+            child_base.position.origin.x = x_offset;
+            if is_absolutely_positioned {
+                child_base.flags_info.flags.set_inorder(false);
+            } else {
+                child_base.position.size.width = remaining_width;
+                child_base.flags_info.flags.set_inorder(has_inorder_children);
+            }
+
+            if !child_base.flags_info.flags.inorder() {
+                child_base.floats_in = FloatContext::new(0);
+            }
+
+            // Per CSS 2.1 § 16.3.1, text decoration propagates to all children in flow.
+            //
+            // TODO(pcwalton): When we have out-of-flow children, don't unconditionally propagate.
+
+            child_base.flags_info.propagate_text_decoration_from_parent(&flags_info);
+            child_base.flags_info.propagate_text_alignment_from_parent(&flags_info)
+        }
+
+        if self.is_positioned() {
+            self.assign_widths_to_absolute_descendants()
+        }
+    }
+
+    /// The padding box of this block relative to the origin of the flow, which is the containing
+    /// block of its absolutely positioned descendants (CSS 2.1 § 10.1). For the root, it is the
+    /// initial containing block, which is the size of the screen.
+    fn absolute_containing_block(&self, screen_height: Au) -> Rect<Au> {
+        match self.box_ {
+            Some(ref box_) => {
+                let position = box_.position.get();
+                let border = box_.border.get();
+                Rect(Point2D(position.origin.x + border.left, position.origin.y + border.top),
+                     Size2D(position.size.width - border.left - border.right,
+                            position.size.height - border.top - border.bottom))
+            }
+            None => Rect(Au::zero_point(), Size2D(self.base.position.size.width, screen_height)),
+        }
+    }
+
+    /// Gives the absolutely positioned descendants that this block is the containing block of the
+    /// width of its padding box. Their widths depend on it, and their parents leave it alone.
+    fn assign_widths_to_absolute_descendants(&mut self) {
+        let width = self.absolute_containing_block(Au(0)).size.width;
+        for kid in self.base.child_iter() {
+            assign_width_to_absolute_descendants(kid, width)
+        }
+    }
+
+    /// Positions the absolutely positioned descendants that this block is the containing block
+    /// of. This happens once the height of this block is known, after all of them have been laid
+    /// out.
+    pub fn position_absolute_descendants(&mut self, screen_height: Au) {
+        let containing_block = self.absolute_containing_block(screen_height);
+        for kid in self.base.child_iter() {
+            place_absolute_descendants(kid, Au::zero_point(), &containing_block)
+        }
+    }
+
+    /// Places this absolutely positioned block within its containing block per CSS 2.1 § 10.3.7
+    /// and § 10.6.4. Its width is already known. `parent_offset` is the offset of the origin of
+    /// its parent flow from that of the containing block, whose padding box is `containing_block`.
+    fn position_absolutely(&mut self, parent_offset: Point2D<Au>, containing_block: &Rect<Au>) {
+        // The static position is where the parent flow put this flow as if it were in the flow.
+        let static_position = parent_offset + self.base.position.origin - containing_block.origin;
+        let containing_width = containing_block.size.width;
+        let containing_height = containing_block.size.height;
+
+        let mut origin = Point2D(Au(0), Au(0));
+        let mut margin_height = Au(0);
+        for box_ in self.box_.iter() {
+            let style = box_.style();
+            let mut margin = box_.margin.get();
+            let mut position = box_.position.get();
+            let noncontent_height = box_.border.get().top + box_.border.get().bottom +
+                box_.padding.get().top + box_.padding.get().bottom;
+
+            // Horizontally, only the offset is left to find.
+            let margin_width = position.size.width + margin.left + margin.right;
+            origin.x = match (MaybeAuto::from_style(style.PositionOffsets.left, containing_width),
+                              MaybeAuto::from_style(style.PositionOffsets.right,
+                                                    containing_width)) {
+                (Specified(_), Specified(right)) if style.Box.direction == direction::rtl => {
+                    containing_width - right - margin_width
+                }
+                (Specified(left), _) => left,
+                (Auto, Specified(right)) => containing_width - right - margin_width,
+                (Auto, Auto) => static_position.x,
+            };
+
+            // Vertically, the height of the containing block is now known, so the height can be
+            // resolved too. The height of an `auto`-height box is that of its contents, which the
+            // box already has, unless it is replaced.
+            let content_height = if box_.is_replaced() {
+                box_.content_height()
+            } else {
+                position.size.height - noncontent_height
+            };
+            let top = MaybeAuto::from_style(style.PositionOffsets.top, containing_height);
+            let bottom = MaybeAuto::from_style(style.PositionOffsets.bottom, containing_height);
+            let height = if box_.is_replaced() {
+                Specified(content_height)
+            } else {
+                MaybeAuto::from_style(style.Box.height, containing_height)
+            };
+            let margin_top = MaybeAuto::from_style(style.Margin.margin_top, containing_width);
+            let margin_bottom = MaybeAuto::from_style(style.Margin.margin_bottom,
+                                                      containing_width);
+            let available_height = containing_height - noncontent_height;
+
+            let (top, height, margin_top, margin_bottom) = match (top, height, bottom) {
+                (Specified(top), Specified(height), Specified(bottom)) => {
+                    let margins = available_height - top - height - bottom;
+                    match (margin_top, margin_bottom) {
+                        (Auto, Auto) => {
+                            let margin_top = margins.scale_by(0.5);
+                            (top, height, margin_top, margins - margin_top)
+                        }
+                        (Auto, Specified(margin_bottom)) => {
+                            (top, height, margins - margin_bottom, margin_bottom)
+                        }
+                        (Specified(margin_top), Auto) => {
+                            (top, height, margin_top, margins - margin_top)
+                        }
+                        // Over-constrained: `bottom` is ignored.
+                        (Specified(margin_top), Specified(margin_bottom)) => {
+                            (top, height, margin_top, margin_bottom)
+                        }
+                    }
+                }
+                (top, height, bottom) => {
+                    let margin_top = margin_top.specified_or_zero();
+                    let margin_bottom = margin_bottom.specified_or_zero();
+                    let height = match (top, height, bottom) {
+                        (Specified(top), Auto, Specified(bottom)) => {
+                            available_height - top - bottom - margin_top - margin_bottom
+                        }
+                        (_, height, _) => height.specified_or_default(content_height),
+                    };
+                    let top = match (top, bottom) {
+                        (Specified(top), _) => top,
+                        (Auto, Specified(bottom)) => {
+                            available_height - bottom - height - margin_top - margin_bottom
+                        }
+                        (Auto, Auto) => static_position.y,
+                    };
+                    (top, height, margin_top, margin_bottom)
+                }
+            };
+            origin.y = top;
+
+            // The kids were placed below the old top margin.
+            let margin_top_delta = margin_top - margin.top;
+            if margin_top_delta != Au(0) {
+                for kid in self.base.child_iter() {
+                    let kid_base = flow::mut_base(kid);
+                    kid_base.position.origin.y = kid_base.position.origin.y + margin_top_delta;
+                }
+            }
+
+            margin.top = margin_top;
+            margin.bottom = margin_bottom;
+            position.origin.y = margin_top;
+            position.size.height = height + noncontent_height;
+            margin_height = position.size.height + margin_top + margin_bottom;
+            box_.margin.set(margin);
+            box_.position.set(position);
+        }
+
+        self.base.position.origin = containing_block.origin + origin - parent_offset;
+        self.base.position.size.height = margin_height;
+
+        // This block has a box, so the screen height doesn't matter.
+        self.position_absolute_descendants(Au(0))
+    }
+
+    /// Assigns the height of a block that establishes a new block formatting context, such as a
+    /// table cell or caption (CSS 2.1 § 9.4.1). The floats inside it can't affect anything outside,
+    /// so it starts an in-order traversal of its own if it has any.
+    pub fn assign_height_formatting_context_root(&mut self, ctx: &mut LayoutContext) {
+        if self.base.num_floats == 0 {
+            return self.assign_height(ctx)
+        }
+
+        for box_ in self.box_.iter() {
+            box_.assign_height();
+        }
+        self.base.floats_in = FloatContext::new(self.base.num_floats);
+        self.assign_height_block_base(ctx, true);
+    }
+
     pub fn build_display_list_block<E:ExtraDisplayListData>(
                                     &mut self,
                                     builder: &DisplayListBuilder,
@@ -534,18 +1065,20 @@ impl BlockFlow {
                                     mut index: uint,
                                     lists: &RefCell<DisplayListCollection<E>>)
                                     -> uint {
-        if self.is_float() {
-            self.build_display_list_float(builder, container_block_size, dirty, index, lists);
-            return index;
-        }
-
-        if self.is_fixed {
+        // Blocks that paint atomically start display lists of their own, which are moved to their
+        // level of the enclosing stacking context once their descendants have been added.
+        if !self.is_root && self.stacking_level().is_some() {
             lists.with_mut(|lists| {
                 index = lists.lists.len();
-                lists.add_list(DisplayList::<E>::new());
+                lists.add_list(DisplayList::<E>::new_at_level(BackgroundAndBordersStackingLevel));
             });
         }
 
+        if self.is_float() {
+            self.build_display_list_float(builder, container_block_size, dirty, index, lists);
+            return index;
+        }
+
         let abs_rect = Rect(self.base.abs_position, self.base.position.size);
         if !abs_rect.intersects(dirty) {
             return index;
@@ -569,7 +1102,12 @@ impl BlockFlow {
         for box_ in self.box_.iter() {
             box_.build_display_list(builder, dirty, self.base.abs_position + rel_offset, (&*self) as &Flow, index, lists);
         }
-        // TODO: handle any out-of-flow elements
+        if self.is_scroll_container() {
+            self.position_children_in_scroll_layer();
+            return index
+        }
+
+        // Absolutely positioned kids have been placed relative to this flow like the others.
         let this_position = self.base.abs_position;
 
         for child in self.base.child_iter() {
@@ -613,8 +1151,10 @@ impl BlockFlow {
             box_.build_display_list(builder, dirty, offset, (&*self) as &Flow, index, lists);
         }
 
-
-        // TODO: handle any out-of-flow elements
+        if self.is_scroll_container() {
+            self.position_children_in_scroll_layer();
+            return false
+        }
 
         // go deeper into the flow tree
         for child in self.base.child_iter() {
@@ -641,7 +1181,6 @@ impl Flow for BlockFlow {
     min/pref widths based on child context widths and dimensions of
     any boxes it is responsible for flowing.  */
 
-    /* TODO: absolute contexts */
     /* TODO: inline-blocks */
     fn bubble_widths(&mut self, _: &mut LayoutContext) {
         let mut min_width = Au::new(0);
@@ -652,6 +1191,11 @@ impl Flow for BlockFlow {
         for child_ctx in self.base.child_iter() {
             assert!(child_ctx.starts_block_flow() || child_ctx.starts_inline_flow());
 
+            // Absolutely positioned kids don't affect the width of their parent.
+            if child_ctx.is_absolutely_positioned() {
+                continue
+            }
+
             let child_base = flow::mut_base(child_ctx);
             min_width = geometry::max(min_width, child_base.min_width);
             pref_width = geometry::max(pref_width, child_base.pref_width);
@@ -688,117 +1232,7 @@ impl Flow for BlockFlow {
     /// Dual boxes consume some width first, and the remainder is assigned to all child (block)
     /// contexts.
     fn assign_widths(&mut self, ctx: &mut LayoutContext) {
-        debug!("assign_widths({}): assigning width for flow {}",
-               if self.is_float() {
-                   "float"
-               } else {
-                   "block"
-               },
-               self.base.id);
-
-        if self.is_root {
-            debug!("Setting root position");
-            self.base.position.origin = Au::zero_point();
-            self.base.position.size.width = ctx.screen_size.width;
-            self.base.floats_in = FloatContext::new(self.base.num_floats);
-            self.base.flags_info.flags.set_inorder(false);
-        }
-
-        // The position was set to the containing block by the flow's parent.
-        let mut remaining_width = self.base.position.size.width;
-        let mut x_offset = Au::new(0);
-
-        if self.is_float() {
-            self.float.get_mut_ref().containing_width = remaining_width;
-
-            // Parent usually sets this, but floats are never inorder
-            self.base.flags_info.flags.set_inorder(false);
-        }
-
-        for box_ in self.box_.iter() {
-            let style = box_.style();
-
-            // The text alignment of a block flow is the text alignment of its box's style.
-            self.base.flags_info.flags.set_text_align(style.Text.text_align);
-
-            box_.assign_width(remaining_width);
-            // Can compute padding here since we know containing block width.
-            box_.compute_padding(style, remaining_width);
-
-            // Margins are 0 right now so base.noncontent_width() is just borders + padding.
-            let available_width = remaining_width - box_.noncontent_width();
-
-            // Top and bottom margins for blocks are 0 if auto.
-            let margin_top = MaybeAuto::from_style(style.Margin.margin_top,
-                                                   remaining_width).specified_or_zero();
-            let margin_bottom = MaybeAuto::from_style(style.Margin.margin_bottom,
-                                                      remaining_width).specified_or_zero();
-
-            let (width, margin_left, margin_right) = if self.is_float() {
-                self.compute_float_margins(box_, remaining_width)
-            } else {
-                self.compute_block_margins(box_, remaining_width, available_width)
-            };
-
-            box_.margin.set(SideOffsets2D::new(margin_top,
-                                               margin_right,
-                                               margin_bottom,
-                                               margin_left));
-
-            let screen_size = ctx.screen_size;
-            let (x, w) = box_.get_x_coord_and_new_width_if_fixed(screen_size.width,
-                                                                 screen_size.height,
-                                                                 width,
-                                                                 box_.offset(),
-                                                                 self.is_fixed);
-
-            x_offset = x;
-            remaining_width = w;
-
-            // The associated box is the border box of this flow.
-            let mut position_ref = box_.position.borrow_mut();
-            if self.is_fixed {
-                position_ref.get().origin.x = x_offset + box_.margin.get().left;
-                x_offset = x_offset + box_.padding.get().left;
-            } else {
-                position_ref.get().origin.x = box_.margin.get().left;
-            }
-            let padding_and_borders = box_.padding.get().left + box_.padding.get().right +
-                box_.border.get().left + box_.border.get().right;
-            position_ref.get().size.width = remaining_width + padding_and_borders;
-        }
-
-        if self.is_float() {
-            self.base.position.size.width = remaining_width;
-        }
-
-        let has_inorder_children = if self.is_float() {
-            self.base.num_floats > 0
-        } else {
-            self.base.flags_info.flags.inorder() || self.base.num_floats > 0
-        };
-
-        // FIXME(ksh8281): avoid copy
-        let flags_info = self.base.flags_info.clone();
-        for kid in self.base.child_iter() {
-            assert!(kid.starts_block_flow() || kid.starts_inline_flow());
-
-            let child_base = flow::mut_base(kid);
-            child_base.position.origin.x = x_offset;
-            child_base.position.size.width = remaining_width;
-            child_base.flags_info.flags.set_inorder(has_inorder_children);
-
-            if !child_base.flags_info.flags.inorder() {
-                child_base.floats_in = FloatContext::new(0);
-            }
-
-            // Per CSS 2.1 § 16.3.1, text decoration propagates to all children in flow.
-            //
-            // TODO(pcwalton): When we have out-of-flow children, don't unconditionally propagate.
-
-            child_base.flags_info.propagate_text_decoration_from_parent(&flags_info);
-            child_base.flags_info.propagate_text_alignment_from_parent(&flags_info)
-        }
+        self.assign_widths_block_base(ctx, None)
     }
 
     fn assign_height_inorder(&mut self, ctx: &mut LayoutContext) {
@@ -820,14 +1254,22 @@ impl Flow for BlockFlow {
         if self.is_float() {
             debug!("assign_height_float: assigning height for float {}", self.base.id);
             self.assign_height_float(ctx);
+            if self.is_positioned() {
+                self.position_absolute_descendants(ctx.screen_size.height)
+            }
         } else {
             debug!("assign_height: assigning height for block {}", self.base.id);
-            // This is the only case in which a block flow can start an inorder
-            // subtraversal.
+            // The root and absolutely positioned blocks are the cases in which a block flow can
+            // start an inorder subtraversal.
             if self.is_root && self.base.num_floats > 0 {
                 self.assign_height_inorder(ctx);
                 return;
             }
+            if self.is_absolutely_positioned() && self.base.num_floats > 0 {
+                self.base.floats_in = FloatContext::new(self.base.num_floats);
+                self.assign_height_block_base(ctx, true);
+                return;
+            }
             self.assign_height_block_base(ctx, false);
         }
     }
@@ -873,6 +1315,13 @@ impl Flow for BlockFlow {
         self.is_root = true
     }
 
+    fn is_absolutely_positioned(&self) -> bool {
+        match self.box_ {
+            Some(ref box_) => !self.is_root && box_.style().Box.position == position::absolute,
+            None => false,
+        }
+    }
+
     fn debug_str(&self) -> ~str {
         let txt = if self.is_float() {
             ~"FloatFlow: "
@@ -887,3 +1336,40 @@ impl Flow for BlockFlow {
         })
     }
 }
+
+/// Returns true if `flow` is the containing block of its absolutely positioned descendants.
+/// Row groups and rows can't be (CSS 2.1 § 9.3.1 leaves the effect of positioning them undefined).
+fn is_absolute_containing_block(flow: &mut Flow) -> bool {
+    match flow.class() {
+        InlineFlowClass | TableRowGroupFlowClass | TableRowFlowClass => false,
+        _ => flow.as_block().is_positioned(),
+    }
+}
+
+/// Sets the width of the absolutely positioned flows among `flow` and its descendants that share
+/// its containing block, whose padding box is `width` wide.
+fn assign_width_to_absolute_descendants(flow: &mut Flow, width: Au) {
+    if flow.is_absolutely_positioned() {
+        flow::mut_base(flow).position.size.width = width
+    } else if !is_absolute_containing_block(flow) {
+        for kid in flow::child_iter(flow) {
+            assign_width_to_absolute_descendants(kid, width)
+        }
+    }
+}
+
+/// Positions the absolutely positioned flows among `flow` and its descendants that share its
+/// containing block. `parent_offset` is the offset of the parent of `flow` from the containing
+/// block.
+fn place_absolute_descendants(flow: &mut Flow,
+                              parent_offset: Point2D<Au>,
+                              containing_block: &Rect<Au>) {
+    if flow.is_absolutely_positioned() {
+        flow.as_block().position_absolutely(parent_offset, containing_block)
+    } else if !is_absolute_containing_block(flow) {
+        let offset = parent_offset + flow::mut_base(flow).position.origin;
+        for kid in flow::child_iter(flow) {
+            place_absolute_descendants(kid, offset, containing_block)
+        }
+    }
+}
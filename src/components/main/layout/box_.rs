@@ -14,6 +14,8 @@ use gfx::display_list::{SolidColorDisplayItem, SolidColorDisplayItemClass, TextD
 use gfx::display_list::{TextDisplayItemClass, TextDisplayItemFlags, ClipDisplayItem};
 use gfx::display_list::{ClipDisplayItemClass, DisplayListCollection};
 use gfx::font::FontStyle;
+use gfx::text::bidi::BidiLevel;
+use gfx::text::bidi;
 use gfx::text::text_run::TextRun;
 use servo_msg::constellation_msg::{FrameRectMsg, PipelineId, SubpageId};
 use servo_net::image::holder::ImageHolder;
@@ -26,10 +28,12 @@ use std::cast;
 use std::cell::RefCell;
 use std::cmp::ApproxEq;
 use std::num::Zero;
-use style::{ComputedValues, TElement, TNode};
+use style::{ComputedValues, TElement, TNode, cascade_anonymous};
 use style::computed_values::{LengthOrPercentage, LengthOrPercentageOrAuto, overflow, LPA_Auto};
-use style::computed_values::{border_style, clear, font_family, line_height, position};
-use style::computed_values::{text_align, text_decoration, vertical_align, visibility, white_space};
+use style::computed_values::{background_repeat, border_style, clear};
+use style::computed_values::{direction, font_family, line_height, position};
+use style::computed_values::{overflow_wrap, text_align, text_decoration, vertical_align};
+use style::computed_values::{visibility, white_space, word_break};
 
 use css::node_style::StyledNode;
 use layout::construct::FlowConstructor;
@@ -95,6 +99,9 @@ pub struct Box {
 
     /// New-line chracter(\n)'s positions(relative, not absolute)
     new_line_pos: ~[uint],
+
+    /// The embedding level of this box's text, resolved by the Unicode bidirectional algorithm.
+    bidi_level: BidiLevel,
 }
 
 /// Info specific to the kind of box. Keep this enum small.
@@ -228,6 +235,10 @@ pub struct ScannedTextBoxInfo {
 
     /// The range within the above text run that this represents.
     range: Range,
+
+    /// The space that justification adds after each word separator in the range, on top of the
+    /// word spacing of the run.
+    extra_word_spacing: Au,
 }
 
 impl ScannedTextBoxInfo {
@@ -236,6 +247,7 @@ impl ScannedTextBoxInfo {
         ScannedTextBoxInfo {
             run: run,
             range: range,
+            extra_word_spacing: Au(0),
         }
     }
 }
@@ -318,9 +330,18 @@ impl Box {
             position_offsets: RefCell::new(Zero::zero()),
             inline_info: RefCell::new(None),
             new_line_pos: ~[],
+            bidi_level: 0,
         }
     }
 
+    /// Constructs a new generic `Box` for an anonymous box whose parent is `node`, such as an
+    /// anonymous table object (CSS 2.1 § 17.2.1).
+    pub fn new_anonymous(node: ThreadSafeLayoutNode) -> Box {
+        Box::from_opaque_node_and_style(OpaqueNode::from_thread_safe_layout_node(&node),
+                                        Arc::new(cascade_anonymous(node.style().get())),
+                                        GenericBox)
+    }
+
     /// Constructs a new `Box` instance from an opaque node.
     pub fn from_opaque_node_and_style(node: OpaqueNode,
                                       style: Arc<ComputedValues>,
@@ -337,6 +358,7 @@ impl Box {
             position_offsets: RefCell::new(Zero::zero()),
             inline_info: RefCell::new(None),
             new_line_pos: ~[],
+            bidi_level: 0,
         }
     }
 
@@ -434,6 +456,7 @@ impl Box {
             position_offsets: RefCell::new(Zero::zero()),
             inline_info: self.inline_info.clone(),
             new_line_pos: self.new_line_pos.clone(),
+            bidi_level: self.bidi_level,
         }
     }
 
@@ -680,16 +703,16 @@ impl Box {
     }
     pub fn relative_position(&self, container_block_size: &Size2D<Au>) -> Point2D<Au> {
         fn left_right(style: &ComputedValues,block_width: Au) -> Au {
-            // TODO(ksh8281) : consider RTL(right-to-left) culture
-            match (style.PositionOffsets.left, style.PositionOffsets.right) {
-                (LPA_Auto, _) => {
-                    -MaybeAuto::from_style(style.PositionOffsets.right, block_width)
-                        .specified_or_zero()
-                }
-                (_, _) => {
-                    MaybeAuto::from_style(style.PositionOffsets.left, block_width)
-                        .specified_or_zero()
-                }
+            // If both are set, `right` is ignored, or `left` in right-to-left text.
+            let ignores_left = match (style.PositionOffsets.left, style.PositionOffsets.right) {
+                (LPA_Auto, _) => true,
+                (_, LPA_Auto) => false,
+                (_, _) => style.Box.direction == direction::rtl,
+            };
+            if ignores_left {
+                -MaybeAuto::from_style(style.PositionOffsets.right, block_width).specified_or_zero()
+            } else {
+                MaybeAuto::from_style(style.PositionOffsets.left, block_width).specified_or_zero()
             }
         }
 
@@ -796,6 +819,19 @@ impl Box {
         self.style().Text.white_space
     }
 
+    /// Returns true if lines may be broken inside this box at places other than newlines.
+    pub fn can_wrap(&self) -> bool {
+        match self.white_space() {
+            white_space::normal | white_space::pre_wrap | white_space::pre_line => true,
+            white_space::nowrap | white_space::pre => false,
+        }
+    }
+
+    /// Returns true if spaces at the start of a line are removed. With `pre-wrap`, they are kept.
+    pub fn collapses_leading_whitespace(&self) -> bool {
+        self.white_space() != white_space::pre_wrap
+    }
+
     /// Returns the text decoration of this box, according to the style of the nearest ancestor
     /// element.
     ///
@@ -807,6 +843,31 @@ impl Box {
         self.style().Text.text_decoration
     }
 
+    /// Returns how lines may break between the letters of words in this box.
+    pub fn word_break(&self) -> word_break::T {
+        self.style().Text.word_break
+    }
+
+    /// Returns true if the text of this box is right-to-left.
+    pub fn is_rtl(&self) -> bool {
+        bidi::is_rtl(self.bidi_level)
+    }
+
+    /// Returns true if a word that does not fit on a line by itself may be broken anywhere.
+    pub fn breaks_long_words(&self) -> bool {
+        self.style().Text.overflow_wrap == overflow_wrap::break_word
+    }
+
+    /// Returns the extra space after each character of text in this box.
+    pub fn letter_spacing(&self) -> Au {
+        self.style().Text.letter_spacing.unwrap_or(Au(0))
+    }
+
+    /// Returns the extra space after each word separator in the text of this box.
+    pub fn word_spacing(&self) -> Au {
+        self.style().Text.word_spacing.unwrap_or(Au(0))
+    }
+
     /// Returns the sum of margin, border, and padding on the left.
     pub fn offset(&self) -> Au {
         self.margin.get().left + self.border.get().left + self.padding.get().left
@@ -927,6 +988,7 @@ impl Box {
     /// necessary.
     pub fn paint_background_if_applicable<E:ExtraDisplayListData>(
                                           &self,
+                                          builder: &DisplayListBuilder,
                                           index: uint,
                                           lists: &RefCell<DisplayListCollection<E>>,
                                           absolute_bounds: &Rect<Au>) {
@@ -949,6 +1011,72 @@ impl Box {
                 lists.lists[index].append_item(SolidColorDisplayItemClass(solid_color_display_item))
             });
         }
+
+        // The background image is painted over the color.
+        let image_url = match style.Background.background_image {
+            Some(ref url) => url.clone(),
+            None => return,
+        };
+        let mut holder = ImageHolder::new(image_url, builder.ctx.image_cache.clone());
+        let image = match holder.get_image() {
+            Some(image) => image,
+            // Layout is notified to reflow once the image has loaded.
+            None => return,
+        };
+        let image_width = Au::from_px(image.get().width as int);
+        let image_height = Au::from_px(image.get().height as int);
+        if image_width == Au(0) || image_height == Au(0) {
+            return
+        }
+
+        // The image is positioned relative to the padding box.
+        let border = self.border.get();
+        let positioning_area =
+            Rect(Point2D(absolute_bounds.origin.x + border.left,
+                         absolute_bounds.origin.y + border.top),
+                 Size2D(absolute_bounds.size.width - border.left - border.right,
+                        absolute_bounds.size.height - border.top - border.bottom));
+        let position = style.Background.background_position;
+        let origin = Point2D(
+            positioning_area.origin.x +
+                specified(position.horizontal, positioning_area.size.width - image_width),
+            positioning_area.origin.y +
+                specified(position.vertical, positioning_area.size.height - image_height));
+
+        let (repeat_x, repeat_y) = match style.Background.background_repeat {
+            background_repeat::repeat => (true, true),
+            background_repeat::repeat_x => (true, false),
+            background_repeat::repeat_y => (false, true),
+            background_repeat::no_repeat => (false, false),
+        };
+        let (start_x, end_x) = background_tile_range(origin.x, image_width, repeat_x,
+                                                     absolute_bounds.origin.x,
+                                                     absolute_bounds.size.width);
+        let (start_y, end_y) = background_tile_range(origin.y, image_height, repeat_y,
+                                                     absolute_bounds.origin.y,
+                                                     absolute_bounds.size.height);
+
+        // Tile the image, clipped to the border box. The renderer draws only the tiles that it
+        // needs.
+        let tiles = ImageDisplayItemClass(~ImageDisplayItem {
+            base: BaseDisplayItem {
+                bounds: Rect(Point2D(start_x, start_y), Size2D(end_x - start_x, end_y - start_y)),
+                extra: ExtraDisplayListData::new(self),
+            },
+            image: image.clone(),
+            stretch_size: Size2D(image_width, image_height),
+        });
+        lists.with_mut(|lists| {
+            let clip_display_item = ~ClipDisplayItem {
+                base: BaseDisplayItem {
+                    bounds: *absolute_bounds,
+                    extra: ExtraDisplayListData::new(self),
+                },
+                child_list: ~[tiles],
+                need_clip: true,
+            };
+            lists.lists[index].append_item(ClipDisplayItemClass(clip_display_item))
+        });
     }
 
     /// Adds the display items necessary to paint the borders of this box to a display list if
@@ -1008,13 +1136,10 @@ impl Box {
     /// * `dirty`: The dirty rectangle in the coordinate system of the owning flow.
     /// * `origin`: The total offset from the display list root flow to the owning flow of this
     ///   box.
-    /// * `list`: The display list to which items should be appended.
-    ///
-    /// TODO: To implement stacking contexts correctly, we need to create a set of display lists,
-    /// one per layer of the stacking context (CSS 2.1 § 9.9.1). Each box is passed the list set
-    /// representing the box's stacking context. When asked to construct its constituent display
-    /// items, each box puts its display items into the correct stack layer according to CSS 2.1
-    /// Appendix E. Finally, the builder flattens the list.
+    /// * `index`: The index of the display list to which items should be appended. The owning
+    ///   flow picks the list for the layer of its stacking context that the box paints in (CSS 2.1
+    ///   Appendix E).
+    /// * `lists`: The display lists of the stacking context being built.
     pub fn build_display_list<E:ExtraDisplayListData>(
                               &self,
                               builder: &DisplayListBuilder,
@@ -1042,7 +1167,7 @@ impl Box {
 
         self.paint_inline_background_border_if_applicable(index, lists, &absolute_box_bounds, &offset);
         // Add the background to the list, if applicable.
-        self.paint_background_if_applicable(index, lists, &absolute_box_bounds);
+        self.paint_background_if_applicable(builder, index, lists, &absolute_box_bounds);
 
         match self.specific {
             UnscannedTextBox(_) => fail!("Shouldn't see unscanned boxes here."),
@@ -1083,6 +1208,7 @@ impl Box {
                         },
                         text_run: text_box.run.clone(),
                         range: text_box.range,
+                        extra_word_spacing: text_box.extra_word_spacing,
                         text_color: text_color,
                         overline_color: flow_flags.overline_color(text_color),
                         underline_color: flow_flags.underline_color(text_color),
@@ -1194,6 +1320,7 @@ impl Box {
                                     extra: ExtraDisplayListData::new(self),
                                 },
                                 image: image.clone(),
+                                stretch_size: bounds.size,
                             };
                             lists.lists[index].append_item(ImageDisplayItemClass(image_display_item));
                         });
@@ -1366,52 +1493,73 @@ impl Box {
                        text_box_info.range,
                        max_width);
 
-                for (glyphs, offset, slice_range) in text_box_info.run.get().iter_slices_for_range(
-                        &text_box_info.range) {
-                    debug!("split_to_width: considering slice (offset={}, range={}, \
-                                                               remain_width={})",
-                           offset,
-                           slice_range,
+                let run = text_box_info.run.get();
+                for &(ref piece_range, is_whitespace) in
+                        run.break_pieces_for_range(&text_box_info.range).iter() {
+                    debug!("split_to_width: considering piece (range={}, remain_width={})",
+                           piece_range,
                            remaining_width);
 
-                    let metrics = text_box_info.run.get().metrics_for_slice(glyphs, &slice_range);
+                    let metrics = run.metrics_for_range(piece_range);
                     let advance = metrics.advance_width;
 
                     let should_continue;
                     if advance <= remaining_width {
                         should_continue = true;
 
-                        if starts_line && pieces_processed_count == 0 && glyphs.is_whitespace() {
+                        if starts_line && pieces_processed_count == 0 && is_whitespace &&
+                                self.collapses_leading_whitespace() {
                             debug!("split_to_width: case=skipping leading trimmable whitespace");
-                            left_range.shift_by(slice_range.length() as int);
+                            left_range.shift_by(piece_range.length() as int);
                         } else {
                             debug!("split_to_width: case=enlarging span");
                             remaining_width = remaining_width - advance;
-                            left_range.extend_by(slice_range.length() as int);
+                            left_range.extend_by(piece_range.length() as int);
                         }
                     } else {
                         // The advance is more than the remaining width.
                         should_continue = false;
-                        let slice_begin = offset + slice_range.begin();
-                        let slice_end = offset + slice_range.end();
+                        let piece_begin = piece_range.begin();
+                        let piece_end = piece_range.end();
 
-                        if glyphs.is_whitespace() {
+                        if is_whitespace {
                             // If there are still things after the trimmable whitespace, create the
                             // right chunk.
-                            if slice_end < text_box_info.range.end() {
+                            if piece_end < text_box_info.range.end() {
                                 debug!("split_to_width: case=skipping trimmable trailing \
                                         whitespace, then split remainder");
-                                let right_range_end = text_box_info.range.end() - slice_end;
-                                right_range = Some(Range::new(slice_end, right_range_end));
+                                let right_range_end = text_box_info.range.end() - piece_end;
+                                right_range = Some(Range::new(piece_end, right_range_end));
                             } else {
                                 debug!("split_to_width: case=skipping trimmable trailing \
                                         whitespace");
                             }
-                        } else if slice_begin < text_box_info.range.end() {
+                        } else if starts_line && left_range.length() == 0 &&
+                                self.breaks_long_words() {
+                            // The word doesn't fit on a line by itself, so break it after the
+                            // last character that fits, keeping at least one on this line.
+                            let mut char_count = 1;
+                            while char_count < piece_range.length() {
+                                let prefix = Range::new(piece_begin, char_count + 1);
+                                if run.metrics_for_range(&prefix).advance_width > remaining_width {
+                                    break
+                                }
+                                char_count += 1;
+                            }
+                            left_range.extend_by(char_count as int);
+
+                            let break_index = piece_begin + char_count;
+                            if break_index < text_box_info.range.end() {
+                                let right_range_end = text_box_info.range.end() - break_index;
+                                right_range = Some(Range::new(break_index, right_range_end));
+                            }
+                            debug!("split_to_width: case=breaking long word with right range={:?}",
+                                   right_range);
+                        } else if piece_begin < text_box_info.range.end() {
                             // There are still some things left over at the end of the line. Create
                             // the right chunk.
-                            let right_range_end = text_box_info.range.end() - slice_begin;
-                            right_range = Some(Range::new(slice_begin, right_range_end));
+                            let right_range_end = text_box_info.range.end() - piece_begin;
+                            right_range = Some(Range::new(piece_begin, right_range_end));
                             debug!("split_to_width: case=splitting remainder with right range={:?}",
                                    right_range);
                         }
@@ -1457,6 +1605,53 @@ impl Box {
         }
     }
 
+    /// Switches this text box over to `run`, which holds the same text shaped differently, as for
+    /// the `::first-line` pseudo-element. The size of the box changes by as much as the size of
+    /// its text does.
+    pub fn change_text_run(&mut self, run: Arc<~TextRun>) {
+        let text_box_info = match self.specific {
+            ScannedTextBox(ref text_box_info) => {
+                let range = &text_box_info.range;
+                let old_size = text_box_info.run.get().metrics_for_range(range).bounding_box.size;
+                let new_size = run.get().metrics_for_range(range).bounding_box.size;
+                let mut position = self.position.borrow_mut();
+                position.get().size.width = position.get().size.width + new_size.width -
+                    old_size.width;
+                position.get().size.height = position.get().size.height + new_size.height -
+                    old_size.height;
+                ScannedTextBoxInfo::new(run, *range)
+            }
+            _ => fail!("only text boxes have text runs"),
+        };
+        self.specific = ScannedTextBox(text_box_info)
+    }
+
+    /// Splits this text box in two before character `index` of its text run, as a line break
+    /// would.
+    pub fn split_text_at(&self, index: uint) -> (Box, Box) {
+        let text_box_info = match self.specific {
+            ScannedTextBox(ref text_box_info) => text_box_info,
+            _ => fail!("only text boxes can be split at a character"),
+        };
+        let range = &text_box_info.range;
+        assert!(index > range.begin() && index < range.end());
+        let ranges = [
+            Range::new(range.begin(), index - range.begin()),
+            Range::new(index, range.end() - index),
+        ];
+        let boxes = ranges.map(|range| {
+            let new_text_box_info = ScannedTextBoxInfo::new(text_box_info.run.clone(), *range);
+            let mut new_metrics = new_text_box_info.run.get().metrics_for_range(range);
+            new_metrics.bounding_box.size.height = self.position.get().size.height;
+            self.transform(new_metrics.bounding_box.size, ScannedTextBox(new_text_box_info))
+        });
+        let mut boxes = boxes.move_iter();
+        let (left_box, right_box) = (boxes.next().unwrap(), boxes.next().unwrap());
+        left_box.clear_noncontent_inline_right();
+        right_box.clear_noncontent_inline_left();
+        (left_box, right_box)
+    }
+
     /// Returns true if this box is an unscanned text box that consists entirely of whitespace.
     pub fn is_whitespace_only(&self) -> bool {
         match self.specific {
@@ -1559,7 +1754,11 @@ impl Box {
         match (&self.specific, &other.specific) {
             (&UnscannedTextBox(_), &UnscannedTextBox(_)) => {
                 self.font_style() == other.font_style() &&
-                    self.text_decoration() == other.text_decoration()
+                    self.text_decoration() == other.text_decoration() &&
+                    self.letter_spacing() == other.letter_spacing() &&
+                    self.word_spacing() == other.word_spacing() &&
+                    self.word_break() == other.word_break() &&
+                    self.bidi_level == other.bidi_level
             }
             _ => false,
         }
@@ -1573,9 +1772,17 @@ impl Box {
         }
     }
 
-    /// Returns true if the contents should be clipped (i.e. if `overflow` is `hidden`).
+    /// Returns true if the contents should be clipped (i.e. if `overflow` is not `visible`).
     pub fn needs_clip(&self) -> bool {
-        self.style().Box.overflow == overflow::hidden
+        self.style().Box.overflow != overflow::visible
+    }
+
+    /// Returns true if the contents can be scrolled (i.e. if `overflow` is `scroll` or `auto`).
+    pub fn is_scroll_container(&self) -> bool {
+        match self.style().Box.overflow {
+            overflow::scroll | overflow::auto => true,
+            overflow::visible | overflow::hidden => false,
+        }
     }
 
     /// Returns a debugging string describing this box.
@@ -1634,3 +1841,20 @@ impl Box {
         layout_context.constellation_chan.send(msg)
     }
 }
+
+/// Returns the range that tiles of a background image should cover along one axis, given the
+/// position of one tile and the extent of the box. Images that don't repeat have a single tile.
+fn background_tile_range(tile_origin: Au, tile_size: Au, repeat: bool, box_origin: Au, box_size: Au)
+                         -> (Au, Au) {
+    if !repeat {
+        return (tile_origin, tile_origin + tile_size)
+    }
+    // Move the first tile back to the last position at or before the start of the box.
+    let offset = (tile_origin - box_origin) % tile_size;
+    let start = if offset > Au(0) {
+        box_origin + offset - tile_size
+    } else {
+        box_origin + offset
+    };
+    (start, box_origin + box_size)
+}
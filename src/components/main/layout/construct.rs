@@ -24,27 +24,45 @@ use css::node_style::StyledNode;
 use layout::block::BlockFlow;
 use layout::box_::{Box, GenericBox, IframeBox, IframeBoxInfo, ImageBox, ImageBoxInfo};
 use layout::box_::{InlineInfo, InlineParentInfo, SpecificBoxInfo, UnscannedTextBox};
-use layout::box_::{UnscannedTextBoxInfo};
+use layout::box_::{ScannedTextBox, UnscannedTextBoxInfo};
 use layout::context::LayoutContext;
 use layout::float_context::FloatType;
-use layout::flow::{Flow, FlowLeafSet, ImmutableFlowUtils, MutableOwnedFlowUtils};
+use layout::generated_content::{GeneratedImage, GeneratedText};
+use layout::flow::{BlockFlowClass, Flow, FlowLeafSet, ImmutableFlowUtils, MutableOwnedFlowUtils};
+use layout::flow::{TableCaptionFlowClass, TableCellFlowClass, TableFlowClass, TableRowFlowClass};
+use layout::flow::{TableRowGroupFlowClass, FlowClass};
+use layout::flow;
 use layout::inline::InlineFlow;
+use layout::lists;
+use layout::table::TableFlow;
+use layout::table_caption::TableCaptionFlow;
+use layout::table_cell::TableCellFlow;
+use layout::table_row::TableRowFlow;
+use layout::table_rowgroup::TableRowGroupFlow;
 use layout::text::TextRunScanner;
 use layout::util::{LayoutDataAccess, OpaqueNode};
 use layout::wrapper::{PostorderNodeMutTraversal, TLayoutNode, ThreadSafeLayoutNode};
 
 use gfx::font_context::FontContext;
+use gfx::text::bidi::BidiLevel;
+use gfx::text::bidi;
+use gfx::text::text_run::TextRun;
 use script::dom::element::{HTMLIframeElementTypeId, HTMLImageElementTypeId};
 use script::dom::node::{CommentNodeTypeId, DoctypeNodeTypeId, DocumentFragmentNodeTypeId};
 use script::dom::node::{DocumentNodeTypeId, ElementNodeTypeId, ProcessingInstructionNodeTypeId};
 use script::dom::node::{TextNodeTypeId};
-use style::computed_values::{display, position, float, white_space};
-use style::ComputedValues;
+use style::computed_values::{LengthOrPercentageOrAuto, display, position, float, white_space};
+use style::computed_values::{direction, list_style_position, unicode_bidi};
+use style::{After, Before, ComputedValues, FirstLetter, FirstLine, PseudoElement};
+use style::{cascade, cascade_anonymous};
 
 use extra::arc::Arc;
+use std::borrow;
 use std::cell::RefCell;
+use std::str;
 use std::util;
 use std::num::Zero;
+use servo_util::namespace;
 
 /// The results of flow construction for a DOM node.
 pub enum ConstructionResult {
@@ -79,6 +97,9 @@ enum ConstructionItem {
     InlineBoxesConstructionItem(InlineBoxesConstructionResult),
     /// Potentially ignorable whitespace.
     WhitespaceConstructionItem(OpaqueNode, Arc<ComputedValues>),
+    /// The specified widths of table columns, from `table-column` and `table-column-group` boxes
+    /// that have not yet found their table.
+    TableColumnsConstructionItem(~[LengthOrPercentageOrAuto]),
 }
 
 impl ConstructionItem {
@@ -91,7 +112,7 @@ impl ConstructionItem {
                     }
                 }
             }
-            WhitespaceConstructionItem(..) => {}
+            WhitespaceConstructionItem(..) | TableColumnsConstructionItem(..) => {}
         }
     }
 }
@@ -145,6 +166,27 @@ impl InlineBlockSplit {
     }
 }
 
+/// The anonymous table objects (CSS 2.1 § 17.2.1) that are open while the children of a table
+/// part are being built. Consecutive children that need the same anonymous object share it.
+struct AnonymousTableParts {
+    /// The anonymous row wrapping cells whose parent is a table or row group.
+    row: Option<~Flow>,
+    /// The anonymous cell wrapping content that isn't a table part.
+    cell: Option<~Flow>,
+    /// Inline boxes waiting to be flushed to the anonymous cell.
+    boxes: Option<~[Box]>,
+}
+
+impl AnonymousTableParts {
+    fn new() -> AnonymousTableParts {
+        AnonymousTableParts {
+            row: None,
+            cell: None,
+            boxes: None,
+        }
+    }
+}
+
 /// Methods on optional vectors.
 ///
 /// TODO(pcwalton): I think this will no longer be necessary once Rust #8981 lands.
@@ -282,6 +324,7 @@ impl<'fc> FlowConstructor<'fc> {
             return
         }
 
+        let boxes = resolve_bidi_levels(boxes, node.style().get().Box.direction);
         let mut inline_flow = ~InlineFlow::from_boxes(self.next_flow_id(), node, boxes) as ~Flow;
         inline_flow.mark_as_leaf(self.layout_context.flow_leaf_set.get());
         TextRunScanner::new().scan_for_runs(self.font_context, inline_flow);
@@ -301,6 +344,296 @@ impl<'fc> FlowConstructor<'fc> {
         }
     }
 
+    /// Like `flush_inline_boxes_to_flow_if_necessary`, but for the inline content of the block
+    /// `node`. This puts the list item marker, if there is one left to place, at the start of the
+    /// boxes, creating an inline flow for the marker alone if the list item begins with a block.
+    /// If the boxes start the first line of the block, this also applies the `::first-letter`
+    /// and `::first-line` pseudo-elements.
+    fn flush_inline_boxes_to_block_flow(&mut self,
+                                        opt_boxes: &mut Option<~[Box]>,
+                                        opt_marker: &mut Option<Box>,
+                                        flow: &mut ~Flow,
+                                        node: ThreadSafeLayoutNode) {
+        let mut boxes = util::replace(opt_boxes, None).to_vec();
+        let marker = util::replace(opt_marker, None);
+        if boxes.len() == 0 && marker.is_none() {
+            return
+        }
+
+        // The boxes start the first line of the block unless an in-flow child precedes them.
+        // FIXME: If the block starts with a block-level child, its first line is the first line
+        // of that child, which does not get the pseudo-elements yet.
+        let is_first_line = !flow::child_iter(&mut **flow).any(|kid| is_in_flow(kid));
+        if is_first_line {
+            match node.pseudo_style(FirstLetter) {
+                None => {}
+                Some(style) => self.split_first_letter(&mut boxes, style.clone(), flow, node),
+            }
+        }
+
+        let is_outside_marker = match marker {
+            None => false,
+            Some(marker) => {
+                let is_outside =
+                    marker.style().List.list_style_position == list_style_position::outside;
+                boxes.unshift(marker);
+                is_outside
+            }
+        };
+
+        let first_line_styles = if is_first_line {
+            match node.pseudo_style(FirstLine) {
+                None => ~[],
+                Some(style) => first_line_styles(node, style, boxes.as_slice()),
+            }
+        } else {
+            ~[]
+        };
+
+        let boxes = resolve_bidi_levels(boxes, node.style().get().Box.direction);
+        let mut inline_flow = ~InlineFlow::from_boxes(self.next_flow_id(), node, boxes) as ~Flow;
+        inline_flow.mark_as_leaf(self.layout_context.flow_leaf_set.get());
+        TextRunScanner::new().scan_for_runs(self.font_context, inline_flow);
+        inline_flow.as_inline().first_line_styles = first_line_styles;
+        self.shape_first_line_runs(inline_flow.as_inline());
+        if is_first_line {
+            inline_flow.as_inline().text_indent = Some(node.style().get().Text.text_indent);
+        }
+
+        // Outside markers take no part in the line boxes; the inline flow hangs them to the left
+        // of its first line.
+        if is_outside_marker {
+            inline_flow.as_inline().detach_outside_marker()
+        }
+
+        flow.add_new_child(inline_flow)
+    }
+
+    /// Shapes the text of the boxes of `inline_flow` again in the fonts of their `::first-line`
+    /// styles, where those differ from their own, so that the first line can be laid out in them.
+    /// Boxes that share a text run share its first-line run too, in the first-line font of the
+    /// first of them.
+    fn shape_first_line_runs(&mut self, inline_flow: &mut InlineFlow) {
+        let mut first_line_runs: ~[(Arc<~TextRun>, Arc<~TextRun>)] = ~[];
+        for box_ in inline_flow.boxes.iter() {
+            let run = match box_.specific {
+                ScannedTextBox(ref text_box_info) => &text_box_info.run,
+                _ => continue,
+            };
+            if first_line_runs.iter().any(|&(ref other, _)| {
+                borrow::ref_eq(other.get(), run.get())
+            }) {
+                continue
+            }
+            let first_line_styles = &inline_flow.first_line_styles;
+            let first_line_style = match first_line_styles.iter().find(|&&(ref style, _)| {
+                borrow::ref_eq(style.get(), box_.style.get())
+            }) {
+                None => continue,
+                Some(&(_, ref first_line_style)) => first_line_style.clone(),
+            };
+            let mut first_line_box = box_.clone();
+            first_line_box.style = first_line_style;
+            let font_style = first_line_box.font_style();
+            if font_style == box_.font_style() &&
+                    first_line_box.letter_spacing() == box_.letter_spacing() &&
+                    first_line_box.word_spacing() == box_.word_spacing() {
+                continue
+            }
+
+            let fontgroup = self.font_context.get_resolved_font_for_style(&font_style);
+            let mut first_line_run = ~fontgroup.borrow().with(|fg| {
+                fg.create_textrun(run.get().text.get().clone(),
+                                  first_line_box.text_decoration(),
+                                  first_line_box.word_break(),
+                                  box_.is_rtl())
+            });
+            first_line_run.letter_spacing = first_line_box.letter_spacing();
+            first_line_run.word_spacing = first_line_box.word_spacing();
+            first_line_runs.push((run.clone(), Arc::new(first_line_run)))
+        }
+        inline_flow.first_line_runs = first_line_runs
+    }
+
+    /// Splits the first typographic letter unit of the block `node` out of its first text box,
+    /// into a box with the `::first-letter` style (CSS 2.1 § 5.12.2). A floated first letter
+    /// becomes a float of its own, added to `flow`.
+    fn split_first_letter(&mut self,
+                          boxes: &mut ~[Box],
+                          style: Arc<ComputedValues>,
+                          flow: &mut ~Flow,
+                          node: ThreadSafeLayoutNode) {
+        // The first letter is in the first box with anything but whitespace, if that is text.
+        let index = match boxes.iter().position(|box_| !box_.is_whitespace_only()) {
+            None => return,
+            Some(index) => index,
+        };
+        let (letter, rest) = match boxes[index].specific {
+            UnscannedTextBox(ref text_box_info) => {
+                match first_letter_and_rest(text_box_info.text.as_slice()) {
+                    None => return,
+                    Some((letter, rest)) => (letter.to_owned(), rest.to_owned()),
+                }
+            }
+            _ => return,
+        };
+
+        let mut letter_box = boxes[index].clone();
+        letter_box.style = style.clone();
+        letter_box.specific = UnscannedTextBox(UnscannedTextBoxInfo::from_text(letter));
+        if rest.is_empty() {
+            boxes.remove(index);
+        } else {
+            // The letter and the rest of the text split the box's part of any inline parents.
+            letter_box.inline_info.with_mut(|info| {
+                for info in info.mut_iter() {
+                    for parent_info in info.parent_info.mut_iter() {
+                        parent_info.border.right = Zero::zero()
+                    }
+                }
+            });
+            boxes[index].inline_info.with_mut(|info| {
+                for info in info.mut_iter() {
+                    for parent_info in info.parent_info.mut_iter() {
+                        parent_info.border.left = Zero::zero()
+                    }
+                }
+            });
+            boxes[index].specific = UnscannedTextBox(UnscannedTextBoxInfo::from_text(rest));
+        }
+
+        if style.get().Box.float == float::none {
+            boxes.insert(index, letter_box);
+            return
+        }
+
+        // A floated first letter is a block of its own. Its text goes in an anonymous inline box
+        // so that the borders and padding of the pseudo-element apply only once.
+        let float_type = FloatType::from_property(style.get().Box.float);
+        let float_box =
+            Box::from_opaque_node_and_style(OpaqueNode::from_thread_safe_layout_node(&node),
+                                            style.clone(),
+                                            GenericBox);
+        let mut float_flow = ~BlockFlow::float_from_node_and_box(self, node, float_box, float_type)
+            as ~Flow;
+        letter_box.style = Arc::new(cascade_anonymous(style.get()));
+        letter_box.inline_info.set(None);
+        self.flush_inline_boxes_to_flow(~[letter_box], &mut float_flow, node);
+        self.mark_as_leaf_if_childless(&mut float_flow);
+        flow.add_new_child(float_flow)
+    }
+
+    /// Computes the ordinal of a list item from its `value` attribute, its preceding list items,
+    /// and the `start` and `reversed` attributes of its list, per HTML5 § 4.5.5. The result is
+    /// recorded in the node's layout data so that the next list item can count on from it.
+    fn compute_list_item_ordinal(&mut self, node: ThreadSafeLayoutNode) -> i32 {
+        // Safe because flow construction visits the previous siblings of a node before the node
+        // itself, and never writes to the attributes of its parent.
+        let parent = unsafe { node.parent_node() };
+        let reversed = parent.map_default(false, |parent| parent.get_attr("reversed").is_some());
+
+        let mut previous_ordinal = None;
+        let mut opt_sibling = unsafe { node.prev_sibling() };
+        loop {
+            match opt_sibling {
+                None => break,
+                Some(sibling) if sibling.is_list_item() => {
+                    previous_ordinal = sibling.list_item_ordinal();
+                    break
+                }
+                Some(sibling) => opt_sibling = unsafe { sibling.prev_sibling() },
+            }
+        }
+
+        let ordinal = match (node.get_int_attr("value"), previous_ordinal) {
+            (Some(value), _) => value,
+            (None, Some(previous)) if reversed => previous - 1,
+            (None, Some(previous)) => previous + 1,
+            (None, None) => {
+                match parent {
+                    None => 1,
+                    Some(parent) => {
+                        match parent.get_int_attr("start") {
+                            Some(start) => start,
+                            None if reversed => {
+                                parent.children().count(|kid| kid.is_list_item()) as i32
+                            }
+                            None => 1,
+                        }
+                    }
+                }
+            }
+        };
+        node.set_list_item_ordinal(ordinal);
+        ordinal
+    }
+
+    /// Builds the marker box of a list item, if it has one (CSS 2.1 § 12.5.1). The marker is an
+    /// image if `list-style-image` is set and text otherwise.
+    fn build_marker_box_if_list_item(&mut self, node: ThreadSafeLayoutNode) -> Option<Box> {
+        if !node.is_list_item() {
+            return None
+        }
+        let ordinal = self.compute_list_item_ordinal(node);
+
+        let style = node.style().get();
+        let specific = match style.List.list_style_image {
+            Some(ref url) => {
+                ImageBox(ImageBoxInfo::new(&node,
+                                           url.clone(),
+                                           self.layout_context.image_cache.clone()))
+            }
+            None => {
+                match lists::marker_text(style.List.list_style_type, ordinal) {
+                    None => return None,
+                    Some(text) => UnscannedTextBox(UnscannedTextBoxInfo::from_text(text)),
+                }
+            }
+        };
+
+        // The marker inherits from the list item but does not take its borders or padding.
+        Some(Box::from_opaque_node_and_style(OpaqueNode::from_thread_safe_layout_node(&node),
+                                             Arc::new(cascade_anonymous(style)),
+                                             specific))
+    }
+
+    /// Adds inline boxes for the resolved content of the given pseudo-element of `node`, if any,
+    /// to `opt_boxes`.
+    ///
+    /// FIXME: Pseudo-elements are always inline, whatever their `display`.
+    fn push_generated_content_boxes(&mut self,
+                                    opt_boxes: &mut Option<~[Box]>,
+                                    node: ThreadSafeLayoutNode,
+                                    pseudo: PseudoElement) {
+        let style = match node.pseudo_style(pseudo) {
+            None => return,
+            Some(style) => style.clone(),
+        };
+        let content = {
+            let layout_data_ref = node.borrow_layout_data();
+            let data = &layout_data_ref.get().as_ref().unwrap().data;
+            match pseudo {
+                Before => data.before_content.clone(),
+                After => data.after_content.clone(),
+                FirstLine | FirstLetter => fail!("no generated content for {:?}", pseudo),
+            }
+        };
+        for item in content.move_iter() {
+            let specific = match item {
+                GeneratedText(text) => UnscannedTextBox(UnscannedTextBoxInfo::from_text(text)),
+                GeneratedImage(url) => {
+                    ImageBox(ImageBoxInfo::new(&node,
+                                               url,
+                                               self.layout_context.image_cache.clone()))
+                }
+            };
+            opt_boxes.push(Box::from_opaque_node_and_style(
+                    OpaqueNode::from_thread_safe_layout_node(&node),
+                    style.clone(),
+                    specific))
+        }
+    }
+
     /// Builds the children flows underneath a node with `display: block`. After this call,
     /// other `BlockFlow`s or `InlineFlow`s will be populated underneath this node, depending on
     /// whether {ib} splits needed to happen.
@@ -308,6 +641,14 @@ impl<'fc> FlowConstructor<'fc> {
         // Gather up boxes for the inline flows we might need to create.
         let mut opt_boxes_for_inline_flow = None;
         let mut first_box = true;
+        let mut opt_marker = self.build_marker_box_if_list_item(node);
+        self.push_generated_content_boxes(&mut opt_boxes_for_inline_flow, node, Before);
+
+        // Table parts outside of a table are wrapped in an anonymous table, which consecutive
+        // table parts share (CSS 2.1 § 17.2.1).
+        let mut anonymous_table = None;
+        let mut anonymous_table_parts = AnonymousTableParts::new();
+
         for kid in node.children() {
             match kid.swap_out_construction_result() {
                 NoConstructionResult => {}
@@ -324,16 +665,37 @@ impl<'fc> FlowConstructor<'fc> {
                     debug!("flushing {} inline box(es) to flow A",
                            opt_boxes_for_inline_flow.as_ref()
                                                     .map_default(0, |boxes| boxes.len()));
-                    self.flush_inline_boxes_to_flow_if_necessary(&mut opt_boxes_for_inline_flow,
-                                                                 flow,
-                                                                 node);
-                    flow.add_new_child(kid_flow)
+                    self.flush_inline_boxes_to_block_flow(&mut opt_boxes_for_inline_flow,
+                                                          &mut opt_marker,
+                                                          flow,
+                                                          node);
+
+                    if is_table_part(kid_flow.class()) {
+                        if anonymous_table.is_none() {
+                            anonymous_table = Some(self.build_anonymous_table(node))
+                        }
+                        self.add_table_part_child(anonymous_table.get_mut_ref(),
+                                                  &mut anonymous_table_parts,
+                                                  kid_flow,
+                                                  node)
+                    } else {
+                        self.close_anonymous_table(flow,
+                                                   &mut anonymous_table,
+                                                   &mut anonymous_table_parts,
+                                                   node);
+                        flow.add_new_child(kid_flow)
+                    }
                 }
                 ConstructionItemConstructionResult(InlineBoxesConstructionItem(
                         InlineBoxesConstructionResult {
                             splits: opt_splits,
                             boxes: boxes
                         })) => {
+                    self.close_anonymous_table(flow,
+                                               &mut anonymous_table,
+                                               &mut anonymous_table_parts,
+                                               node);
+
                     // Add any {ib} splits.
                     match opt_splits {
                         None => {}
@@ -360,8 +722,9 @@ impl<'fc> FlowConstructor<'fc> {
                                        opt_boxes_for_inline_flow.as_ref()
                                                                 .map_default(0,
                                                                              |boxes| boxes.len()));
-                                self.flush_inline_boxes_to_flow_if_necessary(
+                                self.flush_inline_boxes_to_block_flow(
                                         &mut opt_boxes_for_inline_flow,
+                                        &mut opt_marker,
                                         flow,
                                         node);
 
@@ -378,17 +741,28 @@ impl<'fc> FlowConstructor<'fc> {
                 ConstructionItemConstructionResult(WhitespaceConstructionItem(..)) => {
                     // Nothing to do here.
                 }
+                ConstructionItemConstructionResult(TableColumnsConstructionItem(..)) => {
+                    // Columns outside of a table don't affect anything.
+                }
             }
         }
+        self.close_anonymous_table(flow, &mut anonymous_table, &mut anonymous_table_parts, node);
+        self.push_generated_content_boxes(&mut opt_boxes_for_inline_flow, node, After);
 
         // Perform a final flush of any inline boxes that we were gathering up to handle {ib}
         // splits, after stripping ignorable whitespace.
         strip_ignorable_whitespace_from_end(&mut opt_boxes_for_inline_flow);
-        self.flush_inline_boxes_to_flow_if_necessary(&mut opt_boxes_for_inline_flow,
-                                                     flow,
-                                                     node);
+        self.flush_inline_boxes_to_block_flow(&mut opt_boxes_for_inline_flow,
+                                              &mut opt_marker,
+                                              flow,
+                                              node);
+
+        self.mark_as_leaf_if_childless(flow)
+    }
 
-        // The flow is done. If it ended up with no kids, add the flow to the leaf set.
+    /// Finishes a flow whose children have all been added: if it ended up with no kids, it goes in
+    /// the leaf set.
+    fn mark_as_leaf_if_childless(&mut self, flow: &mut ~Flow) {
         if flow.child_count() == 0 {
             flow.mark_as_leaf(self.layout_context.flow_leaf_set.get())
         } else {
@@ -414,6 +788,269 @@ impl<'fc> FlowConstructor<'fc> {
         flow
     }
 
+    /// Builds the flow for a node with `display: table` or `display: inline-table`.
+    ///
+    /// FIXME: Inline tables are laid out like block-level ones.
+    fn build_flow_for_table(&mut self, node: ThreadSafeLayoutNode) -> ~Flow {
+        let box_ = Box::new(self, node);
+        let mut flow = ~TableFlow::from_node_and_box(self, node, box_) as ~Flow;
+        self.build_children_of_table_part(&mut flow, node);
+        flow
+    }
+
+    /// Builds the flow for a node with `display: table-row-group`, `table-header-group` or
+    /// `table-footer-group`.
+    fn build_flow_for_table_row_group(&mut self, node: ThreadSafeLayoutNode) -> ~Flow {
+        let box_ = Box::new(self, node);
+        let mut flow = ~TableRowGroupFlow::from_node_and_box(self, node, box_) as ~Flow;
+        self.build_children_of_table_part(&mut flow, node);
+        flow
+    }
+
+    /// Builds the flow for a node with `display: table-row`.
+    fn build_flow_for_table_row(&mut self, node: ThreadSafeLayoutNode) -> ~Flow {
+        let box_ = Box::new(self, node);
+        let mut flow = ~TableRowFlow::from_node_and_box(self, node, box_) as ~Flow;
+        self.build_children_of_table_part(&mut flow, node);
+        flow
+    }
+
+    /// Builds the flow for a node with `display: table-cell`. Its contents are built like those
+    /// of a block.
+    fn build_flow_for_table_cell(&mut self, node: ThreadSafeLayoutNode) -> ~Flow {
+        let box_ = Box::new(self, node);
+        let column_span = column_span(node);
+        let mut flow = ~TableCellFlow::from_node_and_box(self, node, box_, column_span) as ~Flow;
+        self.build_children_of_block_flow(&mut flow, node);
+        flow
+    }
+
+    /// Builds the flow for a node with `display: table-caption`. Its contents are built like those
+    /// of a block.
+    fn build_flow_for_table_caption(&mut self, node: ThreadSafeLayoutNode) -> ~Flow {
+        let box_ = Box::new(self, node);
+        let mut flow = ~TableCaptionFlow::from_node_and_box(self, node, box_) as ~Flow;
+        self.build_children_of_block_flow(&mut flow, node);
+        flow
+    }
+
+    /// Builds the construction result for a node with `display: table-column` or
+    /// `table-column-group`. Columns generate no flows; only their widths bubble up to the table.
+    /// A column group without columns stands for a single column itself.
+    ///
+    /// FIXME: The `span` attribute of `<col>` and `<colgroup>` is ignored.
+    fn build_construction_result_for_table_columns(&mut self, node: ThreadSafeLayoutNode)
+                                                   -> ConstructionResult {
+        let mut widths = ~[];
+        for kid in node.children() {
+            match kid.swap_out_construction_result() {
+                ConstructionItemConstructionResult(TableColumnsConstructionItem(kid_widths)) => {
+                    widths.push_all_move(kid_widths)
+                }
+                mut result => result.destroy(self.layout_context.flow_leaf_set.get()),
+            }
+        }
+        if widths.is_empty() {
+            widths.push(node.style().get().Box.width)
+        }
+        ConstructionItemConstructionResult(TableColumnsConstructionItem(widths))
+    }
+
+    /// Builds the children of a table, row group or row. Children that can't go there directly
+    /// are wrapped in anonymous table objects (CSS 2.1 § 17.2.1).
+    fn build_children_of_table_part(&mut self, flow: &mut ~Flow, node: ThreadSafeLayoutNode) {
+        let mut parts = AnonymousTableParts::new();
+        for kid in node.children() {
+            match kid.swap_out_construction_result() {
+                NoConstructionResult => {}
+                FlowConstructionResult(kid_flow) => {
+                    self.add_table_part_child(flow, &mut parts, kid_flow, node)
+                }
+                ConstructionItemConstructionResult(InlineBoxesConstructionItem(
+                        InlineBoxesConstructionResult {
+                            splits: opt_splits,
+                            boxes: boxes
+                        })) => {
+                    // Inline content goes in an anonymous cell, {ib} splits and all.
+                    self.open_anonymous_cell(&mut parts, node);
+                    match opt_splits {
+                        None => {}
+                        Some(splits) => {
+                            for split in splits.move_iter() {
+                                let InlineBlockSplit {
+                                    predecessor_boxes: predecessor_boxes,
+                                    flow: kid_flow
+                                } = split;
+                                parts.boxes.push_all_move(predecessor_boxes);
+                                self.flush_boxes_to_anonymous_cell(&mut parts, node);
+                                parts.cell.get_mut_ref().add_new_child(kid_flow)
+                            }
+                        }
+                    }
+                    parts.boxes.push_all_move(boxes)
+                }
+                ConstructionItemConstructionResult(WhitespaceConstructionItem(whitespace_node,
+                                                                              whitespace_style))
+                        => {
+                    // Whitespace between table parts is ignorable, but not inside an anonymous
+                    // cell.
+                    if parts.cell.is_some() {
+                        parts.boxes.push(Box::from_opaque_node_and_style(
+                                whitespace_node,
+                                whitespace_style,
+                                UnscannedTextBox(UnscannedTextBoxInfo::from_text(~" "))))
+                    }
+                }
+                ConstructionItemConstructionResult(TableColumnsConstructionItem(widths)) => {
+                    match flow.class() {
+                        TableFlowClass => {
+                            flow.as_table().column_specified_widths.push_all_move(widths)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        self.close_anonymous_table_parts(flow, &mut parts, node);
+        self.mark_as_leaf_if_childless(flow)
+    }
+
+    /// Adds `kid` to the table part `flow`, going through anonymous table objects if `flow` can't
+    /// contain it directly. Misparented table parts get an anonymous table of their own.
+    fn add_table_part_child(&mut self,
+                            flow: &mut ~Flow,
+                            parts: &mut AnonymousTableParts,
+                            kid: ~Flow,
+                            node: ThreadSafeLayoutNode) {
+        match (flow.class(), kid.class()) {
+            (TableFlowClass, TableCaptionFlowClass) |
+            (TableFlowClass, TableRowGroupFlowClass) |
+            (TableFlowClass, TableRowFlowClass) |
+            (TableRowGroupFlowClass, TableRowFlowClass) |
+            (TableRowFlowClass, TableCellFlowClass) => {
+                self.close_anonymous_table_parts(flow, parts, node);
+                flow.add_new_child(kid)
+            }
+            (_, TableCellFlowClass) => {
+                self.close_anonymous_cell(flow, parts, node);
+                self.open_anonymous_row(parts, node);
+                parts.row.get_mut_ref().add_new_child(kid)
+            }
+            (_, kid_class) => {
+                let kid = if is_table_part(kid_class) {
+                    self.wrap_in_anonymous_table(kid, node)
+                } else {
+                    kid
+                };
+                self.open_anonymous_cell(parts, node);
+                self.flush_boxes_to_anonymous_cell(parts, node);
+                parts.cell.get_mut_ref().add_new_child(kid)
+            }
+        }
+    }
+
+    /// Creates an anonymous table flow whose parent is `node`.
+    fn build_anonymous_table(&mut self, node: ThreadSafeLayoutNode) -> ~Flow {
+        ~TableFlow::from_node_and_box(self, node, Box::new_anonymous(node)) as ~Flow
+    }
+
+    /// Wraps the table part `kid` in an anonymous table.
+    fn wrap_in_anonymous_table(&mut self, kid: ~Flow, node: ThreadSafeLayoutNode) -> ~Flow {
+        let mut table = self.build_anonymous_table(node);
+        let mut parts = AnonymousTableParts::new();
+        self.add_table_part_child(&mut table, &mut parts, kid, node);
+        self.close_anonymous_table_parts(&mut table, &mut parts, node);
+        table.mark_as_nonleaf();
+        table
+    }
+
+    /// Finishes the anonymous table that wraps table parts in a block, if any, and adds it to the
+    /// block.
+    fn close_anonymous_table(&mut self,
+                             flow: &mut ~Flow,
+                             anonymous_table: &mut Option<~Flow>,
+                             parts: &mut AnonymousTableParts,
+                             node: ThreadSafeLayoutNode) {
+        match util::replace(anonymous_table, None) {
+            None => {}
+            Some(mut table) => {
+                self.close_anonymous_table_parts(&mut table, parts, node);
+                table.mark_as_nonleaf();
+                flow.add_new_child(table)
+            }
+        }
+    }
+
+    fn open_anonymous_row(&mut self, parts: &mut AnonymousTableParts, node: ThreadSafeLayoutNode) {
+        if parts.row.is_none() {
+            let row = ~TableRowFlow::from_node_and_box(self, node, Box::new_anonymous(node));
+            parts.row = Some(row as ~Flow)
+        }
+    }
+
+    fn open_anonymous_cell(&mut self, parts: &mut AnonymousTableParts, node: ThreadSafeLayoutNode) {
+        if parts.cell.is_none() {
+            let cell = ~TableCellFlow::from_node_and_box(self,
+                                                          node,
+                                                          Box::new_anonymous(node),
+                                                          1);
+            parts.cell = Some(cell as ~Flow)
+        }
+    }
+
+    /// Moves the inline boxes gathered for the anonymous cell into an inline flow inside it. The
+    /// boxes always border a block boundary, so whitespace at either end is ignorable.
+    fn flush_boxes_to_anonymous_cell(&mut self,
+                                     parts: &mut AnonymousTableParts,
+                                     node: ThreadSafeLayoutNode) {
+        let mut boxes = util::replace(&mut parts.boxes, None);
+        strip_ignorable_whitespace_from_start(&mut boxes);
+        strip_ignorable_whitespace_from_end(&mut boxes);
+        match parts.cell {
+            None => {}
+            Some(ref mut cell) => self.flush_inline_boxes_to_flow_if_necessary(&mut boxes,
+                                                                               cell,
+                                                                               node),
+        }
+    }
+
+    /// Finishes the anonymous cell, if any, and adds it to `flow` or to the anonymous row.
+    fn close_anonymous_cell(&mut self,
+                            flow: &mut ~Flow,
+                            parts: &mut AnonymousTableParts,
+                            node: ThreadSafeLayoutNode) {
+        self.flush_boxes_to_anonymous_cell(parts, node);
+        match util::replace(&mut parts.cell, None) {
+            None => {}
+            Some(mut cell) => {
+                self.mark_as_leaf_if_childless(&mut cell);
+                match flow.class() {
+                    TableRowFlowClass => flow.add_new_child(cell),
+                    _ => {
+                        self.open_anonymous_row(parts, node);
+                        parts.row.get_mut_ref().add_new_child(cell)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Finishes all the anonymous table objects that are open and adds them to `flow`.
+    fn close_anonymous_table_parts(&mut self,
+                                   flow: &mut ~Flow,
+                                   parts: &mut AnonymousTableParts,
+                                   node: ThreadSafeLayoutNode) {
+        self.close_anonymous_cell(flow, parts, node);
+        match util::replace(&mut parts.row, None) {
+            None => {}
+            Some(mut row) => {
+                row.mark_as_nonleaf();
+                flow.add_new_child(row)
+            }
+        }
+    }
+
 
     /// Concatenates the boxes of kids, adding in our own borders/padding/margins if necessary.
     /// Returns the `InlineBoxesConstructionResult`, if any. There will be no
@@ -422,6 +1059,7 @@ impl<'fc> FlowConstructor<'fc> {
                                                   -> ConstructionResult {
         let mut opt_inline_block_splits = None;
         let mut opt_box_accumulator = None;
+        self.push_generated_content_boxes(&mut opt_box_accumulator, node, Before);
  
         // Concatenate all the boxes of our kids, creating {ib} splits as necessary.
         for kid in node.children() {
@@ -475,8 +1113,12 @@ impl<'fc> FlowConstructor<'fc> {
                             whitespace_style,
                             UnscannedTextBox(UnscannedTextBoxInfo::from_text(~" "))))
                 }
+                ConstructionItemConstructionResult(TableColumnsConstructionItem(..)) => {
+                    // Columns outside of a table don't affect anything.
+                }
             }
         }
+        self.push_generated_content_boxes(&mut opt_box_accumulator, node, After);
 
         // fill inline info
         match opt_inline_block_splits {
@@ -666,6 +1308,45 @@ impl<'a> PostorderNodeMutTraversal for FlowConstructor<'a> {
                 let flow = self.build_flow_for_block(node, true);
                 node.set_flow_construction_result(FlowConstructionResult(flow))
             }
+
+            // Absolutely positioned elements are blocks that layout takes out of the flow. They
+            // are never floated (CSS 2.1 § 9.7).
+            //
+            // FIXME: Inside inline content, they cause {ib} splits as if they were in the flow.
+            (_, _, position::absolute) => {
+                let flow = self.build_flow_for_block(node, false);
+                node.set_flow_construction_result(FlowConstructionResult(flow))
+            }
+
+            // Table parts contribute flows of their own, apart from columns, whose widths bubble
+            // up to the table.
+            (display::table, float::none, _) | (display::inline_table, float::none, _) => {
+                let flow = self.build_flow_for_table(node);
+                node.set_flow_construction_result(FlowConstructionResult(flow))
+            }
+            (display::table_row_group, _, _) |
+            (display::table_header_group, _, _) |
+            (display::table_footer_group, _, _) => {
+                let flow = self.build_flow_for_table_row_group(node);
+                node.set_flow_construction_result(FlowConstructionResult(flow))
+            }
+            (display::table_row, _, _) => {
+                let flow = self.build_flow_for_table_row(node);
+                node.set_flow_construction_result(FlowConstructionResult(flow))
+            }
+            (display::table_cell, _, _) => {
+                let flow = self.build_flow_for_table_cell(node);
+                node.set_flow_construction_result(FlowConstructionResult(flow))
+            }
+            (display::table_caption, _, _) => {
+                let flow = self.build_flow_for_table_caption(node);
+                node.set_flow_construction_result(FlowConstructionResult(flow))
+            }
+            (display::table_column, _, _) | (display::table_column_group, _, _) => {
+                let construction_result = self.build_construction_result_for_table_columns(node);
+                node.set_flow_construction_result(construction_result)
+            }
+
             (_, float::none, _) => {
                 let flow = self.build_flow_for_block(node, false);
                 node.set_flow_construction_result(FlowConstructionResult(flow))
@@ -697,6 +1378,27 @@ trait NodeUtils {
     /// Replaces the flow construction result in a node with `NoConstructionResult` and returns the
     /// old value.
     fn swap_out_construction_result(self) -> ConstructionResult;
+
+    /// Returns true if this node is an element with `display: list-item`.
+    fn is_list_item(self) -> bool;
+
+    /// Returns the ordinal recorded for this list item, if any.
+    fn list_item_ordinal(self) -> Option<i32>;
+
+    /// Records the ordinal of this list item.
+    fn set_list_item_ordinal(self, ordinal: i32);
+
+    /// Returns the value of the given attribute in the null namespace, if this is an element that
+    /// has it.
+    fn get_attr(self, name: &str) -> Option<&'static str>;
+
+    /// Returns the value of the given attribute parsed as an integer, if present and valid.
+    fn get_int_attr(self, name: &str) -> Option<i32>;
+
+    /// Cascades the declarations that apply to this node, or to the given pseudo-element of it,
+    /// onto a different parent style than the one it was styled with.
+    fn recascade(self, pseudo: Option<PseudoElement>, parent_style: &ComputedValues)
+                 -> Arc<ComputedValues>;
 }
 
 impl<'ln> NodeUtils for ThreadSafeLayoutNode<'ln> {
@@ -731,8 +1433,8 @@ impl<'ln> NodeUtils for ThreadSafeLayoutNode<'ln> {
                     // If you implement other values for this property, you will almost certainly
                     // want to update this check.
                     match self.style().get().Text.white_space {
-                        white_space::normal => true,
-                        _ => false,
+                        white_space::normal | white_space::nowrap => true,
+                        white_space::pre | white_space::pre_wrap | white_space::pre_line => false,
                     }
                 }
             }
@@ -759,6 +1461,88 @@ impl<'ln> NodeUtils for ThreadSafeLayoutNode<'ln> {
             None => fail!("no layout data"),
         }
     }
+
+    fn is_list_item(self) -> bool {
+        match self.type_id() {
+            ElementNodeTypeId(_) => self.style().get().Box.display == display::list_item,
+            _ => false,
+        }
+    }
+
+    fn list_item_ordinal(self) -> Option<i32> {
+        let layout_data_ref = self.borrow_layout_data();
+        match *layout_data_ref.get() {
+            Some(ref layout_data) => layout_data.data.list_item_ordinal,
+            None => fail!("no layout data"),
+        }
+    }
+
+    fn set_list_item_ordinal(self, ordinal: i32) {
+        let mut layout_data_ref = self.mutate_layout_data();
+        match *layout_data_ref.get() {
+            Some(ref mut layout_data) => layout_data.data.list_item_ordinal = Some(ordinal),
+            None => fail!("no layout data"),
+        }
+    }
+
+    fn get_attr(self, name: &str) -> Option<&'static str> {
+        match self.type_id() {
+            ElementNodeTypeId(_) => {
+                self.with_element(|element| element.get_attr(&namespace::Null, name))
+            }
+            _ => None,
+        }
+    }
+
+    fn get_int_attr(self, name: &str) -> Option<i32> {
+        self.get_attr(name).and_then(|value| FromStr::from_str(value.trim()))
+    }
+
+    fn recascade(self, pseudo: Option<PseudoElement>, parent_style: &ComputedValues)
+                 -> Arc<ComputedValues> {
+        let layout_data_ref = self.borrow_layout_data();
+        let data = &layout_data_ref.get().as_ref().unwrap().data;
+        let declarations = match pseudo {
+            None => data.applicable_declarations.as_slice(),
+            Some(Before) => data.before_applicable_declarations.as_slice(),
+            Some(After) => data.after_applicable_declarations.as_slice(),
+            Some(FirstLine) => data.first_line_applicable_declarations.as_slice(),
+            Some(FirstLetter) => data.first_letter_applicable_declarations.as_slice(),
+        };
+        Arc::new(cascade(declarations, Some(parent_style)))
+    }
+}
+
+/// Returns true if `flow` takes part in the normal flow of its parent, rather than being floated
+/// or absolutely positioned.
+fn is_in_flow(flow: &mut Flow) -> bool {
+    if flow.is_absolutely_positioned() {
+        return false
+    }
+    match flow.class() {
+        BlockFlowClass => !flow.as_block().is_float(),
+        _ => true,
+    }
+}
+
+/// Returns true if flows of the given class must be inside a table (CSS 2.1 § 17.2.1).
+fn is_table_part(class: FlowClass) -> bool {
+    match class {
+        TableCaptionFlowClass | TableRowGroupFlowClass | TableRowFlowClass |
+        TableCellFlowClass => true,
+        _ => false,
+    }
+}
+
+/// Returns the number of columns that a table cell spans, from its `colspan` attribute. Missing,
+/// invalid and zero spans count as one column, and spans are capped at 1000 like HTML5 § 4.9.11
+/// says.
+fn column_span(node: ThreadSafeLayoutNode) -> uint {
+    match node.get_int_attr("colspan") {
+        Some(span) if span > 1000 => 1000,
+        Some(span) if span > 0 => span as uint,
+        _ => 1,
+    }
 }
 
 /// Strips ignorable whitespace from the start of a list of boxes.
@@ -812,3 +1596,361 @@ fn strip_ignorable_whitespace_from_end(opt_boxes: &mut Option<~[Box]>) {
     }
 }
 
+/// Splits text into its first typographic letter unit, with any punctuation around it, and the
+/// rest (CSS 2.1 § 5.12.2). Leading whitespace is dropped. Returns `None` if there is no letter.
+fn first_letter_and_rest<'a>(text: &'a str) -> Option<(&'a str, &'a str)> {
+    fn is_punctuation(c: char) -> bool {
+        match c {
+            '!'..'/' | ':'..'@' | '['..'`' | '{'..'~' | '\u00a1' | '\u00ab' | '\u00bb' |
+            '\u00bf' | '\u2018'..'\u201f' => true,
+            _ => false,
+        }
+    }
+
+    let text = text.trim_left();
+    let mut chars = text.char_indices().skip_while(|&(_, c)| is_punctuation(c));
+    let end = match chars.next() {
+        Some((_, c)) if c.is_whitespace() => return None,
+        Some((i, c)) => i + c.len_utf8_bytes(),
+        None => return None,
+    };
+    let end = match text.slice_from(end).char_indices().find(|&(_, c)| !is_punctuation(c)) {
+        Some((i, _)) => end + i,
+        None => text.len(),
+    };
+    Some((text.slice_to(end), text.slice_from(end)))
+}
+
+/// Returns the explicit formatting character that opens the embedding or override that `style`
+/// establishes through `unicode-bidi` (CSS 2.1 § 9.10), if any.
+fn bidi_control_for_style(style: &ComputedValues) -> Option<char> {
+    match (style.Box.unicode_bidi, style.Box.direction) {
+        (unicode_bidi::normal, _) => None,
+        (unicode_bidi::embed, direction::ltr) => Some(bidi::LEFT_TO_RIGHT_EMBEDDING),
+        (unicode_bidi::embed, direction::rtl) => Some(bidi::RIGHT_TO_LEFT_EMBEDDING),
+        (unicode_bidi::bidi_override, direction::ltr) => Some(bidi::LEFT_TO_RIGHT_OVERRIDE),
+        (unicode_bidi::bidi_override, direction::rtl) => Some(bidi::RIGHT_TO_LEFT_OVERRIDE),
+    }
+}
+
+/// Resolves the embedding levels of the inline `boxes` of a block with the direction
+/// `block_direction`, using the Unicode bidirectional algorithm. Text boxes are split wherever the
+/// level changes, so that all the text of each box has the same level.
+fn resolve_bidi_levels(boxes: ~[Box], block_direction: direction::T) -> ~[Box] {
+    let paragraph_level = match block_direction {
+        direction::ltr => 0,
+        direction::rtl => 1,
+    };
+
+    // The embeddings and overrides of the inline parents of each box are written out as explicit
+    // formatting characters around its content. Boxes other than text count as object
+    // replacement characters.
+    let mut chars = ~[];
+    let mut content_starts = ~[];
+    for box_ in boxes.iter() {
+        let controls: ~[char] = box_.inline_info.with(|info| {
+            match *info {
+                None => ~[],
+                Some(ref info) => {
+                    info.parent_info.rev_iter().filter_map(|parent_info| {
+                        bidi_control_for_style(parent_info.style.get())
+                    }).collect()
+                }
+            }
+        });
+        chars.push_all(controls.as_slice());
+        content_starts.push(chars.len());
+        match box_.specific {
+            UnscannedTextBox(ref text_box_info) => {
+                // Collapsible newlines are spaces, not paragraph separators.
+                let preserves_newlines = match box_.white_space() {
+                    white_space::normal | white_space::nowrap => false,
+                    white_space::pre | white_space::pre_wrap | white_space::pre_line => true,
+                };
+                for c in text_box_info.text.chars() {
+                    chars.push(if c == '\n' && !preserves_newlines { ' ' } else { c })
+                }
+            }
+            _ => chars.push('\ufffc'),
+        }
+        for _ in controls.iter() {
+            chars.push(bidi::POP_DIRECTIONAL_FORMATTING)
+        }
+    }
+    let levels = bidi::resolve_levels(str::from_chars(chars), paragraph_level);
+
+    let mut result = ~[];
+    for (box_, &start) in boxes.move_iter().zip(content_starts.iter()) {
+        let mut box_ = box_;
+        let text = match box_.specific {
+            UnscannedTextBox(ref text_box_info) => Some(text_box_info.text.clone()),
+            _ => None,
+        };
+        let text = match text {
+            None => {
+                box_.bidi_level = levels[start];
+                result.push(box_);
+                continue
+            }
+            Some(text) => text,
+        };
+
+        // Divide the text into pieces with one level each.
+        let mut piece_texts: ~[~str] = ~[];
+        let mut piece_levels: ~[BidiLevel] = ~[];
+        for (c, &level) in text.chars().zip(levels.slice_from(start).iter()) {
+            if piece_levels.len() == 0 || *piece_levels.last() != level {
+                piece_texts.push(~"");
+                piece_levels.push(level);
+            }
+            let last = piece_texts.len() - 1;
+            piece_texts[last].push_char(c);
+        }
+        if piece_texts.len() <= 1 {
+            for &level in piece_levels.iter() {
+                box_.bidi_level = level;
+            }
+            result.push(box_);
+            continue
+        }
+
+        // The pieces split the box's part of any inline parents, like lines do.
+        let piece_count = piece_texts.len();
+        for (i, (piece, &level)) in piece_texts.move_iter().zip(piece_levels.iter()).enumerate() {
+            let mut piece_box = box_.clone();
+            piece_box.specific = UnscannedTextBox(UnscannedTextBoxInfo::from_text(piece));
+            piece_box.bidi_level = level;
+            piece_box.inline_info.with_mut(|info| {
+                for info in info.mut_iter() {
+                    for parent_info in info.parent_info.mut_iter() {
+                        if i > 0 {
+                            parent_info.border.left = Zero::zero()
+                        }
+                        if i < piece_count - 1 {
+                            parent_info.border.right = Zero::zero()
+                        }
+                    }
+                }
+            });
+            result.push(piece_box)
+        }
+    }
+    result
+}
+
+/// Works out the styles that the boxes of the first line of the block `node` take on from its
+/// `::first-line` pseudo-element, which acts as an inline parent of the line's content (CSS 2.1
+/// § 5.12.1). Returns pairs of the style of a box and the style it has on the first line.
+fn first_line_styles(node: ThreadSafeLayoutNode,
+                     first_line_style: &Arc<ComputedValues>,
+                     boxes: &[Box])
+                     -> ~[(Arc<ComputedValues>, Arc<ComputedValues>)] {
+    // The first-line style of each node with boxes on the line, by cascading each one's
+    // declarations onto the first-line style of its parent. Only inline elements can contain
+    // such nodes, and the walk ends once it has found them all, so each node of the block
+    // cascades at most once.
+    fn push_node_styles<'a>(node: ThreadSafeLayoutNode<'a>,
+                            style: &Arc<ComputedValues>,
+                            box_nodes: &mut ~[OpaqueNode],
+                            node_styles: &mut ~[(ThreadSafeLayoutNode<'a>, Arc<ComputedValues>)]) {
+        for kid in node.children() {
+            if box_nodes.is_empty() {
+                return
+            }
+            let kid_node = OpaqueNode::from_thread_safe_layout_node(&kid);
+            let has_boxes = match box_nodes.iter().position(|&box_node| box_node == kid_node) {
+                Some(index) => {
+                    box_nodes.remove(index);
+                    true
+                }
+                None => false,
+            };
+            let is_inline = kid.style().get().Box.display == display::inline;
+            if !has_boxes && !is_inline {
+                continue
+            }
+            let kid_style = kid.recascade(None, style.get());
+            if is_inline {
+                push_node_styles(kid, &kid_style, box_nodes, node_styles);
+            }
+            node_styles.push((kid, kid_style))
+        }
+    }
+    let block_node = OpaqueNode::from_thread_safe_layout_node(&node);
+    let mut box_nodes = ~[];
+    for box_ in boxes.iter() {
+        if box_.node != block_node && !box_nodes.contains(&box_.node) {
+            box_nodes.push(box_.node)
+        }
+    }
+    let mut node_styles = ~[(node, first_line_style.clone())];
+    push_node_styles(node, first_line_style, &mut box_nodes, &mut node_styles);
+
+    let mut result: ~[(Arc<ComputedValues>, Arc<ComputedValues>)] = ~[];
+    for box_ in boxes.iter() {
+        if result.iter().any(|&(ref style, _)| borrow::ref_eq(style.get(), box_.style.get())) {
+            continue
+        }
+        let index = match node_styles.iter().position(|&(box_node, _)| {
+            OpaqueNode::from_thread_safe_layout_node(&box_node) == box_.node
+        }) {
+            None => continue,
+            Some(index) => index,
+        };
+        let (box_node, ref node_style) = node_styles[index];
+
+        // Boxes of pseudo-elements cascade their own declarations, and anonymous boxes such as
+        // list item markers inherit everything. The block's own boxes belong to the line only
+        // through their inline content.
+        let style = if borrow::ref_eq(box_node.style().get(), box_.style.get()) {
+            if index == 0 {
+                continue
+            }
+            node_style.clone()
+        } else {
+            let pseudo = [Before, After, FirstLetter].iter().find(|& &pseudo| {
+                match box_node.pseudo_style(pseudo) {
+                    Some(style) => borrow::ref_eq(style.get(), box_.style.get()),
+                    None => false,
+                }
+            });
+            match pseudo {
+                Some(&pseudo) => box_node.recascade(Some(pseudo), node_style.get()),
+                None => Arc::new(cascade_anonymous(node_style.get())),
+            }
+        };
+        result.push((box_.style.clone(), style))
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{first_letter_and_rest, resolve_bidi_levels};
+    use layout::box_::{Box, GenericBox, InlineInfo, InlineParentInfo, UnscannedTextBox};
+    use layout::box_::UnscannedTextBoxInfo;
+    use layout::test_util::style;
+    use layout::util::OpaqueNode;
+
+    use extra::arc::Arc;
+    use geom::SideOffsets2D;
+    use servo_util::geometry::Au;
+    use std::num::Zero;
+    use style::computed_values::direction;
+
+    fn text_box(text: &str) -> Box {
+        Box::from_opaque_node_and_style(OpaqueNode(1),
+                                        Arc::new(style("")),
+                                        UnscannedTextBox(UnscannedTextBoxInfo::from_text(
+                                            text.to_owned())))
+    }
+
+    /// Puts `box_` inside an inline parent with the style `css` and a border of `border` on
+    /// every side.
+    fn in_parent(box_: Box, css: &str, border: Au) -> Box {
+        let mut info = InlineInfo::new();
+        info.parent_info.push(InlineParentInfo {
+            padding: Zero::zero(),
+            border: SideOffsets2D::new_all_same(border),
+            margin: Zero::zero(),
+            style: Arc::new(style(css)),
+            font_ascent: Au(0),
+            font_descent: Au(0),
+            node: OpaqueNode(2),
+        });
+        box_.inline_info.set(Some(info));
+        box_
+    }
+
+    /// Returns the text and embedding level of each of `boxes`, with `[]` for boxes other than
+    /// text.
+    fn pieces(boxes: &[Box]) -> ~[(~str, u8)] {
+        boxes.iter().map(|box_| {
+            let text = match box_.specific {
+                UnscannedTextBox(ref text_box_info) => text_box_info.text.clone(),
+                _ => ~"[]",
+            };
+            (text, box_.bidi_level)
+        }).collect()
+    }
+
+    #[test]
+    fn test_bidi_splits_text_boxes_by_level() {
+        let boxes = resolve_bidi_levels(~[text_box("abc \u05d0\u05d1 def")], direction::ltr);
+        assert_eq!(pieces(boxes.as_slice()), ~[(~"abc ", 0), (~"\u05d0\u05d1", 1), (~" def", 0)]);
+
+        let boxes = resolve_bidi_levels(~[text_box("\u05d0\u05d1 abc")], direction::rtl);
+        assert_eq!(pieces(boxes.as_slice()), ~[(~"\u05d0\u05d1 ", 1), (~"abc", 2)]);
+
+        // Boxes that are not text count as neutral characters.
+        let boxes = ~[
+            text_box("\u05d0"),
+            Box::from_opaque_node_and_style(OpaqueNode(1), Arc::new(style("")), GenericBox),
+            text_box("\u05d1"),
+        ];
+        assert_eq!(pieces(resolve_bidi_levels(boxes, direction::ltr).as_slice()),
+                   ~[(~"\u05d0", 1), (~"[]", 1), (~"\u05d1", 1)]);
+    }
+
+    #[test]
+    fn test_bidi_embeddings_of_inline_parents() {
+        let boxes = ~[
+            text_box("ab "),
+            in_parent(text_box("cd"), "direction: rtl; unicode-bidi: bidi-override", Au(0)),
+        ];
+        assert_eq!(pieces(resolve_bidi_levels(boxes, direction::ltr).as_slice()),
+                   ~[(~"ab ", 0), (~"cd", 1)]);
+
+        let boxes = ~[in_parent(text_box("cd"), "direction: rtl; unicode-bidi: embed", Au(0))];
+        assert_eq!(pieces(resolve_bidi_levels(boxes, direction::ltr).as_slice()), ~[(~"cd", 2)]);
+
+        // Parents without `unicode-bidi` leave the levels alone.
+        let boxes = ~[in_parent(text_box("cd"), "direction: rtl", Au(0))];
+        assert_eq!(pieces(resolve_bidi_levels(boxes, direction::ltr).as_slice()), ~[(~"cd", 0)]);
+    }
+
+    #[test]
+    fn test_bidi_split_clears_inner_borders() {
+        let boxes = ~[in_parent(text_box("ab \u05d0\u05d1 cd"), "", Au(3))];
+        let boxes = resolve_bidi_levels(boxes, direction::ltr);
+        assert_eq!(boxes.len(), 3);
+        let borders: ~[(Au, Au)] = boxes.iter().map(|box_| {
+            box_.inline_info.with(|info| {
+                let border = info.get_ref().parent_info[0].border;
+                (border.left, border.right)
+            })
+        }).collect();
+        assert_eq!(borders, ~[(Au(3), Au(0)), (Au(0), Au(0)), (Au(0), Au(3))]);
+    }
+
+    #[test]
+    fn test_first_letter_and_rest() {
+        assert_eq!(first_letter_and_rest("Hello"), Some(("H", "ello")));
+        assert_eq!(first_letter_and_rest("  Hello world"), Some(("H", "ello world")));
+        assert_eq!(first_letter_and_rest("été"), Some(("é", "té")));
+    }
+
+    #[test]
+    fn test_first_letter_punctuation() {
+        // Punctuation before and after the letter belongs to it.
+        assert_eq!(first_letter_and_rest("“Hello”"), Some(("“H", "ello”")));
+        assert_eq!(first_letter_and_rest("(\"A\") is first"), Some(("(\"A\")", " is first")));
+        assert_eq!(first_letter_and_rest("...and"), Some(("...a", "nd")));
+        // Whitespace after leading punctuation leaves no letter to style.
+        assert_eq!(first_letter_and_rest("( x"), None);
+        assert_eq!(first_letter_and_rest("?!"), None);
+    }
+
+    #[test]
+    fn test_first_letter_of_empty_text() {
+        assert_eq!(first_letter_and_rest(""), None);
+        assert_eq!(first_letter_and_rest(" "), None);
+        assert_eq!(first_letter_and_rest(" \n\t "), None);
+    }
+
+    #[test]
+    fn test_first_letter_is_whole_text() {
+        assert_eq!(first_letter_and_rest("A"), Some(("A", "")));
+        assert_eq!(first_letter_and_rest(" A. "), Some(("A.", " ")));
+    }
+}
@@ -24,6 +24,11 @@
 ///   boxes/flows that are subject to inline layout and line breaking and structs to represent
 ///   line breaks and mapping to CSS boxes, for the purpose of handling `getClientRects()` and
 ///   similar methods.
+///
+/// * `TableFlow`, `TableRowGroupFlow`, `TableRowFlow`, `TableCellFlow` and `TableCaptionFlow`:
+///   The parts of a CSS table. Each wraps a `BlockFlow` for its box and uses it for the parts of
+///   layout that tables share with blocks; the table flow runs the table layout algorithm and
+///   hands column widths down to its rows.
 
 use css::node_style::StyledNode;
 use layout::block::BlockFlow;
@@ -35,6 +40,10 @@ use layout::incremental::RestyleDamage;
 use layout::inline::InlineFlow;
 use layout::parallel::{FlowParallelInfo, UnsafeFlow};
 use layout::parallel;
+use layout::table::TableFlow;
+use layout::table_cell::TableCellFlow;
+use layout::table_row::TableRowFlow;
+use layout::table_rowgroup::TableRowGroupFlow;
 use layout::wrapper::ThreadSafeLayoutNode;
 use layout::flow_list::{FlowList, Link, Rawlink, FlowListIterator, MutFlowListIterator};
 
@@ -42,14 +51,18 @@ use extra::container::Deque;
 use geom::point::Point2D;
 use geom::Size2D;
 use geom::rect::Rect;
-use gfx::display_list::{ClipDisplayItemClass, DisplayListCollection, DisplayList};
+use gfx::display_list::{BaseDisplayItem, ClipDisplayItem, ClipDisplayItemClass};
+use gfx::display_list::{DisplayListCollection, DisplayList, ContentStackingLevel, ScrollLayer};
+use gfx::display_list::{ScrollLayerDisplayItem, ScrollLayerDisplayItemClass};
 use layout::display_list_builder::ToGfxColor;
 use gfx::color::Color;
+use servo_msg::compositor_msg::{PageLayerId, ScrollLayerId};
 use servo_util::concurrentmap::{ConcurrentHashMap, ConcurrentHashMapIterator};
 use servo_util::geometry::Au;
 use std::cast;
 use std::cell::RefCell;
 use std::sync::atomics::Relaxed;
+use std::util;
 use style::ComputedValues;
 use style::computed_values::text_align;
 
@@ -81,6 +94,26 @@ pub trait Flow {
         fail!("called as_inline() on a non-inline flow")
     }
 
+    /// If this is a table flow, returns the underlying object. Fails otherwise.
+    fn as_table<'a>(&'a mut self) -> &'a mut TableFlow {
+        fail!("called as_table() on a non-table flow")
+    }
+
+    /// If this is a table row group flow, returns the underlying object. Fails otherwise.
+    fn as_table_row_group<'a>(&'a mut self) -> &'a mut TableRowGroupFlow {
+        fail!("called as_table_row_group() on a non-table-row-group flow")
+    }
+
+    /// If this is a table row flow, returns the underlying object. Fails otherwise.
+    fn as_table_row<'a>(&'a mut self) -> &'a mut TableRowFlow {
+        fail!("called as_table_row() on a non-table-row flow")
+    }
+
+    /// If this is a table cell flow, returns the underlying object. Fails otherwise.
+    fn as_table_cell<'a>(&'a mut self) -> &'a mut TableCellFlow {
+        fail!("called as_table_cell() on a non-table-cell flow")
+    }
+
     // Main methods
 
     /// Pass 1 of reflow: computes minimum and preferred widths.
@@ -117,6 +150,12 @@ pub trait Flow {
     /// Marks this flow as the root flow. The default implementation is a no-op.
     fn mark_as_root(&mut self) {}
 
+    /// Returns true if this flow is absolutely positioned and so taken out of the normal flow
+    /// (CSS 2.1 § 9.6). The default implementation returns false.
+    fn is_absolutely_positioned(&self) -> bool {
+        false
+    }
+
     /// Returns a debugging string describing this flow.
     fn debug_str(&self) -> ~str {
         ~"???"
@@ -237,6 +276,11 @@ pub trait MutableOwnedFlowUtils {
 pub enum FlowClass {
     BlockFlowClass,
     InlineFlowClass,
+    TableFlowClass,
+    TableRowGroupFlowClass,
+    TableRowFlowClass,
+    TableCellFlowClass,
+    TableCaptionFlowClass,
 }
 
 /// A top-down traversal.
@@ -289,22 +333,22 @@ pub struct RareFlowFlags {
 
 /// Flags used in flows, tightly packed to save space.
 #[deriving(Clone)]
-pub struct FlowFlags(u8);
+pub struct FlowFlags(u16);
 
 /// The bitmask of flags that represent text decoration fields that get propagated downward.
 ///
 /// NB: If you update this field, you must update the bitfields below.
-static TEXT_DECORATION_OVERRIDE_BITMASK: u8 = 0b0000_1110;
+static TEXT_DECORATION_OVERRIDE_BITMASK: u16 = 0b0000_1110;
 
 /// The bitmask of flags that represent the text alignment field.
 ///
 /// NB: If you update this field, you must update the bitfields below.
-static TEXT_ALIGN_BITMASK: u8 = 0b0011_0000;
+static TEXT_ALIGN_BITMASK: u16 = 0b0111_0000;
 
 /// The number of bits we must shift off to handle the text alignment field.
 ///
 /// NB: If you update this field, you must update the bitfields below.
-static TEXT_ALIGN_SHIFT: u8 = 4;
+static TEXT_ALIGN_SHIFT: u16 = 4;
 
 impl FlowFlagsInfo {
     /// Creates a new set of flow flags from the given style.
@@ -457,21 +501,21 @@ bitfield!(FlowFlags, override_line_through, set_override_line_through, 0b0000_10
 
 // Whether this flow is marked as a leaf. Flows marked as leaves must not have any more kids added
 // to them.
-bitfield!(FlowFlags, is_leaf, set_is_leaf, 0b0100_0000)
+bitfield!(FlowFlags, is_leaf, set_is_leaf, 0b1000_0000)
 
 // Whether this flow is marked as a nonleaf. Flows marked as nonleaves must have children.
-bitfield!(FlowFlags, is_nonleaf, set_is_nonleaf, 0b1000_0000)
+bitfield!(FlowFlags, is_nonleaf, set_is_nonleaf, 0b0001_0000_0000)
 
 // The text alignment for this flow.
 impl FlowFlags {
     #[inline]
     pub fn text_align(self) -> text_align::T {
-        FromPrimitive::from_u8((*self & TEXT_ALIGN_BITMASK) >> TEXT_ALIGN_SHIFT).unwrap()
+        FromPrimitive::from_u16((*self & TEXT_ALIGN_BITMASK) >> TEXT_ALIGN_SHIFT).unwrap()
     }
 
     #[inline]
     pub fn set_text_align(&mut self, value: text_align::T) {
-        *self = FlowFlags((**self & !TEXT_ALIGN_BITMASK) | ((value as u8) << TEXT_ALIGN_SHIFT))
+        *self = FlowFlags((**self & !TEXT_ALIGN_BITMASK) | ((value as u16) << TEXT_ALIGN_SHIFT))
     }
 
     #[inline]
@@ -602,7 +646,7 @@ impl<'a> ImmutableFlowUtils for &'a Flow {
     fn is_block_like(self) -> bool {
         match self.class() {
             BlockFlowClass => true,
-            InlineFlowClass => false,
+            _ => false,
         }
     }
 
@@ -626,18 +670,19 @@ impl<'a> ImmutableFlowUtils for &'a Flow {
         match self.class() {
             // TODO: Change this when inline-blocks are supported.
             InlineFlowClass => false,
-            BlockFlowClass => {
+            _ => {
                 // FIXME: Actually check the type of the node
                 self.child_count() != 0
             }
         }
     }
 
-    /// Returns true if this flow is a block flow, an inline-block flow, or a float flow.
+    /// Returns true if this flow is a block flow, an inline-block flow, a float flow, or one of
+    /// the parts of a table.
     fn starts_block_flow(self) -> bool {
         match self.class() {
-            BlockFlowClass => true,
             InlineFlowClass => false,
+            _ => true,
         }
     }
 
@@ -645,7 +690,7 @@ impl<'a> ImmutableFlowUtils for &'a Flow {
     fn starts_inline_flow(self) -> bool {
         match self.class() {
             InlineFlowClass => true,
-            BlockFlowClass => false,
+            _ => false,
         }
     }
 
@@ -741,9 +786,16 @@ impl<'a> MutableFlowUtils for &'a mut Flow {
                           lists: &RefCell<DisplayListCollection<E>>)
                           -> bool {
         debug!("Flow: building display list for f{}", base(self).id);
+        let (stacking_level, establishes_stacking_context, is_scroll_container) = match self.class() {
+            InlineFlowClass => (None, false, false),
+            _ => (self.as_block().stacking_level(),
+                  self.as_block().establishes_stacking_context(),
+                  self.as_block().is_scroll_container()),
+        };
+
         index = match self.class() {
-            BlockFlowClass => self.as_block().build_display_list_block(builder, container_block_size, dirty, index, lists),
             InlineFlowClass => self.as_inline().build_display_list_inline(builder, container_block_size, dirty, index, lists),
+            _ => self.as_block().build_display_list_block(builder, container_block_size, dirty, index, lists),
         };
 
         if lists.with_mut(|lists| lists.lists[index].list.len() == 0) {
@@ -755,44 +807,104 @@ impl<'a> MutableFlowUtils for &'a mut Flow {
             child_lists.add_list(DisplayList::new());
             let child_lists = RefCell::new(child_lists);
             let container_block_size = match self.class() {
-                BlockFlowClass => {
+                InlineFlowClass => {
+                    base(self).position.size
+                }
+                _ => {
                     if self.as_block().box_.is_some() {
                         self.as_block().box_.get_ref().position.get().size
                     } else {
                         base(self).position.size
                     }
                 },
-                _ => {
-                    base(self).position.size
-                }
+            };
+
+            // The children of a scroll container are painted into its layer, where all of them
+            // can be scrolled into view.
+            let (kid_dirty, scroll_layer_size) = if is_scroll_container {
+                let size = self.as_block().scroll_layer_size();
+                (Rect(Point2D(Au(0), Au(0)), size), size)
+            } else {
+                (*dirty, Size2D(Au(0), Au(0)))
             };
 
             for kid in child_iter(self) {
-                kid.build_display_lists(builder, &container_block_size, dirty, 0u, &child_lists);
+                kid.build_display_lists(builder, &container_block_size, &kid_dirty, 0u, &child_lists);
             }
 
+            let block_box = match self.class() {
+                InlineFlowClass => None,
+                _ => self.as_block().box_.as_ref(),
+            };
+
             let mut child_lists = Some(child_lists.unwrap());
             // Find parent ClipDisplayItemClass and push all child display items
             // under it
             lists.with_mut(|lists| {
                 let mut child_lists = child_lists.take_unwrap();
-                let result = lists.lists[index].list.mut_rev_iter().position(|item| {
+                if is_scroll_container {
+                    let box_ = block_box.expect("scroll container without a box");
+                    add_scroll_layer(lists, index, child_lists, box_, scroll_layer_size);
+                    return
+                }
+
+                // Scroll layers of descendants are kept where every layer can be found.
+                lists.scroll_layers.push_all_move(util::replace(&mut child_lists.scroll_layers, ~[]));
+
+                let mut in_flow_list = Some(child_lists.lists.shift());
+                let mut parent_clip = None;
+                lists.lists[index].list.mut_rev_iter().position(|item| {
                     match *item {
                         ClipDisplayItemClass(ref mut item) => {
-                            item.child_list.push_all_move(child_lists.lists.shift().list);
+                            if !establishes_stacking_context {
+                                item.child_list.push_all_move(in_flow_list.take_unwrap().list);
+                            }
+                            parent_clip = Some((item.base.bounds, item.need_clip));
                             true
                         },
                         _ => false,
                     }
                 });
 
-                if result.is_none() {
-                    fail!("fail to find parent item");
+                let (clip_bounds, need_clip) = match parent_clip {
+                    Some(parent_clip) => parent_clip,
+                    None => fail!("fail to find parent item"),
+                };
+
+                // A stacking context keeps its in-flow descendants in a list of their own, so
+                // that children with a negative z-index can paint between them and its
+                // background.
+                match (in_flow_list, block_box) {
+                    (Some(in_flow_list), Some(box_)) => {
+                        lists.add_list(clip_display_list(in_flow_list, box_, clip_bounds, need_clip))
+                    }
+                    (Some(in_flow_list), None) => lists.add_list(in_flow_list),
+                    (None, _) => {}
                 }
 
-                lists.lists.push_all_move(child_lists.lists);
+                // Floats and inline content are still clipped by this block. Positioned
+                // descendants are left alone.
+                for list in child_lists.lists.move_iter() {
+                    match block_box {
+                        Some(box_) if need_clip && !list.level.is_positioned() => {
+                            lists.add_list(clip_display_list(list, box_, clip_bounds, true))
+                        }
+                        _ => lists.add_list(list),
+                    }
+                }
             });
         }
+
+        // Put the lists of a block that paints atomically into painting order, at its level of
+        // the enclosing stacking context.
+        match stacking_level {
+            Some(level) => {
+                lists.with_mut(|lists| {
+                    lists.flatten_into_level(index, level, establishes_stacking_context)
+                })
+            }
+            None => {}
+        }
         true
     }
 
@@ -815,6 +927,86 @@ impl<'a> MutableFlowUtils for &'a mut Flow {
     }
 }
 
+/// Turns the display lists of the children of a scroll container into the layer of its contents,
+/// and adds it to `lists` ahead of the layers of the scroll containers inside it. The lists are put
+/// into painting order as if the container established a stacking context, since they are painted
+/// on their own. The layer paints right after the container's background and borders, so a
+/// placeholder for it goes into the container's clip item, the last one in `lists.lists[index]`.
+fn add_scroll_layer<E:ExtraDisplayListData>(lists: &mut DisplayListCollection<E>,
+                                            index: uint,
+                                            mut child_lists: DisplayListCollection<E>,
+                                            box_: &Box,
+                                            size: Size2D<Au>) {
+    let id = ScrollLayerId(box_.node.id() as uint);
+
+    // This layer is the parent of the layers of the scroll containers inside it that have not
+    // found one yet.
+    let mut nested_layers = util::replace(&mut child_lists.scroll_layers, ~[]);
+    for layer in nested_layers.mut_iter() {
+        if layer.parent == PageLayerId {
+            layer.parent = id
+        }
+    }
+    child_lists.flatten_into_level(0, ContentStackingLevel, true);
+
+    let mut clip_rect = None;
+    lists.lists[index].list.mut_rev_iter().position(|item| {
+        match *item {
+            ClipDisplayItemClass(ref mut item) => {
+                let border_box = item.base.bounds;
+                let border = box_.border.get();
+                let rect = Rect(Point2D(border_box.origin.x + border.left,
+                                        border_box.origin.y + border.top),
+                                Size2D(border_box.size.width - border.left - border.right,
+                                       border_box.size.height - border.top - border.bottom));
+                item.child_list.push(ScrollLayerDisplayItemClass(~ScrollLayerDisplayItem {
+                    base: BaseDisplayItem {
+                        bounds: rect,
+                        extra: ExtraDisplayListData::new(box_),
+                    },
+                    layer: id,
+                }));
+                clip_rect = Some(rect);
+                true
+            }
+            _ => false,
+        }
+    });
+    let clip_rect = match clip_rect {
+        Some(clip_rect) => clip_rect,
+        None => fail!("fail to find parent item"),
+    };
+
+    lists.scroll_layers.push(ScrollLayer {
+        id: id,
+        parent: PageLayerId,
+        clip_rect: clip_rect,
+        size: size,
+        scroll_offset: Point2D(Au(0), Au(0)),
+        display_list_collection: child_lists,
+    });
+    lists.scroll_layers.push_all_move(nested_layers);
+}
+
+/// Wraps the items of `list` in a clip item with the given bounds, so that they are clipped by
+/// the block that owns `box_` even though they paint at another level of the stacking context.
+fn clip_display_list<E:ExtraDisplayListData>(list: DisplayList<E>,
+                                             box_: &Box,
+                                             bounds: Rect<Au>,
+                                             need_clip: bool)
+                                             -> DisplayList<E> {
+    let mut clipped_list = DisplayList::new_at_level(list.level);
+    clipped_list.append_item(ClipDisplayItemClass(~ClipDisplayItem {
+        base: BaseDisplayItem {
+            bounds: bounds,
+            extra: ExtraDisplayListData::new(box_),
+        },
+        child_list: list.list,
+        need_clip: need_clip,
+    }));
+    clipped_list
+}
+
 impl MutableOwnedFlowUtils for ~Flow {
     /// Adds a new flow as a child of this flow. Fails if this flow is marked as a leaf.
     fn add_new_child(&mut self, mut new_child: ~Flow) {
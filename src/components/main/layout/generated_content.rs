@@ -0,0 +1,355 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Resolves the `content` property of `::before` and `::after` pseudo-elements.
+//!
+//! Counters (CSS 2.1 § 12.4) and quotes (§ 12.3.2) depend on everything that precedes an element
+//! in document order, which the bottom-up flow construction traversal cannot see. So this runs as
+//! a separate top-down pass beforehand and leaves the resolved text and images in the layout data
+//! of each node for the flow constructor to pick up.
+
+use css::node_style::StyledNode;
+use layout::lists;
+use layout::wrapper::{TLayoutNode, ThreadSafeLayoutNode};
+
+use extra::url::Url;
+use script::dom::node::ElementNodeTypeId;
+use servo_util::namespace;
+use std::hashmap::HashMap;
+use style::computed_values::{content, display};
+use style::{After, Before, ComputedValues, PseudoElement};
+
+/// A piece of resolved generated content.
+#[deriving(Clone)]
+pub enum GeneratedContent {
+    GeneratedText(~str),
+    GeneratedImage(Url),
+}
+
+/// The state of counters and quotes at a point in the document.
+pub struct GeneratedContentResolver {
+    /// The instances of each counter that are in scope, innermost last.
+    priv counters: HashMap<~str, ~[i32]>,
+    /// The nesting level of quotes.
+    priv quote_depth: uint,
+}
+
+impl GeneratedContentResolver {
+    pub fn new() -> GeneratedContentResolver {
+        GeneratedContentResolver {
+            counters: HashMap::new(),
+            quote_depth: 0,
+        }
+    }
+
+    /// Resolves the generated content of the subtree rooted at `node`.
+    pub fn resolve(&mut self, node: ThreadSafeLayoutNode) {
+        let mut scoped_counters = ~[];
+        self.resolve_node(node, &mut scoped_counters);
+        self.leave_scope(scoped_counters)
+    }
+
+    /// Resolves the generated content of `node` and its descendants. `scoped_counters` holds the
+    /// counters instantiated by the preceding siblings of the node, which stay in scope for it
+    /// (CSS 2.1 § 12.4.1); the counters that the node instantiates are added to it.
+    fn resolve_node(&mut self, node: ThreadSafeLayoutNode, scoped_counters: &mut ~[~str]) {
+        match node.type_id() {
+            ElementNodeTypeId(_) => {}
+            _ => return,
+        }
+        // Elements that are not displayed neither use nor change counters.
+        if node.style().get().Box.display == display::none {
+            return
+        }
+
+        self.update_counters(node.style().get(), scoped_counters);
+
+        let mut scoped_in_children = ~[];
+        let before = self.resolve_pseudo_element(node, Before, &mut scoped_in_children);
+        for kid in node.children() {
+            self.resolve_node(kid, &mut scoped_in_children);
+        }
+        let after = self.resolve_pseudo_element(node, After, &mut scoped_in_children);
+        self.leave_scope(scoped_in_children);
+
+        let mut layout_data_ref = node.mutate_layout_data();
+        match *layout_data_ref.get() {
+            Some(ref mut layout_data) => {
+                layout_data.data.before_content = before;
+                layout_data.data.after_content = after;
+            }
+            None => fail!("no layout data"),
+        }
+    }
+
+    /// Applies the counter properties of a pseudo-element, which behaves as a child of `node`, and
+    /// resolves its content.
+    fn resolve_pseudo_element(&mut self,
+                              node: ThreadSafeLayoutNode,
+                              pseudo: PseudoElement,
+                              scoped_counters: &mut ~[~str])
+                              -> ~[GeneratedContent] {
+        let style = match node.pseudo_style(pseudo) {
+            None => return ~[],
+            Some(style) => style.get(),
+        };
+        if style.Box.display == display::none {
+            return ~[]
+        }
+        self.update_counters(style, scoped_counters);
+        self.resolve_content(style, |name| {
+            node.with_element(|element| {
+                element.get_attr(&namespace::Null, name).map(|value| value.to_owned())
+            })
+        })
+    }
+
+    /// Resolves the `content` property of `style`, looking up the attributes that `attr()` refers
+    /// to with `get_attr`.
+    fn resolve_content(&mut self, style: &ComputedValues, get_attr: |&str| -> Option<~str>)
+                       -> ~[GeneratedContent] {
+        let items = match style.Box.content {
+            content::normal | content::none => return ~[],
+            content::Content(ref items) => items,
+        };
+
+        let mut result = ~[];
+        let mut text = ~"";
+        for item in items.iter() {
+            match *item {
+                content::StringContent(ref string) => text.push_str(string.as_slice()),
+                content::UrlContent(ref url) => {
+                    if !text.is_empty() {
+                        result.push(GeneratedText(text));
+                        text = ~"";
+                    }
+                    result.push(GeneratedImage(url.clone()))
+                }
+                content::CounterContent(ref name, list_style_type) => {
+                    let value = match self.counters.find(name) {
+                        Some(instances) if !instances.is_empty() => *instances.last(),
+                        _ => 0,
+                    };
+                    text.push_str(lists::format_counter(list_style_type, value))
+                }
+                content::CountersContent(ref name, ref separator, list_style_type) => {
+                    let values = match self.counters.find(name) {
+                        Some(instances) if !instances.is_empty() => instances.clone(),
+                        _ => ~[0],
+                    };
+                    let formatted: ~[~str] = values.iter().map(|&value| {
+                        lists::format_counter(list_style_type, value)
+                    }).collect();
+                    text.push_str(formatted.connect(separator.as_slice()))
+                }
+                content::AttrContent(ref name) => {
+                    match get_attr(name.as_slice()) {
+                        Some(value) => text.push_str(value),
+                        None => {}
+                    }
+                }
+                content::OpenQuote => {
+                    match quote_for_depth(style, self.quote_depth) {
+                        Some(&(ref open, _)) => text.push_str(open.as_slice()),
+                        None => {}
+                    }
+                    self.quote_depth += 1
+                }
+                content::CloseQuote => {
+                    if self.quote_depth > 0 {
+                        self.quote_depth -= 1;
+                        match quote_for_depth(style, self.quote_depth) {
+                            Some(&(_, ref close)) => text.push_str(close.as_slice()),
+                            None => {}
+                        }
+                    }
+                }
+                content::NoOpenQuote => self.quote_depth += 1,
+                content::NoCloseQuote => {
+                    if self.quote_depth > 0 {
+                        self.quote_depth -= 1
+                    }
+                }
+            }
+        }
+        if !text.is_empty() {
+            result.push(GeneratedText(text))
+        }
+        result
+    }
+
+    /// Applies `counter-reset` and then `counter-increment`. `scoped_counters` holds the counters
+    /// instantiated by preceding siblings. A reset of one of those replaces its instance rather
+    /// than nesting a new one inside it; otherwise the counters instantiated, including those that
+    /// an increment instantiates implicitly, are added to `scoped_counters`.
+    fn update_counters(&mut self, style: &ComputedValues, scoped_counters: &mut ~[~str]) {
+        for &(ref name, value) in style.Box.counter_reset.iter() {
+            let instances = self.counters.find_or_insert(name.clone(), ~[]);
+            if scoped_counters.contains(name) {
+                let last = instances.len() - 1;
+                (*instances)[last] = value
+            } else {
+                instances.push(value);
+                scoped_counters.push(name.clone())
+            }
+        }
+        for &(ref name, value) in style.Box.counter_increment.iter() {
+            let instances = self.counters.find_or_insert(name.clone(), ~[]);
+            if instances.is_empty() {
+                instances.push(0);
+                scoped_counters.push(name.clone())
+            }
+            let last = instances.len() - 1;
+            (*instances)[last] += value
+        }
+    }
+
+    /// Drops the innermost instance of each of the given counters.
+    fn leave_scope(&mut self, counters: ~[~str]) {
+        for name in counters.iter() {
+            match self.counters.find_mut(name) {
+                Some(instances) => {
+                    instances.pop();
+                }
+                None => fail!("leaving the scope of an unknown counter"),
+            }
+        }
+    }
+}
+
+/// Returns the pair of quotes to use at the given nesting depth: the innermost pair listed, if the
+/// nesting is deeper than `quotes` goes.
+fn quote_for_depth<'a>(style: &'a ComputedValues, depth: uint) -> Option<&'a (~str, ~str)> {
+    let quotes = &style.Box.quotes;
+    if quotes.is_empty() {
+        None
+    } else if depth < quotes.len() {
+        Some(&quotes[depth])
+    } else {
+        Some(quotes.last())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GeneratedContent, GeneratedContentResolver, GeneratedImage, GeneratedText};
+    use layout::test_util::style;
+
+    fn no_attr(_: &str) -> Option<~str> {
+        None
+    }
+
+    fn text(content: ~[GeneratedContent]) -> ~str {
+        let mut result = ~"";
+        for item in content.iter() {
+            match *item {
+                GeneratedText(ref text) => result.push_str(text.as_slice()),
+                GeneratedImage(_) => fail!("unexpected image"),
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn test_counter_scope() {
+        let mut resolver = GeneratedContentResolver::new();
+        let content = style("content: counter(c) ' ' counters(c, '.', lower-roman)");
+
+        // <div style="counter-reset: c 1">
+        let mut div_counters = ~[];
+        resolver.update_counters(&style("counter-reset: c 1"), &mut div_counters);
+        assert_eq!(div_counters, ~[~"c"]);
+        let mut scoped_in_div = ~[];
+        //   <p style="counter-increment: c">
+        resolver.update_counters(&style("counter-increment: c"), &mut scoped_in_div);
+        assert_eq!(text(resolver.resolve_content(&content, no_attr)), ~"2 ii");
+        //   <p style="counter-reset: c 5">, which nests a new instance of the counter.
+        resolver.update_counters(&style("counter-reset: c 5"), &mut scoped_in_div);
+        assert_eq!(text(resolver.resolve_content(&content, no_attr)), ~"5 ii.v");
+        //   <p style="counter-increment: c">, still in the scope of its preceding sibling's reset.
+        resolver.update_counters(&style("counter-increment: c"), &mut scoped_in_div);
+        assert_eq!(text(resolver.resolve_content(&content, no_attr)), ~"6 ii.vi");
+        // </div>: the nested instance goes out of scope with the div's children.
+        resolver.leave_scope(scoped_in_div);
+        assert_eq!(text(resolver.resolve_content(&content, no_attr)), ~"2 ii");
+        resolver.leave_scope(div_counters);
+        assert_eq!(text(resolver.resolve_content(&content, no_attr)), ~"0 0");
+    }
+
+    #[test]
+    fn test_sibling_resets() {
+        let mut resolver = GeneratedContentResolver::new();
+        let content = style("content: counters(section, '.') ':' counters(sub, '.')");
+        let h1 = style("counter-reset: sub; counter-increment: section");
+        let h2 = style("counter-increment: sub");
+
+        // <h1><h2><h2><h1><h2>, all siblings.
+        let mut scoped = ~[];
+        resolver.update_counters(&h1, &mut scoped);
+        assert_eq!(text(resolver.resolve_content(&content, no_attr)), ~"1:0");
+        resolver.update_counters(&h2, &mut scoped);
+        resolver.update_counters(&h2, &mut scoped);
+        assert_eq!(text(resolver.resolve_content(&content, no_attr)), ~"1:2");
+        // The second h1 resets the instance of its preceding sibling rather than nesting in it.
+        resolver.update_counters(&h1, &mut scoped);
+        assert_eq!(text(resolver.resolve_content(&content, no_attr)), ~"2:0");
+        resolver.update_counters(&h2, &mut scoped);
+        assert_eq!(text(resolver.resolve_content(&content, no_attr)), ~"2:1");
+        assert_eq!(scoped, ~[~"sub", ~"section"]);
+
+        resolver.leave_scope(scoped);
+        assert_eq!(text(resolver.resolve_content(&content, no_attr)), ~"0:0");
+    }
+
+    #[test]
+    fn test_implicit_counter() {
+        let mut resolver = GeneratedContentResolver::new();
+        // An increment of a counter not in scope instantiates it, as if reset to zero.
+        let mut scoped = ~[];
+        resolver.update_counters(&style("counter-increment: c 2"), &mut scoped);
+        assert_eq!(scoped, ~[~"c"]);
+        resolver.update_counters(&style("counter-increment: c 2"), &mut scoped);
+        assert_eq!(scoped, ~[~"c"]);
+        let content = style("content: counters(c, '-')");
+        assert_eq!(text(resolver.resolve_content(&content, no_attr)), ~"4");
+        resolver.leave_scope(scoped);
+        assert_eq!(text(resolver.resolve_content(&content, no_attr)), ~"0");
+    }
+
+    #[test]
+    fn test_quote_depth() {
+        let mut resolver = GeneratedContentResolver::new();
+        // Nesting deeper than the listed pairs reuses the innermost pair.
+        let open = style("quotes: '<' '>' '(' ')'; \
+                          content: open-quote open-quote no-open-quote open-quote");
+        assert_eq!(text(resolver.resolve_content(&open, no_attr)), ~"<((");
+        assert_eq!(resolver.quote_depth, 4);
+        let close = style("quotes: '<' '>' '(' ')'; \
+                           content: close-quote no-close-quote close-quote close-quote");
+        assert_eq!(text(resolver.resolve_content(&close, no_attr)), ~"))>");
+        assert_eq!(resolver.quote_depth, 0);
+        // A close quote with no open quote to match neither prints anything nor nests deeper.
+        assert_eq!(text(resolver.resolve_content(&close, no_attr)), ~"");
+        assert_eq!(resolver.quote_depth, 0);
+        let none = style("quotes: none; content: open-quote 'a' close-quote");
+        assert_eq!(text(resolver.resolve_content(&none, no_attr)), ~"a");
+    }
+
+    #[test]
+    fn test_attr_and_images() {
+        let mut resolver = GeneratedContentResolver::new();
+        let content = style("content: '[' attr(title) attr(alt) ']' url(a.png) 'b'");
+        let items = resolver.resolve_content(&content, |name| {
+            if name == "title" { Some(~"t") } else { None }
+        });
+        match items.as_slice() {
+            [GeneratedText(ref before), GeneratedImage(ref url), GeneratedText(ref after)] => {
+                assert_eq!(*before, ~"[t]");
+                assert_eq!(url.to_str(), ~"http://example.com/a.png");
+                assert_eq!(*after, ~"b");
+            }
+            _ => fail!("expected text, an image and text"),
+        }
+    }
+}
@@ -123,9 +123,10 @@ pub fn compute_damage(old: &ComputedValues, new: &ComputedValues) -> RestyleDama
     // FIXME: We can short-circuit more of this.
 
     add_if_not_equal!(old, new, damage, [ Repaint ],
-        [ Color.color, Background.background_color,
-          Border.border_top_color, Border.border_right_color,
-          Border.border_bottom_color, Border.border_left_color ]);
+        [ Color.color, Background.background_color, Background.background_image,
+          Background.background_repeat, Background.background_attachment,
+          Background.background_position, Border.border_top_color, Border.border_right_color,
+          Border.border_bottom_color, Border.border_left_color, Box.z_index ]);
 
     add_if_not_equal!(old, new, damage, [ Repaint, BubbleWidths, Reflow ],
         [ Border.border_top_width, Border.border_right_width,
@@ -134,7 +135,12 @@ pub fn compute_damage(old: &ComputedValues, new: &ComputedValues) -> RestyleDama
           Padding.padding_top, Padding.padding_right, Padding.padding_bottom, Padding.padding_left,
           Box.position, Box.width, Box.height, Box.float, Box.display,
           Font.font_family, Font.font_size, Font.font_style, Font.font_weight,
-          Text.text_align, Text.text_decoration, Box.line_height ]);
+          Text.text_align, Text.text_decoration, Text.text_indent, Text.letter_spacing,
+          Text.word_spacing, Text.white_space, Text.word_break, Text.overflow_wrap,
+          Box.line_height, Box.direction, Box.unicode_bidi,
+          Table.table_layout, Table.caption_side, Table.border_spacing,
+          List.list_style_position, List.list_style_type, List.list_style_image,
+          Box.content, Box.counter_increment, Box.counter_reset, Box.quotes ]);
 
     // FIXME: test somehow that we checked every CSS property
 
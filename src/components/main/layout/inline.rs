@@ -9,20 +9,32 @@ use layout::context::LayoutContext;
 use layout::display_list_builder::{DisplayListBuilder, ExtraDisplayListData};
 use layout::flow::{BaseFlow, FlowClass, Flow, InlineFlowClass};
 use layout::flow;
+use layout::model;
 use layout::float_context::{FloatContext, FloatLeft, PlacementInfo};
 use layout::util::ElementMapping;
 use layout::wrapper::ThreadSafeLayoutNode;
 
+use extra::arc::Arc;
 use extra::container::Deque;
 use extra::ringbuf::RingBuf;
 use geom::{Point2D, Rect, Size2D};
-use gfx::display_list::DisplayListCollection;
+use gfx::display_list::{ContentStackingLevel, DisplayList, DisplayListCollection};
+use gfx::text::bidi::BidiLevel;
+use gfx::text::bidi;
+use gfx::text::text_run::TextRun;
 use servo_util::geometry::Au;
 use servo_util::range::Range;
+use std::borrow;
 use std::cell::RefCell;
 use std::u16;
 use std::util;
-use style::computed_values::{text_align, vertical_align, white_space};
+use style::computed_values::{LengthOrPercentage, direction, text_align, vertical_align};
+use style::computed_values::white_space;
+use style::ComputedValues;
+
+/// How many times to break the lines of a flow again when the `::first-line` styles change what
+/// fits on the first line.
+static MAX_FIRST_LINE_PASSES: uint = 3;
 
 /// Lineboxes are represented as offsets into the child list, rather than
 /// as an object that "owns" boxes. Choosing a different set of line
@@ -52,7 +64,9 @@ use style::computed_values::{text_align, vertical_align, white_space};
 struct LineBox {
     range: Range,
     bounds: Rect<Au>,
-    green_zone: Size2D<Au>
+    green_zone: Size2D<Au>,
+    /// Whether a preserved newline ends the line, rather than running out of room.
+    ends_in_forced_break: bool,
 }
 
 struct LineboxScanner {
@@ -62,6 +76,10 @@ struct LineboxScanner {
     pending_line: LineBox,
     lines: ~[LineBox],
     cur_y: Au,
+    /// How far the first line is indented, from `text-indent`.
+    first_line_indent: Au,
+    /// The direction of the block, whose start side the indent goes on.
+    direction: direction::T,
 }
 
 impl LineboxScanner {
@@ -73,10 +91,13 @@ impl LineboxScanner {
             pending_line: LineBox {
                 range: Range::empty(),
                 bounds: Rect(Point2D(Au::new(0), Au::new(0)), Size2D(Au::new(0), Au::new(0))),
-                green_zone: Size2D(Au::new(0), Au::new(0))
+                green_zone: Size2D(Au::new(0), Au::new(0)),
+                ends_in_forced_break: false,
             },
             lines: ~[],
-            cur_y: Au::new(0)
+            cur_y: Au::new(0),
+            first_line_indent: Au::new(0),
+            direction: direction::ltr,
         }
     }
 
@@ -95,7 +116,8 @@ impl LineboxScanner {
     fn reset_linebox(&mut self) {
         self.pending_line.range.reset(0,0);
         self.pending_line.bounds = Rect(Point2D(Au::new(0), self.cur_y), Size2D(Au::new(0), Au::new(0)));
-        self.pending_line.green_zone = Size2D(Au::new(0), Au::new(0))
+        self.pending_line.green_zone = Size2D(Au::new(0), Au::new(0));
+        self.pending_line.ends_in_forced_break = false
     }
 
     pub fn scan_for_lines(&mut self, flow: &mut InlineFlow) {
@@ -117,8 +139,13 @@ impl LineboxScanner {
             };
 
             let box_was_appended = match cur_box.white_space() {
-                white_space::normal => self.try_append_to_line(cur_box, flow),
+                white_space::normal | white_space::nowrap => {
+                    self.try_append_to_line(cur_box, flow)
+                }
                 white_space::pre => self.try_append_to_line_by_new_line(cur_box),
+                white_space::pre_wrap | white_space::pre_line => {
+                    self.try_append_to_line_by_new_line_and_width(cur_box, flow)
+                }
             };
 
             if !box_was_appended {
@@ -154,6 +181,8 @@ impl LineboxScanner {
         debug!("LineboxScanner: Flushing line {:u}: {:?}",
                self.lines.len(), self.pending_line);
 
+        self.reset_trailing_whitespace_levels();
+
         // clear line and add line mapping
         debug!("LineboxScanner: Saving information for flushed line {:u}.", self.lines.len());
         self.lines.push(self.pending_line);
@@ -161,6 +190,41 @@ impl LineboxScanner {
         self.reset_linebox();
     }
 
+    /// Gives the whitespace at the end of the pending line the paragraph embedding level, as rule
+    /// L1 of the Unicode bidirectional algorithm does at the end of each line, so that it stays at
+    /// the end of the line rather than being reordered into it. Whitespace that ends a text box
+    /// with other text of another level is split off into a box of its own.
+    fn reset_trailing_whitespace_levels(&mut self) {
+        let paragraph_level = match self.direction {
+            direction::ltr => 0,
+            direction::rtl => 1,
+        };
+        let mut box_i = self.pending_line.range.end();
+        while box_i > self.pending_line.range.begin() {
+            box_i -= 1;
+            let (range, whitespace_count) = match self.new_boxes[box_i].specific {
+                ScannedTextBox(ref text_box_info) => {
+                    let range = text_box_info.range;
+                    (range, text_box_info.run.get().trailing_whitespace_count_for_range(&range))
+                }
+                _ => break,
+            };
+            if whitespace_count == range.length() {
+                self.new_boxes[box_i].bidi_level = paragraph_level;
+                continue
+            }
+            if whitespace_count > 0 && self.new_boxes[box_i].bidi_level != paragraph_level {
+                let (text_box, mut whitespace_box) =
+                    self.new_boxes[box_i].split_text_at(range.end() - whitespace_count);
+                whitespace_box.bidi_level = paragraph_level;
+                self.new_boxes[box_i] = text_box;
+                self.new_boxes.insert(box_i + 1, whitespace_box);
+                self.pending_line.range.extend_by(1);
+            }
+            break
+        }
+    }
+
     // FIXME(eatkinson): this assumes that the tallest box in the line determines the line height
     // This might not be the case with some weird text fonts.
     fn new_height_for_line(&self, new_box: &Box) -> Au {
@@ -180,7 +244,7 @@ impl LineboxScanner {
         debug!("LineboxScanner: Trying to place first box of line {}", self.lines.len());
 
         let first_box_size = first_box.position.get().size;
-        let splittable = first_box.can_split();
+        let splittable = first_box.can_split() && first_box.can_wrap();
         debug!("LineboxScanner: box size: {}, splittable: {}", first_box_size, splittable);
         let line_is_empty: bool = self.pending_line.range.length() == 0;
 
@@ -310,7 +374,27 @@ impl LineboxScanner {
         false
     }
 
+    /// Moves the start of the pending line in by `text-indent`, if it is the first line. The
+    /// start is on the right in right-to-left blocks.
+    fn indent_if_first_line(&mut self) {
+        if self.lines.len() == 0 {
+            if self.direction == direction::ltr {
+                self.pending_line.bounds.origin.x = self.pending_line.bounds.origin.x +
+                    self.first_line_indent;
+            }
+            self.pending_line.green_zone.width = self.pending_line.green_zone.width -
+                self.first_line_indent;
+        }
+    }
+
     fn try_append_to_line_by_new_line(&mut self, in_box: Box) -> bool {
+        // FIXME: Preformatted lines have no green zone to align them in, so they are only
+        // indented in left-to-right blocks.
+        if self.lines.len() == 0 && self.pending_line.range.length() == 0 &&
+                self.direction == direction::ltr {
+            self.pending_line.bounds.origin.x = self.pending_line.bounds.origin.x +
+                self.first_line_indent;
+        }
         if in_box.new_line_pos.len() == 0 {
             // In case of box does not include new-line character
             self.push_box_to_line(in_box);
@@ -332,10 +416,45 @@ impl LineboxScanner {
                 }
                 _ => {}
             }
+            self.pending_line.ends_in_forced_break = true;
             false
         }
     }
 
+    /// Tries to append the given box to the line, breaking the line after each newline in it and
+    /// wrapping the text between newlines as necessary. Returns false only if we should break the
+    /// line.
+    fn try_append_to_line_by_new_line_and_width(&mut self, in_box: Box, flow: &mut InlineFlow)
+                                                 -> bool {
+        if in_box.new_line_pos.len() == 0 {
+            return self.try_append_to_line(in_box, flow)
+        }
+
+        match in_box.split_by_new_line() {
+            SplitDidFit(Some(left_box), right_box) => {
+                // Lay out the text before the newline on its own, since it may wrap onto several
+                // lines, and then force a break.
+                let saved_work_list = util::replace(&mut self.work_list, RingBuf::new());
+                self.work_list.push_back(left_box);
+                while !self.work_list.is_empty() {
+                    let box_ = self.work_list.pop_front().unwrap();
+                    if !self.try_append_to_line(box_, flow) {
+                        self.flush_current_line();
+                    }
+                }
+                self.work_list = saved_work_list;
+                self.pending_line.ends_in_forced_break = true;
+
+                match right_box {
+                    Some(right_box) => self.work_list.push_front(right_box),
+                    None => {}
+                }
+            }
+            _ => error!("LineboxScanner: This split case makes no sense!"),
+        }
+        false
+    }
+
     /// Tries to append the given box to the line, splitting it if necessary. Returns false only if
     /// we should break the line.
     fn try_append_to_line(&mut self, in_box: Box, flow: &mut InlineFlow) -> bool {
@@ -344,6 +463,7 @@ impl LineboxScanner {
             let (line_bounds, _) = self.initial_line_placement(&in_box, self.cur_y, flow);
             self.pending_line.bounds.origin = line_bounds.origin;
             self.pending_line.green_zone = line_bounds.size;
+            self.indent_if_first_line();
         }
 
         debug!("LineboxScanner: Trying to append box to line {:u} (box size: {}, green zone: \
@@ -376,7 +496,7 @@ impl LineboxScanner {
             return true
         }
 
-        if !in_box.can_split() {
+        if !in_box.can_split() || !in_box.can_wrap() {
             // TODO(eatkinson, issue #224): Signal that horizontal overflow happened?
             if line_is_empty {
                 debug!("LineboxScanner: case=box can't split and line {:u} is empty, so \
@@ -385,6 +505,11 @@ impl LineboxScanner {
                 self.push_box_to_line(in_box);
                 return true
             }
+            if !in_box.can_wrap() {
+                debug!("LineboxScanner: case=box can't wrap, so deferring it to the next line.");
+                self.work_list.push_front(in_box);
+                return false
+            }
         }
 
         let available_width = green_zone.width - self.pending_line.bounds.size.width;
@@ -463,6 +588,25 @@ pub struct InlineFlow {
     // must be well-nested, and are only related to the content of
     // boxes (not lines). Ranges are only kept for non-leaf elements.
     elems: ElementMapping,
+
+    /// The marker of a list item with `list-style-position: outside`. It sits outside the line
+    /// boxes, to the left of the first line (CSS 2.1 § 12.5.1).
+    outside_marker: Option<Box>,
+
+    /// The styles that boxes take on when they are on the first line, as pairs of the style of a
+    /// box and its `::first-line` variant. Empty unless the flow starts the first line of a block
+    /// with a `::first-line` pseudo-element.
+    first_line_styles: ~[(Arc<ComputedValues>, Arc<ComputedValues>)],
+
+    /// The text runs of boxes whose `::first-line` styles change their font, paired with runs of
+    /// the same text shaped in the first-line font.
+    first_line_runs: ~[(Arc<~TextRun>, Arc<~TextRun>)],
+
+    /// The `text-indent` of the block, if this flow starts the first line of the block.
+    text_indent: Option<LengthOrPercentage>,
+
+    /// The direction of the block, which `text-align: start` and `end` and the indent follow.
+    direction: direction::T,
 }
 
 impl InlineFlow {
@@ -472,6 +616,11 @@ impl InlineFlow {
             boxes: boxes,
             lines: ~[],
             elems: ElementMapping::new(),
+            outside_marker: None,
+            first_line_styles: ~[],
+            first_line_runs: ~[],
+            text_indent: None,
+            direction: node.style().get().Box.direction,
         }
     }
 
@@ -479,7 +628,90 @@ impl InlineFlow {
         for box_ in self.boxes.iter() {
             box_.teardown();
         }
+        for marker in self.outside_marker.iter() {
+            marker.teardown();
+        }
         self.boxes = ~[];
+        self.outside_marker = None;
+    }
+
+    /// Gives the boxes on the first line their `::first-line` styles and text runs, and the other
+    /// boxes their own. Returns true if any box changed.
+    fn restyle_first_line(&mut self) -> bool {
+        if self.first_line_styles.is_empty() {
+            return false
+        }
+        let first_line_end = if self.lines.is_empty() { 0 } else { self.lines[0].range.end() };
+        let mut changed = false;
+        for (box_i, box_) in self.boxes.mut_iter().enumerate() {
+            let on_first_line = box_i < first_line_end;
+            for &(ref style, ref first_line_style) in self.first_line_styles.iter() {
+                let (from, to) = if on_first_line {
+                    (style, first_line_style)
+                } else {
+                    (first_line_style, style)
+                };
+                if borrow::ref_eq(box_.style.get(), from.get()) {
+                    box_.style = to.clone();
+                    changed = true;
+                    break
+                }
+            }
+
+            let new_run = match box_.specific {
+                ScannedTextBox(ref text_box_info) => {
+                    self.first_line_runs.iter().find(|&&(ref run, ref first_line_run)| {
+                        let from = if on_first_line { run } else { first_line_run };
+                        borrow::ref_eq(from.get(), text_box_info.run.get())
+                    }).map(|&(ref run, ref first_line_run)| {
+                        if on_first_line { first_line_run.clone() } else { run.clone() }
+                    })
+                }
+                _ => None,
+            };
+            match new_run {
+                Some(run) => {
+                    box_.change_text_run(run);
+                    changed = true
+                }
+                None => {}
+            }
+        }
+        changed
+    }
+
+    /// Takes the first box, which flow construction put there as a list item marker, out of the
+    /// line boxes and hangs it outside of them instead.
+    pub fn detach_outside_marker(&mut self) {
+        if self.boxes.len() > 0 {
+            self.outside_marker = Some(self.boxes.shift());
+        }
+    }
+
+    /// Places the outside marker, if any, to the left of the first line, with its baseline on
+    /// that of the line. `first_baseline` is `None` if there are no lines.
+    fn position_outside_marker(&self, first_baseline: Option<Au>) {
+        let marker = match self.outside_marker {
+            None => return,
+            Some(ref marker) => marker,
+        };
+        let size = marker.position.get().size;
+        let ascent = match marker.specific {
+            ScannedTextBox(ref text_box) => {
+                text_box.run.get().metrics_for_range(&text_box.range).ascent
+            }
+            _ => size.height,
+        };
+        let top = match first_baseline {
+            Some(baseline) => baseline - ascent,
+            None => Au::new(0),
+        };
+        marker.position.borrow_mut().get().origin = Point2D(-size.width, top);
+    }
+
+    /// Returns the vertical offset of the top of each line box, relative to this flow.
+    pub fn line_tops(&self) -> ~[Au] {
+        self.lines.iter().map(|line| line.bounds.origin.y).collect()
     }
 
     pub fn build_display_list_inline<E:ExtraDisplayListData>(
@@ -487,7 +719,7 @@ impl InlineFlow {
                                      builder: &DisplayListBuilder,
                                      container_block_size: &Size2D<Au>,
                                      dirty: &Rect<Au>,
-                                     index: uint,
+                                     mut index: uint,
                                      lists: &RefCell<DisplayListCollection<E>>)
                                      -> uint {
         let abs_rect = Rect(self.base.abs_position, self.base.position.size);
@@ -495,13 +727,20 @@ impl InlineFlow {
             return index;
         }
 
+        // Inline content paints above the block backgrounds and floats of its stacking context
+        // (CSS 2.1 Appendix E).
+        lists.with_mut(|lists| {
+            index = lists.lists.len();
+            lists.add_list(DisplayList::<E>::new_at_level(ContentStackingLevel));
+        });
+
         // TODO(#228): Once we form line boxes and have their cached bounds, we can be smarter and
         // not recurse on a line if nothing in it can intersect the dirty region.
         debug!("Flow[{:d}]: building display list for {:u} inline boxes",
                self.base.id,
                self.boxes.len());
 
-        for box_ in self.boxes.iter() {
+        for box_ in self.boxes.iter().chain(self.outside_marker.iter()) {
             let rel_offset: Point2D<Au> = box_.relative_position(container_block_size);
             box_.build_display_list(builder, dirty, self.base.abs_position + rel_offset, (&*self) as &Flow, index, lists);
         }
@@ -585,29 +824,106 @@ impl InlineFlow {
     }
 
     /// Sets box X positions based on alignment for one line.
-    fn set_horizontal_box_positions(boxes: &[Box], line: &LineBox, linebox_align: text_align::T) {
+    fn set_horizontal_box_positions(boxes: &mut [Box],
+                                    line: &LineBox,
+                                    linebox_align: text_align::T,
+                                    block_direction: direction::T,
+                                    is_last_line: bool) {
         // Figure out how much width we have.
         let slack_width = Au::max(Au(0), line.green_zone.width - line.bounds.size.width);
 
-        // Set the box x positions based on that alignment.
+        // Justification stretches every line but the last, and those that end in a forced break,
+        // which are aligned to the start instead. It leaves the spacing of preformatted text
+        // alone.
+        let is_justified = linebox_align == text_align::justify && !is_last_line &&
+            !line.ends_in_forced_break && slack_width > Au(0) &&
+            boxes[line.range.begin()].white_space() == white_space::normal &&
+            InlineFlow::justify_line(boxes, line, slack_width);
+
+        // Set the box x positions based on that alignment. The start of the line is on the right
+        // in right-to-left blocks.
+        let start_offset = match block_direction {
+            direction::ltr => Au(0),
+            direction::rtl => slack_width,
+        };
         let mut offset_x = line.bounds.origin.x;
         offset_x = offset_x + match linebox_align {
-            // So sorry, but justified text is more complicated than shuffling linebox
-            // coordinates.
-            //
-            // TODO(burg, issue #213): Implement `text-align: justify`.
-            text_align::left | text_align::justify => Au(0),
+            text_align::left => Au(0),
+            text_align::justify if is_justified => Au(0),
+            text_align::start | text_align::justify => start_offset,
+            text_align::end => slack_width - start_offset,
             text_align::center => slack_width.scale_by(0.5),
             text_align::right => slack_width,
         };
 
-        for i in line.range.eachi() {
-            let box_ = &boxes[i];
+        // Boxes go from left to right in visual order, which differs from their logical order
+        // where the line holds right-to-left text.
+        let levels: ~[BidiLevel] = line.range.eachi().map(|i| boxes[i].bidi_level).collect();
+        for &i in bidi::visual_order(levels.as_slice()).iter() {
+            let box_ = &boxes[line.range.begin() + i];
             let size = box_.position.get().size;
             box_.position.set(Rect(Point2D(offset_x, box_.position.get().origin.y), size));
             offset_x = offset_x + size.width;
         }
     }
+
+    /// Shares `slack_width` out evenly among the word separators in the text of the line, by
+    /// adding to the extra word spacing of the boxes that hold them. Separators in the
+    /// whitespace at the end of the line hang past its end, so they get no share. Returns false
+    /// if the line has no separators to widen.
+    fn justify_line(boxes: &mut [Box], line: &LineBox, slack_width: Au) -> bool {
+        let separator_count = line.range.eachi().fold(0u, |count, i| {
+            match boxes[i].specific {
+                ScannedTextBox(ref text_box_info) => {
+                    count + text_box_info.run.get().word_separator_count_for_range(
+                        &text_box_info.range)
+                }
+                _ => count,
+            }
+        });
+        let mut trailing_separator_count = 0;
+        let mut box_i = line.range.end();
+        while box_i > line.range.begin() {
+            box_i -= 1;
+            match boxes[box_i].specific {
+                ScannedTextBox(ref text_box_info) => {
+                    let count = text_box_info.run.get().trailing_word_separator_count_for_range(
+                        &text_box_info.range);
+                    trailing_separator_count += count;
+                    if count < text_box_info.range.length() {
+                        break
+                    }
+                }
+                _ => break,
+            }
+        }
+        let separator_count = separator_count - trailing_separator_count;
+        if separator_count == 0 {
+            return false
+        }
+        let extra_word_spacing = slack_width.scale_by(1.0 / (separator_count as f64));
+
+        for i in line.range.eachi() {
+            let box_ = &mut boxes[i];
+            let extra_width = match box_.specific {
+                ScannedTextBox(ref mut text_box_info) => {
+                    let count = text_box_info.run.get().word_separator_count_for_range(
+                        &text_box_info.range);
+                    if count == 0 {
+                        continue
+                    }
+                    text_box_info.extra_word_spacing = text_box_info.extra_word_spacing +
+                        extra_word_spacing;
+                    extra_word_spacing.scale_by(count as f64)
+                }
+                _ => continue,
+            };
+            let mut position = box_.position.get();
+            position.size.width = position.size.width + extra_width;
+            box_.position.set(position)
+        }
+        true
+    }
 }
 
 impl Flow for InlineFlow {
@@ -644,6 +960,12 @@ impl Flow for InlineFlow {
             pref_width = Au::max(pref_width, this_preferred_width);
         }
 
+        // The outside marker hangs into the margin of the list item, so it adds nothing to the
+        // widths of the flow.
+        for marker in self.outside_marker.iter() {
+            marker.compute_borders(marker.style());
+        }
+
         self.base.min_width = min_width;
         self.base.pref_width = pref_width;
         self.base.num_floats = num_floats;
@@ -660,7 +982,7 @@ impl Flow for InlineFlow {
 
         {
             let this = &mut *self;
-            for box_ in this.boxes.iter() {
+            for box_ in this.boxes.iter().chain(this.outside_marker.iter()) {
                 box_.assign_width(self.base.position.size.width);
             }
         }
@@ -702,24 +1024,54 @@ impl Flow for InlineFlow {
         //
         // TODO(pcwalton): Cache the linebox scanner?
         debug!("assign_height_inline: floats_in: {:?}", self.base.floats_in);
+
+        // Boxes may have been on the first line the last time around, so start from their own
+        // styles.
+        self.lines = ~[];
+        self.restyle_first_line();
+
         // assign height for inline boxes
-        for box_ in self.boxes.iter() {
+        for box_ in self.boxes.iter().chain(self.outside_marker.iter()) {
             box_.assign_height();
         }
         let scanner_floats = self.base.floats_in.clone();
         let mut scanner = LineboxScanner::new(scanner_floats);
+        scanner.first_line_indent = match self.text_indent {
+            None => Au::new(0),
+            Some(text_indent) => model::specified(text_indent, self.base.position.size.width),
+        };
+        scanner.direction = self.direction;
 
         // Access the linebox scanner.
         scanner.scan_for_lines(self);
+
+        // The boxes of the first line take on their `::first-line` styles, whose fonts can change
+        // what fits on the line, so break the lines again until the first line holds just the
+        // boxes with those styles. Giving up after a few passes leaves a box that only fits in
+        // one of its fonts on the line in the other.
+        let mut pass = 0;
+        while pass < MAX_FIRST_LINE_PASSES && self.restyle_first_line() {
+            scanner.scan_for_lines(self);
+            pass += 1
+        }
+
         let mut line_height_offset = Au::new(0);
 
         // All lines use text alignment of the flow.
         let text_align = self.base.flags_info.flags.text_align();
 
+        // The baseline of the first line, on which the outside marker sits.
+        let mut first_baseline = None;
+
         // Now, go through each line and lay out the boxes inside.
-        for line in self.lines.mut_iter() {
+        let line_count = self.lines.len();
+        for (line_index, line) in self.lines.mut_iter().enumerate() {
             // Lay out boxes horizontally.
-            InlineFlow::set_horizontal_box_positions(self.boxes, line, text_align);
+            InlineFlow::set_horizontal_box_positions(self.boxes.mut_slice_from(0),
+                                                     line,
+                                                     text_align,
+                                                     self.direction,
+                                                     line_index + 1 == line_count);
 
             // Set the top y position of the current linebox.
             // `line_height_offset` is updated at the end of the previous loop.
@@ -754,13 +1106,15 @@ impl Flow for InlineFlow {
                         let run = &text_box.run;
 
                         // Compute the height based on the line-height and font size
-                        let text_bounds = run.get().metrics_for_range(range).bounding_box;
+                        let text_metrics = run.get().metrics_for_range(range);
+                        let text_bounds = text_metrics.bounding_box;
                         let em_size = text_bounds.size.height;
                         let line_height = cur_box.calculate_line_height(em_size);
 
-                        // Find the top and bottom of the content area.
+                        // Find the top and bottom of the content area, which covers the fonts
+                        // that the text fell back to too.
                         // Those are used in text-top and text-bottom value of 'vertical-align'
-                        let text_ascent = text_box.run.get().font_metrics.ascent;
+                        let text_ascent = text_metrics.ascent;
 
                         // Offset from the top of the box is 1/2 of the leading + ascent
                         let text_offset = text_ascent + (line_height - em_size).scale_by(0.5);
@@ -843,6 +1197,9 @@ impl Flow for InlineFlow {
 
             // Now, the baseline offset from the top of linebox is set as topmost.
             let baseline_offset = topmost;
+            if first_baseline.is_none() {
+                first_baseline = Some(line.bounds.origin.y + baseline_offset);
+            }
 
             // All boxes' y position is updated following the new baseline offset.
             for box_i in line.range.eachi() {
@@ -876,6 +1233,8 @@ impl Flow for InlineFlow {
             line.bounds.size.height = topmost + bottommost;
         } // End of `lines.each` loop.
 
+        self.position_outside_marker(first_baseline);
+
         self.base.position.size.height =
             if self.lines.len() > 0 {
                 self.lines.last().bounds.origin.y + self.lines.last().bounds.size.height
@@ -907,3 +1266,79 @@ impl Flow for InlineFlow {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::{InlineFlow, LineBox};
+    use layout::box_::{Box, GenericBox};
+    use layout::test_util::style;
+    use layout::util::OpaqueNode;
+
+    use extra::arc::Arc;
+    use geom::{Point2D, Rect, Size2D};
+    use gfx::text::bidi::BidiLevel;
+    use servo_util::geometry::Au;
+    use servo_util::range::Range;
+    use style::computed_values::{direction, text_align};
+
+    /// Returns boxes with the given widths and embedding levels.
+    fn boxes(widths_and_levels: &[(Au, BidiLevel)]) -> ~[Box] {
+        let box_style = Arc::new(style(""));
+        widths_and_levels.iter().map(|&(width, level)| {
+            let mut box_ = Box::from_opaque_node_and_style(OpaqueNode(1), box_style.clone(),
+                                                           GenericBox);
+            box_.position.set(Rect(Point2D(Au(0), Au(0)), Size2D(width, Au(10))));
+            box_.bidi_level = level;
+            box_
+        }).collect()
+    }
+
+    /// Lays `boxes` out on one line 100 units wide, and returns their X positions.
+    fn positions(boxes: &mut ~[Box], align: text_align::T, block_direction: direction::T)
+                 -> ~[Au] {
+        let width = boxes.iter().fold(Au(0), |width, box_| width + box_.position.get().size.width);
+        let line = LineBox {
+            range: Range::new(0, boxes.len()),
+            bounds: Rect(Point2D(Au(0), Au(0)), Size2D(width, Au(10))),
+            green_zone: Size2D(Au(100), Au(10)),
+            ends_in_forced_break: false,
+        };
+        InlineFlow::set_horizontal_box_positions(boxes.mut_slice_from(0),
+                                                 &line,
+                                                 align,
+                                                 block_direction,
+                                                 true);
+        boxes.iter().map(|box_| box_.position.get().origin.x).collect()
+    }
+
+    #[test]
+    fn test_visual_order() {
+        // Right-to-left runs are reversed in place.
+        let mut line = boxes([(Au(10), 0), (Au(20), 1), (Au(30), 1)]);
+        assert_eq!(positions(&mut line, text_align::start, direction::ltr),
+                   ~[Au(0), Au(40), Au(10)]);
+
+        // Left-to-right text nested in right-to-left text keeps its own order.
+        let mut line = boxes([(Au(10), 1), (Au(20), 2), (Au(30), 2)]);
+        assert_eq!(positions(&mut line, text_align::left, direction::rtl),
+                   ~[Au(50), Au(0), Au(20)]);
+    }
+
+    #[test]
+    fn test_alignment_follows_direction() {
+        let mut line = boxes([(Au(10), 1), (Au(20), 1), (Au(30), 1)]);
+        assert_eq!(positions(&mut line, text_align::start, direction::rtl),
+                   ~[Au(90), Au(70), Au(40)]);
+        assert_eq!(positions(&mut line, text_align::end, direction::rtl), ~[Au(50), Au(30), Au(0)]);
+        // The last line of justified text goes to the start.
+        assert_eq!(positions(&mut line, text_align::justify, direction::rtl),
+                   ~[Au(90), Au(70), Au(40)]);
+        assert_eq!(positions(&mut line, text_align::right, direction::rtl),
+                   ~[Au(90), Au(70), Au(40)]);
+
+        let mut line = boxes([(Au(10), 0), (Au(20), 0)]);
+        assert_eq!(positions(&mut line, text_align::start, direction::ltr), ~[Au(0), Au(10)]);
+        assert_eq!(positions(&mut line, text_align::end, direction::ltr), ~[Au(70), Au(80)]);
+        assert_eq!(positions(&mut line, text_align::center, direction::ltr), ~[Au(35), Au(45)]);
+    }
+}
@@ -14,7 +14,9 @@ use layout::display_list_builder::{DisplayListBuilder, ToGfxColor};
 use layout::flow::{Flow, FlowLeafSet, ImmutableFlowUtils, MutableFlowUtils, MutableOwnedFlowUtils};
 use layout::flow::{PreorderFlowTraversal, PostorderFlowTraversal};
 use layout::flow;
+use layout::generated_content::GeneratedContentResolver;
 use layout::incremental::RestyleDamage;
+use layout::pagination;
 use layout::parallel::{AssignHeightsAndStoreOverflowTraversalKind, BubbleWidthsTraversalKind};
 use layout::parallel::{UnsafeFlow};
 use layout::parallel;
@@ -22,14 +24,15 @@ use layout::util::{LayoutDataAccess, OpaqueNode, LayoutDataWrapper};
 use layout::wrapper::{DomLeafSet, LayoutNode, TLayoutNode, ThreadSafeLayoutNode};
 
 use extra::arc::{Arc, MutexArc};
+use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
 use gfx::display_list::{ClipDisplayItemClass, DisplayItem, DisplayItemIterator};
-use gfx::display_list::{DisplayList, DisplayListCollection};
+use gfx::display_list::{BackgroundAndBordersStackingLevel, DisplayList, DisplayListCollection};
 use gfx::font_context::FontContextInfo;
 use gfx::opts::Opts;
 use gfx::render_task::{RenderMsg, RenderChan, RenderLayer};
-use gfx::{render_task, color};
+use gfx::{print, render_task, color};
 use script::dom::event::ReflowEvent;
 use script::dom::node::{ElementNodeTypeId, LayoutDataRef};
 use script::dom::element::{HTMLBodyElementTypeId, HTMLHtmlElementTypeId};
@@ -37,9 +40,11 @@ use script::layout_interface::{AddStylesheetMsg, ContentBoxQuery};
 use script::layout_interface::{ContentBoxesQuery, ContentBoxesResponse, ExitNowMsg, LayoutQuery};
 use script::layout_interface::{HitTestQuery, ContentBoxResponse, HitTestResponse, MouseOverQuery, MouseOverResponse};
 use script::layout_interface::{ContentChangedDocumentDamage, LayoutChan, Msg, PrepareToExitMsg};
+use script::layout_interface::{PrintMsg, ScrollAreaQuery, ScrollAreaResponse, SetScrollOffsetMsg};
 use script::layout_interface::{QueryMsg, ReapLayoutDataMsg, Reflow, ReflowDocumentDamage, UntrustedNodeAddress};
 use script::layout_interface::{ReflowForDisplay, ReflowMsg};
 use script::script_task::{ReflowCompleteMsg, ScriptChan, SendEventMsg};
+use servo_msg::compositor_msg::{LayerId, PageLayerId, ScrollLayerId};
 use servo_msg::constellation_msg::{ConstellationChan, PipelineId};
 use servo_net::image_cache_task::{ImageCacheTask, ImageResponseMsg};
 use servo_net::local_image_cache::{ImageResponder, LocalImageCache};
@@ -48,13 +53,15 @@ use servo_util::time::{ProfilerChan, profile};
 use servo_util::time;
 use servo_util::task::spawn_named;
 use servo_util::workqueue::WorkQueue;
+use servo_util::visited::VisitedUrls;
 use std::cast::transmute;
 use std::cast;
 use std::cell::RefCell;
 use std::comm::Port;
+use std::hashmap::HashMap;
 use std::ptr;
 use std::util;
-use style::{AuthorOrigin, Stylesheet, Stylist};
+use style::{AuthorOrigin, Device, Print, Screen, Stylesheet, Stylist};
 
 /// Information needed by the layout task.
 pub struct LayoutTask {
@@ -94,6 +101,16 @@ pub struct LayoutTask {
     /// A cached display list.
     display_list_collection: Option<Arc<DisplayListCollection<OpaqueNode>>>,
 
+    /// The vertical offset of the top of each page of the cached display list. Only computed when
+    /// printing.
+    page_starts: ~[Au],
+
+    /// The background color of the cached display list.
+    background_color: color::Color,
+
+    /// How far the contents of each scrolling box have been scrolled, by the user or by script.
+    scroll_offsets: HashMap<LayerId, Point2D<Au>>,
+
     stylist: ~Stylist,
 
     /// The workers that we use for parallel operation.
@@ -243,6 +260,7 @@ impl LayoutTask {
                   script_chan: ScriptChan,
                   render_chan: RenderChan<OpaqueNode>,
                   img_cache_task: ImageCacheTask,
+                  visited_urls: VisitedUrls,
                   opts: Opts,
                   profiler_chan: ProfilerChan,
                   shutdown_chan: Chan<()>) {
@@ -255,6 +273,7 @@ impl LayoutTask {
                                                  script_chan,
                                                  render_chan,
                                                  img_cache_task,
+                                                 visited_urls,
                                                  &opts,
                                                  profiler_chan);
                 layout.start();
@@ -271,6 +290,7 @@ impl LayoutTask {
            script_chan: ScriptChan,
            render_chan: RenderChan<OpaqueNode>, 
            image_cache_task: ImageCacheTask,
+           visited_urls: VisitedUrls,
            opts: &Opts,
            profiler_chan: ProfilerChan)
            -> LayoutTask {
@@ -281,6 +301,8 @@ impl LayoutTask {
         } else {
             None
         };
+        let mut stylist = ~new_stylist(Device::new(Screen, screen_size.width, screen_size.height));
+        stylist.set_visited_urls(visited_urls);
 
         LayoutTask {
             id: id,
@@ -296,7 +318,10 @@ impl LayoutTask {
             flow_leaf_set: Arc::new(FlowLeafSet::new()),
 
             display_list_collection: None,
-            stylist: ~new_stylist(),
+            page_starts: ~[],
+            background_color: color::rgba(255.0, 255.0, 255.0, 255.0),
+            scroll_offsets: HashMap::new(),
+            stylist: stylist,
             parallel_traversal: parallel_traversal,
             profiler_chan: profiler_chan,
             opts: opts.clone()
@@ -351,6 +376,13 @@ impl LayoutTask {
                     self.handle_reap_layout_data(dead_layout_data)
                 }
             }
+            PrintMsg(response_chan) => {
+                self.handle_print();
+                response_chan.send(());
+            }
+            SetScrollOffsetMsg(layer_id, offset) => {
+                self.scroll_offsets.insert(layer_id, offset);
+            }
             PrepareToExitMsg(response_chan) => {
                 debug!("layout: PrepareToExitMsg received");
                 self.prepare_to_exit(response_chan);
@@ -417,6 +449,7 @@ impl LayoutTask {
     #[inline(never)]
     fn construct_flow_tree(&self, layout_context: &mut LayoutContext, node: LayoutNode) -> ~Flow {
         let node = ThreadSafeLayoutNode::new(node);
+        GeneratedContentResolver::new().resolve(node);
         node.traverse_postorder_mut(&mut FlowConstructor::init(layout_context));
 
         let mut layout_data_ref = node.mutate_layout_data();
@@ -536,13 +569,24 @@ impl LayoutTask {
             _ => false
         };
 
-        let current_screen_size = Size2D(Au::from_px(data.window_size.width as int),
-                                         Au::from_px(data.window_size.height as int));
+        // When printing, the page box takes the place of the window.
+        let (media_type, viewport_size) = if self.opts.print_output.is_some() {
+            (Print, self.opts.page_size)
+        } else {
+            (Screen, data.window_size)
+        };
+        let current_screen_size = Size2D(Au::from_px(viewport_size.width as int),
+                                         Au::from_px(viewport_size.height as int));
         if self.screen_size != current_screen_size {
             all_style_damage = true
         }
         self.screen_size = current_screen_size;
 
+        // Media queries may apply differently to the new viewport size.
+        let device = Device::new(media_type, current_screen_size.width, current_screen_size.height);
+        let rules_changed = self.stylist.set_device(device);
+        self.stylist.set_document_url(data.url.clone());
+
         // Create a layout context for use throughout the following passes.
         let mut layout_ctx = self.build_layout_context(node);
 
@@ -551,7 +595,7 @@ impl LayoutTask {
                                       || {
             // Perform CSS selector matching if necessary.
             match data.damage.level {
-                ReflowDocumentDamage => {}
+                ReflowDocumentDamage if !rules_changed => {}
                 _ => {
                     profile(time::LayoutSelectorMatchCategory, self.profiler_chan.clone(), || {
                         match self.parallel_traversal {
@@ -609,7 +653,8 @@ impl LayoutTask {
             profile(time::LayoutDispListBuildCategory, self.profiler_chan.clone(), || {
                 let root_size = flow::base(layout_root).position.size;
                 let mut display_list_collection = DisplayListCollection::new();
-                display_list_collection.add_list(DisplayList::<OpaqueNode>::new());
+                display_list_collection.add_list(
+                    DisplayList::<OpaqueNode>::new_at_level(BackgroundAndBordersStackingLevel));
                 let display_list_collection = ~RefCell::new(display_list_collection);
                 let dirty = flow::base(layout_root).position.clone();
                 let display_list_builder = DisplayListBuilder {
@@ -617,7 +662,10 @@ impl LayoutTask {
                 };
                 layout_root.build_display_lists(&display_list_builder, &root_size, &dirty, 0u, display_list_collection);
 
-                let display_list_collection = Arc::new(display_list_collection.unwrap());
+                let mut display_list_collection = display_list_collection.unwrap();
+                display_list_collection.assign_layers();
+                self.update_scroll_offsets(&mut display_list_collection);
+                let display_list_collection = Arc::new(display_list_collection);
 
                 let mut color = color::rgba(255.0, 255.0, 255.0, 255.0);
 
@@ -652,6 +700,13 @@ impl LayoutTask {
                 };
 
                 self.display_list_collection = Some(display_list_collection.clone());
+                self.background_color = color;
+
+                // Display list building has positioned the flows absolutely, so the tree can now
+                // be broken into pages.
+                if self.opts.print_output.is_some() {
+                    self.page_starts = pagination::paginate(layout_root, self.screen_size.height);
+                }
 
                 debug!("Layout done!");
 
@@ -669,6 +724,85 @@ impl LayoutTask {
         data.script_chan.send(ReflowCompleteMsg(self.id, data.id));
     }
 
+    /// Brings the recorded scroll offsets within the scrolling areas of the newly built scroll
+    /// layers, and gives the layers their offsets. Offsets of boxes that no longer scroll are
+    /// forgotten.
+    fn update_scroll_offsets(&mut self, display_list_collection: &mut DisplayListCollection<OpaqueNode>) {
+        let mut scroll_offsets = HashMap::new();
+        for layer in display_list_collection.scroll_layers.mut_iter() {
+            layer.scroll_offset = clamp_scroll_offset(self.scroll_offset(layer.id),
+                                                      layer.size,
+                                                      layer.clip_rect.size);
+            scroll_offsets.insert(layer.id, layer.scroll_offset);
+        }
+        self.scroll_offsets = scroll_offsets;
+    }
+
+    /// Returns how far the given scroll layer has been scrolled.
+    fn scroll_offset(&self, layer_id: LayerId) -> Point2D<Au> {
+        scroll_offset(&self.scroll_offsets, layer_id)
+    }
+
+    /// Renders the cached display list page by page and writes the pages to the print output file.
+    fn handle_print(&self) {
+        let path = match self.opts.print_output {
+            Some(ref path) => path.clone(),
+            None => {
+                debug!("layout: ignoring print request with no output file");
+                return
+            }
+        };
+        let display_list_collection = match self.display_list_collection {
+            Some(ref display_list_collection) => display_list_collection.clone(),
+            None => {
+                debug!("layout: ignoring print request before the first reflow");
+                return
+            }
+        };
+
+        let pages = print::render_pages(display_list_collection.get(),
+                                        self.page_starts,
+                                        self.opts.page_size,
+                                        self.background_color,
+                                        &self.opts,
+                                        self.profiler_chan.clone());
+        print::write_pages(path, pages, self.opts.page_size);
+    }
+
+    /// Returns the union of the bounds of the display items of the given node, in page
+    /// coordinates.
+    fn union_boxes_for_node(&self, node: OpaqueNode) -> Option<Rect<Au>> {
+        fn union_boxes_for_node<'a>(
+                                accumulator: &mut Option<Rect<Au>>,
+                                mut iter: DisplayItemIterator<'a,OpaqueNode>,
+                                node: OpaqueNode,
+                                origin: Point2D<Au>) {
+            for item in iter {
+                union_boxes_for_node(accumulator, item.children(), node, origin);
+                if item.base().extra == node {
+                    let bounds = item.base().bounds.translate(&origin);
+                    match *accumulator {
+                        None => *accumulator = Some(bounds),
+                        Some(ref mut acc) => *acc = acc.union(&bounds),
+                    }
+                }
+            }
+        }
+
+        let display_list_collection = self.display_list_collection.as_ref().unwrap().get();
+        let mut rect = None;
+        for display_list in display_list_collection.iter() {
+            union_boxes_for_node(&mut rect, display_list.iter(), node, Au::zero_point());
+        }
+        for layer in display_list_collection.scroll_layers.iter() {
+            let origin = layer_origin(display_list_collection, &self.scroll_offsets, layer.id);
+            for display_list in layer.display_list_collection.iter() {
+                union_boxes_for_node(&mut rect, display_list.iter(), node, origin);
+            }
+        }
+        rect
+    }
+
     /// Handles a query from the script task. This is the main routine that DOM functions like
     /// `getClientRects()` or `getBoundingClientRect()` ultimately invoke.
     fn handle_query(&self, query: LayoutQuery) {
@@ -678,26 +812,8 @@ impl LayoutTask {
             ContentBoxQuery(node, reply_chan) => {
                 let node = OpaqueNode::from_script_node(&node);
 
-                fn union_boxes_for_node<'a>(
-                                        accumulator: &mut Option<Rect<Au>>,
-                                        mut iter: DisplayItemIterator<'a,OpaqueNode>,
-                                        node: OpaqueNode) {
-                    for item in iter {
-                        union_boxes_for_node(accumulator, item.children(), node);
-                        if item.base().extra == node {
-                            match *accumulator {
-                                None => *accumulator = Some(item.base().bounds),
-                                Some(ref mut acc) => *acc = acc.union(&item.base().bounds),
-                            }
-                        }
-                    }
-                }
-
-                let mut rect = None;
-                for display_list in self.display_list_collection.as_ref().unwrap().get().iter() {
-                    union_boxes_for_node(&mut rect, display_list.iter(), node);
-                }
-                reply_chan.send(ContentBoxResponse(rect.unwrap_or(Au::zero_rect())))
+                reply_chan.send(ContentBoxResponse(self.union_boxes_for_node(node)
+                                                       .unwrap_or(Au::zero_rect())))
             }
             ContentBoxesQuery(node, reply_chan) => {
                 let node = OpaqueNode::from_script_node(&node);
@@ -705,65 +821,45 @@ impl LayoutTask {
                 fn add_boxes_for_node<'a>(
                                       accumulator: &mut ~[Rect<Au>],
                                       mut iter: DisplayItemIterator<'a,OpaqueNode>,
-                                      node: OpaqueNode) {
+                                      node: OpaqueNode,
+                                      origin: Point2D<Au>) {
                     for item in iter {
-                        add_boxes_for_node(accumulator, item.children(), node);
+                        add_boxes_for_node(accumulator, item.children(), node, origin);
                         if item.base().extra == node {
-                            accumulator.push(item.base().bounds)
+                            accumulator.push(item.base().bounds.translate(&origin))
                         }
                     }
                 }
 
+                let display_list_collection = self.display_list_collection.as_ref().unwrap().get();
                 let mut boxes = ~[];
-                for display_list in self.display_list_collection.as_ref().unwrap().get().iter() {
-                    add_boxes_for_node(&mut boxes, display_list.iter(), node);
+                for display_list in display_list_collection.iter() {
+                    add_boxes_for_node(&mut boxes, display_list.iter(), node, Au::zero_point());
+                }
+                for layer in display_list_collection.scroll_layers.iter() {
+                    let origin = layer_origin(display_list_collection,
+                                              &self.scroll_offsets,
+                                              layer.id);
+                    for display_list in layer.display_list_collection.iter() {
+                        add_boxes_for_node(&mut boxes, display_list.iter(), node, origin);
+                    }
                 }
                 reply_chan.send(ContentBoxesResponse(boxes))
             }
             HitTestQuery(_, point, reply_chan) => {
-                fn hit_test(x: Au, y: Au, list: &[DisplayItem<OpaqueNode>])
-                            -> Option<HitTestResponse> {
-                    for item in list.rev_iter() {
-                        match *item {
-                            ClipDisplayItemClass(ref cc) => {
-                                let ret = hit_test(x, y, cc.child_list);
-                                if !ret.is_none() {
-                                    return ret;
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
-
-                    for item in list.rev_iter() {
-                        match *item {
-                            ClipDisplayItemClass(_) => continue,
-                            _ => {}
-                        }
-                        let bounds = item.bounds();
-
-                        // TODO(tikue): This check should really be performed by a method of
-                        // DisplayItem.
-                        if x < bounds.origin.x + bounds.size.width &&
-                                bounds.origin.x <= x &&
-                                y < bounds.origin.y + bounds.size.height &&
-                                bounds.origin.y <= y {
-                            return Some(HitTestResponse(item.base()
-                                                            .extra
-                                                            .to_untrusted_node_address()))
-                        }
-                    }
-                    let ret: Option<HitTestResponse> = None;
-                    ret
-                }
-                for display_list in self.display_list_collection.as_ref().unwrap().get().lists.rev_iter() {
-                    let (x, y) = (Au::from_frac_px(point.x as f64),
-                                  Au::from_frac_px(point.y as f64));
-                    let resp = hit_test(x,y,display_list.list);
-                    if resp.is_some() {
-                        reply_chan.send(Ok(resp.unwrap())); 
+                let display_list_collection = self.display_list_collection.as_ref().unwrap().get();
+                let point = Point2D(Au::from_frac_px(point.x as f64),
+                                    Au::from_frac_px(point.y as f64));
+                match hit_test_layer(display_list_collection,
+                                     &self.scroll_offsets,
+                                     PageLayerId,
+                                     display_list_collection.lists,
+                                     point) {
+                    Some(resp) => {
+                        reply_chan.send(Ok(resp));
                         return
                     }
+                    None => {}
                 }
                 reply_chan.send(Err(()));
 
@@ -795,19 +891,72 @@ impl LayoutTask {
                     }
                 }
 
-                let mut mouse_over_list:~[UntrustedNodeAddress] = ~[];
-                for display_list in self.display_list_collection.as_ref().unwrap().get().lists.rev_iter() {
-                    let (x, y) = (Au::from_frac_px(point.x as f64),
-                                  Au::from_frac_px(point.y as f64));
-                    mouse_over_test(x,y,display_list.list, &mut mouse_over_list);
+                fn mouse_over_layer(layout_task: &LayoutTask,
+                                    display_list_collection: &DisplayListCollection<OpaqueNode>,
+                                    layer_id: LayerId,
+                                    lists: &[DisplayList<OpaqueNode>],
+                                    point: Point2D<Au>,
+                                    result: &mut ~[UntrustedNodeAddress]) {
+                    for layer in display_list_collection.scroll_layers.rev_iter() {
+                        if layer.parent != layer_id || !rect_contains(&layer.clip_rect, point) {
+                            continue
+                        }
+                        let layer_point = point - layer.clip_rect.origin +
+                            layout_task.scroll_offset(layer.id);
+                        mouse_over_layer(layout_task,
+                                         display_list_collection,
+                                         layer.id,
+                                         layer.display_list_collection.lists,
+                                         layer_point,
+                                         result);
+                    }
+                    for display_list in lists.rev_iter() {
+                        mouse_over_test(point.x, point.y, display_list.list, result);
+                    }
                 }
 
+                let display_list_collection = self.display_list_collection.as_ref().unwrap().get();
+                let point = Point2D(Au::from_frac_px(point.x as f64),
+                                    Au::from_frac_px(point.y as f64));
+                let mut mouse_over_list:~[UntrustedNodeAddress] = ~[];
+                mouse_over_layer(self,
+                                 display_list_collection,
+                                 PageLayerId,
+                                 display_list_collection.lists,
+                                 point,
+                                 &mut mouse_over_list);
+
                 if mouse_over_list.is_empty() {
                     reply_chan.send(Err(()));
                 } else {
                     reply_chan.send(Ok(MouseOverResponse(mouse_over_list)));
                 }
             }
+            ScrollAreaQuery(node, reply_chan) => {
+                let node = OpaqueNode::from_script_node(&node);
+                let layer_id = ScrollLayerId(node.id() as uint);
+                let display_list_collection = self.display_list_collection.as_ref().unwrap().get();
+                let response = match display_list_collection.scroll_layer(layer_id) {
+                    Some(layer) => {
+                        ScrollAreaResponse {
+                            layer: Some(layer_id),
+                            scroll_offset: self.scroll_offset(layer_id),
+                            scroll_size: layer.size,
+                            client_size: layer.clip_rect.size,
+                        }
+                    }
+                    None => {
+                        let size = self.union_boxes_for_node(node).unwrap_or(Au::zero_rect()).size;
+                        ScrollAreaResponse {
+                            layer: None,
+                            scroll_offset: Au::zero_point(),
+                            scroll_size: size,
+                            client_size: size,
+                        }
+                    }
+                };
+                reply_chan.send(response)
+            }
         }
     }
 
@@ -836,3 +985,281 @@ impl LayoutTask {
     }
 }
 
+/// Returns how far the given scroll layer has been scrolled, according to `scroll_offsets`.
+fn scroll_offset(scroll_offsets: &HashMap<LayerId, Point2D<Au>>, layer_id: LayerId)
+                 -> Point2D<Au> {
+    match scroll_offsets.find(&layer_id) {
+        Some(&offset) => offset,
+        None => Au::zero_point(),
+    }
+}
+
+/// Brings a scroll offset within the scrolling area of a layer of the given size that is clipped
+/// to `clip_size`.
+fn clamp_scroll_offset(offset: Point2D<Au>, size: Size2D<Au>, clip_size: Size2D<Au>)
+                       -> Point2D<Au> {
+    let max_x = Au::max(size.width - clip_size.width, Au(0));
+    let max_y = Au::max(size.height - clip_size.height, Au(0));
+    Point2D(Au::max(Au::min(offset.x, max_x), Au(0)), Au::max(Au::min(offset.y, max_y), Au(0)))
+}
+
+/// Returns the position of the top left corner of the contents of the given layer in page
+/// coordinates, taking the scroll offsets of it and its ancestors into account.
+fn layer_origin<E>(display_list_collection: &DisplayListCollection<E>,
+                   scroll_offsets: &HashMap<LayerId, Point2D<Au>>,
+                   layer_id: LayerId)
+                   -> Point2D<Au> {
+    match display_list_collection.scroll_layer(layer_id) {
+        None => Au::zero_point(),
+        Some(layer) => {
+            layer_origin(display_list_collection, scroll_offsets, layer.parent) +
+                layer.clip_rect.origin - scroll_offset(scroll_offsets, layer_id)
+        }
+    }
+}
+
+/// Returns the node of the topmost item of `list` at the given point. Items are tested in the
+/// reverse of the order they paint in, so that the topmost one wins. The collection's lists are
+/// already in CSS 2.1 Appendix E order.
+fn hit_test(x: Au, y: Au, list: &[DisplayItem<OpaqueNode>]) -> Option<HitTestResponse> {
+    for item in list.rev_iter() {
+        let bounds = item.bounds();
+
+        // TODO(tikue): This check should really be performed by a method of
+        // DisplayItem.
+        let contains_point = x < bounds.origin.x + bounds.size.width &&
+            bounds.origin.x <= x &&
+            y < bounds.origin.y + bounds.size.height &&
+            bounds.origin.y <= y;
+
+        match *item {
+            ClipDisplayItemClass(ref cc) => {
+                if cc.need_clip && !contains_point {
+                    continue
+                }
+                let ret = hit_test(x, y, cc.child_list);
+                if !ret.is_none() {
+                    return ret;
+                }
+            }
+            _ if contains_point => {
+                return Some(HitTestResponse(item.base().extra.to_untrusted_node_address()))
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Returns the node of the topmost item at the given point of the layer `layer_id`, whose lists are
+/// `lists`. The layers above it are tested from the top down before its own lists. Whatever paints
+/// above a scrolling box and overlaps it is in an overlay above the box's layer, so this follows
+/// painting order.
+fn hit_test_layer(display_list_collection: &DisplayListCollection<OpaqueNode>,
+                  scroll_offsets: &HashMap<LayerId, Point2D<Au>>,
+                  layer_id: LayerId,
+                  lists: &[DisplayList<OpaqueNode>],
+                  point: Point2D<Au>)
+                  -> Option<HitTestResponse> {
+    for layer in display_list_collection.scroll_layers.rev_iter() {
+        if layer.parent != layer_id || !rect_contains(&layer.clip_rect, point) {
+            continue
+        }
+        let layer_point = point - layer.clip_rect.origin + scroll_offset(scroll_offsets, layer.id);
+        let resp = hit_test_layer(display_list_collection,
+                                  scroll_offsets,
+                                  layer.id,
+                                  layer.display_list_collection.lists,
+                                  layer_point);
+        if resp.is_some() {
+            return resp
+        }
+    }
+    for display_list in lists.rev_iter() {
+        let resp = hit_test(point.x, point.y, display_list.list);
+        if resp.is_some() {
+            return resp
+        }
+    }
+    None
+}
+
+/// Returns true if the given rectangle contains the given point.
+fn rect_contains(rect: &Rect<Au>, point: Point2D<Au>) -> bool {
+    rect.origin.x <= point.x && point.x < rect.origin.x + rect.size.width &&
+        rect.origin.y <= point.y && point.y < rect.origin.y + rect.size.height
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clamp_scroll_offset, hit_test_layer, layer_origin};
+    use layout::util::OpaqueNode;
+
+    use geom::point::Point2D;
+    use geom::rect::Rect;
+    use geom::size::Size2D;
+    use gfx::color;
+    use gfx::display_list::{BaseDisplayItem, ContentStackingLevel, DisplayItem, DisplayList};
+    use gfx::display_list::{DisplayListCollection, ScrollLayer, ScrollLayerDisplayItem};
+    use gfx::display_list::{ScrollLayerDisplayItemClass, SolidColorDisplayItem};
+    use gfx::display_list::SolidColorDisplayItemClass;
+    use script::layout_interface::{HitTestResponse, UntrustedNodeAddress};
+    use servo_msg::compositor_msg::{LayerId, OverlayLayerId, PageLayerId, ScrollLayerId};
+    use servo_util::geometry::Au;
+    use std::hashmap::HashMap;
+
+    fn point(x: int, y: int) -> Point2D<Au> {
+        Point2D(Au::from_px(x), Au::from_px(y))
+    }
+
+    fn rect(x: int, y: int, width: int, height: int) -> Rect<Au> {
+        Rect(point(x, y), Size2D(Au::from_px(width), Au::from_px(height)))
+    }
+
+    fn solid_color(node: OpaqueNode, bounds: Rect<Au>) -> DisplayItem<OpaqueNode> {
+        SolidColorDisplayItemClass(~SolidColorDisplayItem {
+            base: BaseDisplayItem {
+                bounds: bounds,
+                extra: node,
+            },
+            color: color::rgb(0, 0, 0),
+        })
+    }
+
+    fn scroll_layer(id: LayerId,
+                    parent: LayerId,
+                    clip_rect: Rect<Au>,
+                    size: Size2D<Au>,
+                    items: ~[DisplayItem<OpaqueNode>])
+                    -> ScrollLayer<OpaqueNode> {
+        let mut display_list_collection = DisplayListCollection::new();
+        display_list_collection.add_list(DisplayList {
+            list: items,
+            level: ContentStackingLevel,
+        });
+        ScrollLayer {
+            id: id,
+            parent: parent,
+            clip_rect: clip_rect,
+            size: size,
+            scroll_offset: Point2D(Au(0), Au(0)),
+            display_list_collection: display_list_collection,
+        }
+    }
+
+    fn hit(display_list_collection: &DisplayListCollection<OpaqueNode>,
+           scroll_offsets: &HashMap<LayerId, Point2D<Au>>,
+           point: Point2D<Au>)
+           -> Option<UntrustedNodeAddress> {
+        match hit_test_layer(display_list_collection,
+                             scroll_offsets,
+                             PageLayerId,
+                             display_list_collection.lists,
+                             point) {
+            Some(HitTestResponse(address)) => Some(address),
+            None => None,
+        }
+    }
+
+    /// A page with a background, and a box at (10, 10) that scrolls 50px by 50px of contents 200px
+    /// tall. The only thing in the box is 100px down.
+    fn scrolling_page() -> DisplayListCollection<OpaqueNode> {
+        let mut display_list_collection = DisplayListCollection::new();
+        display_list_collection.add_list(DisplayList {
+            list: ~[
+                solid_color(OpaqueNode(1), rect(0, 0, 200, 200)),
+                ScrollLayerDisplayItemClass(~ScrollLayerDisplayItem {
+                    base: BaseDisplayItem {
+                        bounds: rect(10, 10, 50, 50),
+                        extra: OpaqueNode(2),
+                    },
+                    layer: ScrollLayerId(2),
+                }),
+            ],
+            level: ContentStackingLevel,
+        });
+        display_list_collection.scroll_layers.push(scroll_layer(ScrollLayerId(2),
+                                                                PageLayerId,
+                                                                rect(10, 10, 50, 50),
+                                                                Size2D(Au::from_px(50),
+                                                                       Au::from_px(200)),
+                                                                ~[
+            solid_color(OpaqueNode(3), rect(0, 100, 50, 20)),
+        ]));
+        display_list_collection
+    }
+
+    #[test]
+    fn test_clamp_scroll_offset() {
+        let size = Size2D(Au::from_px(200), Au::from_px(300));
+        let clip_size = Size2D(Au::from_px(100), Au::from_px(100));
+        assert_eq!(clamp_scroll_offset(point(50, -10), size, clip_size), point(50, 0));
+        assert_eq!(clamp_scroll_offset(point(500, 500), size, clip_size), point(100, 200));
+
+        // Contents that fit can't scroll at all.
+        assert_eq!(clamp_scroll_offset(point(10, 10), clip_size, size), point(0, 0));
+    }
+
+    #[test]
+    fn test_layer_origin() {
+        let mut display_list_collection = DisplayListCollection::new();
+        display_list_collection.scroll_layers = ~[
+            scroll_layer(ScrollLayerId(1), PageLayerId, rect(10, 20, 100, 100),
+                         Size2D(Au::from_px(100), Au::from_px(500)), ~[]),
+            scroll_layer(ScrollLayerId(2), ScrollLayerId(1), rect(5, 5, 50, 50),
+                         Size2D(Au::from_px(80), Au::from_px(50)), ~[]),
+        ];
+        let mut scroll_offsets = HashMap::new();
+        scroll_offsets.insert(ScrollLayerId(1), point(0, 30));
+        scroll_offsets.insert(ScrollLayerId(2), point(2, 0));
+
+        assert_eq!(layer_origin(&display_list_collection, &scroll_offsets, PageLayerId),
+                   point(0, 0));
+        assert_eq!(layer_origin(&display_list_collection, &scroll_offsets, ScrollLayerId(1)),
+                   point(10, -10));
+        assert_eq!(layer_origin(&display_list_collection, &scroll_offsets, ScrollLayerId(2)),
+                   point(13, -5));
+    }
+
+    #[test]
+    fn test_hit_test_scrolled_layer() {
+        let display_list_collection = scrolling_page();
+        let mut scroll_offsets = HashMap::new();
+
+        // Unscrolled, the box shows nothing of its contents, so the box itself is hit.
+        assert_eq!(hit(&display_list_collection, &scroll_offsets, point(20, 25)),
+                   Some(OpaqueNode(2).to_untrusted_node_address()));
+
+        // Scrolled down, the contents are under the point.
+        scroll_offsets.insert(ScrollLayerId(2), point(0, 90));
+        assert_eq!(hit(&display_list_collection, &scroll_offsets, point(20, 25)),
+                   Some(OpaqueNode(3).to_untrusted_node_address()));
+
+        // The contents are clipped to the box.
+        assert_eq!(hit(&display_list_collection, &scroll_offsets, point(20, 65)),
+                   Some(OpaqueNode(1).to_untrusted_node_address()));
+        assert_eq!(hit(&display_list_collection, &scroll_offsets, point(300, 300)), None);
+    }
+
+    #[test]
+    fn test_hit_test_overlay() {
+        // Something paints above the box and overlaps it, so it is in an overlay above the box's
+        // layer, and wins.
+        let mut display_list_collection = scrolling_page();
+        display_list_collection.scroll_layers.push(scroll_layer(OverlayLayerId(2),
+                                                                PageLayerId,
+                                                                rect(0, 0, 30, 30),
+                                                                Size2D(Au::from_px(30),
+                                                                       Au::from_px(30)),
+                                                                ~[
+            solid_color(OpaqueNode(4), rect(0, 0, 30, 30)),
+        ]));
+        let mut scroll_offsets = HashMap::new();
+        scroll_offsets.insert(ScrollLayerId(2), point(0, 90));
+
+        assert_eq!(hit(&display_list_collection, &scroll_offsets, point(20, 25)),
+                   Some(OpaqueNode(4).to_untrusted_node_address()));
+        assert_eq!(hit(&display_list_collection, &scroll_offsets, point(40, 25)),
+                   Some(OpaqueNode(3).to_untrusted_node_address()));
+    }
+}
@@ -0,0 +1,104 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Formatting of list item markers and counter values (CSS 2.1 § 12.5 and § 12.6.2).
+
+use std::ascii::StrAsciiExt;
+use style::computed_values::list_style_type;
+
+static LOWER_ROMAN: [(i32, &'static str), ..13] = [
+    (1000, "m"), (900, "cm"), (500, "d"), (400, "cd"), (100, "c"), (90, "xc"),
+    (50, "l"), (40, "xl"), (10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i"),
+];
+
+/// Formats `value` in the given list style. Styles that cannot represent the value fall back to
+/// decimal, as CSS 2.1 § 12.6.2 requires. Glyph styles (`disc`, `circle`, `square`) produce
+/// their glyph regardless of the value, and `none` produces the empty string.
+pub fn format_counter(style: list_style_type::T, value: i32) -> ~str {
+    match style {
+        list_style_type::none => ~"",
+        list_style_type::disc => ~"•",
+        list_style_type::circle => ~"◦",
+        list_style_type::square => ~"▪",
+        list_style_type::decimal => value.to_str(),
+        list_style_type::lower_alpha => format_alphabetic(value, 'a' as u8),
+        list_style_type::upper_alpha => format_alphabetic(value, 'A' as u8),
+        list_style_type::lower_roman => format_roman(value),
+        list_style_type::upper_roman => format_roman(value).to_ascii_upper(),
+    }
+}
+
+/// Returns the text of the marker box for a list item with the given ordinal, or `None` if the
+/// list item has no marker.
+pub fn marker_text(style: list_style_type::T, ordinal: i32) -> Option<~str> {
+    match style {
+        list_style_type::none => None,
+        list_style_type::disc | list_style_type::circle | list_style_type::square => {
+            Some(format_counter(style, ordinal) + " ")
+        }
+        _ => Some(format_counter(style, ordinal) + ". "),
+    }
+}
+
+/// a, b, ..., z, aa, ab, ...
+fn format_alphabetic(value: i32, first: u8) -> ~str {
+    if value < 1 {
+        return value.to_str()
+    }
+    let mut value = value;
+    let mut digits = ~[];
+    while value > 0 {
+        value -= 1;
+        digits.push(first + (value % 26) as u8);
+        value /= 26;
+    }
+    digits.reverse();
+    digits.iter().map(|&digit| digit as char).collect()
+}
+
+fn format_roman(value: i32) -> ~str {
+    if value < 1 || value > 3999 {
+        return value.to_str()
+    }
+    let mut value = value;
+    let mut result = ~"";
+    for &(magnitude, numeral) in LOWER_ROMAN.iter() {
+        while value >= magnitude {
+            result.push_str(numeral);
+            value -= magnitude;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use style::computed_values::list_style_type;
+
+    #[test]
+    fn test_alphabetic() {
+        assert_eq!(format_counter(list_style_type::lower_alpha, 1), ~"a");
+        assert_eq!(format_counter(list_style_type::lower_alpha, 26), ~"z");
+        assert_eq!(format_counter(list_style_type::upper_alpha, 27), ~"AA");
+        assert_eq!(format_counter(list_style_type::upper_alpha, 703), ~"AAA");
+        assert_eq!(format_counter(list_style_type::lower_alpha, 0), ~"0");
+    }
+
+    #[test]
+    fn test_roman() {
+        assert_eq!(format_counter(list_style_type::lower_roman, 4), ~"iv");
+        assert_eq!(format_counter(list_style_type::lower_roman, 1994), ~"mcmxciv");
+        assert_eq!(format_counter(list_style_type::upper_roman, 3999), ~"MMMCMXCIX");
+        assert_eq!(format_counter(list_style_type::upper_roman, 4000), ~"4000");
+        assert_eq!(format_counter(list_style_type::lower_roman, -1), ~"-1");
+    }
+
+    #[test]
+    fn test_marker_text() {
+        assert_eq!(marker_text(list_style_type::decimal, 3), Some(~"3. "));
+        assert_eq!(marker_text(list_style_type::disc, 3), Some(~"• "));
+        assert_eq!(marker_text(list_style_type::none, 3), None);
+    }
+}
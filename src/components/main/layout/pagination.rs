@@ -0,0 +1,80 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Fragmentation of the laid-out flow tree into pages for printing.
+//!
+//! Pages may only break between block-level flows or between line boxes, so that neither a line of
+//! text nor the top of a block is sliced in two. Content taller than a page with no break point in
+//! it is split at the page boundary.
+
+use layout::flow::{Flow, InlineFlowClass};
+use layout::flow;
+
+use servo_util::geometry::Au;
+
+/// Returns the vertical offset of the top of each page, the first of which is always zero. Must be
+/// called after display list building, which computes the absolute positions of the flows.
+pub fn paginate(root: &Flow, page_height: Au) -> ~[Au] {
+    let mut break_points = ~[];
+    collect_break_points(root, &mut break_points);
+    compute_page_starts(break_points, flow::base(root).position.size.height, page_height)
+}
+
+/// Gathers the absolute offsets at which a page may break: the tops of block children and of line
+/// boxes.
+fn collect_break_points(flow: &Flow, break_points: &mut ~[Au]) {
+    let flow_top = flow::base(flow).abs_position.y;
+    match flow.class() {
+        InlineFlowClass => {
+            for line_top in flow.as_immutable_inline().line_tops().iter() {
+                break_points.push(flow_top + *line_top);
+            }
+        }
+        _ => {}
+    }
+    for child in flow::imm_child_iter(flow) {
+        break_points.push(flow::base(child).abs_position.y);
+        collect_break_points(child, break_points);
+    }
+}
+
+/// Chooses page starts from `break_points`, putting each break at the last break point that still
+/// fits on the current page.
+pub fn compute_page_starts(break_points: &[Au], content_height: Au, page_height: Au) -> ~[Au] {
+    let mut page_starts = ~[Au(0)];
+    let mut page_start = Au(0);
+    while page_start + page_height < content_height {
+        let page_end = page_start + page_height;
+        let mut next_start = page_end;
+        let mut found_break_point = false;
+        for &break_point in break_points.iter() {
+            if break_point > page_start && break_point <= page_end &&
+                    (!found_break_point || break_point > next_start) {
+                next_start = break_point;
+                found_break_point = true;
+            }
+        }
+        page_starts.push(next_start);
+        page_start = next_start;
+    }
+    page_starts
+}
+
+#[test]
+fn test_compute_page_starts() {
+    let px = |n: int| Au::from_px(n);
+
+    // Everything fits on one page.
+    assert_eq!(compute_page_starts(&[px(0), px(50)], px(90), px(100)), ~[px(0)]);
+
+    // Breaks happen at the last break point that fits on each page.
+    assert_eq!(compute_page_starts(&[px(0), px(120), px(40), px(80), px(200), px(160)],
+                                   px(220),
+                                   px(100)),
+               ~[px(0), px(80), px(160)]);
+
+    // Content with no break points is sliced at page boundaries.
+    assert_eq!(compute_page_starts(&[px(0)], px(250), px(100)),
+               ~[px(0), px(100), px(200)]);
+}
@@ -0,0 +1,499 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! CSS tables and the table layout algorithms (CSS 2.1 § 17).
+//!
+//! Only the separated borders model is supported. The table wrapper box of CSS 2.1 § 17.4 isn't a
+//! flow of its own: the table flow carries the margins and places its captions around its border
+//! box itself.
+
+use layout::block::BlockFlow;
+use layout::box_::Box;
+use layout::construct::FlowConstructor;
+use layout::context::LayoutContext;
+use layout::float_context::FloatContext;
+use layout::flow::{TableCaptionFlowClass, TableFlowClass, TableRowFlowClass};
+use layout::flow::{TableRowGroupFlowClass, FlowClass, Flow};
+use layout::flow;
+use layout::model::{MaybeAuto, Specified, Auto};
+use layout::wrapper::ThreadSafeLayoutNode;
+
+use geom::Point2D;
+use servo_util::geometry::Au;
+use servo_util::geometry;
+use style::computed_values::{LengthOrPercentageOrAuto, border_spacing, caption_side};
+use style::computed_values::table_layout;
+
+/// A table flow (CSS 2.1 § 17.2). Its children are captions, row groups and rows; anonymous table
+/// objects have already been generated for anything else.
+pub struct TableFlow {
+    /// The block flow that holds the table's box.
+    block: BlockFlow,
+
+    /// The `width` of each column given by a `table-column` or `table-column-group` box, such as a
+    /// `<col>` element.
+    column_specified_widths: ~[LengthOrPercentageOrAuto],
+
+    /// The largest minimum width of the cells in each column.
+    column_min_widths: ~[Au],
+
+    /// The largest preferred width of the cells in each column.
+    column_pref_widths: ~[Au],
+
+    /// The `width` of each of the cells in the first row of the table.
+    first_row_specified_widths: ~[LengthOrPercentageOrAuto],
+
+    /// The used width of each column.
+    column_widths: ~[Au],
+}
+
+impl TableFlow {
+    pub fn from_node_and_box(constructor: &mut FlowConstructor,
+                             node: ThreadSafeLayoutNode,
+                             box_: Box)
+                             -> TableFlow {
+        TableFlow {
+            block: BlockFlow::from_node_and_box(constructor, node, box_),
+            column_specified_widths: ~[],
+            column_min_widths: ~[],
+            column_pref_widths: ~[],
+            first_row_specified_widths: ~[],
+            column_widths: ~[],
+        }
+    }
+
+    fn border_spacing(&self) -> border_spacing::T {
+        match self.block.box_ {
+            Some(ref box_) => box_.style().Table.border_spacing,
+            None => border_spacing::T {
+                horizontal: Au(0),
+                vertical: Au(0),
+            },
+        }
+    }
+
+    /// The total width of the horizontal border spacing, which goes between the columns and at
+    /// both edges of the table.
+    fn horizontal_spacing_width(&self) -> Au {
+        let column_count = self.column_min_widths.len() as i32;
+        Au(*self.border_spacing().horizontal * (column_count + 1))
+    }
+
+    /// The specified width of column `index` for the fixed table layout algorithm: that of the
+    /// column box if it isn't `auto`, or otherwise that of the cell in the first row.
+    fn fixed_column_width(&self, index: uint, table_width: Au) -> MaybeAuto {
+        let from_column = if index < self.column_specified_widths.len() {
+            MaybeAuto::from_style(self.column_specified_widths[index], table_width)
+        } else {
+            Auto
+        };
+        match from_column {
+            Specified(width) => Specified(width),
+            Auto if index < self.first_row_specified_widths.len() => {
+                MaybeAuto::from_style(self.first_row_specified_widths[index], table_width)
+            }
+            Auto => Auto,
+        }
+    }
+}
+
+impl Flow for TableFlow {
+    fn class(&self) -> FlowClass {
+        TableFlowClass
+    }
+
+    fn as_block<'a>(&'a mut self) -> &'a mut BlockFlow {
+        &mut self.block
+    }
+
+    fn as_table<'a>(&'a mut self) -> &'a mut TableFlow {
+        self
+    }
+
+    /// Gathers the minimum and preferred widths of the columns from the rows, and from them those
+    /// of the table itself.
+    fn bubble_widths(&mut self, _: &mut LayoutContext) {
+        let mut min_widths = ~[];
+        let mut pref_widths = ~[];
+        let mut first_row_specified_widths = None;
+        let mut caption_min_width = Au(0);
+        let mut caption_pref_width = Au(0);
+        for kid in self.block.base.child_iter() {
+            match kid.class() {
+                TableCaptionFlowClass => {
+                    let kid_base = flow::mut_base(kid);
+                    caption_min_width = geometry::max(caption_min_width, kid_base.min_width);
+                    caption_pref_width = geometry::max(caption_pref_width, kid_base.pref_width);
+                }
+                TableRowGroupFlowClass => {
+                    let row_group = kid.as_table_row_group();
+                    widen_columns(&mut min_widths, row_group.column_min_widths);
+                    widen_columns(&mut pref_widths, row_group.column_pref_widths);
+                    if first_row_specified_widths.is_none() &&
+                            row_group.block.base.children.len() > 0 {
+                        first_row_specified_widths =
+                            Some(row_group.column_specified_widths.clone());
+                    }
+                }
+                TableRowFlowClass => {
+                    let row = kid.as_table_row();
+                    widen_columns(&mut min_widths, row.column_min_widths);
+                    widen_columns(&mut pref_widths, row.column_pref_widths);
+                    if first_row_specified_widths.is_none() {
+                        first_row_specified_widths = Some(row.column_specified_widths.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Columns given by column boxes count even if no cell is in them.
+        while min_widths.len() < self.column_specified_widths.len() {
+            min_widths.push(Au(0));
+            pref_widths.push(Au(0));
+        }
+        self.column_min_widths = min_widths;
+        self.column_pref_widths = pref_widths;
+        self.first_row_specified_widths = first_row_specified_widths.unwrap_or(~[]);
+
+        let spacing_width = self.horizontal_spacing_width();
+        let mut min_width = sum(self.column_min_widths) + spacing_width;
+        let mut pref_width = sum(self.column_pref_widths) + spacing_width;
+        for box_ in self.block.box_.iter() {
+            let style = box_.style();
+            box_.compute_borders(style);
+            box_.compute_padding(style, Au(0));
+            let specified_width = MaybeAuto::from_style(style.Box.width,
+                                                        Au(0)).specified_or_zero();
+            let margin_left = MaybeAuto::from_style(style.Margin.margin_left,
+                                                    Au(0)).specified_or_zero();
+            let margin_right = MaybeAuto::from_style(style.Margin.margin_right,
+                                                     Au(0)).specified_or_zero();
+            let noncontent_width = margin_left + margin_right + box_.border.get().left +
+                box_.border.get().right + box_.padding.get().left + box_.padding.get().right;
+            min_width = geometry::max(min_width, specified_width) + noncontent_width;
+            pref_width = geometry::max(pref_width, specified_width) + noncontent_width;
+        }
+
+        // Floats inside the table stay in its cells and captions.
+        self.block.base.num_floats = 0;
+        self.block.base.min_width = geometry::max(min_width, caption_min_width);
+        self.block.base.pref_width = geometry::max(pref_width,
+                                                   geometry::max(caption_pref_width,
+                                                                 self.block.base.min_width));
+    }
+
+    /// Runs the table layout algorithm to find the widths of the columns and of the table, then
+    /// hands the column widths down to the rows.
+    fn assign_widths(&mut self, ctx: &mut LayoutContext) {
+        let containing_width = self.block.base.position.size.width;
+        let spacing_width = self.horizontal_spacing_width();
+        let min_width = sum(self.column_min_widths) + spacing_width;
+        let pref_width = sum(self.column_pref_widths) + spacing_width;
+
+        let mut content_width = min_width;
+        let mut column_widths = self.column_min_widths.clone();
+        for box_ in self.block.box_.iter() {
+            let style = box_.style();
+            box_.compute_padding(style, containing_width);
+            let margin_left = MaybeAuto::from_style(style.Margin.margin_left,
+                                                    containing_width).specified_or_zero();
+            let margin_right = MaybeAuto::from_style(style.Margin.margin_right,
+                                                     containing_width).specified_or_zero();
+            let available_width = containing_width - margin_left - margin_right -
+                box_.border.get().left - box_.border.get().right - box_.padding.get().left -
+                box_.padding.get().right;
+
+            match (style.Table.table_layout,
+                   MaybeAuto::from_style(style.Box.width, containing_width)) {
+                // The fixed table layout algorithm (CSS 2.1 § 17.5.2.1) only applies to tables
+                // with a specified width.
+                (table_layout::fixed, Specified(width)) => {
+                    let column_width = width - spacing_width;
+                    let specified_widths: ~[MaybeAuto] =
+                        range(0, self.column_min_widths.len()).map(|i| {
+                            self.fixed_column_width(i, column_width)
+                        }).collect();
+                    let fixed_width = specified_widths.iter().fold(Au(0), |sum, width| {
+                        sum + width.specified_or_zero()
+                    });
+                    content_width = geometry::max(width, fixed_width + spacing_width);
+                    column_widths = fixed_column_widths(specified_widths,
+                                                        content_width - spacing_width);
+                }
+                // The automatic table layout algorithm (CSS 2.1 § 17.5.2.2).
+                (_, Specified(width)) => {
+                    content_width = geometry::max(width, min_width);
+                    column_widths = auto_column_widths(self.column_min_widths,
+                                                       self.column_pref_widths,
+                                                       content_width - spacing_width);
+                }
+                (_, Auto) => {
+                    content_width = geometry::max(min_width,
+                                                  geometry::min(pref_width, available_width));
+                    column_widths = auto_column_widths(self.column_min_widths,
+                                                       self.column_pref_widths,
+                                                       content_width - spacing_width);
+                }
+            }
+        }
+        self.column_widths = column_widths;
+
+        self.block.assign_widths_block_base(ctx, Some(content_width));
+
+        // Captions are as wide as the border box of the table.
+        let mut caption_x = Au(0);
+        let mut caption_width = content_width;
+        for box_ in self.block.box_.iter() {
+            caption_x = box_.margin.get().left;
+            caption_width = box_.position.get().size.width;
+        }
+
+        let border_spacing = self.border_spacing();
+        for kid in self.block.base.child_iter() {
+            match kid.class() {
+                TableCaptionFlowClass => {
+                    let kid_base = flow::mut_base(kid);
+                    kid_base.position.origin.x = caption_x;
+                    kid_base.position.size.width = caption_width;
+                }
+                TableRowGroupFlowClass => {
+                    let row_group = kid.as_table_row_group();
+                    row_group.column_widths = self.column_widths.clone();
+                    row_group.border_spacing = border_spacing;
+                }
+                TableRowFlowClass => {
+                    let row = kid.as_table_row();
+                    row.column_widths = self.column_widths.clone();
+                    row.border_spacing = border_spacing;
+                }
+                _ => {}
+            }
+
+            // Floats can't flow into or out of a table, so its parts are never laid out in order.
+            let kid_base = flow::mut_base(kid);
+            kid_base.flags_info.flags.set_inorder(false);
+            kid_base.floats_in = FloatContext::new(0);
+        }
+    }
+
+    /// Stacks the top captions, the rows with vertical border spacing around them, and the bottom
+    /// captions. A specified height is a minimum for tables (CSS 2.1 § 17.5.3).
+    ///
+    /// FIXME: Extra height should be distributed among the rows rather than left at the bottom.
+    fn assign_height(&mut self, ctx: &mut LayoutContext) {
+        let spacing = self.border_spacing().vertical;
+        let mut y = Au(0);
+        for box_ in self.block.box_.iter() {
+            y = box_.margin.get().top;
+        }
+
+        for kid in self.block.base.child_iter() {
+            if is_caption_on_side(kid, caption_side::top) {
+                let kid_base = flow::mut_base(kid);
+                kid_base.position.origin.y = y;
+                y = y + kid_base.position.size.height;
+            }
+        }
+
+        let table_top = y;
+        for box_ in self.block.box_.iter() {
+            y = y + box_.border.get().top + box_.padding.get().top;
+        }
+        y = y + spacing;
+        for kid in self.block.base.child_iter() {
+            match kid.class() {
+                TableRowGroupFlowClass | TableRowFlowClass => {
+                    let kid_base = flow::mut_base(kid);
+                    kid_base.position.origin.y = y;
+                    y = y + kid_base.position.size.height + spacing;
+                }
+                _ => {}
+            }
+        }
+
+        for box_ in self.block.box_.iter() {
+            let style = box_.style();
+            let noncontent_height = box_.border.get().top + box_.border.get().bottom +
+                box_.padding.get().top + box_.padding.get().bottom;
+            y = y + box_.padding.get().bottom + box_.border.get().bottom;
+            let specified_height = MaybeAuto::from_style(style.Box.height,
+                                                         Au(0)).specified_or_zero();
+            y = geometry::max(y, table_top + specified_height + noncontent_height);
+
+            let mut position = box_.position.get();
+            position.origin.y = table_top;
+            position.size.height = y - table_top;
+            box_.position.set(position);
+        }
+
+        for kid in self.block.base.child_iter() {
+            if is_caption_on_side(kid, caption_side::bottom) {
+                let kid_base = flow::mut_base(kid);
+                kid_base.position.origin.y = y;
+                y = y + kid_base.position.size.height;
+            }
+        }
+
+        for box_ in self.block.box_.iter() {
+            y = y + box_.margin.get().bottom;
+        }
+        self.block.base.position.size.height = y;
+        self.block.base.floats_out = self.block.base.floats_in.clone();
+
+        if self.block.is_positioned() {
+            self.block.position_absolute_descendants(ctx.screen_size.height)
+        }
+    }
+
+    /// Floats don't interact with tables, so they pass by unchanged.
+    fn assign_height_inorder(&mut self, ctx: &mut LayoutContext) {
+        self.assign_height(ctx);
+        let height = self.block.base.position.size.height;
+        self.block.base.floats_out = self.block.base.floats_in.clone()
+                                                              .translate(Point2D(Au(0), -height));
+    }
+
+    fn collapse_margins(&mut self,
+                        top_margin_collapsible: bool,
+                        first_in_flow: &mut bool,
+                        margin_top: &mut Au,
+                        top_offset: &mut Au,
+                        collapsing: &mut Au,
+                        collapsible: &mut Au) {
+        self.block.collapse_margins(top_margin_collapsible,
+                                    first_in_flow,
+                                    margin_top,
+                                    top_offset,
+                                    collapsing,
+                                    collapsible)
+    }
+
+    fn debug_str(&self) -> ~str {
+        let txt = ~"TableFlow: ";
+        txt.append(match self.block.box_ {
+            Some(ref rb) => rb.debug_str(),
+            None => ~"",
+        })
+    }
+}
+
+fn is_caption_on_side(flow: &mut Flow, side: caption_side::T) -> bool {
+    match flow.class() {
+        TableCaptionFlowClass => {
+            match flow.as_block().box_ {
+                Some(ref box_) => box_.style().Table.caption_side == side,
+                None => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+fn sum(widths: &[Au]) -> Au {
+    widths.iter().fold(Au(0), |sum, width| sum + *width)
+}
+
+/// Widens each of `widths` to at least the corresponding one of `new_widths`, adding columns as
+/// necessary.
+pub fn widen_columns(widths: &mut ~[Au], new_widths: &[Au]) {
+    for (i, &new_width) in new_widths.iter().enumerate() {
+        if i < widths.len() {
+            widths[i] = geometry::max(widths[i], new_width);
+        } else {
+            widths.push(new_width);
+        }
+    }
+}
+
+/// Divides `width` among the columns per the automatic table layout algorithm (CSS 2.1 §
+/// 17.5.2.2). Each column gets at least its minimum width; width beyond the sum of the minimums
+/// goes to the columns in proportion to how much they can grow towards their preferred widths, and
+/// width beyond the sum of the preferred widths in proportion to the preferred widths.
+pub fn auto_column_widths(min_widths: &[Au], pref_widths: &[Au], width: Au) -> ~[Au] {
+    let min_total = sum(min_widths);
+    let pref_total = sum(pref_widths);
+    if min_widths.is_empty() || width <= min_total {
+        return min_widths.to_owned()
+    }
+
+    if width < pref_total {
+        return range(0, min_widths.len()).map(|i| {
+            min_widths[i] + proportion(pref_widths[i] - min_widths[i],
+                                       width - min_total,
+                                       pref_total - min_total)
+        }).collect()
+    }
+
+    let extra_width = width - pref_total;
+    if pref_total == Au(0) {
+        let share = extra_width / Au(pref_widths.len() as i32);
+        return pref_widths.map(|_| share)
+    }
+    pref_widths.map(|&pref_width| {
+        pref_width + proportion(extra_width, pref_width, pref_total)
+    })
+}
+
+/// Returns `length * numerator / denominator` without overflowing or losing precision in between.
+fn proportion(length: Au, numerator: Au, denominator: Au) -> Au {
+    Au(((*length as i64) * (*numerator as i64) / (*denominator as i64)) as i32)
+}
+
+/// Divides `width` among the columns per the fixed table layout algorithm (CSS 2.1 § 17.5.2.1).
+/// Columns with a specified width get it, and the rest of the width is shared equally by the
+/// others, or by all columns if every width was specified.
+pub fn fixed_column_widths(specified_widths: &[MaybeAuto], width: Au) -> ~[Au] {
+    if specified_widths.is_empty() {
+        return ~[]
+    }
+
+    let mut fixed_width = Au(0);
+    let mut auto_count = 0;
+    for specified_width in specified_widths.iter() {
+        match *specified_width {
+            Specified(width) => fixed_width = fixed_width + width,
+            Auto => auto_count += 1,
+        }
+    }
+    let remaining_width = geometry::max(width - fixed_width, Au(0));
+
+    if auto_count == 0 {
+        let share = remaining_width / Au(specified_widths.len() as i32);
+        return specified_widths.map(|specified_width| specified_width.specified_or_zero() + share)
+    }
+    let share = remaining_width / Au(auto_count);
+    specified_widths.map(|specified_width| specified_width.specified_or_default(share))
+}
+
+#[test]
+fn test_auto_column_widths() {
+    let px = |n: int| Au::from_px(n);
+
+    // Narrower than the minimum: every column gets its minimum width.
+    assert_eq!(auto_column_widths([px(10), px(20)], [px(50), px(100)], px(20)), ~[px(10), px(20)]);
+
+    // Between the minimum and preferred widths: columns grow in proportion to how far they can.
+    assert_eq!(auto_column_widths([px(10), px(20)], [px(50), px(100)], px(90)), ~[px(30), px(60)]);
+
+    // Wider than the preferred width: the extra goes in proportion to the preferred widths.
+    assert_eq!(auto_column_widths([px(10), px(20)], [px(50), px(100)], px(300)),
+               ~[px(100), px(200)]);
+
+    // Columns without content share the extra width equally.
+    assert_eq!(auto_column_widths([px(0), px(0)], [px(0), px(0)], px(100)), ~[px(50), px(50)]);
+}
+
+#[test]
+fn test_fixed_column_widths() {
+    let px = |n: int| Au::from_px(n);
+
+    assert_eq!(fixed_column_widths([Specified(px(100)), Auto, Auto], px(300)),
+               ~[px(100), px(100), px(100)]);
+    assert_eq!(fixed_column_widths([Specified(px(100)), Specified(px(50))], px(300)),
+               ~[px(175), px(125)]);
+
+    // Specified widths win over the table width.
+    assert_eq!(fixed_column_widths([Specified(px(200)), Auto], px(100)), ~[px(200), px(0)]);
+}
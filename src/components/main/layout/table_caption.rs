@@ -0,0 +1,71 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! CSS table captions.
+
+use layout::block::BlockFlow;
+use layout::box_::Box;
+use layout::construct::FlowConstructor;
+use layout::context::LayoutContext;
+use layout::flow::{TableCaptionFlowClass, FlowClass, Flow};
+use layout::wrapper::ThreadSafeLayoutNode;
+
+use style::computed_values::caption_side;
+
+/// A table caption flow (CSS 2.1 § 17.4). Captions are laid out like blocks as wide as the border
+/// box of their table, which places them above or below itself according to `caption-side`.
+pub struct TableCaptionFlow {
+    /// The block flow that lays out the caption's contents.
+    block: BlockFlow,
+}
+
+impl TableCaptionFlow {
+    pub fn from_node_and_box(constructor: &mut FlowConstructor,
+                             node: ThreadSafeLayoutNode,
+                             box_: Box)
+                             -> TableCaptionFlow {
+        TableCaptionFlow {
+            block: BlockFlow::from_node_and_box(constructor, node, box_),
+        }
+    }
+
+    pub fn caption_side(&self) -> caption_side::T {
+        match self.block.box_ {
+            Some(ref box_) => box_.style().Table.caption_side,
+            None => caption_side::top,
+        }
+    }
+}
+
+impl Flow for TableCaptionFlow {
+    fn class(&self) -> FlowClass {
+        TableCaptionFlowClass
+    }
+
+    fn as_block<'a>(&'a mut self) -> &'a mut BlockFlow {
+        &mut self.block
+    }
+
+    fn bubble_widths(&mut self, ctx: &mut LayoutContext) {
+        self.block.bubble_widths(ctx)
+    }
+
+    fn assign_widths(&mut self, ctx: &mut LayoutContext) {
+        self.block.assign_widths(ctx)
+    }
+
+    /// Captions establish block formatting contexts (CSS 2.1 § 9.4.1), so floats inside them are
+    /// laid out here rather than by the table.
+    fn assign_height(&mut self, ctx: &mut LayoutContext) {
+        self.block.assign_height_formatting_context_root(ctx)
+    }
+
+    fn debug_str(&self) -> ~str {
+        let txt = ~"TableCaptionFlow: ";
+        txt.append(match self.block.box_ {
+            Some(ref rb) => rb.debug_str(),
+            None => ~"",
+        })
+    }
+}
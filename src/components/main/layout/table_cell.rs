@@ -0,0 +1,154 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! CSS table cells.
+
+use layout::block::BlockFlow;
+use layout::box_::Box;
+use layout::construct::FlowConstructor;
+use layout::context::LayoutContext;
+use layout::flow::{TableCellFlowClass, FlowClass, Flow};
+use layout::flow;
+use layout::model::MaybeAuto;
+use layout::wrapper::ThreadSafeLayoutNode;
+
+use servo_util::geometry::Au;
+use servo_util::geometry;
+use style::computed_values::{LengthOrPercentageOrAuto, LPA_Auto, vertical_align};
+
+/// A table cell flow (CSS 2.1 § 17.5). Cells are block containers whose width is imposed by their
+/// column and whose height is stretched to that of their row.
+///
+/// Cells can span several columns, but `rowspan` isn't supported: every cell is in a single row.
+pub struct TableCellFlow {
+    /// The block flow that lays out the cell's contents.
+    block: BlockFlow,
+
+    /// The number of columns that the cell spans, which is at least one.
+    column_span: uint,
+}
+
+impl TableCellFlow {
+    pub fn from_node_and_box(constructor: &mut FlowConstructor,
+                             node: ThreadSafeLayoutNode,
+                             box_: Box,
+                             column_span: uint)
+                             -> TableCellFlow {
+        TableCellFlow {
+            block: BlockFlow::from_node_and_box(constructor, node, box_),
+            column_span: column_span,
+        }
+    }
+
+    /// The `width` property of the cell, which the fixed table layout algorithm uses for the
+    /// cells of the first row.
+    pub fn specified_width(&self) -> LengthOrPercentageOrAuto {
+        match self.block.box_ {
+            Some(ref box_) => box_.style().Box.width,
+            None => LPA_Auto,
+        }
+    }
+
+    /// Stretches the cell to the height of its row, moving its contents down according to
+    /// `vertical-align` (CSS 2.1 § 17.5.3).
+    pub fn stretch_to_row_height(&mut self, row_height: Au) {
+        let extra_height = row_height - self.block.base.position.size.height;
+        let content_offset = match self.block.box_ {
+            Some(ref box_) => {
+                match box_.vertical_align() {
+                    vertical_align::middle => extra_height.scale_by(0.5),
+                    vertical_align::bottom => extra_height,
+                    // FIXME: Cells with `vertical-align: baseline` should have their first lines
+                    // aligned across the row.
+                    _ => Au(0),
+                }
+            }
+            None => Au(0),
+        };
+
+        for kid in self.block.base.child_iter() {
+            let kid_base = flow::mut_base(kid);
+            kid_base.position.origin.y = kid_base.position.origin.y + content_offset;
+        }
+        for box_ in self.block.box_.iter() {
+            let mut position = box_.position.get();
+            position.size.height = position.size.height + extra_height;
+            box_.position.set(position);
+        }
+        self.block.base.position.size.height = row_height;
+    }
+}
+
+impl Flow for TableCellFlow {
+    fn class(&self) -> FlowClass {
+        TableCellFlowClass
+    }
+
+    fn as_block<'a>(&'a mut self) -> &'a mut BlockFlow {
+        &mut self.block
+    }
+
+    fn as_table_cell<'a>(&'a mut self) -> &'a mut TableCellFlow {
+        self
+    }
+
+    /// Unlike for blocks, a specified width is a lower bound on the minimum and preferred widths
+    /// of the contents rather than being added to them (CSS 2.1 § 17.5.2.2). Margins don't apply
+    /// to cells.
+    fn bubble_widths(&mut self, _: &mut LayoutContext) {
+        let mut min_width = Au(0);
+        let mut pref_width = Au(0);
+        let mut num_floats = 0;
+        for kid in self.block.base.child_iter() {
+            let kid_base = flow::mut_base(kid);
+            min_width = geometry::max(min_width, kid_base.min_width);
+            pref_width = geometry::max(pref_width, kid_base.pref_width);
+            num_floats = num_floats + kid_base.num_floats;
+        }
+
+        for box_ in self.block.box_.iter() {
+            let style = box_.style();
+            box_.compute_borders(style);
+            // Percentages can't be resolved until the width of the table is known.
+            box_.compute_padding(style, Au(0));
+            let noncontent_width = box_.border.get().left + box_.border.get().right +
+                box_.padding.get().left + box_.padding.get().right;
+            let specified_width = MaybeAuto::from_style(style.Box.width,
+                                                        Au(0)).specified_or_zero();
+            min_width = geometry::max(min_width, specified_width) + noncontent_width;
+            pref_width = geometry::max(pref_width, specified_width) + noncontent_width;
+        }
+
+        // The cell needs the count to lay out its own floats. Cells establish block formatting
+        // contexts, so the row doesn't add it to its own.
+        self.block.base.num_floats = num_floats;
+        self.block.base.min_width = min_width;
+        self.block.base.pref_width = geometry::max(min_width, pref_width);
+    }
+
+    /// The row has set the width of the cell to that of its column; the contents get whatever is
+    /// left after borders and padding.
+    fn assign_widths(&mut self, ctx: &mut LayoutContext) {
+        let column_width = self.block.base.position.size.width;
+        let mut content_width = column_width;
+        for box_ in self.block.box_.iter() {
+            box_.compute_padding(box_.style(), column_width);
+            content_width = column_width - box_.border.get().left - box_.border.get().right -
+                box_.padding.get().left - box_.padding.get().right;
+        }
+        self.block.assign_widths_block_base(ctx, Some(geometry::max(content_width, Au(0))))
+    }
+
+    fn assign_height(&mut self, ctx: &mut LayoutContext) {
+        self.block.assign_height_formatting_context_root(ctx)
+    }
+
+    fn debug_str(&self) -> ~str {
+        let txt = ~"TableCellFlow: ";
+        txt.append(match self.block.box_ {
+            Some(ref rb) => rb.debug_str(),
+            None => ~"",
+        })
+    }
+}
@@ -0,0 +1,192 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! CSS table rows.
+
+use layout::block::BlockFlow;
+use layout::box_::Box;
+use layout::construct::FlowConstructor;
+use layout::context::LayoutContext;
+use layout::flow::{TableRowFlowClass, FlowClass, Flow};
+use layout::flow;
+use layout::model::MaybeAuto;
+use layout::wrapper::ThreadSafeLayoutNode;
+
+use servo_util::geometry::Au;
+use servo_util::geometry;
+use std::vec;
+use style::computed_values::{LengthOrPercentageOrAuto, LPA_Auto, LPA_Length, LPA_Percentage};
+use style::computed_values::border_spacing;
+
+/// A table row flow (CSS 2.1 § 17.2). Its children are always table cells, which it places side by
+/// side in the columns that the table computed.
+///
+/// Borders don't apply to rows in the separated borders model (CSS 2.1 § 17.6.1), so they are
+/// never computed for the row's box.
+pub struct TableRowFlow {
+    /// The block flow that holds the row's box.
+    block: BlockFlow,
+
+    /// The minimum width of each of the row's cells, by column.
+    column_min_widths: ~[Au],
+
+    /// The preferred width of each of the row's cells, by column.
+    column_pref_widths: ~[Au],
+
+    /// The `width` of each of the row's cells, by column.
+    column_specified_widths: ~[LengthOrPercentageOrAuto],
+
+    /// The used widths of the columns, assigned by the table.
+    column_widths: ~[Au],
+
+    /// The border spacing of the table, assigned by the table.
+    border_spacing: border_spacing::T,
+}
+
+impl TableRowFlow {
+    pub fn from_node_and_box(constructor: &mut FlowConstructor,
+                             node: ThreadSafeLayoutNode,
+                             box_: Box)
+                             -> TableRowFlow {
+        TableRowFlow {
+            block: BlockFlow::from_node_and_box(constructor, node, box_),
+            column_min_widths: ~[],
+            column_pref_widths: ~[],
+            column_specified_widths: ~[],
+            column_widths: ~[],
+            border_spacing: border_spacing::T {
+                horizontal: Au(0),
+                vertical: Au(0),
+            },
+        }
+    }
+}
+
+impl Flow for TableRowFlow {
+    fn class(&self) -> FlowClass {
+        TableRowFlowClass
+    }
+
+    fn as_block<'a>(&'a mut self) -> &'a mut BlockFlow {
+        &mut self.block
+    }
+
+    fn as_table_row<'a>(&'a mut self) -> &'a mut TableRowFlow {
+        self
+    }
+
+    /// Collects the widths that each cell contributes to its columns. Cells that span several
+    /// columns share their widths equally among them.
+    fn bubble_widths(&mut self, _: &mut LayoutContext) {
+        let mut min_widths = ~[];
+        let mut pref_widths = ~[];
+        let mut specified_widths = ~[];
+        for kid in self.block.base.child_iter() {
+            let column_span = kid.as_table_cell().column_span;
+            let specified_width = kid.as_table_cell().specified_width();
+            for _ in range(0, column_span) {
+                specified_widths.push(split_specified_width(specified_width, column_span));
+            }
+            let kid_base = flow::mut_base(kid);
+            min_widths.push_all_move(split_width(kid_base.min_width, column_span));
+            pref_widths.push_all_move(split_width(kid_base.pref_width, column_span));
+        }
+
+        self.block.base.min_width = min_widths.iter().fold(Au(0), |sum, width| sum + *width);
+        self.block.base.pref_width = pref_widths.iter().fold(Au(0), |sum, width| sum + *width);
+        self.column_min_widths = min_widths;
+        self.column_pref_widths = pref_widths;
+        self.column_specified_widths = specified_widths;
+    }
+
+    /// Places each cell in its columns, giving it their widths and the spacing between them.
+    /// Columns the table doesn't know about have no width.
+    fn assign_widths(&mut self, ctx: &mut LayoutContext) {
+        self.block.assign_widths_block_base(ctx, None);
+
+        let spacing = self.border_spacing.horizontal;
+        let mut x = spacing;
+        let mut column = 0;
+        for kid in self.block.base.child_iter() {
+            let column_span = kid.as_table_cell().column_span;
+            let mut width = spacing * Au((column_span - 1) as i32);
+            for i in range(column, column + column_span) {
+                if i < self.column_widths.len() {
+                    width = width + self.column_widths[i];
+                }
+            }
+            column += column_span;
+
+            let kid_base = flow::mut_base(kid);
+            kid_base.position.origin.x = x;
+            kid_base.position.size.width = width;
+            x = x + width + spacing;
+        }
+    }
+
+    /// The row is as tall as its tallest cell, or its specified height if that is taller, and all
+    /// of its cells are stretched to that height.
+    fn assign_height(&mut self, _: &mut LayoutContext) {
+        let mut height = Au(0);
+        for kid in self.block.base.child_iter() {
+            height = geometry::max(height, flow::mut_base(kid).position.size.height);
+        }
+        for box_ in self.block.box_.iter() {
+            let specified_height = MaybeAuto::from_style(box_.style().Box.height,
+                                                         Au(0)).specified_or_zero();
+            height = geometry::max(height, specified_height);
+        }
+
+        for kid in self.block.base.child_iter() {
+            flow::mut_base(kid).position.origin.y = Au(0);
+            kid.as_table_cell().stretch_to_row_height(height);
+        }
+
+        for box_ in self.block.box_.iter() {
+            let mut position = box_.position.get();
+            position.size.height = height;
+            box_.position.set(position);
+        }
+        self.block.base.position.size.height = height;
+    }
+
+    fn debug_str(&self) -> ~str {
+        let txt = ~"TableRowFlow: ";
+        txt.append(match self.block.box_ {
+            Some(ref rb) => rb.debug_str(),
+            None => ~"",
+        })
+    }
+}
+
+/// Divides `width` into `count` equal parts, the last of which gets any remainder.
+fn split_width(width: Au, count: uint) -> ~[Au] {
+    let share = width / Au(count as i32);
+    let mut widths = vec::from_elem(count, share);
+    widths[count - 1] = width - share * Au((count - 1) as i32);
+    widths
+}
+
+/// The part of a cell's specified width that goes to each of the `count` columns it spans.
+fn split_specified_width(width: LengthOrPercentageOrAuto, count: uint)
+                         -> LengthOrPercentageOrAuto {
+    match width {
+        LPA_Auto => LPA_Auto,
+        LPA_Length(length) => LPA_Length(length / Au(count as i32)),
+        LPA_Percentage(percentage) => LPA_Percentage(percentage / (count as f64)),
+    }
+}
+
+#[test]
+fn test_split_width() {
+    assert_eq!(split_width(Au(100), 1), ~[Au(100)]);
+    assert_eq!(split_width(Au(100), 3), ~[Au(33), Au(33), Au(34)]);
+}
+
+#[test]
+fn test_split_specified_width() {
+    assert_eq!(split_specified_width(LPA_Length(Au(90)), 3), LPA_Length(Au(30)));
+    assert_eq!(split_specified_width(LPA_Percentage(0.5), 2), LPA_Percentage(0.25));
+    assert_eq!(split_specified_width(LPA_Auto, 2), LPA_Auto);
+}
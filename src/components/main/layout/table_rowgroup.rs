@@ -0,0 +1,133 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! CSS table row groups.
+
+use layout::block::BlockFlow;
+use layout::box_::Box;
+use layout::construct::FlowConstructor;
+use layout::context::LayoutContext;
+use layout::flow::{TableRowGroupFlowClass, FlowClass, Flow};
+use layout::flow;
+use layout::table;
+use layout::wrapper::ThreadSafeLayoutNode;
+
+use servo_util::geometry::Au;
+use style::computed_values::{LengthOrPercentageOrAuto, border_spacing};
+
+/// A table row group flow (CSS 2.1 § 17.2), for `display: table-row-group`, `table-header-group`
+/// and `table-footer-group`. Its children are always table rows, which it stacks vertically.
+///
+/// FIXME: Header and footer groups should be moved to the top and bottom of the table.
+pub struct TableRowGroupFlow {
+    /// The block flow that holds the row group's box.
+    block: BlockFlow,
+
+    /// The largest minimum width of the cells in each column.
+    column_min_widths: ~[Au],
+
+    /// The largest preferred width of the cells in each column.
+    column_pref_widths: ~[Au],
+
+    /// The `width` of each of the cells in the first row of the group.
+    column_specified_widths: ~[LengthOrPercentageOrAuto],
+
+    /// The used widths of the columns, assigned by the table.
+    column_widths: ~[Au],
+
+    /// The border spacing of the table, assigned by the table.
+    border_spacing: border_spacing::T,
+}
+
+impl TableRowGroupFlow {
+    pub fn from_node_and_box(constructor: &mut FlowConstructor,
+                             node: ThreadSafeLayoutNode,
+                             box_: Box)
+                             -> TableRowGroupFlow {
+        TableRowGroupFlow {
+            block: BlockFlow::from_node_and_box(constructor, node, box_),
+            column_min_widths: ~[],
+            column_pref_widths: ~[],
+            column_specified_widths: ~[],
+            column_widths: ~[],
+            border_spacing: border_spacing::T {
+                horizontal: Au(0),
+                vertical: Au(0),
+            },
+        }
+    }
+}
+
+impl Flow for TableRowGroupFlow {
+    fn class(&self) -> FlowClass {
+        TableRowGroupFlowClass
+    }
+
+    fn as_block<'a>(&'a mut self) -> &'a mut BlockFlow {
+        &mut self.block
+    }
+
+    fn as_table_row_group<'a>(&'a mut self) -> &'a mut TableRowGroupFlow {
+        self
+    }
+
+    fn bubble_widths(&mut self, _: &mut LayoutContext) {
+        let mut min_widths = ~[];
+        let mut pref_widths = ~[];
+        let mut specified_widths = None;
+        for kid in self.block.base.child_iter() {
+            let row = kid.as_table_row();
+            table::widen_columns(&mut min_widths, row.column_min_widths);
+            table::widen_columns(&mut pref_widths, row.column_pref_widths);
+            if specified_widths.is_none() {
+                specified_widths = Some(row.column_specified_widths.clone());
+            }
+        }
+
+        self.block.base.min_width = min_widths.iter().fold(Au(0), |sum, width| sum + *width);
+        self.block.base.pref_width = pref_widths.iter().fold(Au(0), |sum, width| sum + *width);
+        self.column_min_widths = min_widths;
+        self.column_pref_widths = pref_widths;
+        self.column_specified_widths = specified_widths.unwrap_or(~[]);
+    }
+
+    fn assign_widths(&mut self, ctx: &mut LayoutContext) {
+        self.block.assign_widths_block_base(ctx, None);
+
+        for kid in self.block.base.child_iter() {
+            let row = kid.as_table_row();
+            row.column_widths = self.column_widths.clone();
+            row.border_spacing = self.border_spacing;
+        }
+    }
+
+    /// Stacks the rows with the table's vertical border spacing between them.
+    fn assign_height(&mut self, _: &mut LayoutContext) {
+        let spacing = self.border_spacing.vertical;
+        let mut y = Au(0);
+        for (i, kid) in self.block.base.child_iter().enumerate() {
+            if i > 0 {
+                y = y + spacing;
+            }
+            let kid_base = flow::mut_base(kid);
+            kid_base.position.origin.y = y;
+            y = y + kid_base.position.size.height;
+        }
+
+        for box_ in self.block.box_.iter() {
+            let mut position = box_.position.get();
+            position.size.height = y;
+            box_.position.set(position);
+        }
+        self.block.base.position.size.height = y;
+    }
+
+    fn debug_str(&self) -> ~str {
+        let txt = ~"TableRowGroupFlow: ";
+        txt.append(match self.block.box_ {
+            Some(ref rb) => rb.debug_str(),
+            None => ~"",
+        })
+    }
+}
@@ -0,0 +1,19 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Helpers shared by the layout tests.
+
+use extra::url::Url;
+use std::from_str::FromStr;
+use style::{ComputedValues, cascade, parse_style_attribute};
+
+/// The URL that the style attributes of test elements resolve URLs against.
+pub fn base_url() -> Url {
+    FromStr::from_str("http://example.com/").unwrap()
+}
+
+/// Returns the computed style of an element with no parent whose style attribute is `css`.
+pub fn style(css: &str) -> ComputedValues {
+    cascade([parse_style_attribute(css, &base_url()).normal], None)
+}
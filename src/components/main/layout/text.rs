@@ -9,8 +9,7 @@ use layout::flow::Flow;
 
 use extra::arc::Arc;
 use gfx::font_context::FontContext;
-use gfx::text::text_run::TextRun;
-use gfx::text::util::{CompressWhitespaceNewline, transform_text, CompressNone};
+use gfx::text::util::{CompressNone, CompressWhitespace, CompressWhitespaceNewline, transform_text};
 use servo_util::range::Range;
 use std::vec;
 use style::computed_values::white_space;
@@ -114,11 +113,13 @@ impl TextRunScanner {
 
                 let font_style = old_box.font_style();
                 let decoration = old_box.text_decoration();
+                let word_break = old_box.word_break();
 
                 // TODO(#115): Use the actual CSS `white-space` property of the relevant style.
                 let compression = match old_box.white_space() {
-                    white_space::normal => CompressWhitespaceNewline,
-                    white_space::pre => CompressNone,
+                    white_space::normal | white_space::nowrap => CompressWhitespaceNewline,
+                    white_space::pre | white_space::pre_wrap => CompressNone,
+                    white_space::pre_line => CompressWhitespace,
                 };
 
                 let mut new_line_pos = ~[];
@@ -131,11 +132,15 @@ impl TextRunScanner {
                 new_whitespace = whitespace;
 
                 if transformed_text.len() > 0 {
-                    // TODO(#177): Text run creation must account for the renderability of text by
-                    // font group fonts. This is probably achieved by creating the font group above
-                    // and then letting `FontGroup` decide which `Font` to stick into the text run.
                     let fontgroup = font_context.get_resolved_font_for_style(&font_style);
-                    let run = ~fontgroup.borrow().with(|fg| fg.create_textrun(transformed_text.clone(), decoration));
+                    let mut run = ~fontgroup.borrow().with(|fg| {
+                        fg.create_textrun(transformed_text.clone(),
+                                          decoration,
+                                          word_break,
+                                          old_box.is_rtl())
+                    });
+                    run.letter_spacing = old_box.letter_spacing();
+                    run.word_spacing = old_box.word_spacing();
 
                     debug!("TextRunScanner: pushing single text box in range: {} ({})",
                            self.clump,
@@ -158,18 +163,17 @@ impl TextRunScanner {
                 }
             },
             (false, true) => {
-                // TODO(#177): Text run creation must account for the renderability of text by
-                // font group fonts. This is probably achieved by creating the font group above
-                // and then letting `FontGroup` decide which `Font` to stick into the text run.
                 let in_box = &in_boxes[self.clump.begin()];
                 let font_style = in_box.font_style();
                 let fontgroup = font_context.get_resolved_font_for_style(&font_style);
                 let decoration = in_box.text_decoration();
+                let word_break = in_box.word_break();
 
                 // TODO(#115): Use the actual CSS `white-space` property of the relevant style.
                 let compression = match in_box.white_space() {
-                    white_space::normal => CompressWhitespaceNewline,
-                    white_space::pre => CompressNone,
+                    white_space::normal | white_space::nowrap => CompressWhitespaceNewline,
+                    white_space::pre | white_space::pre_wrap => CompressNone,
+                    white_space::pre_line => CompressWhitespace,
                 };
 
                 struct NewLinePositions {
@@ -220,11 +224,12 @@ impl TextRunScanner {
                 // sequence. If no clump takes ownership, however, it will leak.
                 let clump = self.clump;
                 let run = if clump.length() != 0 && run_str.len() > 0 {
-                    fontgroup.borrow().with(|fg| {
-                        fg.fonts[0].borrow().with_mut(|font| {
-                            Some(Arc::new(~TextRun::new(font, run_str.clone(), decoration)))
-                        })
-                    })
+                    let mut run = ~fontgroup.borrow().with(|fg| {
+                        fg.create_textrun(run_str.clone(), decoration, word_break, in_box.is_rtl())
+                    });
+                    run.letter_spacing = in_box.letter_spacing();
+                    run.word_spacing = in_box.word_spacing();
+                    Some(Arc::new(run))
                 } else {
                     None
                 };
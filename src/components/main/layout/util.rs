@@ -4,6 +4,7 @@
 
 use layout::box_::Box;
 use layout::construct::{ConstructionResult, NoConstructionResult};
+use layout::generated_content::GeneratedContent;
 use layout::parallel::DomParallelInfo;
 use layout::wrapper::{LayoutNode, TLayoutNode, ThreadSafeLayoutNode};
 
@@ -137,6 +138,10 @@ pub struct PrivateLayoutData {
 
     after_applicable_declarations: SmallVec0<Arc<~[PropertyDeclaration]>>,
 
+    first_line_applicable_declarations: SmallVec0<Arc<~[PropertyDeclaration]>>,
+
+    first_letter_applicable_declarations: SmallVec0<Arc<~[PropertyDeclaration]>>,
+
     /// The results of CSS styling for this node.
     before_style: Option<Arc<ComputedValues>>,
 
@@ -144,6 +149,10 @@ pub struct PrivateLayoutData {
 
     after_style: Option<Arc<ComputedValues>>,
 
+    first_line_style: Option<Arc<ComputedValues>>,
+
+    first_letter_style: Option<Arc<ComputedValues>>,
+
     /// Description of how to account for recent style changes.
     restyle_damage: Option<int>,
 
@@ -151,6 +160,16 @@ pub struct PrivateLayoutData {
     /// `ConstructionItem`. See comments in `construct.rs` for more details.
     flow_construction_result: ConstructionResult,
 
+    /// The ordinal value of this node's list item marker, if it is a list item. Later list items
+    /// in the same list number themselves from this.
+    list_item_ordinal: Option<i32>,
+
+    /// The resolved content of the `::before` pseudo-element of this node.
+    before_content: ~[GeneratedContent],
+
+    /// The resolved content of the `::after` pseudo-element of this node.
+    after_content: ~[GeneratedContent],
+
     /// Information needed during parallel traversals.
     parallel: DomParallelInfo,
 }
@@ -162,11 +181,18 @@ impl PrivateLayoutData {
             applicable_declarations: SmallVec16::new(),
             before_applicable_declarations: SmallVec0::new(),
             after_applicable_declarations: SmallVec0::new(),
+            first_line_applicable_declarations: SmallVec0::new(),
+            first_letter_applicable_declarations: SmallVec0::new(),
             before_style: None,
             style: None,
             after_style: None,
+            first_line_style: None,
+            first_letter_style: None,
             restyle_damage: None,
             flow_construction_result: NoConstructionResult,
+            list_item_ordinal: None,
+            before_content: ~[],
+            after_content: ~[],
             parallel: DomParallelInfo::new(),
         }
     }
@@ -177,6 +203,8 @@ impl PrivateLayoutData {
         self.applicable_declarations = SmallVec16::new();
         self.before_applicable_declarations = SmallVec0::new();
         self.after_applicable_declarations = SmallVec0::new();
+        self.first_line_applicable_declarations = SmallVec0::new();
+        self.first_letter_applicable_declarations = SmallVec0::new();
     }
 }
 
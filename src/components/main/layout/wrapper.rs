@@ -16,15 +16,20 @@
 
 use extra::url::Url;
 use script::dom::element::{Element, HTMLAreaElementTypeId, HTMLAnchorElementTypeId};
-use script::dom::element::{HTMLLinkElementTypeId};
+use script::dom::element::{HTMLButtonElementTypeId, HTMLFieldSetElementTypeId};
+use script::dom::element::{HTMLInputElementTypeId, HTMLLinkElementTypeId};
+use script::dom::element::{HTMLOptGroupElementTypeId, HTMLOptionElementTypeId};
+use script::dom::element::{HTMLSelectElementTypeId, HTMLTextAreaElementTypeId};
 use script::dom::htmliframeelement::HTMLIFrameElement;
 use script::dom::htmlimageelement::HTMLImageElement;
 use script::dom::node::{AbstractNode, DocumentNodeTypeId, ElementNodeTypeId, Node, NodeTypeId};
+use script::dom::node::{TextNodeTypeId};
 use script::dom::text::Text;
 use servo_msg::constellation_msg::{PipelineId, SubpageId};
 use servo_util::concurrentmap::{ConcurrentHashMap, ConcurrentHashMapIterator};
 use servo_util::namespace;
 use servo_util::namespace::Namespace;
+use std::ascii::StrAsciiExt;
 use std::cast;
 use std::cell::{Ref, RefMut};
 use style::{PropertyDeclarationBlock, TElement, TNode, AttrSelector};
@@ -231,6 +236,16 @@ impl<'ln> TNode<LayoutElement<'ln>> for LayoutNode<'ln> {
         self.node_is_document()
     }
 
+    fn is_empty(&self) -> bool {
+        self.children().all(|kid| {
+            match kid.type_id() {
+                ElementNodeTypeId(..) => false,
+                TextNodeTypeId => unsafe { kid.with_text(|text| text.element.data.is_empty()) },
+                _ => true,
+            }
+        })
+    }
+
     fn match_attr(&self, attr: &AttrSelector, test: |&str| -> bool) -> bool {
         self.with_element(|element| {
             let name = if element.element.html_element_in_html_document() {
@@ -353,6 +368,43 @@ impl<'le> TElement for LayoutElement<'le> {
     fn get_hover_state(&self) -> bool {
         self.element.node.get_hover_state()
     }
+
+    fn get_focus_state(&self) -> bool {
+        self.element.node.get_focus_state()
+    }
+
+    fn get_checked_state(&self) -> bool {
+        // FIXME: This uses the default checkedness and selectedness, as form controls do not
+        // keep their own state yet.
+        match self.element.node.type_id {
+            ElementNodeTypeId(HTMLInputElementTypeId) => {
+                let type_ = self.get_attr(&namespace::Null, "type").unwrap_or("text");
+                (type_.eq_ignore_ascii_case("checkbox") || type_.eq_ignore_ascii_case("radio")) &&
+                    self.get_attr(&namespace::Null, "checked").is_some()
+            }
+            ElementNodeTypeId(HTMLOptionElementTypeId) => {
+                self.get_attr(&namespace::Null, "selected").is_some()
+            }
+            _ => false,
+        }
+    }
+
+    fn get_disabled_state(&self) -> Option<bool> {
+        // FIXME: Controls in a disabled `fieldset` and options in a disabled `optgroup` should be
+        // disabled too.
+        match self.element.node.type_id {
+            ElementNodeTypeId(HTMLButtonElementTypeId) |
+            ElementNodeTypeId(HTMLFieldSetElementTypeId) |
+            ElementNodeTypeId(HTMLInputElementTypeId) |
+            ElementNodeTypeId(HTMLOptGroupElementTypeId) |
+            ElementNodeTypeId(HTMLOptionElementTypeId) |
+            ElementNodeTypeId(HTMLSelectElementTypeId) |
+            ElementNodeTypeId(HTMLTextAreaElementTypeId) => {
+                Some(self.get_attr(&namespace::Null, "disabled").is_some())
+            }
+            _ => None,
+        }
+    }
 }
 
 /// A thread-safe version of `LayoutNode`, used during flow construction. This type of layout
@@ -395,6 +447,17 @@ impl<'ln> ThreadSafeLayoutNode<'ln> {
         self.node.node().next_sibling.map(|node| self.new_with_this_lifetime(node))
     }
 
+    /// Returns the parent of this node. Unsafe because this can lead to races; callers must only
+    /// read data that the current traversal does not write.
+    pub unsafe fn parent_node(&self) -> Option<ThreadSafeLayoutNode<'ln>> {
+        self.node.node().parent_node.map(|node| self.new_with_this_lifetime(node))
+    }
+
+    /// Returns the previous sibling of this node. Unsafe for the same reasons as `parent_node`.
+    pub unsafe fn prev_sibling(&self) -> Option<ThreadSafeLayoutNode<'ln>> {
+        self.node.node().prev_sibling.map(|node| self.new_with_this_lifetime(node))
+    }
+
     /// Returns an iterator over this node's children.
     pub fn children(&self) -> ThreadSafeLayoutNodeChildrenIterator<'ln> {
         ThreadSafeLayoutNodeChildrenIterator {
@@ -52,7 +52,7 @@ use gfx::opts;
 #[cfg(not(test))]
 use servo_net::image_cache_task::{ImageCacheTask, SyncImageCacheTask};
 #[cfg(not(test))]
-use servo_net::resource_task::ResourceTask;
+use servo_net::resource_task::ResourceTaskWithHttpCache;
 #[cfg(not(test))]
 use servo_util::time::Profiler;
 
@@ -93,15 +93,25 @@ pub mod layout {
     pub mod float_context;
     pub mod flow;
     pub mod flow_list;
+    pub mod generated_content;
     pub mod layout_task;
+    pub mod lists;
     pub mod inline;
     pub mod model;
+    pub mod pagination;
     pub mod parallel;
+    pub mod table;
+    pub mod table_caption;
+    pub mod table_cell;
+    pub mod table_row;
+    pub mod table_rowgroup;
     pub mod text;
     pub mod util;
     pub mod incremental;
     pub mod wrapper;
     pub mod extra;
+    #[cfg(test)]
+    pub mod test_util;
 }
 
 pub mod windowing;
@@ -149,11 +159,12 @@ fn run(opts: Opts) {
     pool.spawn(TaskOpts::new(), proc() {
         let opts = &opts_clone;
         // Create a Servo instance.
-        let resource_task = ResourceTask();
-        // If we are emitting an output file, then we need to block on
-        // image load or we risk emitting an output file missing the
-        // image.
-        let image_cache_task = if opts.output_file.is_some() {
+        let http_cache_dir = opts.http_cache_dir.as_ref().map(|dir| Path::new(dir.as_slice()));
+        let resource_task = ResourceTaskWithHttpCache(http_cache_dir);
+        // If we are emitting an output file or printing, then we need to
+        // block on image load or we risk emitting an output file missing
+        // the image.
+        let image_cache_task = if opts.output_file.is_some() || opts.print_output.is_some() {
                 SyncImageCacheTask(resource_task.clone())
             } else {
                 ImageCacheTask(resource_task.clone())
@@ -73,6 +73,34 @@ impl Epoch {
     }
 }
 
+/// Identifies one of the layers that a pipeline renders into.
+#[deriving(Clone, Eq, IterBytes)]
+pub enum LayerId {
+    /// The layer of the whole page.
+    PageLayerId,
+    /// The layer of the contents of a box with `overflow: scroll` or `auto`, which scroll
+    /// independently of the page. The number is the opaque address of the box's node.
+    ScrollLayerId(uint),
+    /// The layer of what paints above the contents of a scrolling box and overlaps them, which
+    /// has to be composited above them. The number is the same as that of the box's layer.
+    OverlayLayerId(uint),
+}
+
+/// What the compositor needs to know about the layer of a scrolling box, or of an overlay.
+pub struct ScrollLayerMetadata {
+    /// The layer of the box's contents, or the overlay.
+    id: LayerId,
+    /// The layer that the box itself is painted in.
+    parent: LayerId,
+    /// The padding box of the box, or the area of the overlay, in the coordinates of its parent
+    /// layer. The contents are clipped to it.
+    clip_rect: Rect<uint>,
+    /// The size of the scrollable contents.
+    page_size: Size2D<uint>,
+    /// The offset that the contents start out scrolled to.
+    scroll_offset: Point2D<f32>,
+}
+
 /// The interface used by the renderer to acquire draw targets for each render frame and
 /// submit them to be drawn to the display.
 pub trait RenderListener {
@@ -80,8 +108,9 @@ pub trait RenderListener {
     fn new_layer(&self, PipelineId, Size2D<uint>);
     fn set_layer_page_size_and_color(&self, PipelineId, Size2D<uint>, Epoch, Color);
     fn set_layer_clip_rect(&self, PipelineId, Rect<uint>);
+    fn set_scroll_layers(&self, PipelineId, ~[ScrollLayerMetadata], Epoch);
     fn delete_layer(&self, PipelineId);
-    fn paint(&self, id: PipelineId, layer_buffer_set: ~LayerBufferSet, Epoch);
+    fn paint(&self, id: PipelineId, layer_id: LayerId, layer_buffer_set: ~LayerBufferSet, Epoch);
     fn set_render_state(&self, render_state: RenderState);
 }
 
@@ -91,6 +120,7 @@ pub trait ScriptListener : Clone {
     fn set_ready_state(&self, ReadyState);
     fn invalidate_rect(&self, PipelineId, Rect<uint>);
     fn scroll_fragment_point(&self, PipelineId, Point2D<f32>);
+    fn scroll_layer_to_point(&self, PipelineId, LayerId, Point2D<f32>);
     fn close(&self);
 }
 
@@ -0,0 +1,500 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Cookie parsing and storage, as described in RFC 6265.
+
+use std::ascii::StrAsciiExt;
+use std::char;
+use std::i64;
+use extra::arc::MutexArc;
+use extra::url::Url;
+
+/// Handle to the cookie jar, shared between the resource manager and the HTTP loader tasks.
+pub type CookieJarHandle = MutexArc<CookieJar>;
+
+/// Where a cookie is being read or written from. HttpOnly cookies are invisible to script.
+#[deriving(Eq, Clone)]
+pub enum CookieSource {
+    /// An HTTP request or response.
+    HTTP,
+    /// A non-HTTP API, such as `document.cookie`.
+    NonHTTP,
+}
+
+/// A stored cookie (RFC 6265 §5.3).
+#[deriving(Clone)]
+pub struct Cookie {
+    name: ~str,
+    value: ~str,
+    domain: ~str,
+    path: ~str,
+    /// Seconds since the epoch at which the cookie expires, or `None` for a session cookie.
+    expiry_time: Option<i64>,
+    creation_time: i64,
+    /// Only sent to exactly `domain`, because the cookie had no Domain attribute.
+    host_only: bool,
+    secure_only: bool,
+    http_only: bool,
+}
+
+impl Cookie {
+    /// Parses a `Set-Cookie` header value received for `request_url`, following RFC 6265 §5.2
+    /// and §5.3. Returns `None` if the cookie must be ignored.
+    pub fn parse(header: &str, request_url: &Url, source: CookieSource, now: i64)
+                 -> Option<Cookie> {
+        let (name_value_pair, attributes) = match header.find(';') {
+            Some(i) => (header.slice_to(i), header.slice_from(i + 1)),
+            None => (header, ""),
+        };
+        let (name, value) = match name_value_pair.find('=') {
+            Some(i) => (name_value_pair.slice_to(i).trim(), name_value_pair.slice_from(i + 1).trim()),
+            None => return None,
+        };
+        if name.is_empty() {
+            return None
+        }
+
+        let mut expires = None;
+        let mut max_age = None;
+        let mut domain = ~"";
+        let mut path = None;
+        let mut secure_only = false;
+        let mut http_only = false;
+        for attribute in attributes.split(';') {
+            let (attribute_name, attribute_value) = match attribute.find('=') {
+                Some(i) => (attribute.slice_to(i).trim(), attribute.slice_from(i + 1).trim()),
+                None => (attribute.trim(), ""),
+            };
+            match attribute_name.to_ascii_lower().as_slice() {
+                "expires" => {
+                    for time in parse_cookie_date(attribute_value).iter() {
+                        expires = Some(*time);
+                    }
+                }
+                "max-age" => {
+                    for delta in from_str::<i64>(attribute_value).iter() {
+                        max_age = Some(if *delta <= 0 { i64::min_value } else { now + *delta });
+                    }
+                }
+                "domain" => {
+                    if !attribute_value.is_empty() {
+                        domain = attribute_value.trim_left_chars(&'.').to_ascii_lower();
+                    }
+                }
+                "path" => {
+                    path = if attribute_value.starts_with("/") {
+                        Some(attribute_value.to_owned())
+                    } else {
+                        None
+                    };
+                }
+                "secure" => secure_only = true,
+                "httponly" => http_only = true,
+                _ => (),
+            }
+        }
+
+        let request_host = request_url.host.to_ascii_lower();
+        if is_public_suffix(domain) {
+            // A site may not set cookies for a whole registry, only for itself (RFC 6265 §5.3
+            // step 5).
+            if domain != request_host {
+                return None
+            }
+            domain = ~"";
+        }
+        let host_only = domain.is_empty();
+        if !host_only && !domain_match(request_host, domain) {
+            return None
+        }
+        if http_only && source == NonHTTP {
+            return None
+        }
+
+        Some(Cookie {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            domain: if host_only { request_host } else { domain },
+            path: match path {
+                Some(path) => path,
+                None => default_path(request_url.path),
+            },
+            // Max-Age takes precedence over Expires.
+            expiry_time: max_age.or(expires),
+            creation_time: now,
+            host_only: host_only,
+            secure_only: secure_only,
+            http_only: http_only,
+        })
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        match self.expiry_time {
+            Some(expiry_time) => expiry_time <= now,
+            None => false,
+        }
+    }
+
+    /// Whether the cookie should be sent with a request for `url` (RFC 6265 §5.4).
+    pub fn appropriate_for_url(&self, url: &Url, source: CookieSource) -> bool {
+        let host = url.host.to_ascii_lower();
+        let domain_matches = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(host, self.domain)
+        };
+        let path = if url.path.is_empty() { "/" } else { url.path.as_slice() };
+        domain_matches &&
+            path_match(path, self.path) &&
+            (!self.secure_only || "https" == url.scheme) &&
+            (!self.http_only || source == HTTP)
+    }
+}
+
+/// Second-level public suffixes, under which anyone can register a domain. Every top-level domain
+/// counts as a public suffix too.
+static PUBLIC_SUFFIXES: &'static [&'static str] = &[
+    "ac.uk", "co.uk", "gov.uk", "org.uk", "ltd.uk", "plc.uk", "me.uk",
+    "com.au", "net.au", "org.au", "edu.au", "gov.au",
+    "co.jp", "ne.jp", "or.jp", "ac.jp", "go.jp",
+    "co.nz", "org.nz", "co.in", "co.kr", "co.za",
+    "com.br", "com.cn", "com.mx", "com.tr", "com.tw",
+    "github.io", "appspot.com", "blogspot.com", "herokuapp.com",
+];
+
+/// Whether cookies may not be set for `domain` as a whole, because sites under it belong to
+/// different owners. This covers top-level domains and a minimal list of other public suffixes.
+pub fn is_public_suffix(domain: &str) -> bool {
+    !domain.is_empty() &&
+        (!domain.contains_char('.') || PUBLIC_SUFFIXES.iter().any(|suffix| *suffix == domain))
+}
+
+/// Parses the value of an Expires attribute into seconds since the epoch, with the lenient
+/// cookie-date algorithm of RFC 6265 §5.1.1, which accepts the many date formats that servers
+/// send.
+pub fn parse_cookie_date(value: &str) -> Option<i64> {
+    static MONTHS: [&'static str, ..12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug",
+                                           "sep", "oct", "nov", "dec"];
+    fn is_delimiter(c: char) -> bool {
+        match c {
+            '\x09' | '\x20' .. '\x2f' | '\x3b' .. '\x40' | '\x5b' .. '\x60' |
+            '\x7b' .. '\x7e' => true,
+            _ => false,
+        }
+    }
+
+    let mut time = None;
+    let mut day_of_month = None;
+    let mut month = None;
+    let mut year = None;
+    for token in value.split(is_delimiter).filter(|token| !token.is_empty()) {
+        if time.is_none() {
+            match parse_time(token) {
+                Some(hms) => { time = Some(hms); continue }
+                None => (),
+            }
+        }
+        if day_of_month.is_none() {
+            match leading_digits(token, 1, 2) {
+                Some(day) => { day_of_month = Some(day); continue }
+                None => (),
+            }
+        }
+        if month.is_none() && token.len() >= 3 {
+            let prefix = token.slice_to(3).to_ascii_lower();
+            match MONTHS.iter().position(|name| *name == prefix.as_slice()) {
+                Some(index) => { month = Some(index as i64 + 1); continue }
+                None => (),
+            }
+        }
+        if year.is_none() {
+            match leading_digits(token, 2, 4) {
+                Some(value) => { year = Some(value); continue }
+                None => (),
+            }
+        }
+    }
+
+    let (hour, minute, second) = match time { Some(time) => time, None => return None };
+    let (day_of_month, month, year) = match (day_of_month, month, year) {
+        (Some(day_of_month), Some(month), Some(year)) => (day_of_month, month, year),
+        _ => return None,
+    };
+    let year = match year {
+        70 .. 99 => year + 1900,
+        0 .. 69 => year + 2000,
+        _ => year,
+    };
+    if day_of_month < 1 || day_of_month > days_in_month(year, month) || year < 1601 ||
+            hour > 23 || minute > 59 || second > 59 {
+        return None
+    }
+    Some(days_since_epoch(year, month, day_of_month) * 86400 + hour * 3600 + minute * 60 +
+         second)
+}
+
+/// Parses `token` as `1*2DIGIT ":" 1*2DIGIT ":" 1*2DIGIT ( non-digit *OCTET )`.
+fn parse_time(token: &str) -> Option<(i64, i64, i64)> {
+    let mut parts = token.splitn(':', 2);
+    let hour = parts.next().and_then(|part| digits(part, 1, 2));
+    let minute = parts.next().and_then(|part| digits(part, 1, 2));
+    let second = parts.next().and_then(|part| leading_digits(part, 1, 2));
+    match (hour, minute, second) {
+        (Some(hour), Some(minute), Some(second)) => Some((hour, minute, second)),
+        _ => None,
+    }
+}
+
+/// Parses `token` as `min*max DIGIT`.
+fn digits(token: &str, min: uint, max: uint) -> Option<i64> {
+    if token.chars().all(|c| c.is_digit()) {
+        leading_digits(token, min, max)
+    } else {
+        None
+    }
+}
+
+/// Parses the start of `token` as `min*max DIGIT ( non-digit *OCTET )`.
+fn leading_digits(token: &str, min: uint, max: uint) -> Option<i64> {
+    let count = token.find(|c: char| !c.is_digit()).unwrap_or(token.len());
+    if count < min || count > max {
+        return None
+    }
+    Some(token.slice_to(count).chars().fold(0, |value, c| {
+        value * 10 + char::to_digit(c, 10).unwrap() as i64
+    }))
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The number of days from 1970-01-01 to the given date of the proleptic Gregorian calendar.
+fn days_since_epoch(year: i64, month: i64, day_of_month: i64) -> i64 {
+    let mut days = day_of_month - 1;
+    for month in range(1, month) {
+        days += days_in_month(year, month);
+    }
+    // Count the leap days between the epoch and the start of the year.
+    let leap_days = |year: i64| (year - 1) / 4 - (year - 1) / 100 + (year - 1) / 400;
+    days + (year - 1970) * 365 + leap_days(year) - leap_days(1970)
+}
+
+fn is_ip_address(host: &str) -> bool {
+    host.chars().all(|c| c.is_digit() || c == '.') || host.contains_char(':')
+}
+
+/// RFC 6265 §5.1.3.
+pub fn domain_match(string: &str, domain: &str) -> bool {
+    string == domain ||
+        (string.ends_with(domain) &&
+         string.len() > domain.len() &&
+         string.char_at(string.len() - domain.len() - 1) == '.' &&
+         !is_ip_address(string))
+}
+
+/// RFC 6265 §5.1.4.
+pub fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path ||
+        (request_path.starts_with(cookie_path) &&
+         (cookie_path.ends_with("/") || request_path.char_at(cookie_path.len()) == '/'))
+}
+
+/// The path a cookie without a Path attribute is scoped to (RFC 6265 §5.1.4).
+pub fn default_path(request_path: &str) -> ~str {
+    if !request_path.starts_with("/") {
+        return ~"/"
+    }
+    match request_path.rfind('/') {
+        Some(0) | None => ~"/",
+        Some(i) => request_path.slice_to(i).to_owned(),
+    }
+}
+
+/// The cookie store owned by the resource task.
+pub struct CookieJar {
+    cookies: ~[Cookie],
+}
+
+impl CookieJar {
+    pub fn new() -> CookieJar {
+        CookieJar {
+            cookies: ~[],
+        }
+    }
+
+    /// Stores the cookie in a `Set-Cookie` header (or a `document.cookie` assignment),
+    /// replacing any cookie with the same name, domain and path.
+    pub fn set_cookie_from_header(&mut self, url: &Url, header: &str, source: CookieSource,
+                                  now: i64) {
+        let mut cookie = match Cookie::parse(header, url, source, now) {
+            Some(cookie) => cookie,
+            None => {
+                debug!("cookie: ignoring {:s} from {:s}", header, url.to_str());
+                return
+            }
+        };
+
+        match self.cookies.iter().position(|c| {
+            c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path
+        }) {
+            Some(i) => {
+                // Script may not overwrite HttpOnly cookies.
+                if self.cookies[i].http_only && source == NonHTTP {
+                    return
+                }
+                cookie.creation_time = self.cookies[i].creation_time;
+                self.cookies.remove(i);
+            }
+            None => (),
+        }
+
+        // Setting an already-expired cookie is how servers delete them.
+        if !cookie.is_expired(now) {
+            self.cookies.push(cookie);
+        }
+    }
+
+    /// The value of the `Cookie` header for a request to `url`, if any cookies apply. Longer
+    /// paths come first, then older cookies (RFC 6265 §5.4).
+    pub fn cookies_for_url(&mut self, url: &Url, source: CookieSource, now: i64)
+                           -> Option<~str> {
+        self.cookies.retain(|cookie| !cookie.is_expired(now));
+
+        let mut matching: ~[&Cookie] = self.cookies.iter().filter(|cookie| {
+            cookie.appropriate_for_url(url, source)
+        }).collect();
+        if matching.is_empty() {
+            return None
+        }
+
+        matching.sort_by(|a, b| {
+            if a.path.len() != b.path.len() {
+                b.path.len().cmp(&a.path.len())
+            } else {
+                a.creation_time.cmp(&b.creation_time)
+            }
+        });
+        let pairs: ~[~str] = matching.iter().map(|cookie| {
+            format!("{:s}={:s}", cookie.name, cookie.value)
+        }).collect();
+        Some(pairs.connect("; "))
+    }
+}
+
+#[cfg(test)]
+fn url(string: &str) -> Url {
+    use std::from_str::FromStr;
+    FromStr::from_str(string).unwrap()
+}
+
+#[test]
+fn test_domain_and_path_match() {
+    assert!(domain_match("www.example.com", "example.com"));
+    assert!(domain_match("example.com", "example.com"));
+    assert!(!domain_match("badexample.com", "example.com"));
+    assert!(!domain_match("10.0.0.1", "0.0.1"));
+
+    assert!(path_match("/docs/index.html", "/docs"));
+    assert!(path_match("/docs/", "/docs/"));
+    assert!(!path_match("/documents", "/docs"));
+
+    assert_eq!(default_path("/docs/index.html"), ~"/docs");
+    assert_eq!(default_path("/index.html"), ~"/");
+    assert_eq!(default_path(""), ~"/");
+}
+
+#[test]
+fn test_parse() {
+    let request_url = url("http://www.example.com/docs/index.html");
+
+    let cookie = Cookie::parse("id=a3fWa; Max-Age=60; Domain=.Example.com; Secure; HttpOnly",
+                               &request_url, HTTP, 1000).unwrap();
+    assert_eq!(cookie.name, ~"id");
+    assert_eq!(cookie.value, ~"a3fWa");
+    assert_eq!(cookie.domain, ~"example.com");
+    assert_eq!(cookie.path, ~"/docs");
+    assert_eq!(cookie.expiry_time, Some(1060));
+    assert!(!cookie.host_only && cookie.secure_only && cookie.http_only);
+
+    let cookie = Cookie::parse("lang=en", &request_url, NonHTTP, 1000).unwrap();
+    assert_eq!(cookie.domain, ~"www.example.com");
+    assert!(cookie.host_only);
+    assert_eq!(cookie.expiry_time, None);
+
+    assert!(Cookie::parse("novalue", &request_url, HTTP, 0).is_none());
+    assert!(Cookie::parse("a=b; Domain=other.com", &request_url, HTTP, 0).is_none());
+    assert!(Cookie::parse("a=b; HttpOnly", &request_url, NonHTTP, 0).is_none());
+}
+
+#[test]
+fn test_parse_cookie_date() {
+    assert_eq!(parse_cookie_date("Thu, 01 Jan 1970 00:00:01 GMT"), Some(1));
+    assert_eq!(parse_cookie_date("Thu, 01-Jan-1970 00:00:01 GMT"), Some(1));
+    assert_eq!(parse_cookie_date("Sunday, 06-Nov-94 08:49:37 GMT"), Some(784111777));
+    assert_eq!(parse_cookie_date("Sun Nov  6 08:49:37 1994"), Some(784111777));
+    assert_eq!(parse_cookie_date("Wed, 09 Jun 2021 10:18:14 GMT"), Some(1623233894));
+    assert_eq!(parse_cookie_date("Wed, 09-Jun-21 10:18:14 GMT"), Some(1623233894));
+    assert_eq!(parse_cookie_date("29 February 2000 00:00:00"), Some(951782400));
+
+    assert_eq!(parse_cookie_date("Thu, 01 Jan 1970"), None);
+    assert_eq!(parse_cookie_date("Thu, 32 Jan 1970 00:00:00 GMT"), None);
+    assert_eq!(parse_cookie_date("Sat, 29 Feb 2003 00:00:00 GMT"), None);
+    assert_eq!(parse_cookie_date("Thu, 01 Jan 1970 24:00:00 GMT"), None);
+    assert_eq!(parse_cookie_date("Mon, 01 Jan 1600 00:00:00 GMT"), None);
+}
+
+#[test]
+fn test_public_suffix_domains() {
+    let request_url = url("http://www.example.co.uk/");
+    assert!(Cookie::parse("a=b; Domain=uk", &request_url, HTTP, 0).is_none());
+    assert!(Cookie::parse("a=b; Domain=.co.uk", &request_url, HTTP, 0).is_none());
+    let cookie = Cookie::parse("a=b; Domain=example.co.uk", &request_url, HTTP, 0).unwrap();
+    assert!(!cookie.host_only);
+
+    assert!(Cookie::parse("a=b; Domain=com", &url("http://example.com/"), HTTP, 0).is_none());
+
+    // A host that is itself a public suffix gets a host-only cookie.
+    let cookie = Cookie::parse("a=b; Domain=localhost", &url("http://localhost/"), HTTP, 0)
+        .unwrap();
+    assert!(cookie.host_only);
+    assert_eq!(cookie.domain, ~"localhost");
+}
+
+#[test]
+fn test_cookie_jar() {
+    let mut jar = CookieJar::new();
+    let request_url = url("http://www.example.com/docs/index.html");
+
+    jar.set_cookie_from_header(&request_url, "a=1; Path=/", HTTP, 0);
+    jar.set_cookie_from_header(&request_url, "b=2", HTTP, 1);
+    jar.set_cookie_from_header(&request_url, "c=3; Path=/; HttpOnly", HTTP, 2);
+    jar.set_cookie_from_header(&request_url, "d=4; Path=/; Secure", HTTP, 3);
+    assert_eq!(jar.cookies_for_url(&request_url, HTTP, 10), Some(~"b=2; a=1; c=3"));
+    assert_eq!(jar.cookies_for_url(&request_url, NonHTTP, 10), Some(~"b=2; a=1"));
+    assert_eq!(jar.cookies_for_url(&url("http://example.com/"), HTTP, 10), None);
+
+    // Script can't clobber HttpOnly cookies, but servers can replace and delete cookies.
+    jar.set_cookie_from_header(&request_url, "c=evil; Path=/", NonHTTP, 11);
+    jar.set_cookie_from_header(&request_url, "a=5; Path=/", HTTP, 12);
+    jar.set_cookie_from_header(&request_url, "b=; Max-Age=0", HTTP, 13);
+    assert_eq!(jar.cookies_for_url(&request_url, HTTP, 14), Some(~"a=5; c=3"));
+
+    jar.set_cookie_from_header(&request_url, "a=; Path=/; Expires=Thu, 01-Jan-1970 00:00:01 GMT",
+                               HTTP, 15);
+    assert_eq!(jar.cookies_for_url(&request_url, HTTP, 16), Some(~"c=3"));
+
+    jar.set_cookie_from_header(&request_url, "a=5; Path=/", HTTP, 17);
+    jar.set_cookie_from_header(&request_url, "e=6; Path=/; Max-Age=10", HTTP, 20);
+    assert_eq!(jar.cookies_for_url(&request_url, NonHTTP, 30), Some(~"a=5"));
+}
@@ -2,24 +2,25 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use resource_task::{Done, Payload, Metadata, LoadResponse, LoaderTask, start_sending};
+use resource_task::{Done, Payload, Metadata, LoadData, LoadResponse, LoaderTask, start_sending};
+use resource_task::{LoadError, InvalidDataUrl, InvalidBase64};
 
-use extra::url::Url;
 use extra::base64::FromBase64;
 
 use http::headers::test_utils::from_stream_with_str;
 use http::headers::content_type::MediaType;
 
 pub fn factory() -> LoaderTask {
-    proc(url, start_chan) {
+    proc(load_data, start_chan) {
         // NB: we don't spawn a new task.
         // Hypothesis: data URLs are too small for parallel base64 etc. to be worth it.
         // Should be tested at some point.
-        load(url, start_chan)
+        load(load_data, start_chan)
     }
 }
 
-fn load(url: Url, start_chan: Chan<LoadResponse>) {
+fn load(load_data: LoadData, start_chan: Chan<LoadResponse>) {
+    let url = load_data.url;
     assert!("data" == url.scheme);
 
     let mut metadata = Metadata::default(url.clone());
@@ -27,7 +28,7 @@ fn load(url: Url, start_chan: Chan<LoadResponse>) {
     // Split out content type and data.
     let parts: ~[&str] = url.path.splitn(',', 1).to_owned_vec();
     if parts.len() != 2 {
-        start_sending(start_chan, metadata).send(Done(Err(())));
+        start_sending(start_chan, metadata).send(Done(Err(InvalidDataUrl)));
         return;
     }
 
@@ -50,7 +51,7 @@ fn load(url: Url, start_chan: Chan<LoadResponse>) {
     if is_base64 {
         match parts[1].from_base64() {
             Err(..) => {
-                progress_chan.send(Done(Err(())));
+                progress_chan.send(Done(Err(InvalidBase64)));
             }
             Ok(data) => {
                 progress_chan.send(Payload(data));
@@ -69,11 +70,11 @@ fn load(url: Url, start_chan: Chan<LoadResponse>) {
 fn assert_parse(url:          &'static str,
                 content_type: Option<(~str, ~str)>,
                 charset:      Option<~str>,
-                data:         Option<~[u8]>) {
+                data:         Result<~[u8], LoadError>) {
     use std::from_str::FromStr;
 
     let (start_port, start_chan) = Chan::new();
-    load(FromStr::from_str(url).unwrap(), start_chan);
+    load(LoadData::new(FromStr::from_str(url).unwrap()), start_chan);
 
     let response = start_port.recv();
     assert_eq!(&response.metadata.content_type, &content_type);
@@ -82,10 +83,10 @@ fn assert_parse(url:          &'static str,
     let progress = response.progress_port.recv();
 
     match data {
-        None => {
-            assert_eq!(progress, Done(Err(())));
+        Err(error) => {
+            assert_eq!(progress, Done(Err(error)));
         }
-        Some(dat) => {
+        Ok(dat) => {
             assert_eq!(progress, Payload(dat));
             assert_eq!(response.progress_port.recv(), Done(Ok(())));
         }
@@ -94,40 +95,45 @@ fn assert_parse(url:          &'static str,
 
 #[test]
 fn empty_invalid() {
-    assert_parse("data:", None, None, None);
+    assert_parse("data:", None, None, Err(InvalidDataUrl));
 }
 
 #[test]
 fn plain() {
-    assert_parse("data:,hello%20world", None, None, Some(bytes!("hello world").into_owned()));
+    assert_parse("data:,hello%20world", None, None, Ok(bytes!("hello world").into_owned()));
 }
 
 #[test]
 fn plain_ct() {
     assert_parse("data:text/plain,hello",
-        Some((~"text", ~"plain")), None, Some(bytes!("hello").into_owned()));
+        Some((~"text", ~"plain")), None, Ok(bytes!("hello").into_owned()));
 }
 
 #[test]
 fn plain_charset() {
     assert_parse("data:text/plain;charset=latin1,hello",
-        Some((~"text", ~"plain")), Some(~"latin1"), Some(bytes!("hello").into_owned()));
+        Some((~"text", ~"plain")), Some(~"latin1"), Ok(bytes!("hello").into_owned()));
 }
 
 #[test]
 fn base64() {
-    assert_parse("data:;base64,C62+7w==", None, None, Some(~[0x0B, 0xAD, 0xBE, 0xEF]));
+    assert_parse("data:;base64,C62+7w==", None, None, Ok(~[0x0B, 0xAD, 0xBE, 0xEF]));
 }
 
 #[test]
 fn base64_ct() {
     assert_parse("data:application/octet-stream;base64,C62+7w==",
-        Some((~"application", ~"octet-stream")), None, Some(~[0x0B, 0xAD, 0xBE, 0xEF]));
+        Some((~"application", ~"octet-stream")), None, Ok(~[0x0B, 0xAD, 0xBE, 0xEF]));
 }
 
 #[test]
 fn base64_charset() {
     assert_parse("data:text/plain;charset=koi8-r;base64,8PLl9+XkIO3l5Pfl5A==",
         Some((~"text", ~"plain")), Some(~"koi8-r"),
-        Some(~[0xF0, 0xF2, 0xE5, 0xF7, 0xE5, 0xE4, 0x20, 0xED, 0xE5, 0xE4, 0xF7, 0xE5, 0xE4]));
+        Ok(~[0xF0, 0xF2, 0xE5, 0xF7, 0xE5, 0xE4, 0x20, 0xED, 0xE5, 0xE4, 0xF7, 0xE5, 0xE4]));
+}
+
+#[test]
+fn base64_invalid() {
+    assert_parse("data:;base64,!!!!", None, None, Err(InvalidBase64));
 }
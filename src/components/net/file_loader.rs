@@ -3,6 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use resource_task::{ProgressMsg, Metadata, Payload, Done, LoaderTask, start_sending};
+use resource_task::{LoadError, FileNotFound, FileReadError};
 use servo_util::io::result;
 
 use std::io;
@@ -12,7 +13,7 @@ use servo_util::task::spawn_named;
 static READ_SIZE: uint = 1024;
 
 fn read_all(reader: &mut io::Stream, progress_chan: &SharedChan<ProgressMsg>)
-        -> Result<(), ()> {
+        -> Result<(), LoadError> {
     loop {
         match (result(|| {
             let data = reader.read_bytes(READ_SIZE);
@@ -21,26 +22,33 @@ fn read_all(reader: &mut io::Stream, progress_chan: &SharedChan<ProgressMsg>)
             Ok(()) => (),
             Err(e) => match e.kind {
                 io::EndOfFile => return Ok(()),
-                _ => return Err(()),
+                _ => return Err(FileReadError(e.desc.to_owned())),
             }
         }
     }
 }
 
 pub fn factory() -> LoaderTask {
-    let f: LoaderTask = proc(url, start_chan) {
+    let f: LoaderTask = proc(load_data, start_chan) {
+        let url = load_data.url;
         assert!("file" == url.scheme);
         let progress_chan = start_sending(start_chan, Metadata::default(url.clone()));
         spawn_named("file_loader", proc() {
-            // ignore_io_error causes us to get None instead of a task failure.
-            let _guard = io::ignore_io_error();
-            match File::open_mode(&Path::new(url.path), io::Open, io::Read) {
-                Some(ref mut reader) => {
+            // Trapping the error gets us None instead of a task failure.
+            match result(|| File::open_mode(&Path::new(url.path.clone()), io::Open, io::Read)) {
+                Ok(Some(ref mut reader)) => {
                     let res = read_all(reader as &mut io::Stream, &progress_chan);
                     progress_chan.send(Done(res));
                 }
-                None => {
-                    progress_chan.send(Done(Err(())));
+                Ok(None) => {
+                    progress_chan.send(Done(Err(FileReadError(~"couldn't open file"))));
+                }
+                Err(e) => {
+                    let error = match e.kind {
+                        io::FileNotFound => FileNotFound(url.path.clone()),
+                        _ => FileReadError(e.desc.to_owned()),
+                    };
+                    progress_chan.send(Done(Err(error)));
                 }
             };
         });
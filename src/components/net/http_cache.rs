@@ -0,0 +1,453 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! An HTTP cache shared between the resource manager and the HTTP loader tasks.
+//!
+//! Responses are kept in memory and, if a cache directory is given, mirrored to disk so that
+//! they survive a restart. Freshness is computed from `Cache-Control` and `Expires` (falling
+//! back to the `Last-Modified` heuristic of RFC 2616 §13.2.4), and stale entries carrying an
+//! `ETag` or `Last-Modified` validator are revalidated with a conditional request.
+
+use resource_task::{Metadata, Payload, Done, LoadResponse, start_sending};
+
+use std::ascii::StrAsciiExt;
+use std::hash::Hash;
+use std::io;
+use std::io::File;
+use std::io::fs;
+use std::str;
+use extra::arc::MutexArc;
+use extra::time;
+use extra::url::Url;
+use servo_util::url::{UrlMap, url_map};
+
+/// Handle to the cache, shared between the resource manager and the loader tasks.
+pub type HttpCacheHandle = MutexArc<HttpCache>;
+
+/// The validators used to revalidate a stale entry with a conditional request.
+#[deriving(Clone, Eq)]
+pub struct CacheValidators {
+    /// Sent back as `If-None-Match`.
+    etag: Option<~str>,
+    /// Sent back as `If-Modified-Since`.
+    last_modified: Option<~str>,
+}
+
+impl CacheValidators {
+    /// Extracts the validators from a list of response headers.
+    pub fn from_headers(headers: &[(~str, ~str)]) -> CacheValidators {
+        CacheValidators {
+            etag: find_header(headers, "etag").map(|v| v.to_owned()),
+            last_modified: find_header(headers, "last-modified").map(|v| v.to_owned()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+
+    /// The request headers that make a request conditional on these validators.
+    pub fn conditional_headers(&self) -> ~[(~str, ~str)] {
+        let mut headers = ~[];
+        for etag in self.etag.iter() {
+            headers.push((~"If-None-Match", etag.clone()));
+        }
+        for last_modified in self.last_modified.iter() {
+            headers.push((~"If-Modified-Since", last_modified.clone()));
+        }
+        headers
+    }
+}
+
+/// A response stored in the cache.
+#[deriving(Clone)]
+pub struct CachedResponse {
+    metadata: Metadata,
+    body: ~[u8],
+    validators: CacheValidators,
+    /// Time, in seconds since the epoch, from which the entry must be revalidated before use.
+    expires_at: i64,
+}
+
+/// The result of looking up a URL in the cache.
+pub enum CacheLookup {
+    /// The entry may be used without contacting the server.
+    Fresh(CachedResponse),
+    /// The entry may only be used once the server confirms it is unchanged.
+    Stale(CachedResponse),
+    Miss,
+}
+
+pub struct HttpCache {
+    /// The in-memory store.
+    entries: UrlMap<CachedResponse>,
+    /// Directory in which entries are mirrored, if the cache is persistent.
+    disk_dir: Option<Path>,
+}
+
+impl HttpCache {
+    pub fn new(disk_dir: Option<Path>) -> HttpCache {
+        for dir in disk_dir.iter() {
+            let _guard = io::ignore_io_error();
+            if !dir.exists() {
+                fs::mkdir_recursive(dir, 0o755);
+            }
+        }
+        HttpCache {
+            entries: url_map(),
+            disk_dir: disk_dir,
+        }
+    }
+
+    /// Looks up `url`, falling back to the on-disk store if it is not in memory.
+    pub fn lookup(&mut self, url: &Url, now: i64) -> CacheLookup {
+        if !self.entries.contains_key(url) {
+            match self.read_from_disk(url) {
+                Some(entry) => {
+                    self.entries.insert(url.clone(), entry);
+                }
+                None => return Miss,
+            }
+        }
+
+        let entry = self.entries.get(url).clone();
+        if now < entry.expires_at {
+            Fresh(entry)
+        } else if !entry.validators.is_empty() {
+            Stale(entry)
+        } else {
+            // Nothing to revalidate with, so the entry is useless.
+            self.evict(url);
+            Miss
+        }
+    }
+
+    pub fn store(&mut self, url: &Url, entry: CachedResponse) {
+        debug!("http_cache: storing {:s} until {}", url.to_str(), entry.expires_at);
+        self.write_to_disk(url, &entry);
+        self.entries.insert(url.clone(), entry);
+    }
+
+    /// Marks a revalidated entry as fresh until `expires_at`.
+    pub fn refresh(&mut self, url: &Url, expires_at: i64) {
+        let entry = match self.entries.find_mut(url) {
+            Some(entry) => {
+                entry.expires_at = expires_at;
+                entry.clone()
+            }
+            None => return,
+        };
+        self.write_to_disk(url, &entry);
+    }
+
+    pub fn evict(&mut self, url: &Url) {
+        self.entries.remove(url);
+        for path in self.disk_path(url).iter() {
+            let _guard = io::ignore_io_error();
+            fs::unlink(path);
+        }
+    }
+
+    fn disk_path(&self, url: &Url) -> Option<Path> {
+        self.disk_dir.as_ref().map(|dir| dir.join(format!("{:x}", url.to_str().hash())))
+    }
+
+    /// The on-disk format is seven lines (URL, expiry time, ETag, Last-Modified, MIME type,
+    /// charset, with empty lines for absent values, and the number of response headers), then
+    /// one `name: value` line per response header, then the raw body. Only 200 responses are
+    /// stored, so the status line is not written. Cookies are never written to disk.
+    fn write_to_disk(&self, url: &Url, entry: &CachedResponse) {
+        let path = match self.disk_path(url) {
+            Some(path) => path,
+            None => return,
+        };
+        let content_type = match entry.metadata.content_type {
+            Some((ref type_, ref subtype)) => format!("{:s}/{:s}", *type_, *subtype),
+            None => ~"",
+        };
+        let response_headers = match entry.metadata.headers {
+            Some(ref headers) => persistable_headers(*headers),
+            None => ~[],
+        };
+        let mut header = format!("{:s}\n{}\n{:s}\n{:s}\n{:s}\n{:s}\n{}\n",
+                                 url.to_str(),
+                                 entry.expires_at,
+                                 entry.validators.etag.clone().unwrap_or(~""),
+                                 entry.validators.last_modified.clone().unwrap_or(~""),
+                                 content_type,
+                                 entry.metadata.charset.clone().unwrap_or(~""),
+                                 response_headers.len());
+        for &(ref name, ref value) in response_headers.iter() {
+            header.push_str(format!("{:s}: {:s}\n", *name, *value));
+        }
+
+        let _guard = io::ignore_io_error();
+        match File::create(&path) {
+            Some(ref mut file) => {
+                file.write(header.as_bytes());
+                file.write(entry.body);
+            }
+            None => debug!("http_cache: couldn't write {:s}", path.display().to_str()),
+        }
+    }
+
+    fn read_from_disk(&self, url: &Url) -> Option<CachedResponse> {
+        let path = match self.disk_path(url) {
+            Some(path) => path,
+            None => return None,
+        };
+        let bytes = {
+            let _guard = io::ignore_io_error();
+            match File::open(&path) {
+                Some(ref mut file) => file.read_to_end(),
+                None => return None,
+            }
+        };
+
+        // Split off the fixed lines, then the response headers.
+        let mut lines = ~[];
+        let mut start = 0;
+        let mut line_count = 7;
+        while lines.len() < line_count {
+            match bytes.slice_from(start).position_elem(&('\n' as u8)) {
+                Some(len) => {
+                    match str::from_utf8_opt(bytes.slice(start, start + len)) {
+                        Some(line) => lines.push(line.to_owned()),
+                        None => {
+                            // The entry is corrupt, so drop it.
+                            let _guard = io::ignore_io_error();
+                            fs::unlink(&path);
+                            return None
+                        }
+                    }
+                    start += len + 1;
+                }
+                None => return None,
+            }
+            if lines.len() == 7 {
+                match from_str::<uint>(lines[6]) {
+                    Some(header_count) => line_count = 7 + header_count,
+                    None => return None,
+                }
+            }
+        }
+
+        // Guard against hash collisions.
+        if lines[0] != url.to_str() {
+            return None
+        }
+
+        let optional = |s: &~str| if s.is_empty() { None } else { Some(s.clone()) };
+        let mut metadata = Metadata::default(url.clone());
+        metadata.content_type = optional(&lines[4]).and_then(|content_type| {
+            let parts: ~[&str] = content_type.splitn('/', 1).collect();
+            if parts.len() == 2 {
+                Some((parts[0].to_owned(), parts[1].to_owned()))
+            } else {
+                None
+            }
+        });
+        metadata.charset = optional(&lines[5]);
+        metadata.status = Some((200, ~"OK"));
+        metadata.headers = Some(lines.slice_from(7).iter().filter_map(|line| {
+            line.find_str(": ").map(|i| (line.slice_to(i).to_owned(), line.slice_from(i + 2).to_owned()))
+        }).collect());
+
+        from_str(lines[1]).map(|expires_at| {
+            CachedResponse {
+                metadata: metadata.clone(),
+                body: bytes.slice_from(start).to_owned(),
+                validators: CacheValidators {
+                    etag: optional(&lines[2]),
+                    last_modified: optional(&lines[3]),
+                },
+                expires_at: expires_at,
+            }
+        })
+    }
+}
+
+/// The `Cache-Control` directives that affect a private cache.
+#[deriving(Eq)]
+pub struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    max_age: Option<i64>,
+}
+
+pub fn parse_cache_control(value: &str) -> CacheControl {
+    let mut cache_control = CacheControl {
+        no_store: false,
+        no_cache: false,
+        max_age: None,
+    };
+    for directive in value.split(',') {
+        let directive = directive.trim();
+        let (name, argument) = match directive.find('=') {
+            Some(i) => (directive.slice_to(i).trim(),
+                        Some(directive.slice_from(i + 1).trim().trim_chars(&'"'))),
+            None => (directive, None),
+        };
+        match name.to_ascii_lower().as_slice() {
+            "no-store" => cache_control.no_store = true,
+            "no-cache" => cache_control.no_cache = true,
+            "max-age" => cache_control.max_age = argument.and_then(from_str),
+            _ => (),
+        }
+    }
+    cache_control
+}
+
+/// Parses an RFC 1123 date, as used by `Date`, `Expires` and `Last-Modified`, into seconds
+/// since the epoch.
+pub fn parse_http_date(value: &str) -> Option<i64> {
+    match time::strptime(value.trim(), "%a, %d %b %Y %H:%M:%S %Z") {
+        Ok(tm) => Some(tm.to_timespec().sec),
+        Err(_) => None,
+    }
+}
+
+/// Returns the time from which a response with the given headers, received at `now`, is stale,
+/// or `None` if the response must not be stored at all.
+pub fn expiry_time(headers: &[(~str, ~str)], now: i64) -> Option<i64> {
+    let cache_control = find_header(headers, "cache-control").map(parse_cache_control);
+    match cache_control {
+        Some(CacheControl { no_store: true, .. }) => return None,
+        Some(CacheControl { no_cache: true, .. }) => return Some(now),
+        Some(CacheControl { max_age: Some(max_age), .. }) => return Some(now + max_age),
+        _ => (),
+    }
+
+    // Expires and Last-Modified are relative to the server's clock.
+    let date = find_header(headers, "date").and_then(parse_http_date).unwrap_or(now);
+    match find_header(headers, "expires") {
+        // An invalid Expires value means "already expired".
+        Some(expires) => return Some(match parse_http_date(expires) {
+            Some(expires) => now + expires - date,
+            None => now,
+        }),
+        None => (),
+    }
+
+    match find_header(headers, "last-modified").and_then(parse_http_date) {
+        Some(last_modified) if last_modified < date => Some(now + (date - last_modified) / 10),
+        _ => Some(now),
+    }
+}
+
+/// Case-insensitively finds the value of the first header called `name`.
+pub fn find_header<'a>(headers: &'a [(~str, ~str)], name: &str) -> Option<&'a str> {
+    for &(ref header_name, ref value) in headers.iter() {
+        if header_name.eq_ignore_ascii_case(name) {
+            return Some(value.as_slice())
+        }
+    }
+    None
+}
+
+/// Returns `headers` without the ones that set cookies, which must not be persisted.
+fn persistable_headers(headers: &[(~str, ~str)]) -> ~[(~str, ~str)] {
+    headers.iter().filter(|&&(ref name, _)| {
+        !name.eq_ignore_ascii_case("set-cookie") && !name.eq_ignore_ascii_case("set-cookie2")
+    }).map(|header| header.clone()).collect()
+}
+
+/// Responds to a load with a stored response, without touching the network.
+pub fn send_cached_response(entry: CachedResponse, start_chan: Chan<LoadResponse>) {
+    let mut metadata = entry.metadata;
+    metadata.cached = true;
+    let progress_chan = start_sending(start_chan, metadata);
+    progress_chan.send(Payload(entry.body));
+    progress_chan.send(Done(Ok(())));
+}
+
+#[cfg(test)]
+fn headers(list: &[(&str, &str)]) -> ~[(~str, ~str)] {
+    list.iter().map(|&(name, value)| (name.to_owned(), value.to_owned())).collect()
+}
+
+#[cfg(test)]
+fn entry(url: &Url, validators: CacheValidators, expires_at: i64) -> CachedResponse {
+    CachedResponse {
+        metadata: Metadata::default(url.clone()),
+        body: ~[1, 2, 3],
+        validators: validators,
+        expires_at: expires_at,
+    }
+}
+
+#[test]
+fn test_parse_cache_control() {
+    assert_eq!(parse_cache_control("no-store"),
+               CacheControl { no_store: true, no_cache: false, max_age: None });
+    assert_eq!(parse_cache_control("private, Max-Age=\"60\", no-cache"),
+               CacheControl { no_store: false, no_cache: true, max_age: Some(60) });
+    assert_eq!(parse_cache_control("max-age=bogus"),
+               CacheControl { no_store: false, no_cache: false, max_age: None });
+}
+
+#[test]
+fn test_expiry_time() {
+    let now = 1000;
+    assert_eq!(expiry_time(headers([("Cache-Control", "no-store")]), now), None);
+    assert_eq!(expiry_time(headers([("Cache-Control", "no-cache, max-age=60")]), now), Some(now));
+    assert_eq!(expiry_time(headers([("cache-control", "max-age=60"),
+                                    ("Expires", "Thu, 01 Jan 1970 00:00:00 GMT")]), now),
+               Some(now + 60));
+    assert_eq!(expiry_time(headers([("Date", "Thu, 01 Jan 1970 00:01:00 GMT"),
+                                    ("Expires", "Thu, 01 Jan 1970 00:03:00 GMT")]), now),
+               Some(now + 120));
+    assert_eq!(expiry_time(headers([("Expires", "0")]), now), Some(now));
+    assert_eq!(expiry_time(headers([("Date", "Thu, 01 Jan 1970 00:16:40 GMT"),
+                                    ("Last-Modified", "Thu, 01 Jan 1970 00:00:00 GMT")]), now),
+               Some(now + 100));
+    assert_eq!(expiry_time(headers([]), now), Some(now));
+}
+
+#[test]
+fn test_lookup() {
+    use std::from_str::FromStr;
+
+    let url: Url = FromStr::from_str("http://example.com/style.css").unwrap();
+    let etag = CacheValidators { etag: Some(~"\"abc\""), last_modified: None };
+    let none = CacheValidators { etag: None, last_modified: None };
+    let mut cache = HttpCache::new(None);
+
+    match cache.lookup(&url, 0) { Miss => (), _ => fail!("expected a miss") }
+
+    cache.store(&url, entry(&url, etag.clone(), 10));
+    match cache.lookup(&url, 5) { Fresh(e) => assert_eq!(e.body, ~[1, 2, 3]), _ => fail!() }
+    match cache.lookup(&url, 10) { Stale(e) => assert!(e.validators == etag), _ => fail!() }
+
+    cache.refresh(&url, 20);
+    match cache.lookup(&url, 15) { Fresh(..) => (), _ => fail!("expected a refreshed entry") }
+
+    // Stale entries without validators are dropped.
+    cache.store(&url, entry(&url, none, 10));
+    match cache.lookup(&url, 10) { Miss => (), _ => fail!("expected a miss") }
+    assert!(!cache.entries.contains_key(&url));
+}
+
+#[test]
+fn test_conditional_headers() {
+    let validators = CacheValidators::from_headers(headers([
+        ("ETag", "\"abc\""),
+        ("Last-Modified", "Thu, 01 Jan 1970 00:00:00 GMT"),
+    ]));
+    assert_eq!(validators.conditional_headers(),
+               ~[(~"If-None-Match", ~"\"abc\""),
+                 (~"If-Modified-Since", ~"Thu, 01 Jan 1970 00:00:00 GMT")]);
+}
+
+#[test]
+fn test_persistable_headers() {
+    let response_headers = headers([
+        ("Content-Type", "text/html"),
+        ("Set-Cookie", "id=1"),
+        ("set-cookie2", "id=2"),
+        ("ETag", "\"abc\""),
+    ]);
+    assert_eq!(persistable_headers(response_headers),
+               headers([("Content-Type", "text/html"), ("ETag", "\"abc\"")]));
+}
@@ -2,64 +2,168 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use resource_task::{Metadata, Payload, Done, LoadResponse, LoaderTask, start_sending};
+use resource_task::{Metadata, Payload, Done, LoadData, LoadResponse, LoaderTask, start_sending};
+use resource_task::{LoadError, DnsFailure, ConnectionFailed, TooManyRedirects, RedirectLoop};
+use http_cache::{HttpCacheHandle, CachedResponse, CacheValidators, Fresh, Stale, Miss};
+use http_cache::{expiry_time, send_cached_response};
+use cookie::{CookieJarHandle, HTTP};
 
+use std::ascii::StrAsciiExt;
 use std::vec;
 use std::hashmap::HashSet;
+use extra::time;
 use extra::url::Url;
 use http::client::RequestWriter;
-use http::method::Get;
+use http::method::{Method, Get, Head, Post, Put, Delete, Patch};
 use http::headers::HeaderEnum;
+use http::headers::request::ExtensionHeader;
 use std::io::Reader;
+use std::io::net::addrinfo::get_host_addresses;
 use servo_util::task::spawn_named;
 
-pub fn factory() -> LoaderTask {
-    let f: LoaderTask = proc(url, start_chan) {
-        spawn_named("http_loader", proc() load(url, start_chan))
+/// A loader that sends and stores cookies in `cookie_jar`, and serves responses from and
+/// stores them in `http_cache` if there is one.
+pub fn factory(http_cache: Option<HttpCacheHandle>, cookie_jar: CookieJarHandle) -> LoaderTask {
+    let f: LoaderTask = proc(load_data, start_chan) {
+        spawn_named("http_loader", proc() load(load_data, start_chan, http_cache, cookie_jar))
     };
     f
 }
 
-fn send_error(url: Url, start_chan: Chan<LoadResponse>) {
-    start_sending(start_chan, Metadata::default(url)).send(Done(Err(())));
+fn send_error(url: Url, error: LoadError, start_chan: Chan<LoadResponse>) {
+    info!("error loading {:s}: {:s}", url.to_str(), error.to_str());
+    start_sending(start_chan, Metadata::default(url)).send(Done(Err(error)));
 }
 
-fn load(mut url: Url, start_chan: Chan<LoadResponse>) {
+/// Works out why a request to `url` couldn't be completed. Resolving the host again is only
+/// worth it because this is the slow path.
+fn connection_error(url: &Url) -> LoadError {
+    match get_host_addresses(url.host) {
+        Some(ref addresses) if !addresses.is_empty() => ConnectionFailed(url.host.clone()),
+        _ => DnsFailure(url.host.clone()),
+    }
+}
+
+/// The method to use, and whether to keep the request body, when following a redirect with
+/// the given status code.
+fn method_for_redirect(code: u16, method: Method) -> (Method, bool) {
+    match (code, method) {
+        // Browsers turn a POST that got a 301 or 302 into a GET, even though RFC 2616 says not
+        // to; everyone depends on it.
+        (301, Post) | (302, Post) => (Get, false),
+        (303, Head) => (Head, false),
+        (303, _) => (Get, false),
+        (_, method) => (method, true),
+    }
+}
+
+/// Whether a request with `method` may be answered from the cache. Only GET responses are
+/// stored, but a HEAD can be answered with the headers of one.
+fn is_cacheable_method(method: &Method) -> bool {
+    match *method {
+        Get | Head => true,
+        _ => false,
+    }
+}
+
+/// Whether a request with `method` invalidates what the cache holds for its URL. Safe methods
+/// such as HEAD, OPTIONS and TRACE don't (RFC 2616 §13.10).
+fn invalidates_cache(method: &Method) -> bool {
+    match *method {
+        Post | Put | Delete | Patch => true,
+        _ => false,
+    }
+}
+
+/// Sends a cached response for a request with `method`. HEAD gets the headers only.
+fn send_cached_response_for_method(entry: CachedResponse,
+                                   method: &Method,
+                                   start_chan: Chan<LoadResponse>) {
+    let mut entry = entry;
+    match *method {
+        Head => entry.body = ~[],
+        _ => (),
+    }
+    send_cached_response(entry, start_chan)
+}
+
+fn load(mut load_data: LoadData, start_chan: Chan<LoadResponse>,
+        http_cache: Option<HttpCacheHandle>, cookie_jar: CookieJarHandle) {
     // FIXME: At the time of writing this FIXME, servo didn't have any central
     //        location for configuration. If you're reading this and such a
     //        repository DOES exist, please update this constant to use it.
     let max_redirects = 50u;
     let mut iters = 0u;
 
+    // A POST that redirects back to its own URL as a GET is not a loop, so the method is
+    // part of the key.
     let mut redirected_to = HashSet::new();
 
     // Loop to handle redirects.
     loop {
         iters = iters + 1;
+        let url = load_data.url.clone();
 
         if iters > max_redirects {
-            info!("too many redirects");
-            send_error(url, start_chan);
+            send_error(url, TooManyRedirects(max_redirects), start_chan);
             return;
         }
 
-        if redirected_to.contains(&url) {
-            info!("redirect loop");
-            send_error(url, start_chan);
+        let request_key = (load_data.method.to_str(), url.clone());
+        if redirected_to.contains(&request_key) {
+            send_error(url.clone(), RedirectLoop(url.to_str()), start_chan);
             return;
         }
 
-        redirected_to.insert(url.clone());
+        redirected_to.insert(request_key);
+
+        let cacheable = is_cacheable_method(&load_data.method);
+        if invalidates_cache(&load_data.method) {
+            for http_cache in http_cache.iter() {
+                http_cache.access(|cache| cache.evict(&url));
+            }
+        }
 
         assert!("http" == url.scheme);
 
-        info!("requesting {:s}", url.to_str());
+        // A stale entry is kept around in case the server tells us it is still valid.
+        let mut stale_entry: Option<CachedResponse> = None;
+        for http_cache in http_cache.iter().filter(|_| cacheable) {
+            match http_cache.access(|cache| cache.lookup(&url, time::get_time().sec)) {
+                Fresh(entry) => {
+                    info!("serving {:s} from the cache", url.to_str());
+                    send_cached_response_for_method(entry, &load_data.method, start_chan);
+                    return;
+                }
+                Stale(entry) => stale_entry = Some(entry),
+                Miss => (),
+            }
+        }
+
+        info!("requesting {:s} {:s}", load_data.method.to_str(), url.to_str());
 
-        let request = ~RequestWriter::new(Get, url.clone());
+        let mut request = ~RequestWriter::new(load_data.method.clone(), url.clone());
+        for &(ref name, ref value) in load_data.headers.iter() {
+            request.headers.insert(ExtensionHeader(name.clone(), value.clone()));
+        }
+        let now = time::get_time().sec;
+        match cookie_jar.access(|jar| jar.cookies_for_url(&url, HTTP, now)) {
+            Some(cookies) => request.headers.insert(ExtensionHeader(~"Cookie", cookies)),
+            None => ()
+        }
+        for entry in stale_entry.iter() {
+            for (name, value) in entry.validators.conditional_headers().move_iter() {
+                request.headers.insert(ExtensionHeader(name, value));
+            }
+        }
+        for data in load_data.data.iter() {
+            request.headers.content_length = Some(data.len());
+            request.write(data.as_slice());
+        }
         let mut response = match request.read_response() {
             Ok(r) => r,
             Err(_) => {
-                send_error(url, start_chan);
+                send_error(url.clone(), connection_error(&url), start_chan);
                 return;
             }
         };
@@ -71,21 +175,75 @@ fn load(mut url: Url, start_chan: Chan<LoadResponse>) {
                 info!(" - {:s}: {:s}", header.header_name(), header.header_value());
             });
 
+        let headers: ~[(~str, ~str)] = response.headers.iter().map(|header| {
+            (header.header_name(), header.header_value())
+        }).collect();
+        let now = time::get_time().sec;
+
+        // Redirects and 304s can set cookies too.
+        for &(ref name, ref value) in headers.iter() {
+            if name.eq_ignore_ascii_case("set-cookie") {
+                cookie_jar.access(|jar| jar.set_cookie_from_header(&url, *value, HTTP, now));
+            }
+        }
+
+        if 304 == response.status.code() {
+            match (stale_entry, http_cache.as_ref()) {
+                (Some(entry), Some(http_cache)) => {
+                    info!("{:s} not modified, serving it from the cache", url.to_str());
+                    let expires_at = expiry_time(headers, now).unwrap_or(now);
+                    http_cache.access(|cache| cache.refresh(&url, expires_at));
+                    send_cached_response_for_method(entry, &load_data.method, start_chan);
+                    return;
+                }
+                _ => ()
+            }
+        }
+
         if 3 == (response.status.code() / 100) {
             match response.headers.location {
                 Some(new_url) => {
                     info!("redirecting to {:s}", new_url.to_str());
-                    url = new_url;
+                    let (method, keep_body) = method_for_redirect(response.status.code(),
+                                                                  load_data.method.clone());
+                    if !keep_body {
+                        load_data.data = None;
+                        load_data.headers.retain(|&(ref name, _)| {
+                            !name.eq_ignore_ascii_case("content-type")
+                        });
+                    }
+                    load_data.method = method;
+                    load_data.url = new_url;
                     continue;
                 }
                 None => ()
             }
         }
 
-        let mut metadata = Metadata::default(url);
+        let mut metadata = Metadata::default(url.clone());
         metadata.set_content_type(&response.headers.content_type);
+        metadata.status = Some((response.status.code(), response.status.reason()));
+        metadata.headers = Some(headers.clone());
+
+        // Only complete 200 responses to GETs with something to decide their freshness by are
+        // kept.
+        let validators = CacheValidators::from_headers(headers);
+        let is_get = match load_data.method { Get => true, _ => false };
+        let expires_at = match http_cache {
+            Some(..) if is_get && 200 == response.status.code() => {
+                expiry_time(headers, now).and_then(|expires_at| {
+                    if expires_at > now || !validators.is_empty() {
+                        Some(expires_at)
+                    } else {
+                        None
+                    }
+                })
+            }
+            _ => None,
+        };
+        let mut body = ~[];
 
-        let progress_chan = start_sending(start_chan, metadata);
+        let progress_chan = start_sending(start_chan, metadata.clone());
         loop {
             let mut buf = vec::with_capacity(1024);
 
@@ -93,6 +251,9 @@ fn load(mut url: Url, start_chan: Chan<LoadResponse>) {
             match response.read(buf) {
                 Some(len) => {
                     unsafe { buf.set_len(len); }
+                    if expires_at.is_some() {
+                        body.push_all(buf);
+                    }
                     progress_chan.send(Payload(buf));
                 }
                 None => {
@@ -102,7 +263,54 @@ fn load(mut url: Url, start_chan: Chan<LoadResponse>) {
             }
         }
 
+        match (expires_at, http_cache.as_ref()) {
+            (Some(expires_at), Some(http_cache)) => {
+                let entry = CachedResponse {
+                    metadata: metadata,
+                    body: body,
+                    validators: validators,
+                    expires_at: expires_at,
+                };
+                http_cache.access(|cache| cache.store(&url, entry.clone()));
+            }
+            _ => ()
+        }
+
         // We didn't get redirected.
         break;
     }
 }
+
+#[test]
+fn test_method_for_redirect() {
+    use http::method::Put;
+
+    let is_get = |(method, keep_body): (Method, bool)| {
+        match method { Get => !keep_body, _ => false }
+    };
+    assert!(is_get(method_for_redirect(301, Post)));
+    assert!(is_get(method_for_redirect(302, Post)));
+    assert!(is_get(method_for_redirect(303, Put)));
+    match method_for_redirect(303, Head) { (Head, false) => (), _ => fail!() }
+    match method_for_redirect(307, Post) { (Post, true) => (), _ => fail!() }
+    match method_for_redirect(302, Put) { (Put, true) => (), _ => fail!() }
+}
+
+#[test]
+fn test_cache_use_for_method() {
+    use http::method::{Options, Trace};
+
+    assert!(is_cacheable_method(&Get));
+    assert!(is_cacheable_method(&Head));
+    assert!(!is_cacheable_method(&Post));
+    assert!(!is_cacheable_method(&Options));
+
+    assert!(!invalidates_cache(&Get));
+    assert!(!invalidates_cache(&Head));
+    assert!(!invalidates_cache(&Options));
+    assert!(!invalidates_cache(&Trace));
+    assert!(invalidates_cache(&Post));
+    assert!(invalidates_cache(&Put));
+    assert!(invalidates_cache(&Delete));
+    assert!(invalidates_cache(&Patch));
+}
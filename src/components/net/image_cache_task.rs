@@ -458,7 +458,7 @@ impl ImageCacheTask {
 
 fn load_image_data(url: Url, resource_task: ResourceTask) -> Result<~[u8], ()> {
     let (response_port, response_chan) = Chan::new();
-    resource_task.send(resource_task::Load(url, response_chan));
+    resource_task.send(resource_task::Load(resource_task::LoadData::new(url.clone()), response_chan));
 
     let mut image_data = ~[];
 
@@ -471,7 +471,8 @@ fn load_image_data(url: Url, resource_task: ResourceTask) -> Result<~[u8], ()> {
             resource_task::Done(result::Ok(..)) => {
                 return Ok(image_data);
             }
-            resource_task::Done(result::Err(..)) => {
+            resource_task::Done(result::Err(error)) => {
+                debug!("image_cache_task: couldn't load {:s}: {:s}", url.to_str(), error.to_str());
                 return Err(());
             }
         }
@@ -705,7 +706,7 @@ mod tests {
                     resource_task::Load(_, response) => {
                         let chan = start_sending(response, Metadata::default(parse_url("file:///fake", None)));
                         chan.send(resource_task::Payload(test_image_bin()));
-                        chan.send(resource_task::Done(Err(())));
+                        chan.send(resource_task::Done(Err(resource_task::ConnectionFailed(~"fake"))));
                         image_bin_sent_chan.send(());
                     }
                     resource_task::Exit => {
@@ -743,7 +744,7 @@ mod tests {
         let mock_resource_task = mock_resource_task(proc(response) {
             response.send(resource_task::Payload(test_image_bin()));
             // ERROR fetching image
-            response.send(resource_task::Done(Err(())));
+            response.send(resource_task::Done(Err(resource_task::ConnectionFailed(~"fake"))));
         });
 
         let image_cache_task = ImageCacheTask(mock_resource_task.clone());
@@ -773,7 +774,7 @@ mod tests {
         let mock_resource_task = mock_resource_task(proc(response) {
             response.send(resource_task::Payload(test_image_bin()));
             // ERROR fetching image
-            response.send(resource_task::Done(Err(())));
+            response.send(resource_task::Done(Err(resource_task::ConnectionFailed(~"fake"))));
         });
 
         let image_cache_task = ImageCacheTask(mock_resource_task.clone());
@@ -904,7 +905,7 @@ mod tests {
         let mock_resource_task = mock_resource_task(proc(response) {
             wait_port.recv();
             response.send(resource_task::Payload(test_image_bin()));
-            response.send(resource_task::Done(Err(())));
+            response.send(resource_task::Done(Err(resource_task::ConnectionFailed(~"fake"))));
         });
 
         let image_cache_task = ImageCacheTask(mock_resource_task.clone());
@@ -26,6 +26,8 @@ pub mod image {
 
 pub mod file_loader;
 pub mod http_loader;
+pub mod http_cache;
+pub mod cookie;
 pub mod data_loader;
 pub mod image_cache_task;
 pub mod local_image_cache;
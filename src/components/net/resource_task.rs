@@ -7,22 +7,55 @@
 use file_loader;
 use http_loader;
 use data_loader;
+use http_cache::{HttpCache, HttpCacheHandle, find_header};
+use cookie::{CookieJar, CookieJarHandle, CookieSource};
 
 use std::comm::{Chan, Port, SharedChan};
+use extra::arc::MutexArc;
+use extra::time;
 use extra::url::Url;
 use util::spawn_listener;
 use http::headers::content_type::MediaType;
+use http::method::{Method, Get};
 
 #[cfg(test)]
 use std::from_str::FromStr;
 
 pub enum ControlMsg {
     /// Request the data associated with a particular URL
-    Load(Url, Chan<LoadResponse>),
+    Load(LoadData, Chan<LoadResponse>),
+    /// Store a cookie for a URL, given in `Set-Cookie` header syntax
+    SetCookieForUrl(Url, ~str, CookieSource),
+    /// Get the value of the `Cookie` header for a URL, if any cookies apply
+    GetCookiesForUrl(Url, Chan<Option<~str>>, CookieSource),
     Exit
 }
 
+/// A request for a resource. Everything but the URL only matters to HTTP.
+#[deriving(Clone)]
+pub struct LoadData {
+    url: Url,
+    method: Method,
+    /// Request headers in addition to the ones the loader sets itself.
+    headers: ~[(~str, ~str)],
+    /// Request body.
+    data: Option<~[u8]>,
+}
+
+impl LoadData {
+    /// A plain GET of `url`.
+    pub fn new(url: Url) -> LoadData {
+        LoadData {
+            url: url,
+            method: Get,
+            headers: ~[],
+            data: None,
+        }
+    }
+}
+
 /// Metadata about a loaded resource, such as is obtained from HTTP headers.
+#[deriving(Clone)]
 pub struct Metadata {
     /// Final URL after redirects.
     final_url: Url,
@@ -32,6 +65,15 @@ pub struct Metadata {
 
     /// Character set.
     charset: Option<~str>,
+
+    /// HTTP status code and reason phrase, for HTTP responses.
+    status: Option<(u16, ~str)>,
+
+    /// Response headers in the order they were received, for HTTP responses.
+    headers: Option<~[(~str, ~str)]>,
+
+    /// Whether the response was served from the HTTP cache.
+    cached: bool,
 }
 
 impl Metadata {
@@ -41,6 +83,26 @@ impl Metadata {
             final_url:    url,
             content_type: None,
             charset:      None,
+            status:       None,
+            headers:      None,
+            cached:       false,
+        }
+    }
+
+    /// Whether this is an HTTP response with a status other than 2xx. Non-HTTP loads are
+    /// always considered successful.
+    pub fn is_http_error(&self) -> bool {
+        match self.status {
+            Some((code, _)) => code / 100 != 2,
+            None => false,
+        }
+    }
+
+    /// The value of the first response header called `name`, ignoring case.
+    pub fn header<'a>(&'a self, name: &str) -> Option<&'a str> {
+        match self.headers {
+            Some(ref headers) => find_header(*headers, name),
+            None => None,
         }
     }
 
@@ -80,7 +142,46 @@ pub enum ProgressMsg {
     /// Binary data - there may be multiple of these
     Payload(~[u8]),
     /// Indicates loading is complete, either successfully or not
-    Done(Result<(), ()>)
+    Done(Result<(), LoadError>)
+}
+
+/// Why a load failed. HTTP error statuses are not load errors; see `Metadata::status`.
+#[deriving(Eq, Clone)]
+pub enum LoadError {
+    /// There is no loader for the URL's scheme.
+    UnsupportedScheme(~str),
+    /// The file at the given path doesn't exist.
+    FileNotFound(~str),
+    /// The file exists but couldn't be read.
+    FileReadError(~str),
+    /// The host name didn't resolve.
+    DnsFailure(~str),
+    /// The host resolved but the request couldn't be sent or the response read.
+    ConnectionFailed(~str),
+    /// More redirects were followed than the given limit.
+    TooManyRedirects(uint),
+    /// A redirect led back to the given URL.
+    RedirectLoop(~str),
+    /// A `data:` URL without a comma.
+    InvalidDataUrl,
+    /// A `data:` URL with undecodable base64 data.
+    InvalidBase64,
+}
+
+impl ToStr for LoadError {
+    fn to_str(&self) -> ~str {
+        match *self {
+            UnsupportedScheme(ref scheme) => format!("unsupported URL scheme '{:s}'", *scheme),
+            FileNotFound(ref path) => format!("file not found: {:s}", *path),
+            FileReadError(ref reason) => format!("error reading file: {:s}", *reason),
+            DnsFailure(ref host) => format!("couldn't resolve host {:s}", *host),
+            ConnectionFailed(ref host) => format!("couldn't connect to {:s}", *host),
+            TooManyRedirects(limit) => format!("more than {} redirects", limit),
+            RedirectLoop(ref url) => format!("redirect loop at {:s}", *url),
+            InvalidDataUrl => ~"malformed data: URL",
+            InvalidBase64 => ~"invalid base64 in data: URL",
+        }
+    }
 }
 
 /// For use by loaders in responding to a Load message.
@@ -96,9 +197,9 @@ pub fn start_sending(start_chan: Chan<LoadResponse>,
 
 /// Convenience function for synchronously loading a whole resource.
 pub fn load_whole_resource(resource_task: &ResourceTask, url: Url)
-        -> Result<(Metadata, ~[u8]), ()> {
+        -> Result<(Metadata, ~[u8]), LoadError> {
     let (start_port, start_chan) = Chan::new();
-    resource_task.send(Load(url, start_chan));
+    resource_task.send(Load(LoadData::new(url), start_chan));
     let response = start_port.recv();
 
     let mut buf = ~[];
@@ -114,7 +215,7 @@ pub fn load_whole_resource(resource_task: &ResourceTask, url: Url)
 /// Handle to a resource task
 pub type ResourceTask = SharedChan<ControlMsg>;
 
-pub type LoaderTask = proc(url: Url, Chan<LoadResponse>);
+pub type LoaderTask = proc(load_data: LoadData, Chan<LoadResponse>);
 
 /**
 Creates a task to load a specific resource
@@ -124,20 +225,27 @@ each URL scheme
 */
 type LoaderTaskFactory = extern "Rust" fn() -> LoaderTask;
 
-/// Create a ResourceTask with the default loaders
+/// Create a ResourceTask with the default loaders and an in-memory HTTP cache
 pub fn ResourceTask() -> ResourceTask {
+    ResourceTaskWithHttpCache(None)
+}
+
+/// Create a ResourceTask with the default loaders and an HTTP cache that is
+/// also persisted in `cache_dir`, if given
+pub fn ResourceTaskWithHttpCache(cache_dir: Option<Path>) -> ResourceTask {
     let loaders = ~[
         (~"file", file_loader::factory),
-        (~"http", http_loader::factory),
         (~"data", data_loader::factory),
     ];
-    create_resource_task_with_loaders(loaders)
+    let http_cache = MutexArc::new(HttpCache::new(cache_dir));
+    create_resource_task_with_loaders(loaders, Some(http_cache))
 }
 
-fn create_resource_task_with_loaders(loaders: ~[(~str, LoaderTaskFactory)]) -> ResourceTask {
+fn create_resource_task_with_loaders(loaders: ~[(~str, LoaderTaskFactory)],
+                                     http_cache: Option<HttpCacheHandle>) -> ResourceTask {
     let chan = spawn_listener("ResourceManager", proc(from_client) {
         // TODO: change copy to move once we can move out of closures
-        ResourceManager(from_client, loaders).start()
+        ResourceManager(from_client, loaders, http_cache).start()
     });
     chan
 }
@@ -146,14 +254,21 @@ pub struct ResourceManager {
     from_client: Port<ControlMsg>,
     /// Per-scheme resource loaders
     loaders: ~[(~str, LoaderTaskFactory)],
+    /// The cache that HTTP loads go through, if caching is enabled
+    http_cache: Option<HttpCacheHandle>,
+    /// The cookies shared by every pipeline
+    cookie_jar: CookieJarHandle,
 }
 
 
 pub fn ResourceManager(from_client: Port<ControlMsg>, 
-                       loaders: ~[(~str, LoaderTaskFactory)]) -> ResourceManager {
+                       loaders: ~[(~str, LoaderTaskFactory)],
+                       http_cache: Option<HttpCacheHandle>) -> ResourceManager {
     ResourceManager {
         from_client : from_client,
         loaders : loaders,
+        http_cache : http_cache,
+        cookie_jar : MutexArc::new(CookieJar::new()),
     }
 }
 
@@ -162,8 +277,16 @@ impl ResourceManager {
     fn start(&self) {
         loop {
             match self.from_client.recv() {
-              Load(url, start_chan) => {
-                self.load(url.clone(), start_chan)
+              Load(load_data, start_chan) => {
+                self.load(load_data, start_chan)
+              }
+              SetCookieForUrl(url, cookie, source) => {
+                let now = time::get_time().sec;
+                self.cookie_jar.access(|jar| jar.set_cookie_from_header(&url, cookie, source, now));
+              }
+              GetCookiesForUrl(url, consumer, source) => {
+                let now = time::get_time().sec;
+                consumer.send(self.cookie_jar.access(|jar| jar.cookies_for_url(&url, source, now)));
               }
               Exit => {
                 break
@@ -172,20 +295,27 @@ impl ResourceManager {
         }
     }
 
-    fn load(&self, url: Url, start_chan: Chan<LoadResponse>) {
-        match self.get_loader_factory(&url) {
+    fn load(&self, load_data: LoadData, start_chan: Chan<LoadResponse>) {
+        match self.get_loader_factory(&load_data.url) {
             Some(loader_factory) => {
-                debug!("resource_task: loading url: {:s}", url.to_str());
-                loader_factory(url, start_chan);
+                debug!("resource_task: loading url: {:s}", load_data.url.to_str());
+                loader_factory(load_data, start_chan);
             }
             None => {
-                debug!("resource_task: no loader for scheme {:s}", url.scheme);
-                start_sending(start_chan, Metadata::default(url)).send(Done(Err(())));
+                debug!("resource_task: no loader for scheme {:s}", load_data.url.scheme);
+                let error = UnsupportedScheme(load_data.url.scheme.clone());
+                start_sending(start_chan, Metadata::default(load_data.url)).send(Done(Err(error)));
             }
         }
     }
 
     fn get_loader_factory(&self, url: &Url) -> Option<LoaderTask> {
+        // HTTP loads share the cache and the cookie jar, so they aren't in the per-scheme
+        // table.
+        if "http" == url.scheme {
+            return Some(http_loader::factory(self.http_cache.clone(), self.cookie_jar.clone()));
+        }
+
         for scheme_loader in self.loaders.iter() {
             match *scheme_loader {
                 (ref scheme, ref loader_factory) => {
@@ -209,10 +339,10 @@ fn test_exit() {
 fn test_bad_scheme() {
     let resource_task = ResourceTask();
     let (start, start_chan) = Chan::new();
-    resource_task.send(Load(FromStr::from_str("bogus://whatever").unwrap(), start_chan));
+    resource_task.send(Load(LoadData::new(FromStr::from_str("bogus://whatever").unwrap()), start_chan));
     let response = start.recv();
     match response.progress_port.recv() {
-      Done(result) => { assert!(result.is_err()) }
+      Done(result) => { assert_eq!(result, Err(UnsupportedScheme(~"bogus"))) }
       _ => fail!("bleh")
     }
     resource_task.send(Exit);
@@ -223,8 +353,8 @@ static snicklefritz_payload: [u8, ..3] = [1, 2, 3];
 
 #[cfg(test)]
 fn snicklefritz_loader_factory() -> LoaderTask {
-    let f: LoaderTask = proc(url: Url, start_chan: Chan<LoadResponse>) {
-        let progress_chan = start_sending(start_chan, Metadata::default(url));
+    let f: LoaderTask = proc(load_data: LoadData, start_chan: Chan<LoadResponse>) {
+        let progress_chan = start_sending(start_chan, Metadata::default(load_data.url));
         progress_chan.send(Payload(snicklefritz_payload.into_owned()));
         progress_chan.send(Done(Ok(())));
     };
@@ -234,9 +364,9 @@ fn snicklefritz_loader_factory() -> LoaderTask {
 #[test]
 fn should_delegate_to_scheme_loader() {
     let loader_factories = ~[(~"snicklefritz", snicklefritz_loader_factory)];
-    let resource_task = create_resource_task_with_loaders(loader_factories);
+    let resource_task = create_resource_task_with_loaders(loader_factories, None);
     let (start, start_chan) = Chan::new();
-    resource_task.send(Load(FromStr::from_str("snicklefritz://heya").unwrap(), start_chan));
+    resource_task.send(Load(LoadData::new(FromStr::from_str("snicklefritz://heya").unwrap()), start_chan));
 
     let response = start.recv();
     let progress = response.progress_port;
@@ -245,3 +375,30 @@ fn should_delegate_to_scheme_loader() {
     assert!(progress.recv() == Done(Ok(())));
     resource_task.send(Exit);
 }
+
+#[test]
+fn test_metadata_status_and_headers() {
+    let mut metadata = Metadata::default(FromStr::from_str("http://example.com/").unwrap());
+    assert!(!metadata.is_http_error());
+    assert_eq!(metadata.header("Refresh"), None);
+
+    metadata.status = Some((404, ~"Not Found"));
+    metadata.headers = Some(~[(~"Refresh", ~"5; url=/")]);
+    assert!(metadata.is_http_error());
+    assert_eq!(metadata.header("refresh"), Some("5; url=/"));
+}
+
+#[test]
+fn test_cookies_through_resource_task() {
+    use cookie::{HTTP, NonHTTP};
+
+    let resource_task = ResourceTask();
+    let url: Url = FromStr::from_str("http://example.com/").unwrap();
+    resource_task.send(SetCookieForUrl(url.clone(), ~"session=1; HttpOnly", HTTP));
+    resource_task.send(SetCookieForUrl(url.clone(), ~"theme=dark", NonHTTP));
+
+    let (port, chan) = Chan::new();
+    resource_task.send(GetCookiesForUrl(url.clone(), chan, NonHTTP));
+    assert_eq!(port.recv(), Some(~"theme=dark"));
+    resource_task.send(Exit);
+}
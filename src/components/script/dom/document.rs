@@ -26,6 +26,8 @@ use html::hubbub_html_parser::build_element_from_tag;
 use hubbub::hubbub::{QuirksMode, NoQuirks, LimitedQuirks, FullQuirks};
 use layout_interface::{DocumentDamageLevel, ContentChangedDocumentDamage};
 use servo_util::namespace::Null;
+use servo_net::cookie::NonHTTP;
+use servo_net::resource_task::{GetCookiesForUrl, SetCookieForUrl};
 
 use extra::url::{Url, from_str};
 use js::jsapi::{JSObject, JSContext, JSTracer};
@@ -324,6 +326,25 @@ impl Document {
         }
     }
 
+    // http://www.whatwg.org/specs/web-apps/current-work/#dom-document-cookie
+    pub fn Cookie(&self) -> DOMString {
+        // Documents without a server-based URL are cookie-averse.
+        if "http" != self.url.scheme {
+            return ~""
+        }
+        let (port, chan) = Chan::new();
+        self.window.resource_task.send(GetCookiesForUrl(self.url.clone(), chan, NonHTTP));
+        port.recv().unwrap_or(~"")
+    }
+
+    // http://www.whatwg.org/specs/web-apps/current-work/#dom-document-cookie
+    pub fn SetCookie(&mut self, cookie: DOMString) {
+        if "http" != self.url.scheme {
+            return
+        }
+        self.window.resource_task.send(SetCookieForUrl(self.url.clone(), cookie, NonHTTP));
+    }
+
     // http://www.whatwg.org/specs/web-apps/current-work/#document.title
     pub fn Title(&self, _: AbstractDocument) -> DOMString {
         let mut title = ~"";
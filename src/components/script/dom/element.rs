@@ -18,11 +18,15 @@ use dom::document;
 use dom::htmlserializer::serialize;
 use layout_interface::{ContentBoxQuery, ContentBoxResponse, ContentBoxesQuery};
 use layout_interface::{ContentBoxesResponse, ContentChangedDocumentDamage};
-use layout_interface::{MatchSelectorsDocumentDamage};
+use layout_interface::{MatchSelectorsDocumentDamage, ScrollAreaQuery, ScrollAreaResponse};
+use layout_interface::SetScrollOffsetMsg;
 use style;
+use servo_util::geometry::{Au, to_frac_px};
 use servo_util::namespace;
 use servo_util::namespace::{Namespace, Null};
 
+use geom::point::Point2D;
+
 use std::ascii::StrAsciiExt;
 use std::cast;
 use std::str::eq;
@@ -221,7 +225,9 @@ impl Element {
 
         match local_name.as_slice() {
             "style" => {
-                self.style_attribute = Some(style::parse_style_attribute(value))
+                let doc = self.node.owner_doc();
+                let base_url = doc.document().url.clone();
+                self.style_attribute = Some(style::parse_style_attribute(value, &base_url))
             }
             "id" => {
                 // XXX: this dual declaration are workaround to avoid the compile error:
@@ -342,6 +348,19 @@ impl Element {
             _ => false
         }
     }
+
+    /// Returns true if clicking on this element gives it the focus. Only form controls and links
+    /// can take the focus so far.
+    pub fn is_focusable(&self) -> bool {
+        if self.namespace != namespace::HTML {
+            return false
+        }
+        match self.tag_name.as_slice() {
+            "button" | "input" | "select" | "textarea" => true,
+            "a" | "area" => self.get_attribute(namespace::Null, "href").is_some(),
+            _ => false
+        }
+    }
 }
 
 // http://www.whatwg.org/html/#reflecting-content-attributes-in-idl-attributes
@@ -532,26 +551,59 @@ impl Element {
     pub fn ScrollIntoView(&self, _top: bool) {
     }
 
-    pub fn ScrollTop(&self) -> i32 {
-        0
+    pub fn ScrollTop(&self, abstract_self: AbstractNode) -> i32 {
+        self.scroll_area(abstract_self).scroll_offset.y.to_nearest_px() as i32
     }
 
-    pub fn SetScrollTop(&mut self, _scroll_top: i32) {
+    pub fn SetScrollTop(&mut self, abstract_self: AbstractNode, scroll_top: i32) {
+        self.scroll_to(abstract_self, None, Some(Au::from_px(scroll_top as int)))
     }
 
-    pub fn ScrollLeft(&self) -> i32 {
-        0
+    pub fn ScrollLeft(&self, abstract_self: AbstractNode) -> i32 {
+        self.scroll_area(abstract_self).scroll_offset.x.to_nearest_px() as i32
     }
 
-    pub fn SetScrollLeft(&mut self, _scroll_left: i32) {
+    pub fn SetScrollLeft(&mut self, abstract_self: AbstractNode, scroll_left: i32) {
+        self.scroll_to(abstract_self, Some(Au::from_px(scroll_left as int)), None)
     }
 
-    pub fn ScrollWidth(&self) -> i32 {
-        0
+    pub fn ScrollWidth(&self, abstract_self: AbstractNode) -> i32 {
+        self.scroll_area(abstract_self).scroll_size.width.to_nearest_px() as i32
     }
 
-    pub fn ScrollHeight(&self) -> i32 {
-        0
+    pub fn ScrollHeight(&self, abstract_self: AbstractNode) -> i32 {
+        self.scroll_area(abstract_self).scroll_size.height.to_nearest_px() as i32
+    }
+
+    fn scroll_area(&self, abstract_self: AbstractNode) -> ScrollAreaResponse {
+        let win = self.node.owner_doc().document().window;
+        assert!(abstract_self.is_element());
+        let (port, chan) = Chan::new();
+        win.page.query_layout(ScrollAreaQuery(abstract_self, chan), port)
+    }
+
+    /// Scrolls the element to the given offsets, clamped to its scrolling area, leaving out
+    /// offsets that are `None` unchanged. Elements that are not scroll containers are left alone.
+    fn scroll_to(&self, abstract_self: AbstractNode, x: Option<Au>, y: Option<Au>) {
+        let area = self.scroll_area(abstract_self);
+        let layer = match area.layer {
+            None => return,
+            Some(layer) => layer,
+        };
+
+        let max_x = Au::max(area.scroll_size.width - area.client_size.width, Au(0));
+        let max_y = Au::max(area.scroll_size.height - area.client_size.height, Au(0));
+        let x = Au::max(Au::min(x.unwrap_or(area.scroll_offset.x), max_x), Au(0));
+        let y = Au::max(Au::min(y.unwrap_or(area.scroll_offset.y), max_y), Au(0));
+        if x == area.scroll_offset.x && y == area.scroll_offset.y {
+            return
+        }
+
+        let win = self.node.owner_doc().document().window;
+        win.page.layout_chan.send(SetScrollOffsetMsg(layer, Point2D(x, y)));
+        win.compositor.scroll_layer_to_point(win.page.id,
+                                             layer,
+                                             Point2D(to_frac_px(x) as f32, to_frac_px(y) as f32));
     }
 
     pub fn ClientTop(&self) -> i32 {
@@ -11,6 +11,7 @@ use dom::mouseevent::MouseEvent;
 use dom::uievent::UIEvent;
 
 use geom::point::Point2D;
+use servo_msg::compositor_msg::LayerId;
 
 use std::cast;
 use std::unstable::raw::Box;
@@ -21,7 +22,8 @@ pub enum Event_ {
     ClickEvent(uint, Point2D<f32>),
     MouseDownEvent(uint, Point2D<f32>),
     MouseUpEvent(uint, Point2D<f32>),
-    MouseMoveEvent(Point2D<f32>)
+    MouseMoveEvent(Point2D<f32>),
+    ScrollEvent(LayerId, Point2D<f32>)
 }
 
 pub struct AbstractEvent {
@@ -103,6 +103,8 @@ impl NodeFlags {
 bitfield!(NodeFlags, is_in_doc, set_is_in_doc, 0x01)
 /// Specifies whether this node is hover state for this node
 bitfield!(NodeFlags, get_in_hover_state, set_is_in_hover_state, 0x02)
+/// Specifies whether this node has the focus
+bitfield!(NodeFlags, get_in_focus_state, set_is_in_focus_state, 0x04)
 
 #[unsafe_destructor]
 impl Drop for Node {
@@ -556,6 +558,14 @@ impl<'a> AbstractNode {
     pub fn set_hover_state(&self, state: bool) {
         self.mut_node().flags.set_is_in_hover_state(state);
     }
+
+    pub fn get_focus_state(&self) -> bool {
+        self.node().flags.get_in_focus_state()
+    }
+
+    pub fn set_focus_state(&self, state: bool) {
+        self.mut_node().flags.set_is_in_focus_state(state);
+    }
 }
 
 impl AbstractNode {
@@ -593,6 +603,9 @@ impl AbstractNode {
         // Unregister elements having "id".
         document.mut_document().unregister_nodes_with_id(&self);
 
+        // A node that is no longer in the document can't keep the focus.
+        document.document().window.page.blur_subtree(self);
+
         document.document().content_changed();
     }
 
@@ -1743,6 +1756,14 @@ impl Node {
     pub fn set_hover_state(&mut self, state: bool) {
         self.flags.set_is_in_hover_state(state);
     }
+
+    pub fn get_focus_state(&self) -> bool {
+        self.flags.get_in_focus_state()
+    }
+
+    pub fn set_focus_state(&mut self, state: bool) {
+        self.flags.set_is_in_focus_state(state);
+    }
 }
 
 impl Reflectable for Node {
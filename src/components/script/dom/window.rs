@@ -12,10 +12,11 @@ use dom::node::AbstractNode;
 use dom::location::Location;
 use dom::navigator::Navigator;
 
-use layout_interface::{ReflowForDisplay, DocumentDamageLevel};
+use layout_interface::{PrintMsg, ReflowForDisplay, DocumentDamageLevel};
 use script_task::{ExitWindowMsg, FireTimerMsg, Page, ScriptChan};
 use servo_msg::compositor_msg::ScriptListener;
 use servo_net::image_cache_task::ImageCacheTask;
+use servo_net::resource_task::ResourceTask;
 use servo_util::task::{spawn_named};
 
 use js::glue::*;
@@ -69,6 +70,7 @@ pub struct Window {
     location: Option<@mut Location>,
     navigator: Option<@mut Navigator>,
     image_cache_task: ImageCacheTask,
+    resource_task: ResourceTask,
     active_timers: ~HashSet<TimerHandle>,
     next_timer_handle: i32,
 }
@@ -166,6 +168,10 @@ impl Window {
     }
 
     pub fn Print(&self) {
+        self.page.join_layout();
+        let (response_port, response_chan) = Chan::new();
+        self.page.layout_chan.send(PrintMsg(response_chan));
+        response_port.recv();
     }
 
     pub fn ShowModalDialog(&self, _cx: *JSContext, _url: DOMString, _argument: JSVal) -> JSVal {
@@ -239,7 +245,8 @@ impl Window {
                page: @mut Page,
                script_chan: ScriptChan,
                compositor: @ScriptListener,
-               image_cache_task: ImageCacheTask)
+               image_cache_task: ImageCacheTask,
+               resource_task: ResourceTask)
                -> @mut Window {
         let win = @mut Window {
             eventtarget: EventTarget::new_inherited(WindowTypeId),
@@ -263,6 +270,7 @@ impl Window {
             location: None,
             navigator: None,
             image_cache_task: image_cache_task,
+            resource_task: resource_task,
             active_timers: ~HashSet::new(),
             next_timer_handle: 0
         };
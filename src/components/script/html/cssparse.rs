@@ -8,7 +8,8 @@ use std::comm::Port;
 use encoding::EncodingRef;
 use encoding::all::UTF_8;
 use style::Stylesheet;
-use servo_net::resource_task::{Load, LoadResponse, ProgressMsg, Payload, Done, ResourceTask};
+use servo_net::resource_task::{Load, LoadData, LoadResponse, ProgressMsg, Payload, Done};
+use servo_net::resource_task::ResourceTask;
 use servo_util::task::spawn_named;
 use extra::url::Url;
 
@@ -29,19 +30,16 @@ pub fn spawn_css_parser(provenance: StylesheetProvenance,
     spawn_named("cssparser", proc() {
         let sheet = match provenance {
             UrlProvenance(url) => {
-                debug!("cssparse: loading style sheet at {:s}", url.to_str());
-                let (input_port, input_chan) = Chan::new();
-                resource_task.send(Load(url, input_chan));
-                let LoadResponse { metadata: metadata, progress_port: progress_port }
-                    = input_port.recv();
-                let protocol_encoding_label = metadata.charset.as_ref().map(|s| s.as_slice());
-                let iter = ProgressMsgPortIterator { progress_port: progress_port };
-                Stylesheet::from_bytes_iter(
-                    iter, metadata.final_url,
-                    protocol_encoding_label, Some(environment_encoding))
+                // A sheet that fails to load applies no rules.
+                match load_stylesheet(url.clone(), &resource_task, environment_encoding, &mut ~[]) {
+                    Some(sheet) => sheet,
+                    None => Stylesheet::from_str("", url, environment_encoding),
+                }
             }
             InlineProvenance(base_url, data) => {
-                Stylesheet::from_str(data, base_url, environment_encoding)
+                let mut sheet = Stylesheet::from_str(data, base_url, environment_encoding);
+                load_imports(&mut sheet, &resource_task, environment_encoding, &mut ~[]);
+                sheet
             }
         };
         result_chan.send(sheet);
@@ -50,15 +48,75 @@ pub fn spawn_css_parser(provenance: StylesheetProvenance,
     return result_port;
 }
 
-struct ProgressMsgPortIterator {
-    progress_port: Port<ProgressMsg>
+/// Fetches and parses the style sheet at `url`, along with everything it imports. `importers`
+/// holds the URLs of the sheets that import this one, directly or not. Returns `None` if the
+/// sheet couldn't be loaded, or the server answered with an error.
+fn load_stylesheet(url: Url,
+                   resource_task: &ResourceTask,
+                   environment_encoding: EncodingRef,
+                   importers: &mut ~[Url])
+                   -> Option<Stylesheet> {
+    debug!("cssparse: loading style sheet at {:s}", url.to_str());
+    let (input_port, input_chan) = Chan::new();
+    resource_task.send(Load(LoadData::new(url.clone()), input_chan));
+    let LoadResponse { metadata: metadata, progress_port: progress_port }
+        = input_port.recv();
+    if metadata.is_http_error() {
+        error!("cssparse: not using the style sheet at {:s}, since the server answered {:?}",
+               url.to_str(),
+               metadata.status);
+        return None
+    }
+
+    let protocol_encoding_label = metadata.charset.as_ref().map(|s| s.as_slice());
+    let mut failed = false;
+    let mut sheet = {
+        let iter = ProgressMsgPortIterator { progress_port: progress_port, failed: &mut failed };
+        Stylesheet::from_bytes_iter(
+            iter, metadata.final_url.clone(),
+            protocol_encoding_label, Some(environment_encoding))
+    };
+    if failed {
+        return None
+    }
+
+    importers.push(url);
+    load_imports(&mut sheet, resource_task, environment_encoding, importers);
+    importers.pop();
+    Some(sheet)
+}
+
+/// Loads the sheets `sheet` imports, refusing any that would create an import cycle.
+fn load_imports(sheet: &mut Stylesheet,
+                resource_task: &ResourceTask,
+                environment_encoding: EncodingRef,
+                importers: &mut ~[Url]) {
+    sheet.load_imports(|url| {
+        if importers.contains(url) {
+            error!("cssparse: not importing {:s}, since it imports itself", url.to_str());
+            None
+        } else {
+            load_stylesheet(url.clone(), resource_task, environment_encoding, importers)
+        }
+    })
 }
 
-impl Iterator<~[u8]> for ProgressMsgPortIterator {
+struct ProgressMsgPortIterator<'a> {
+    progress_port: Port<ProgressMsg>,
+    /// Set if the load ends in an error.
+    failed: &'a mut bool,
+}
+
+impl<'a> Iterator<~[u8]> for ProgressMsgPortIterator<'a> {
     fn next(&mut self) -> Option<~[u8]> {
         match self.progress_port.recv() {
             Payload(data) => Some(data),
-            Done(..) => None
+            Done(Ok(())) => None,
+            Done(Err(error)) => {
+                error!("error loading style sheet: {:s}", error.to_str());
+                *self.failed = true;
+                None
+            }
         }
     }
 }
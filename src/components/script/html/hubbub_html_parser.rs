@@ -13,12 +13,13 @@ use dom::types::*;
 use html::cssparse::{InlineProvenance, StylesheetProvenance, UrlProvenance, spawn_css_parser};
 use script_task::page_from_context;
 
+use extra::time::precise_time_ns;
 use extra::url::Url;
 use hubbub::hubbub;
 use js::jsapi::JSContext;
 use servo_msg::constellation_msg::SubpageId;
 use servo_net::image_cache_task::ImageCacheTask;
-use servo_net::resource_task::{Load, Payload, Done, ResourceTask, load_whole_resource};
+use servo_net::resource_task::{Load, LoadData, Payload, Done, ResourceTask, load_whole_resource};
 use servo_util::url::parse_url;
 use servo_util::task::spawn_named;
 use servo_util::namespace::Null;
@@ -42,6 +43,9 @@ macro_rules! handle_element(
     )
 )
 
+/// How often the partially parsed document is handed to layout while the page is still loading,
+/// in nanoseconds.
+static PARTIAL_REFLOW_INTERVAL: u64 = 100000000;
 
 pub struct JSFile {
     data: ~str,
@@ -91,37 +95,44 @@ Runs a task that coordinates parsing links to css stylesheets.
 
 This function should be spawned in a separate task and spins waiting
 for the html builder to find links to css stylesheets and sends off
-tasks to parse each link.  When the html process finishes, it notifies
-the listener, who then collects the css rules from each task it
-spawned, collates them, and sends them to the given result channel.
+tasks to parse each link. Each sheet is sent to the given result
+channel as soon as it and every sheet before it have been parsed, so
+that sheets arrive in document order while the page is still loading.
 
 # Arguments
 
-* `to_parent` - A channel on which to send back the full set of rules.
+* `to_parent` - A channel on which to send back the parsed sheets.
 * `from_parent` - A port on which to receive new links.
 
 */
 fn css_link_listener(to_parent: SharedChan<HtmlDiscoveryMessage>,
                      from_parent: Port<CSSMessage>,
                      resource_task: ResourceTask) {
-    let mut result_vec = ~[];
+    let (result_port, result_chan): (Port<Port<Stylesheet>>, Chan<Port<Stylesheet>>) =
+        Chan::new();
+
+    // Send the sheets back in order.
+    spawn_named("parse_html:css:results", proc() {
+        loop {
+            match result_port.recv_opt() {
+                Some(port) => {
+                    to_parent.try_send(HtmlDiscoveredStyle(port.recv()));
+                }
+                None => break,
+            }
+        }
+    });
 
     loop {
         match from_parent.recv_opt() {
             Some(CSSTaskNewFile(provenance)) => {
-                result_vec.push(spawn_css_parser(provenance, resource_task.clone()));
+                result_chan.send(spawn_css_parser(provenance, resource_task.clone()));
             }
             Some(CSSTaskExit) | None => {
                 break;
             }
         }
     }
-
-    // Send the sheets back in order
-    // FIXME: Shouldn't wait until after we've recieved CSSTaskExit to start sending these
-    for port in result_vec.iter() {
-        to_parent.try_send(HtmlDiscoveredStyle(port.recv()));
-    }
 }
 
 fn js_script_listener(to_parent: SharedChan<HtmlDiscoveryMessage>,
@@ -133,8 +144,12 @@ fn js_script_listener(to_parent: SharedChan<HtmlDiscoveryMessage>,
         match from_parent.recv_opt() {
             Some(JSTaskNewFile(url)) => {
                 match load_whole_resource(&resource_task, url.clone()) {
-                    Err(_) => {
-                        error!("error loading script {:s}", url.to_str());
+                    Err(error) => {
+                        error!("error loading script {:s}: {:s}", url.to_str(), error.to_str());
+                    }
+                    Ok((metadata, _)) if metadata.is_http_error() => {
+                        let (code, reason) = metadata.status.unwrap();
+                        error!("error loading script {:s}: {} {:s}", url.to_str(), code, reason);
                     }
                     Ok((metadata, bytes)) => {
                         result_vec.push(JSFile {
@@ -247,7 +262,8 @@ pub fn parse_html(cx: *JSContext,
                   url: Url,
                   resource_task: ResourceTask,
                   image_cache_task: ImageCacheTask,
-                  next_subpage_id: SubpageId)
+                  next_subpage_id: SubpageId,
+                  partial_reflow: |&Port<HtmlDiscoveryMessage>|)
                   -> HtmlParserResult {
     debug!("Hubbub: parsing {:?}", url);
     // Spawn a CSS parser to receive links to CSS style sheets.
@@ -270,7 +286,7 @@ pub fn parse_html(cx: *JSContext,
 
     // Wait for the LoadResponse so that the parser knows the final URL.
     let (input_port, input_chan) = Chan::new();
-    resource_task.send(Load(url.clone(), input_chan));
+    resource_task.send(Load(LoadData::new(url.clone()), input_chan));
     let load_response = input_port.recv();
 
     debug!("Fetched page; metadata is {:?}", load_response.metadata);
@@ -503,14 +519,24 @@ pub fn parse_html(cx: *JSContext,
     debug!("set tree handler");
 
     debug!("loaded page");
+    let mut last_reflow_time = precise_time_ns();
     loop {
         match load_response.progress_port.recv() {
             Payload(data) => {
                 debug!("received data");
                 parser.parse_chunk(data);
+
+                // Show what we have so far, so that large pages don't stay blank until the
+                // whole document has arrived.
+                let now = precise_time_ns();
+                if now - last_reflow_time >= PARTIAL_REFLOW_INTERVAL {
+                    debug!("flushing partially parsed document to layout");
+                    partial_reflow(&discovery_port);
+                    last_reflow_time = now;
+                }
             }
-            Done(Err(..)) => {
-                fail!("Failed to load page URL {:s}", url.to_str());
+            Done(Err(error)) => {
+                fail!("Failed to load page URL {:s}: {:s}", url.to_str(), error.to_str());
             }
             Done(..) => {
                 break;
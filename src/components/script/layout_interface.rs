@@ -13,6 +13,7 @@ use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
 use script_task::{ScriptChan};
+use servo_msg::compositor_msg::LayerId;
 use servo_util::geometry::Au;
 use std::cmp;
 use std::comm::{Chan, SharedChan};
@@ -39,6 +40,14 @@ pub enum Msg {
     /// TODO(pcwalton): Maybe think about batching to avoid message traffic.
     ReapLayoutDataMsg(LayoutDataRef),
 
+    /// Writes the most recently laid out document out page by page, to the file given by the
+    /// `--print` option. A response message will be sent on the supplied channel when done.
+    PrintMsg(Chan<()>),
+
+    /// Records that the given scroll layer has been scrolled, either by the user or by script, so
+    /// that hit testing, queries and later display lists take the new offset into account.
+    SetScrollOffsetMsg(LayerId, Point2D<Au>),
+
     /// Requests that the layout task enter a quiescent state in which no more messages are
     /// accepted except `ExitMsg`. A response message will be sent on the supplied channel when
     /// this happens.
@@ -58,6 +67,9 @@ pub enum LayoutQuery {
     /// Requests the node containing the point of interest
     HitTestQuery(AbstractNode, Point2D<f32>, Chan<Result<HitTestResponse, ()>>),
     MouseOverQuery(AbstractNode, Point2D<f32>, Chan<Result<MouseOverResponse, ()>>),
+    /// Requests the scrolling area of an element, as in the `scrollTop` and `scrollWidth`
+    /// attributes.
+    ScrollAreaQuery(AbstractNode, Chan<ScrollAreaResponse>),
 }
 
 /// The address of a node. Layout sends these back. They must be validated via
@@ -69,6 +81,19 @@ pub struct ContentBoxesResponse(~[Rect<Au>]);
 pub struct HitTestResponse(UntrustedNodeAddress);
 pub struct MouseOverResponse(~[UntrustedNodeAddress]);
 
+/// The scrolling area of an element. Elements that are not scroll containers have no layer and
+/// report the size of their boxes as both their scroll size and their client size.
+pub struct ScrollAreaResponse {
+    /// The scroll layer of the element, if it has one.
+    layer: Option<LayerId>,
+    /// How far the contents of the element are scrolled.
+    scroll_offset: Point2D<Au>,
+    /// The size of the scrollable contents, including the padding box.
+    scroll_size: Size2D<Au>,
+    /// The size of the padding box.
+    client_size: Size2D<Au>,
+}
+
 /// Determines which part of the 
 #[deriving(Eq, Ord)]
 pub enum DocumentDamageLevel {
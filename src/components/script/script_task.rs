@@ -10,12 +10,13 @@ use dom::bindings::utils::{Reflectable, GlobalStaticData};
 use dom::document::AbstractDocument;
 use dom::element::Element;
 use dom::event::{Event_, ResizeEvent, ReflowEvent, ClickEvent, MouseDownEvent, MouseMoveEvent, MouseUpEvent};
+use dom::event::ScrollEvent;
 use dom::event::Event;
 use dom::eventtarget::AbstractEventTarget;
 use dom::htmldocument::HTMLDocument;
 use dom::node::AbstractNode;
 use dom::window::{TimerData, TimerHandle, Window};
-use html::hubbub_html_parser::HtmlParserResult;
+use html::hubbub_html_parser::{HtmlParserResult, HtmlDiscoveryMessage, JSFile};
 use html::hubbub_html_parser::{HtmlDiscoveredStyle, HtmlDiscoveredIFrame, HtmlDiscoveredScript};
 use html::hubbub_html_parser;
 use layout_interface::{AddStylesheetMsg, DocumentDamage};
@@ -23,6 +24,7 @@ use layout_interface::{ContentBoxQuery, ContentBoxResponse};
 use layout_interface::{DocumentDamageLevel, HitTestQuery, HitTestResponse, LayoutQuery, MouseOverQuery, MouseOverResponse};
 use layout_interface::{LayoutChan, MatchSelectorsDocumentDamage, QueryMsg};
 use layout_interface::{Reflow, ReflowDocumentDamage, ReflowForDisplay, ReflowGoal, ReflowMsg};
+use layout_interface::SetScrollOffsetMsg;
 use layout_interface::ContentChangedDocumentDamage;
 use layout_interface;
 
@@ -43,7 +45,7 @@ use servo_msg::constellation_msg::{PipelineId, SubpageId};
 use servo_msg::constellation_msg;
 use servo_net::image_cache_task::ImageCacheTask;
 use servo_net::resource_task::ResourceTask;
-use servo_util::geometry::to_frac_px;
+use servo_util::geometry::{Au, to_frac_px};
 use servo_util::url::parse_url;
 use servo_util::task::spawn_named;
 use servo_util::namespace::Null;
@@ -131,7 +133,10 @@ pub struct Page {
     resize_event: Option<Size2D<uint>>,
 
     /// Pending scroll to fragment event, if any
-    fragment_node: Option<AbstractNode>
+    fragment_node: Option<AbstractNode>,
+
+    /// The element of this page's document that has the focus, if any.
+    focused_node: Option<AbstractNode>,
 }
 
 pub struct PageTree {
@@ -158,6 +163,7 @@ impl PageTree {
                 next_subpage_id: SubpageId(0),
                 resize_event: None,
                 fragment_node: None,
+                focused_node: None,
                 last_reflow_id: 0
             },
             inner: ~[],
@@ -246,6 +252,36 @@ impl Page {
         };
     }
 
+    /// Moves the focus to `new_focus`, or takes it away if that is `None`. Returns true if that
+    /// changes which element has it, in which case the page needs restyling.
+    pub fn set_focused_node(&mut self, new_focus: Option<AbstractNode>) -> bool {
+        if new_focus == self.focused_node {
+            return false
+        }
+        for node in self.focused_node.iter() {
+            node.set_focus_state(false)
+        }
+        for node in new_focus.iter() {
+            node.set_focus_state(true)
+        }
+        self.focused_node = new_focus;
+        true
+    }
+
+    /// Takes the focus away if `node` or one of its descendants has it, as when `node` is removed
+    /// from the document.
+    pub fn blur_subtree(&mut self, node: AbstractNode) {
+        let has_focus = match self.focused_node {
+            None => false,
+            Some(focused_node) => {
+                focused_node == node || focused_node.ancestors().any(|ancestor| ancestor == node)
+            }
+        };
+        if has_focus {
+            self.set_focused_node(None);
+        }
+    }
+
     /// Sends a ping to layout and waits for the response. The response will arrive when the
     /// layout task has finished any pending request messages.
     pub fn join_layout(&mut self) {
@@ -406,7 +442,7 @@ pub struct ScriptTask {
     /// The JavaScript runtime.
     js_runtime: js::rust::rt,
 
-    mouse_over_targets:Option<~[AbstractNode]>
+    mouse_over_targets:Option<~[AbstractNode]>,
 }
 
 /// Returns the relevant page from the associated JS Context.
@@ -442,7 +478,7 @@ impl ScriptTask {
             compositor: compositor,
 
             js_runtime: js_runtime,
-            mouse_over_targets:None
+            mouse_over_targets:None,
         };
 
         script_task
@@ -678,13 +714,17 @@ impl ScriptTask {
             }
         }
 
+        // The focus belonged to the document being replaced.
+        page.focused_node = None;
+
         let cx = self.js_runtime.cx();
         // Create the window and document objects.
         let window = Window::new(cx.ptr,
                                  page,
                                  self.chan.clone(),
                                  self.compositor,
-                                 self.image_cache_task.clone());
+                                 self.image_cache_task.clone(),
+                                 self.resource_task.clone());
         page.initialize_js_info(cx, window.reflector().get_jsobject());
 
         RegisterBindings::Register(page.js_info.get_ref().js_compartment);
@@ -694,12 +734,34 @@ impl ScriptTask {
         //
         // Note: We can parse the next document in parallel with any previous documents.
         let document = HTMLDocument::new(window, Some(url.clone()));
-        let html_parsing_result = hubbub_html_parser::parse_html(cx.ptr,
-                                                                 document,
-                                                                 url.clone(),
-                                                                 self.resource_task.clone(),
-                                                                 self.image_cache_task.clone(),
-                                                                 page.next_subpage_id.clone());
+        let mut js_scripts = None;
+        let html_parsing_result = {
+            // While the page is loading, the parser periodically hands us what it has built so
+            // far. The root frame is only installed for the duration of that reflow, so that the
+            // parser's individual DOM insertions in between don't each trigger one.
+            let partial_reflow = |discovery_port: &Port<HtmlDiscoveryMessage>| {
+                loop {
+                    match discovery_port.try_recv() {
+                        Some(msg) => self.handle_html_discovery(page, msg, &mut js_scripts),
+                        None => break,
+                    }
+                }
+
+                page.frame = Some(Frame {
+                    document: document,
+                    window: window,
+                });
+                document.document().content_changed();
+                page.frame = None;
+            };
+            hubbub_html_parser::parse_html(cx.ptr,
+                                           document,
+                                           url.clone(),
+                                           self.resource_task.clone(),
+                                           self.image_cache_task.clone(),
+                                           page.next_subpage_id.clone(),
+                                           partial_reflow)
+        };
 
         let HtmlParserResult {
             discovery_port
@@ -711,33 +773,10 @@ impl ScriptTask {
             window: window,
         });
 
-        // Send style sheets over to layout.
-        //
-        // FIXME: These should be streamed to layout as they're parsed. We don't need to stop here
-        // in the script task.
-
-        let mut js_scripts = None;
+        // Send the remaining style sheets over to layout, and wait for the scripts.
         loop {
             match discovery_port.recv_opt() {
-                Some(HtmlDiscoveredScript(scripts)) => {
-                    assert!(js_scripts.is_none());
-                    js_scripts = Some(scripts);
-                }
-                Some(HtmlDiscoveredStyle(sheet)) => {
-                    page.layout_chan.send(AddStylesheetMsg(sheet));
-                }
-                Some(HtmlDiscoveredIFrame((iframe_url, subpage_id, sandboxed))) => {
-                    page.next_subpage_id = SubpageId(*subpage_id + 1);
-                    let sandboxed = if sandboxed {
-                        IFrameSandboxed
-                    } else {
-                        IFrameUnsandboxed
-                    };
-                    self.constellation_chan.send(LoadIframeUrlMsg(iframe_url,
-                                                                  pipeline_id,
-                                                                  subpage_id,
-                                                                  sandboxed));
-                }
+                Some(msg) => self.handle_html_discovery(page, msg, &mut js_scripts),
                 None => break
             }
         }
@@ -782,6 +821,35 @@ impl ScriptTask {
         self.constellation_chan.send(LoadCompleteMsg(page.id, url));
     }
 
+    /// Acts on a resource discovered by the HTML parser: style sheets go to layout, iframes to
+    /// the constellation, and the document's scripts are stored in `js_scripts`.
+    fn handle_html_discovery(&self,
+                             page: &mut Page,
+                             msg: HtmlDiscoveryMessage,
+                             js_scripts: &mut Option<~[JSFile]>) {
+        match msg {
+            HtmlDiscoveredScript(scripts) => {
+                assert!(js_scripts.is_none());
+                *js_scripts = Some(scripts);
+            }
+            HtmlDiscoveredStyle(sheet) => {
+                page.layout_chan.send(AddStylesheetMsg(sheet));
+            }
+            HtmlDiscoveredIFrame((iframe_url, subpage_id, sandboxed)) => {
+                page.next_subpage_id = SubpageId(*subpage_id + 1);
+                let sandboxed = if sandboxed {
+                    IFrameSandboxed
+                } else {
+                    IFrameUnsandboxed
+                };
+                self.constellation_chan.send(LoadIframeUrlMsg(iframe_url,
+                                                              page.id,
+                                                              subpage_id,
+                                                              sandboxed));
+            }
+        }
+    }
+
     fn find_fragment_node(&self, page: &mut Page, fragid: ~str) -> Option<AbstractNode> {
         let document = page.frame.expect("root frame is None").document; 
         match document.document().GetElementById(fragid.to_owned()) {
@@ -872,6 +940,8 @@ impl ScriptTask {
                             }
                         }
 
+                        self.focus_nearest_focusable_ancestor(page, node);
+
                         if node.is_element() {
                             node.with_imm_element(|element| {
                                 if "a" == element.tag_name {
@@ -954,6 +1024,37 @@ impl ScriptTask {
                     Err(()) => {},
               }
             }
+
+            ScrollEvent(layer_id, point) => {
+                debug!("ScrollEvent: layer {:?} scrolled to {:?}", layer_id, point);
+
+                let point = Point2D(Au::from_frac_px(point.x as f64),
+                                    Au::from_frac_px(point.y as f64));
+                page.layout_chan.send(SetScrollOffsetMsg(layer_id, point));
+            }
+        }
+    }
+
+    /// Moves the focus within `page` to the nearest focusable inclusive ancestor of `node`, or takes
+    /// it away if there is none, and restyles the page if that changes which element has it.
+    fn focus_nearest_focusable_ancestor(&mut self, page: @mut Page, node: AbstractNode) {
+        let mut new_focus = Some(node);
+        loop {
+            match new_focus {
+                None => break,
+                Some(node) => {
+                    if node.is_element() &&
+                            node.with_imm_element(|element| element.is_focusable()) {
+                        break
+                    }
+                    new_focus = node.parent_node()
+                }
+            }
+        }
+
+        if page.set_focused_node(new_focus) {
+            page.damage(MatchSelectorsDocumentDamage);
+            page.reflow(ReflowForDisplay, self.chan.clone(), self.compositor);
         }
     }
 
@@ -992,6 +1093,7 @@ fn shut_down_layout(page: @mut Page) {
 
     // Destroy all nodes. Setting frame and js_info to None will trigger our
     // compartment to shutdown, run GC, etc.
+    page.focused_node = None;
     page.frame = None;
     page.js_info = None;
 
@@ -88,7 +88,6 @@ pub mod specified {
                 _ => None
             }
         }
-        #[allow(dead_code)]
         #[inline]
         pub fn parse(input: &ComponentValue) -> Option<LengthOrPercentage> {
             LengthOrPercentage::parse_internal(input, /* negative_ok = */ true)
@@ -3,8 +3,12 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use std::ascii::StrAsciiExt;
+use std::iter::Peekable;
 use cssparser::parse_rule_list;
 use cssparser::ast::*;
+use extra::url::Url;
+
+use common_types::{Au, CSSFloat, specified};
 
 use errors::{ErrorLoggerIterator, log_css_error};
 use stylesheets::{CSSRule, CSSMediaRule, parse_style_rule, parse_nested_at_rule};
@@ -23,32 +27,90 @@ pub struct MediaQueryList {
     media_queries: ~[MediaQuery]
 }
 
-// For now, this is a "Level 2 MQ", ie. a media type.
 struct MediaQuery {
+    qualifier: Option<Qualifier>,
     media_type: MediaQueryType,
-    // TODO: Level 3 MQ expressions
+    expressions: ~[Expression],
 }
 
 
+#[deriving(Eq)]
+enum Qualifier {
+    Only,
+    Not,
+}
+
 enum MediaQueryType {
     All,  // Always true
     MediaType(MediaType),
+    UnknownMediaType,  // Always false
 }
 
-#[deriving(Eq)]
+#[deriving(Eq, Clone)]
 pub enum MediaType {
     Screen,
     Print,
 }
 
+/// A media feature test, such as `(min-width: 600px)`.
+enum Expression {
+    Width(Range<Au>),
+    Height(Range<Au>),
+    /// Width and height, which the viewport's are compared to as the ratio of one to the other.
+    AspectRatio(Range<(i64, i64)>),
+    /// In dots per CSS pixel.
+    Resolution(Range<CSSFloat>),
+    Orientation(Orientation),
+}
+
+/// The `min-` and `max-` prefixes of media features.
+enum Range<T> {
+    Min(T),
+    Max(T),
+    Equal(T),
+}
+
+impl<T: Ord> Range<T> {
+    fn evaluate(&self, value: T) -> bool {
+        match *self {
+            Min(ref min) => value >= *min,
+            Max(ref max) => value <= *max,
+            Equal(ref expected) => value == *expected,
+        }
+    }
+}
+
+#[deriving(Eq)]
+enum Orientation {
+    Portrait,
+    Landscape,
+}
+
+/// The medium that media queries are evaluated against.
+#[deriving(Eq, Clone)]
 pub struct Device {
     media_type: MediaType,
-    // TODO: Level 3 MQ data: viewport size, etc.
+    viewport_width: Au,
+    viewport_height: Au,
+    /// In dots per CSS pixel.
+    resolution: CSSFloat,
+}
+
+impl Device {
+    pub fn new(media_type: MediaType, viewport_width: Au, viewport_height: Au) -> Device {
+        Device {
+            media_type: media_type,
+            viewport_width: viewport_width,
+            viewport_height: viewport_height,
+            // FIXME: Layout doesn't know about high-DPI displays yet.
+            resolution: 1.0,
+        }
+    }
 }
 
 
 pub fn parse_media_rule(rule: AtRule, parent_rules: &mut ~[CSSRule],
-                        namespaces: &NamespaceMap) {
+                        namespaces: &NamespaceMap, base_url: &Url) {
     let media_queries = parse_media_query_list(rule.prelude);
     let block = match rule.block {
         Some(block) => block,
@@ -60,9 +122,9 @@ pub fn parse_media_rule(rule: AtRule, parent_rules: &mut ~[CSSRule],
     let mut rules = ~[];
     for rule in ErrorLoggerIterator(parse_rule_list(block.move_iter())) {
         match rule {
-            QualifiedRule(rule) => parse_style_rule(rule, &mut rules, namespaces),
+            QualifiedRule(rule) => parse_style_rule(rule, &mut rules, namespaces, base_url),
             AtRule(rule) => parse_nested_at_rule(
-                rule.name.to_ascii_lower(), rule, &mut rules, namespaces),
+                rule.name.to_ascii_lower(), rule, &mut rules, namespaces, base_url),
         }
     }
     parent_rules.push(CSSMediaRule(MediaRule {
@@ -73,48 +135,184 @@ pub fn parse_media_rule(rule: AtRule, parent_rules: &mut ~[CSSRule],
 
 
 pub fn parse_media_query_list(input: &[ComponentValue]) -> MediaQueryList {
-    let iter = &mut input.skip_whitespace();
-    let mut next = iter.next();
-    if next.is_none() {
-        return MediaQueryList{ media_queries: ~[MediaQuery{media_type: All}] }
+    let mut iter = input.skip_whitespace().peekable();
+    if iter.peek().is_none() {
+        return MediaQueryList {
+            media_queries: ~[MediaQuery { qualifier: None, media_type: All, expressions: ~[] }]
+        }
     }
     let mut queries = ~[];
     loop {
-        let mq = match next {
-            Some(&Ident(ref value)) => {
-                // FIXME: Workaround for https://github.com/mozilla/rust/issues/10683
-                let value_lower = value.to_ascii_lower(); 
-                match value_lower.as_slice() {
-                    "screen" => Some(MediaQuery{ media_type: MediaType(Screen) }),
-                    "print" => Some(MediaQuery{ media_type: MediaType(Print) }),
-                    "all" => Some(MediaQuery{ media_type: All }),
-                    _ => None
-                }
+        // A query that fails to parse is "not all", and is left out.
+        for mq in parse_media_query(&mut iter).move_iter() {
+            queries.push(mq);
+        }
+        // Skip to the next comma-separated part.
+        loop {
+            match iter.next() {
+                Some(&Comma) => break,
+                None => return MediaQueryList { media_queries: queries },
+                _ => (),
+            }
+        }
+    }
+}
+
+
+/// Parses a media query, up to but not including the comma that ends it.
+fn parse_media_query<'a, I: Iterator<&'a ComponentValue>>(
+        iter: &mut Peekable<&'a ComponentValue, I>) -> Option<MediaQuery> {
+    let mut qualifier = None;
+    let mut media_type = None;
+    let mut expressions = ~[];
+
+    match iter.peek() {
+        Some(& &Ident(ref value)) => {
+            let value_lower = value.to_ascii_lower();
+            match value_lower.as_slice() {
+                "only" => qualifier = Some(Only),
+                "not" => qualifier = Some(Not),
+                _ => (),
+            }
+        }
+        _ => (),
+    }
+    if qualifier.is_some() {
+        iter.next();
+    }
+
+    match iter.peek() {
+        Some(& &Ident(ref value)) => {
+            // FIXME: Workaround for https://github.com/mozilla/rust/issues/10683
+            let value_lower = value.to_ascii_lower();
+            media_type = Some(match value_lower.as_slice() {
+                "screen" => MediaType(Screen),
+                "print" => MediaType(Print),
+                "all" => All,
+                // Reserved words that can't be media types.
+                "only" | "not" | "and" | "or" => return None,
+                _ => UnknownMediaType,
+            });
+        }
+        // Only media queries with a media type can have a qualifier.
+        _ if qualifier.is_some() => return None,
+        _ => (),
+    }
+
+    if media_type.is_some() {
+        iter.next();
+    } else {
+        expressions.push(match iter.next() {
+            Some(&ParenthesisBlock(ref block)) => match parse_expression(block.as_slice()) {
+                Some(expression) => expression,
+                None => return None,
             },
-            _ => None
-        };
+            _ => return None,
+        });
+    }
+
+    // Any further expressions are each introduced with "and".
+    loop {
+        match iter.peek() {
+            None | Some(& &Comma) => break,
+            _ => (),
+        }
         match iter.next() {
-            None => {
-                for mq in mq.move_iter() {
-                    queries.push(mq);
-                }
-                return MediaQueryList{ media_queries: queries }
-            },
-            Some(&Comma) => {
-                for mq in mq.move_iter() {
-                    queries.push(mq);
-                }
+            Some(&Ident(ref value)) if value.eq_ignore_ascii_case("and") => (),
+            _ => return None,
+        }
+        match iter.next() {
+            Some(&ParenthesisBlock(ref block)) => match parse_expression(block.as_slice()) {
+                Some(expression) => expressions.push(expression),
+                None => return None,
             },
-            // Ingnore this comma-separated part
-            _ => loop {
-                match iter.next() {
-                    Some(&Comma) => break,
-                    None => return MediaQueryList{ media_queries: queries },
-                    _ => (),
+            _ => return None,
+        }
+    }
+
+    Some(MediaQuery {
+        qualifier: qualifier,
+        media_type: match media_type {
+            Some(media_type) => media_type,
+            None => All,
+        },
+        expressions: expressions,
+    })
+}
+
+
+/// Parses the inside of a `(feature: value)` expression.
+fn parse_expression(input: &[ComponentValue]) -> Option<Expression> {
+    let mut iter = input.skip_whitespace();
+    let name = match iter.next() {
+        Some(&Ident(ref value)) => value.to_ascii_lower(),
+        _ => return None,
+    };
+    match iter.next() {
+        Some(&Colon) => (),
+        // TODO: features in a boolean context, such as `(color)`.
+        _ => return None,
+    }
+    let value: ~[&ComponentValue] = iter.collect();
+
+    let feature = if name.starts_with("min-") || name.starts_with("max-") {
+        name.slice_from(4)
+    } else {
+        name.as_slice()
+    };
+    match (feature, value.as_slice()) {
+        ("width", [value]) => parse_length(value).map(|length| Width(range(name, length))),
+        ("height", [value]) => parse_length(value).map(|length| Height(range(name, length))),
+        ("aspect-ratio", [&Number(ref width), &Delim('/'), &Number(ref height)]) => {
+            match (width.int_value, height.int_value) {
+                (Some(width), Some(height)) if width > 0 && height > 0 => {
+                    Some(AspectRatio(range(name, (width, height))))
                 }
-            },
+                _ => None,
+            }
+        }
+        ("resolution", [&Dimension(ref value, ref unit)]) if value.value > 0. => {
+            let unit_lower = unit.to_ascii_lower();
+            let dppx = match unit_lower.as_slice() {
+                "dppx" => value.value,
+                "dpi" => value.value / 96.,
+                "dpcm" => value.value * 2.54 / 96.,
+                _ => return None,
+            };
+            Some(Resolution(range(name, dppx)))
         }
-        next = iter.next();
+        ("orientation", [&Ident(ref value)]) if feature == name.as_slice() => {
+            let value_lower = value.to_ascii_lower();
+            match value_lower.as_slice() {
+                "portrait" => Some(Orientation(Portrait)),
+                "landscape" => Some(Orientation(Landscape)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+
+/// Applies the `min-` or `max-` prefix of a feature name to its value.
+fn range<T>(name: &str, value: T) -> Range<T> {
+    if name.starts_with("min-") {
+        Min(value)
+    } else if name.starts_with("max-") {
+        Max(value)
+    } else {
+        Equal(value)
+    }
+}
+
+
+/// Lengths in media queries are absolute; `em` and `ex` are relative to the initial font size.
+fn parse_length(input: &ComponentValue) -> Option<Au> {
+    match specified::Length::parse_non_negative(input) {
+        Some(specified::Au_(length)) => Some(length),
+        Some(specified::Em(value)) => Some(Au::from_frac_px(value * 16.)),
+        Some(specified::Ex(value)) => Some(Au::from_frac_px(value * 8.)),
+        None => None,
     }
 }
 
@@ -122,11 +320,99 @@ pub fn parse_media_query_list(input: &[ComponentValue]) -> MediaQueryList {
 impl MediaQueryList {
     pub fn evaluate(&self, device: &Device) -> bool {
         self.media_queries.iter().any(|mq| {
-            match mq.media_type {
+            let media_type_matches = match mq.media_type {
                 MediaType(media_type) => media_type == device.media_type,
                 All => true,
+                UnknownMediaType => false,
+            };
+            let matches = media_type_matches &&
+                mq.expressions.iter().all(|expression| expression.evaluate(device));
+            match mq.qualifier {
+                Some(Not) => !matches,
+                _ => matches,
             }
-            // TODO: match Level 3 expressions
         })
     }
 }
+
+
+impl Expression {
+    fn evaluate(&self, device: &Device) -> bool {
+        match *self {
+            Width(ref range) => range.evaluate(device.viewport_width),
+            Height(ref range) => range.evaluate(device.viewport_height),
+            AspectRatio(ref range) => {
+                // Ratios are compared by cross-multiplying, which is exact.
+                let width = *device.viewport_width as i64;
+                let height = *device.viewport_height as i64;
+                height > 0 && match *range {
+                    Min((min_width, min_height)) => width * min_height >= min_width * height,
+                    Max((max_width, max_height)) => width * max_height <= max_width * height,
+                    Equal((expected_width, expected_height)) => {
+                        width * expected_height == expected_width * height
+                    }
+                }
+            }
+            Resolution(ref range) => range.evaluate(device.resolution),
+            Orientation(orientation) => {
+                let actual = if device.viewport_height >= device.viewport_width {
+                    Portrait
+                } else {
+                    Landscape
+                };
+                orientation == actual
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use cssparser;
+    use common_types::Au;
+    use super::*;
+
+    fn evaluate(input: &str, device: &Device) -> bool {
+        let input = cssparser::tokenize(input).map(|(v, _)| v).to_owned_vec();
+        parse_media_query_list(input).evaluate(device)
+    }
+
+    #[test]
+    fn test_media_types() {
+        let device = Device::new(Screen, Au::from_px(800), Au::from_px(600));
+        assert!(evaluate("", &device));
+        assert!(evaluate("screen", &device));
+        assert!(evaluate("print, screen", &device));
+        assert!(!evaluate("print", &device));
+        assert!(!evaluate("tv", &device));
+        assert!(evaluate("not print", &device));
+        assert!(evaluate("not tv", &device));
+        assert!(evaluate("only screen", &device));
+        assert!(!evaluate("only", &device));
+    }
+
+    #[test]
+    fn test_expressions() {
+        let device = Device::new(Screen, Au::from_px(800), Au::from_px(600));
+        assert!(evaluate("(min-width: 600px)", &device));
+        assert!(!evaluate("(max-width: 600px)", &device));
+        assert!(evaluate("screen and (max-width: 50em) and (height: 600px)", &device));
+        assert!(!evaluate("print and (min-width: 600px)", &device));
+        assert!(evaluate("not screen and (max-width: 600px)", &device));
+        assert!(evaluate("(orientation: landscape)", &device));
+        assert!(!evaluate("(orientation: portrait)", &device));
+        assert!(evaluate("(min-aspect-ratio: 4/3)", &device));
+        assert!(!evaluate("(min-aspect-ratio: 16/9)", &device));
+        assert!(evaluate("(aspect-ratio: 4/3)", &device));
+        assert!(evaluate("(max-aspect-ratio: 8/6)", &device));
+        assert!(!evaluate("(aspect-ratio: 401/300)", &device));
+        assert!(evaluate("(resolution: 96dpi)", &device));
+        assert!(!evaluate("(min-resolution: 2dppx)", &device));
+
+        // Invalid queries are "not all", without affecting the others in the list.
+        assert!(!evaluate("(min-width 600px)", &device));
+        assert!(!evaluate("(min-orientation: portrait)", &device));
+        assert!(evaluate("(bogus: 1), screen", &device));
+    }
+}
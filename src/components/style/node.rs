@@ -16,6 +16,10 @@ pub trait TNode<E:TElement> : Clone {
     fn is_document(&self) -> bool;
     fn is_element(&self) -> bool;
 
+    /// Returns true if this node has no children other than comments and processing
+    /// instructions, as `:empty` requires.
+    fn is_empty(&self) -> bool;
+
     /// FIXME(pcwalton): This should not use the `with` pattern.
     fn with_element<'a, R>(&self, f: |&E| -> R) -> R;
 
@@ -28,5 +32,11 @@ pub trait TElement {
     fn get_local_name<'a>(&'a self) -> &'a str;
     fn get_namespace<'a>(&'a self) -> &'a Namespace;
     fn get_hover_state(&self) -> bool;
+    fn get_focus_state(&self) -> bool;
+    fn get_checked_state(&self) -> bool;
+
+    /// Returns `Some(true)` if this is a form control that is disabled, `Some(false)` if it is
+    /// one that is enabled, and `None` if it can be neither.
+    fn get_disabled_state(&self) -> Option<bool>;
 }
 
@@ -6,6 +6,7 @@
 
 use std::ascii::StrAsciiExt;
 pub use extra::arc::Arc;
+pub use extra::url::Url;
 pub use cssparser::*;
 pub use cssparser::ast::*;
 
@@ -71,13 +72,13 @@ pub mod longhands {
             % endif
             pub use self::computed_value::*;
             ${caller.body()}
-            pub fn parse_declared(input: &[ComponentValue])
+            pub fn parse_declared(input: &[ComponentValue], base_url: &Url)
                                -> Option<DeclaredValue<SpecifiedValue>> {
                 match CSSWideKeyword::parse(input) {
                     Some(Some(keyword)) => Some(CSSWideKeyword(keyword)),
                     Some(None) => Some(CSSWideKeyword(${
                         "Inherit" if inherited else "Initial"})),
-                    None => parse_specified(input),
+                    None => parse_specified(input, base_url),
                 }
             }
         }
@@ -86,9 +87,9 @@ pub mod longhands {
     <%def name="longhand(name, inherited=False, no_super=False)">
         <%self:raw_longhand name="${name}" inherited="${inherited}">
             ${caller.body()}
-            pub fn parse_specified(input: &[ComponentValue])
+            pub fn parse_specified(input: &[ComponentValue], base_url: &Url)
                                -> Option<DeclaredValue<SpecifiedValue>> {
-                parse(input).map(super::SpecifiedValue)
+                parse(input, base_url).map(super::SpecifiedValue)
             }
         </%self:raw_longhand>
     </%def>
@@ -96,7 +97,7 @@ pub mod longhands {
     <%def name="single_component_value(name, inherited=False)">
         <%self:longhand name="${name}" inherited="${inherited}">
             ${caller.body()}
-            pub fn parse(input: &[ComponentValue]) -> Option<SpecifiedValue> {
+            pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
                 one_component_value(input).and_then(from_component_value)
             }
         </%self:longhand>
@@ -212,7 +213,7 @@ pub mod longhands {
             #[inline] pub fn get_initial_value() -> computed_value::T {
                 Au::from_px(3)  // medium
             }
-            pub fn parse(input: &[ComponentValue]) -> Option<SpecifiedValue> {
+            pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
                 one_component_value(input).and_then(parse_border_width)
             }
             pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
@@ -270,6 +271,37 @@ pub mod longhands {
     ${single_keyword("float", "none left right")}
     ${single_keyword("clear", "none left right both")}
 
+    // CSS 2.1, Section 9.9 - Layered presentation
+
+    <%self:single_component_value name="z-index">
+        pub use to_computed_value = super::computed_as_specified;
+        pub type SpecifiedValue = computed_value::T;
+        pub mod computed_value {
+            #[deriving(Eq, Clone)]
+            pub enum T {
+                Auto,
+                Integer(i32),
+            }
+        }
+        #[inline] pub fn get_initial_value() -> computed_value::T { Auto }
+        /// auto | <integer>
+        pub fn from_component_value(input: &ComponentValue) -> Option<SpecifiedValue> {
+            match input {
+                &Ident(ref value) if value.eq_ignore_ascii_case("auto") => Some(Auto),
+                &Number(ref value) => match value.int_value {
+                    Some(value) => Some(Integer(value as i32)),
+                    None => None,
+                },
+                _ => None,
+            }
+        }
+    </%self:single_component_value>
+
+    // CSS 2.1, Section 9.10 - Text direction
+
+    ${single_keyword("direction", "ltr rtl", inherited=True)}
+    ${single_keyword("unicode-bidi", "normal embed bidi-override")}
+
     // CSS 2.1, Section 10 - Visual formatting model details
 
     ${predefined_type("width", "LengthOrPercentageOrAuto",
@@ -387,7 +419,7 @@ pub mod longhands {
 
 
     // CSS 2.1, Section 11 - Visual effects
-    ${single_keyword("overflow", "visible hidden", inherited=False)} // TODO: scroll auto
+    ${single_keyword("overflow", "visible hidden scroll auto", inherited=False)}
 
     // TODO: collapse. Well, do tables first.
     ${single_keyword("visibility", "visible hidden", inherited=True)}
@@ -395,11 +427,24 @@ pub mod longhands {
     // CSS 2.1, Section 12 - Generated content, automatic numbering, and lists
 
     <%self:longhand name="content" inherited="False">
+            use servo_util::url::parse_url;
             pub use to_computed_value = super::computed_as_specified;
             pub mod computed_value {
+                use super::super::Url;
+                use super::super::list_style_type;
                 #[deriving(Eq, Clone)]
                 pub enum Content {
                     StringContent(~str),
+                    UrlContent(Url),
+                    /// counter(name, style)
+                    CounterContent(~str, list_style_type::T),
+                    /// counters(name, separator, style)
+                    CountersContent(~str, ~str, list_style_type::T),
+                    AttrContent(~str),
+                    OpenQuote,
+                    CloseQuote,
+                    NoOpenQuote,
+                    NoCloseQuote,
                 }
                 #[deriving(Eq, Clone)]
                 pub enum T {
@@ -411,9 +456,9 @@ pub mod longhands {
             pub type SpecifiedValue = computed_value::T;
             #[inline] pub fn get_initial_value() -> computed_value::T  { normal }
 
-            // normal | none | [ <string> ]+
-            // TODO: <uri>, <counter>, attr(<identifier>), open-quote, close-quote, no-open-quote, no-close-quote
-            pub fn parse(input: &[ComponentValue]) -> Option<SpecifiedValue> {
+            // normal | none | [ <string> | <uri> | <counter> | attr(<identifier>) | open-quote |
+            //                   close-quote | no-open-quote | no-close-quote ]+
+            pub fn parse(input: &[ComponentValue], base_url: &Url) -> Option<SpecifiedValue> {
                 match one_component_value(input) {
                     Some(&Ident(ref keyword)) => match keyword.to_ascii_lower().as_slice() {
                         "normal" => return Some(normal),
@@ -427,12 +472,184 @@ pub mod longhands {
                     match component_value {
                         &String(ref value)
                         => content.push(StringContent(value.to_owned())),
+                        &URL(ref url)
+                        => content.push(UrlContent(parse_url(url.as_slice(),
+                                                             Some(base_url.clone())))),
+                        &Function(ref name, ref arguments) => {
+                            match parse_function(name.as_slice(), arguments.as_slice()) {
+                                Some(item) => content.push(item),
+                                None => return None,
+                            }
+                        }
+                        &Ident(ref keyword) => match keyword.to_ascii_lower().as_slice() {
+                            "open-quote" => content.push(OpenQuote),
+                            "close-quote" => content.push(CloseQuote),
+                            "no-open-quote" => content.push(NoOpenQuote),
+                            "no-close-quote" => content.push(NoCloseQuote),
+                            _ => return None,
+                        },
                         _ => return None  // invalid/unsupported value
                     }
                 }
                 Some(Content(content))
             }
+
+            // counter(<identifier> [, <list-style-type>]?) |
+            // counters(<identifier>, <string> [, <list-style-type>]?) | attr(<identifier>)
+            fn parse_function(name: &str, arguments: &[ComponentValue]) -> Option<Content> {
+                // Split the comma-separated arguments, each of which is a single component value.
+                let mut iter = arguments.skip_whitespace();
+                let mut arguments = ~[];
+                loop {
+                    match iter.next() {
+                        Some(argument) => arguments.push(argument),
+                        None => return None,
+                    }
+                    match iter.next() {
+                        Some(&Comma) => (),
+                        None => break,
+                        _ => return None,
+                    }
+                }
+                let name_lower = name.to_ascii_lower();
+                match (name_lower.as_slice(), arguments.as_slice()) {
+                    ("attr", [&Ident(ref attr_name)]) => Some(AttrContent(attr_name.to_owned())),
+                    ("counter", [&Ident(ref counter_name)])
+                    => Some(CounterContent(counter_name.to_owned(), list_style_type::decimal)),
+                    ("counter", [&Ident(ref counter_name), style]) => {
+                        list_style_type::from_component_value(style).map(|style| {
+                            CounterContent(counter_name.to_owned(), style)
+                        })
+                    }
+                    ("counters", [&Ident(ref counter_name), &String(ref separator)]) => {
+                        Some(CountersContent(counter_name.to_owned(),
+                                             separator.to_owned(),
+                                             list_style_type::decimal))
+                    }
+                    ("counters", [&Ident(ref counter_name), &String(ref separator), style]) => {
+                        list_style_type::from_component_value(style).map(|style| {
+                            CountersContent(counter_name.to_owned(), separator.to_owned(), style)
+                        })
+                    }
+                    _ => None,
+                }
+            }
     </%self:longhand>
+
+    <%def name="counter_action(name, default_value)">
+        <%self:longhand name="${name}">
+            pub use to_computed_value = super::computed_as_specified;
+            pub mod computed_value {
+                /// Pairs of counter names and values.
+                pub type T = ~[(~str, i32)];
+            }
+            pub type SpecifiedValue = computed_value::T;
+            #[inline] pub fn get_initial_value() -> computed_value::T { ~[] }
+            /// none | [ <identifier> <integer>? ]+
+            pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+                match one_component_value(input) {
+                    Some(&Ident(ref value)) if value.eq_ignore_ascii_case("none") => return Some(~[]),
+                    _ => ()
+                }
+                let mut counters = ~[];
+                // Whether the last component value was a counter name, which an integer may
+                // follow.
+                let mut after_name = false;
+                for component_value in input.skip_whitespace() {
+                    match component_value {
+                        &Ident(ref name) => {
+                            counters.push((name.to_owned(), ${default_value}));
+                            after_name = true;
+                        }
+                        &Number(ref value) if after_name => {
+                            let value = match value.int_value {
+                                Some(value) => value as i32,
+                                None => return None,
+                            };
+                            let (name, _) = counters.pop();
+                            counters.push((name, value));
+                            after_name = false;
+                        }
+                        _ => return None,
+                    }
+                }
+                if counters.is_empty() { None } else { Some(counters) }
+            }
+        </%self:longhand>
+    </%def>
+
+    ${counter_action("counter-increment", "1")}
+    ${counter_action("counter-reset", "0")}
+
+    <%self:longhand name="quotes" inherited="True">
+        pub use to_computed_value = super::computed_as_specified;
+        pub mod computed_value {
+            /// Pairs of open and close quotes, outermost first.
+            pub type T = ~[(~str, ~str)];
+        }
+        pub type SpecifiedValue = computed_value::T;
+        #[inline] pub fn get_initial_value() -> computed_value::T {
+            ~[(~"\u201c", ~"\u201d"), (~"\u2018", ~"\u2019")]
+        }
+        /// none | [ <string> <string> ]+
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            match one_component_value(input) {
+                Some(&Ident(ref value)) if value.eq_ignore_ascii_case("none") => return Some(~[]),
+                _ => ()
+            }
+            let mut quotes = ~[];
+            let mut iter = input.skip_whitespace();
+            loop {
+                let open = match iter.next() {
+                    Some(&String(ref value)) => value.to_owned(),
+                    None => break,
+                    _ => return None,
+                };
+                let close = match iter.next() {
+                    Some(&String(ref value)) => value.to_owned(),
+                    _ => return None,
+                };
+                quotes.push((open, close));
+            }
+            if quotes.is_empty() { None } else { Some(quotes) }
+        }
+    </%self:longhand>
+
+    ${new_style_struct("List")}
+
+    ${single_keyword("list-style-position", "outside inside", inherited=True)}
+
+    // TODO: armenian, georgian, lower-greek, decimal-leading-zero
+    ${single_keyword("list-style-type",
+                     "disc none circle square decimal lower-alpha upper-alpha lower-roman upper-roman",
+                     inherited=True)}
+
+    <%self:longhand name="list-style-image" inherited="True">
+        use servo_util::url::parse_url;
+        // The computed value is the same as the specified value.
+        pub use to_computed_value = super::computed_as_specified;
+        pub mod computed_value {
+            use super::super::Url;
+            pub type T = Option<Url>;
+        }
+        pub type SpecifiedValue = computed_value::T;
+        #[inline] pub fn get_initial_value() -> SpecifiedValue {
+            None
+        }
+        pub fn from_component_value(input: &ComponentValue, base_url: &Url)
+                                    -> Option<SpecifiedValue> {
+            match input {
+                &URL(ref url) => Some(Some(parse_url(url.as_slice(), Some(base_url.clone())))),
+                &Ident(ref value) if value.eq_ignore_ascii_case("none") => Some(None),
+                _ => None,
+            }
+        }
+        /// none | <url>
+        pub fn parse(input: &[ComponentValue], base_url: &Url) -> Option<SpecifiedValue> {
+            one_component_value(input).and_then(|value| from_component_value(value, base_url))
+        }
+    </%self:longhand>
+
     // CSS 2.1, Section 13 - Paged media
 
     // CSS 2.1, Section 14 - Colors and Backgrounds
@@ -442,6 +659,170 @@ pub mod longhands {
     ${predefined_type("background-color", "CSSColor",
                       "RGBA(RGBA { red: 0., green: 0., blue: 0., alpha: 0. }) /* transparent */")}
 
+    <%self:longhand name="background-image">
+        use servo_util::url::parse_url;
+        // The computed value is the same as the specified value.
+        pub use to_computed_value = super::computed_as_specified;
+        pub mod computed_value {
+            use super::super::Url;
+            pub type T = Option<Url>;
+        }
+        pub type SpecifiedValue = computed_value::T;
+        #[inline] pub fn get_initial_value() -> SpecifiedValue {
+            None
+        }
+        pub fn from_component_value(input: &ComponentValue, base_url: &Url)
+                                    -> Option<SpecifiedValue> {
+            match input {
+                &URL(ref url) => Some(Some(parse_url(url.as_slice(), Some(base_url.clone())))),
+                &Ident(ref value) if value.eq_ignore_ascii_case("none") => Some(None),
+                _ => None,
+            }
+        }
+        /// none | <url>
+        pub fn parse(input: &[ComponentValue], base_url: &Url) -> Option<SpecifiedValue> {
+            one_component_value(input).and_then(|value| from_component_value(value, base_url))
+        }
+    </%self:longhand>
+
+    ${single_keyword("background-repeat", "repeat repeat-x repeat-y no-repeat")}
+
+    // FIXME: `fixed` is painted as if it were `scroll`, since the compositor scrolls backgrounds
+    // with the page.
+    ${single_keyword("background-attachment", "scroll fixed")}
+
+    <%self:longhand name="background-position">
+        #[deriving(Clone)]
+        pub struct SpecifiedValue {
+            horizontal: specified::LengthOrPercentage,
+            vertical: specified::LengthOrPercentage,
+        }
+        pub mod computed_value {
+            use super::super::computed::LengthOrPercentage;
+            #[deriving(Eq, Clone)]
+            pub struct T {
+                horizontal: LengthOrPercentage,
+                vertical: LengthOrPercentage,
+            }
+        }
+        #[inline] pub fn get_initial_value() -> computed_value::T {
+            computed_value::T {
+                horizontal: computed::LP_Percentage(0.0),
+                vertical: computed::LP_Percentage(0.0),
+            }
+        }
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> computed_value::T {
+            computed_value::T {
+                horizontal: computed::compute_LengthOrPercentage(value.horizontal, context),
+                vertical: computed::compute_LengthOrPercentage(value.vertical, context),
+            }
+        }
+
+        enum PositionComponent {
+            PositionLength(specified::LengthOrPercentage),
+            Left,
+            Center,
+            Right,
+            Top,
+            Bottom,
+        }
+        impl PositionComponent {
+            fn parse(input: &ComponentValue) -> Option<PositionComponent> {
+                match get_ident_lower(input) {
+                    Some(keyword) => match keyword.as_slice() {
+                        "left" => Some(Left),
+                        "center" => Some(Center),
+                        "right" => Some(Right),
+                        "top" => Some(Top),
+                        "bottom" => Some(Bottom),
+                        _ => None,
+                    },
+                    None => specified::LengthOrPercentage::parse(input).map(PositionLength),
+                }
+            }
+            fn to_length_or_percentage(self) -> specified::LengthOrPercentage {
+                match self {
+                    PositionLength(value) => value,
+                    Left | Top => specified::LP_Percentage(0.0),
+                    Center => specified::LP_Percentage(0.5),
+                    Right | Bottom => specified::LP_Percentage(1.0),
+                }
+            }
+            fn is_vertical_keyword(&self) -> bool {
+                match *self { Top | Bottom => true, _ => false }
+            }
+            fn is_horizontal_keyword(&self) -> bool {
+                match *self { Left | Right => true, _ => false }
+            }
+            fn is_keyword(&self) -> bool {
+                match *self { PositionLength(_) => false, _ => true }
+            }
+        }
+
+        /// Whether `input` can be part of a `background-position` value.
+        pub fn is_position_component(input: &ComponentValue) -> bool {
+            PositionComponent::parse(input).is_some()
+        }
+
+        /// Parses a position from its one or two components. With one component, the other
+        /// direction is centered. Keywords may be given in either order; lengths and percentages
+        /// come horizontal first.
+        pub fn from_components(first: &ComponentValue, second: Option<&ComponentValue>)
+                               -> Option<SpecifiedValue> {
+            let first = match PositionComponent::parse(first) {
+                Some(component) => component,
+                None => return None,
+            };
+            let second = match second {
+                Some(second) => match PositionComponent::parse(second) {
+                    Some(component) => component,
+                    None => return None,
+                },
+                None if first.is_vertical_keyword() => {
+                    return Some(SpecifiedValue {
+                        horizontal: specified::LP_Percentage(0.5),
+                        vertical: first.to_length_or_percentage(),
+                    })
+                }
+                None => Center,
+            };
+            let (horizontal, vertical) =
+                if first.is_vertical_keyword() || second.is_horizontal_keyword() {
+                    // Swapped keywords, as in `top left`.
+                    if !first.is_keyword() || !second.is_keyword() {
+                        return None
+                    }
+                    (second, first)
+                } else {
+                    (first, second)
+                };
+            if horizontal.is_vertical_keyword() || vertical.is_horizontal_keyword() {
+                return None
+            }
+            Some(SpecifiedValue {
+                horizontal: horizontal.to_length_or_percentage(),
+                vertical: vertical.to_length_or_percentage(),
+            })
+        }
+
+        /// [ <percentage> | <length> | left | center | right ]
+        /// [ <percentage> | <length> | top | center | bottom ]?
+        /// | [ left | center | right ] || [ top | center | bottom ]
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            let mut iter = input.skip_whitespace();
+            let first = match iter.next() {
+                Some(first) => first,
+                None => return None,
+            };
+            let second = iter.next();
+            if iter.next().is_some() {
+                return None
+            }
+            from_components(first, second)
+        }
+    </%self:longhand>
+
 
     ${new_style_struct("Color")}
 
@@ -454,7 +835,8 @@ pub mod longhands {
         #[inline] pub fn get_initial_value() -> computed_value::T {
             RGBA { red: 0., green: 0., blue: 0., alpha: 1. }  /* black */
         }
-        pub fn parse_specified(input: &[ComponentValue]) -> Option<DeclaredValue<SpecifiedValue>> {
+        pub fn parse_specified(input: &[ComponentValue], _base_url: &Url)
+                               -> Option<DeclaredValue<SpecifiedValue>> {
             match one_component_value(input).and_then(Color::parse) {
                 Some(RGBA(rgba)) => Some(SpecifiedValue(rgba)),
                 Some(CurrentColor) => Some(CSSWideKeyword(Inherit)),
@@ -487,7 +869,7 @@ pub mod longhands {
         /// <familiy-name>#
         /// <familiy-name> = <string> | [ <ident>+ ]
         /// TODO: <generic-familiy>
-        pub fn parse(input: &[ComponentValue]) -> Option<SpecifiedValue> {
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
             from_iter(input.skip_whitespace())
         }
         pub fn from_iter<'a>(mut iter: SkipWhitespaceIterator<'a>) -> Option<SpecifiedValue> {
@@ -661,8 +1043,46 @@ pub mod longhands {
 
     ${new_style_struct("Text")}
 
-    // TODO: initial value should be 'start' (CSS Text Level 3, direction-dependent.)
-    ${single_keyword("text-align", "left right center justify", inherited=True)}
+    // 'start' and 'end' are from CSS Text Level 3, and follow 'direction'.
+    ${single_keyword("text-align", "start end left right center justify", inherited=True)}
+
+    ${predefined_type("text-indent", "LengthOrPercentage",
+                      "computed::LP_Length(Au(0))",
+                      inherited=True)}
+
+    <%def name="spacing(name)">
+        <%self:single_component_value name="${name}" inherited="True">
+            #[deriving(Clone)]
+            pub enum SpecifiedValue {
+                SpecifiedNormal,
+                SpecifiedLength(specified::Length),
+            }
+            pub mod computed_value {
+                use super::super::Au;
+                /// `None` is `normal`.
+                pub type T = Option<Au>;
+            }
+            #[inline] pub fn get_initial_value() -> computed_value::T { None }
+            pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                                  -> computed_value::T {
+                match value {
+                    SpecifiedNormal => None,
+                    SpecifiedLength(value) => Some(computed::compute_Au(value, context)),
+                }
+            }
+            /// normal | <length>
+            pub fn from_component_value(input: &ComponentValue) -> Option<SpecifiedValue> {
+                match input {
+                    &Ident(ref value) if value.eq_ignore_ascii_case("normal")
+                    => Some(SpecifiedNormal),
+                    _ => specified::Length::parse(input).map(SpecifiedLength),
+                }
+            }
+        </%self:single_component_value>
+    </%def>
+
+    ${spacing("letter-spacing")}
+    ${spacing("word-spacing")}
 
     <%self:longhand name="text-decoration">
         pub use to_computed_value = super::computed_as_specified;
@@ -682,7 +1102,7 @@ pub mod longhands {
             none
         }
         /// none | [ underline || overline || line-through || blink ]
-        pub fn parse(input: &[ComponentValue]) -> Option<SpecifiedValue> {
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
             let mut result = SpecifiedValue {
                 underline: false, overline: false, line_through: false,
             };
@@ -709,9 +1129,56 @@ pub mod longhands {
         }
     </%self:longhand>
 
-    ${single_keyword("white-space", "normal pre", inherited=True)}
+    ${single_keyword("white-space", "normal pre nowrap pre-wrap pre-line", inherited=True)}
+    ${single_keyword("word-break", "normal break-all keep-all", inherited=True)}
+    ${single_keyword("overflow-wrap", "normal break-word", inherited=True)}
 
     // CSS 2.1, Section 17 - Tables
+    ${new_style_struct("Table")}
+
+    ${single_keyword("table-layout", "auto fixed")}
+    ${single_keyword("caption-side", "top bottom", inherited=True)}
+
+    <%self:longhand name="border-spacing" inherited="True">
+        #[deriving(Clone)]
+        pub struct SpecifiedValue {
+            horizontal: specified::Length,
+            vertical: specified::Length,
+        }
+        pub mod computed_value {
+            use super::super::Au;
+            #[deriving(Eq, Clone)]
+            pub struct T {
+                horizontal: Au,
+                vertical: Au,
+            }
+        }
+        #[inline] pub fn get_initial_value() -> computed_value::T {
+            computed_value::T { horizontal: Au(0), vertical: Au(0) }
+        }
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> computed_value::T {
+            computed_value::T {
+                horizontal: computed::compute_Au(value.horizontal, context),
+                vertical: computed::compute_Au(value.vertical, context),
+            }
+        }
+        /// <length> <length>?
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Option<SpecifiedValue> {
+            let mut lengths = ~[];
+            for component_value in input.skip_whitespace() {
+                match specified::Length::parse_non_negative(component_value) {
+                    Some(length) => lengths.push(length),
+                    None => return None,
+                }
+            }
+            match lengths.len() {
+                1 => Some(SpecifiedValue { horizontal: lengths[0], vertical: lengths[0] }),
+                2 => Some(SpecifiedValue { horizontal: lengths[0], vertical: lengths[1] }),
+                _ => None,
+            }
+        }
+    </%self:longhand>
 
     // CSS 2.1, Section 18 - User interface
 }
@@ -733,7 +1200,8 @@ pub mod shorthands {
                     ${sub_property.ident}: Option<${sub_property.ident}::SpecifiedValue>,
                 % endfor
             }
-            pub fn parse(input: &[ComponentValue]) -> Option<Longhands> {
+            #[allow(unused_variable)]
+            pub fn parse(input: &[ComponentValue], base_url: &Url) -> Option<Longhands> {
                 ${caller.body()}
             }
         }
@@ -767,11 +1235,72 @@ pub mod shorthands {
     </%def>
 
 
-    // TODO: other background-* properties
-    <%self:shorthand name="background" sub_properties="background-color">
-        one_component_value(input).and_then(specified::CSSColor::parse).map(|color| {
-            Longhands { background_color: Some(color) }
-        })
+    <%self:shorthand name="background" sub_properties="background-color background-image
+            background-repeat background-attachment background-position">
+        let mut color = None;
+        let mut image = None;
+        let mut repeat = None;
+        let mut attachment = None;
+        let mut position = None;
+        let mut any = false;
+        let mut iter = input.skip_whitespace();
+        loop {
+            let component_value = match iter.next() {
+                Some(component_value) => component_value,
+                None => break,
+            };
+            if color.is_none() {
+                match specified::CSSColor::parse(component_value) {
+                    Some(c) => { color = Some(c); any = true; continue },
+                    None => ()
+                }
+            }
+            if image.is_none() {
+                match background_image::from_component_value(component_value, base_url) {
+                    Some(i) => { image = Some(i); any = true; continue },
+                    None => ()
+                }
+            }
+            if repeat.is_none() {
+                match background_repeat::from_component_value(component_value) {
+                    Some(r) => { repeat = Some(r); any = true; continue },
+                    None => ()
+                }
+            }
+            if attachment.is_none() {
+                match background_attachment::from_component_value(component_value) {
+                    Some(a) => { attachment = Some(a); any = true; continue },
+                    None => ()
+                }
+            }
+            if position.is_none() && background_position::is_position_component(component_value) {
+                // The position takes one or two adjacent components.
+                let mut copied_iter = iter.clone();
+                let second = match copied_iter.next() {
+                    Some(second) if background_position::is_position_component(second) => {
+                        iter = copied_iter;
+                        Some(second)
+                    }
+                    _ => None,
+                };
+                match background_position::from_components(component_value, second) {
+                    Some(p) => { position = Some(p); any = true; continue },
+                    None => return None,
+                }
+            }
+            return None
+        }
+        if any {
+            Some(Longhands {
+                background_color: color,
+                background_image: image,
+                background_repeat: repeat,
+                background_attachment: attachment,
+                background_position: position,
+            })
+        } else {
+            None
+        }
     </%self:shorthand>
 
     ${four_sides_shorthand("margin", "margin-%s", "margin_top::from_component_value")}
@@ -921,6 +1450,57 @@ pub mod shorthands {
         })
     </%self:shorthand>
 
+    <%self:shorthand name="list-style"
+                     sub_properties="list-style-position list-style-image list-style-type">
+        // `none` is valid for both list-style-type and list-style-image, so it is counted
+        // separately and assigned to whichever of them is left unset.
+        let mut nb_nones = 0u;
+        let mut position = None;
+        let mut image = None;
+        let mut type_ = None;
+        for component_value in input.skip_whitespace() {
+            if get_ident_lower(component_value).filtered(
+                    |v| v.eq_ignore_ascii_case("none")).is_some() {
+                nb_nones += 1;
+                continue;
+            }
+            if position.is_none() {
+                match list_style_position::from_component_value(component_value) {
+                    Some(p) => { position = Some(p); continue },
+                    None => ()
+                }
+            }
+            if image.is_none() {
+                match list_style_image::from_component_value(component_value, base_url) {
+                    Some(i) => { image = Some(i); continue },
+                    None => ()
+                }
+            }
+            if type_.is_none() {
+                match list_style_type::from_component_value(component_value) {
+                    Some(t) => { type_ = Some(t); continue },
+                    None => ()
+                }
+            }
+            return None
+        }
+        // A `none` sets whichever of list-style-type and list-style-image is not otherwise
+        // specified.
+        let (type_, image) = match (nb_nones, type_, image) {
+            (0, None, None) if position.is_none() => return None,
+            (0, type_, image) => (type_, image),
+            (1, None, None) | (2, None, None) => (Some(list_style_type::none), Some(None)),
+            (1, Some(t), None) => (Some(t), Some(None)),
+            (1, None, Some(i)) => (Some(list_style_type::none), Some(i)),
+            _ => return None,
+        };
+        Some(Longhands {
+            list_style_position: position,
+            list_style_image: image,
+            list_style_type: type_,
+        })
+    </%self:shorthand>
+
 }
 
 
@@ -930,12 +1510,13 @@ pub struct PropertyDeclarationBlock {
 }
 
 
-pub fn parse_style_attribute(input: &str) -> PropertyDeclarationBlock {
-    parse_property_declaration_list(tokenize(input))
+pub fn parse_style_attribute(input: &str, base_url: &Url) -> PropertyDeclarationBlock {
+    parse_property_declaration_list(tokenize(input), base_url)
 }
 
 
-pub fn parse_property_declaration_list<I: Iterator<Node>>(input: I) -> PropertyDeclarationBlock {
+pub fn parse_property_declaration_list<I: Iterator<Node>>(input: I, base_url: &Url)
+                                                         -> PropertyDeclarationBlock {
     let mut important = ~[];
     let mut normal = ~[];
     for item in ErrorLoggerIterator(parse_declaration_list(input)) {
@@ -945,7 +1526,7 @@ pub fn parse_property_declaration_list<I: Iterator<Node>>(input: I) -> PropertyD
             Declaration(Declaration{ location: l, name: n, value: v, important: i}) => {
                 // TODO: only keep the last valid declaration for a given name.
                 let list = if i { &mut important } else { &mut normal };
-                match PropertyDeclaration::parse(n, v, list) {
+                match PropertyDeclaration::parse(n, v, list, base_url) {
                     UnknownProperty => log_css_error(l, format!(
                         "Unsupported property: {}:{}", n, v.iter().to_css())),
                     InvalidValue => log_css_error(l, format!(
@@ -1004,13 +1585,14 @@ enum PropertyDeclarationParseResult {
 
 impl PropertyDeclaration {
     pub fn parse(name: &str, value: &[ComponentValue],
-                 result_list: &mut ~[PropertyDeclaration]) -> PropertyDeclarationParseResult {
+                 result_list: &mut ~[PropertyDeclaration],
+                 base_url: &Url) -> PropertyDeclarationParseResult {
         // FIXME: local variable to work around Rust #10683
         let name_lower = name.to_ascii_lower();
         match name_lower.as_slice() {
             % for property in LONGHANDS:
                 "${property.name}" => result_list.push(${property.ident}_declaration(
-                    match longhands::${property.ident}::parse_declared(value) {
+                    match longhands::${property.ident}::parse_declared(value, base_url) {
                         Some(value) => value,
                         None => return InvalidValue,
                     }
@@ -1033,7 +1615,7 @@ impl PropertyDeclaration {
                             ));
                         % endfor
                     },
-                    None => match shorthands::${shorthand.ident}::parse(value) {
+                    None => match shorthands::${shorthand.ident}::parse(value, base_url) {
                         Some(result) => {
                             % for sub_property in shorthand.sub_properties:
                                 result_list.push(${sub_property.ident}_declaration(
@@ -1052,6 +1634,18 @@ impl PropertyDeclaration {
         }
         ValidDeclaration
     }
+
+    /// Returns true if this declaration may take effect through `:visited`. Only colors may, so
+    /// that whether a link was visited cannot be found out from the layout of the page.
+    pub fn applies_to_visited_links(&self) -> bool {
+        match *self {
+            color_declaration(..) | background_color_declaration(..) => true,
+            % for side in ["top", "right", "bottom", "left"]:
+                border_${side}_color_declaration(..) => true,
+            % endfor
+            _ => false,
+        }
+    }
 }
 
 
@@ -1200,6 +1794,14 @@ pub fn cascade(applicable_declarations: &[Arc<~[PropertyDeclaration]>],
 }
 
 
+/// Computes the style of an anonymous box, such as the anonymous table objects of CSS 2.1 §
+/// 17.2.1: inherited properties take their values from `parent_style` and all others take their
+/// initial values.
+pub fn cascade_anonymous(parent_style: &ComputedValues) -> ComputedValues {
+    cascade([], Some(parent_style))
+}
+
+
 // Only re-export the types for computed values.
 pub mod computed_values {
     % for property in LONGHANDS:
@@ -3,20 +3,24 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use extra::arc::Arc;
+use extra::url::Url;
 use std::ascii::StrAsciiExt;
 use std::hashmap::HashMap;
 use std::str;
 use std::to_bytes;
+use std::util::replace;
 
 use servo_util::namespace;
 use servo_util::smallvec::{SmallVec, SmallVec16};
 use servo_util::sort;
+use servo_util::url::parse_url;
+use servo_util::visited::VisitedUrls;
 
-use media_queries::{Device, Screen};
+use media_queries::Device;
 use node::{TElement, TNode};
 use properties::{PropertyDeclaration, PropertyDeclarationBlock};
 use selectors::*;
-use stylesheets::{Stylesheet, iter_style_rules};
+use stylesheets::{Stylesheet, iter_style_rules, media_queries_changed};
 
 pub enum StylesheetOrigin {
     UserAgentOrigin,
@@ -107,6 +111,7 @@ impl SelectorMap {
                               N:TNode<E>>(
                               &self,
                               node: &N,
+                              link_history: &LinkHistory,
                               matching_rules_list: &mut SmallVec16<Rule>) {
         if self.empty {
             return
@@ -118,6 +123,7 @@ impl SelectorMap {
             match element.get_attr(&namespace::Null, "id") {
                 Some(id) => {
                     SelectorMap::get_matching_rules_from_hash(node,
+                                                              link_history,
                                                               &self.id_hash,
                                                               id,
                                                               matching_rules_list)
@@ -129,6 +135,7 @@ impl SelectorMap {
                 Some(ref class_attr) => {
                     for class in class_attr.split(SELECTOR_WHITESPACE) {
                         SelectorMap::get_matching_rules_from_hash(node,
+                                                                  link_history,
                                                                   &self.class_hash,
                                                                   class,
                                                                   matching_rules_list);
@@ -140,10 +147,12 @@ impl SelectorMap {
             // HTML elements in HTML documents must be matched case-insensitively.
             // TODO(pradeep): Case-sensitivity depends on the document type.
             SelectorMap::get_matching_rules_from_hash_ignoring_case(node,
+                                                                    link_history,
                                                                     &self.element_hash,
                                                                     element.get_local_name(),
                                                                     matching_rules_list);
             SelectorMap::get_matching_rules(node,
+                                            link_history,
                                             self.universal_rules,
                                             matching_rules_list);
         });
@@ -155,12 +164,13 @@ impl SelectorMap {
     fn get_matching_rules_from_hash<E:TElement,
                                     N:TNode<E>>(
                                     node: &N,
+                                    link_history: &LinkHistory,
                                     hash: &HashMap<~str,~[Rule]>,
                                     key: &str,
                                     matching_rules: &mut SmallVec16<Rule>) {
         match hash.find_equiv(&key) {
             Some(rules) => {
-                SelectorMap::get_matching_rules(node, *rules, matching_rules)
+                SelectorMap::get_matching_rules(node, link_history, *rules, matching_rules)
             }
             None => {}
         }
@@ -169,12 +179,13 @@ impl SelectorMap {
     fn get_matching_rules_from_hash_ignoring_case<E:TElement,
                                                   N:TNode<E>>(
                                                   node: &N,
+                                                  link_history: &LinkHistory,
                                                   hash: &HashMap<~str,~[Rule]>,
                                                   key: &str,
                                                   matching_rules: &mut SmallVec16<Rule>) {
         match hash.find_equiv(&LowercaseAsciiString(key)) {
             Some(rules) => {
-                SelectorMap::get_matching_rules(node, *rules, matching_rules)
+                SelectorMap::get_matching_rules(node, link_history, *rules, matching_rules)
             }
             None => {}
         }
@@ -184,10 +195,17 @@ impl SelectorMap {
     fn get_matching_rules<E:TElement,
                           N:TNode<E>>(
                           node: &N,
+                          link_history: &LinkHistory,
                           rules: &[Rule],
                           matching_rules: &mut SmallVec16<Rule>) {
+        let unvisited_history = link_history.without_visits();
         for rule in rules.iter() {
-            if matches_compound_selector(rule.selector.get(), node) {
+            let link_history = if rule.ignores_link_history {
+                &unvisited_history
+            } else {
+                link_history
+            };
+            if matches_compound_selector(rule.selector.get(), node, link_history) {
                 // TODO(pradeep): Is the cloning inefficient?
                 matching_rules.push(rule.clone());
             }
@@ -289,40 +307,131 @@ impl SelectorMap {
     }
 }
 
+/// What `:link` and `:visited` are matched against: the URLs visited in this session, and the URL
+/// of the document, which relative links are resolved against.
+struct LinkHistory {
+    visited_urls: VisitedUrls,
+    document_url: Option<Url>,
+}
+
+impl LinkHistory {
+    /// Returns a history in which no link has been visited.
+    fn without_visits(&self) -> LinkHistory {
+        LinkHistory {
+            visited_urls: self.visited_urls.clone(),
+            document_url: None,
+        }
+    }
+
+    fn is_visited(&self, link: &str) -> bool {
+        match self.document_url {
+            None => false,
+            Some(ref document_url) => {
+                self.visited_urls.contains(&parse_url(link, Some(document_url.clone())))
+            }
+        }
+    }
+}
+
 pub struct Stylist {
     priv element_map: PerPseudoElementSelectorMap,
     priv before_map: PerPseudoElementSelectorMap,
     priv after_map: PerPseudoElementSelectorMap,
+    priv first_line_map: PerPseudoElementSelectorMap,
+    priv first_letter_map: PerPseudoElementSelectorMap,
     priv rules_source_order: uint,
+    /// What media queries are evaluated against.
+    priv device: Device,
+    /// Kept so that the maps can be rebuilt when media queries start matching differently.
+    priv stylesheets: ~[(Stylesheet, StylesheetOrigin)],
+    priv link_history: LinkHistory,
 }
 
 impl Stylist {
     #[inline]
-    pub fn new() -> Stylist {
+    pub fn new(device: Device) -> Stylist {
         Stylist {
             element_map: PerPseudoElementSelectorMap::new(),
             before_map: PerPseudoElementSelectorMap::new(),
             after_map: PerPseudoElementSelectorMap::new(),
+            first_line_map: PerPseudoElementSelectorMap::new(),
+            first_letter_map: PerPseudoElementSelectorMap::new(),
             rules_source_order: 0u,
+            device: device,
+            stylesheets: ~[],
+            link_history: LinkHistory {
+                visited_urls: VisitedUrls::new(),
+                document_url: None,
+            },
+        }
+    }
+
+    /// Sets the visited URLs that `:visited` matches against.
+    pub fn set_visited_urls(&mut self, visited_urls: VisitedUrls) {
+        self.link_history.visited_urls = visited_urls
+    }
+
+    /// Sets the URL of the document being styled, which links are relative to.
+    pub fn set_document_url(&mut self, url: Url) {
+        self.link_history.document_url = Some(url)
+    }
+
+    /// Changes the device that media queries are evaluated against, e.g. when the window is
+    /// resized. Returns true if that changed which rules apply, in which case selectors need to
+    /// be matched again.
+    pub fn set_device(&mut self, device: Device) -> bool {
+        if device == self.device {
+            return false
         }
+        let changed = self.stylesheets.iter().any(|&(ref stylesheet, _)| {
+            media_queries_changed(stylesheet.rules.as_slice(), &self.device, &device)
+        });
+        self.device = device;
+        if !changed {
+            return false
+        }
+
+        self.element_map = PerPseudoElementSelectorMap::new();
+        self.before_map = PerPseudoElementSelectorMap::new();
+        self.after_map = PerPseudoElementSelectorMap::new();
+        self.first_line_map = PerPseudoElementSelectorMap::new();
+        self.first_letter_map = PerPseudoElementSelectorMap::new();
+        self.rules_source_order = 0;
+        let stylesheets = replace(&mut self.stylesheets, ~[]);
+        for (stylesheet, origin) in stylesheets.move_iter() {
+            self.add_stylesheet(stylesheet, origin);
+        }
+        true
     }
 
     pub fn add_stylesheet(&mut self, stylesheet: Stylesheet, origin: StylesheetOrigin) {
-        let (mut element_map, mut before_map, mut after_map) = match origin {
+        self.add_rules(&stylesheet, origin);
+        self.stylesheets.push((stylesheet, origin));
+    }
+
+    fn add_rules(&mut self, stylesheet: &Stylesheet, origin: StylesheetOrigin) {
+        let (mut element_map, mut before_map, mut after_map, mut first_line_map,
+             mut first_letter_map) = match origin {
             UserAgentOrigin => (
                 &mut self.element_map.user_agent,
                 &mut self.before_map.user_agent,
                 &mut self.after_map.user_agent,
+                &mut self.first_line_map.user_agent,
+                &mut self.first_letter_map.user_agent,
             ),
             AuthorOrigin => (
                 &mut self.element_map.author,
                 &mut self.before_map.author,
                 &mut self.after_map.author,
+                &mut self.first_line_map.author,
+                &mut self.first_letter_map.author,
             ),
             UserOrigin => (
                 &mut self.element_map.user,
                 &mut self.before_map.user,
                 &mut self.after_map.user,
+                &mut self.first_line_map.user,
+                &mut self.first_letter_map.user,
             ),
         };
 
@@ -336,20 +445,44 @@ impl Stylist {
                             None => &mut element_map,
                             Some(Before) => &mut before_map,
                             Some(After) => &mut after_map,
+                            Some(FirstLine) => &mut first_line_map,
+                            Some(FirstLetter) => &mut first_letter_map,
+                        };
+                        let compound_selector = selector.compound_selectors.get();
+                        let declarations = style_rule.declarations.$priority.get().as_slice();
+                        let rules = if uses_visited(compound_selector) {
+                            // Rules that match through `:visited` may only change colors.
+                            ~[(visited_safe_declarations(declarations, true), false)]
+                        } else if uses_link(compound_selector) {
+                            // `:link` stops matching once a link is visited, so only colors may
+                            // depend on it that way. The other declarations apply as though every
+                            // link were unvisited.
+                            ~[
+                                (visited_safe_declarations(declarations, true), false),
+                                (visited_safe_declarations(declarations, false), true),
+                            ]
+                        } else {
+                            ~[(style_rule.declarations.$priority.clone(), false)]
                         };
-                        map.$priority.insert(Rule {
-                                selector: selector.compound_selectors.clone(),
-                                specificity: selector.specificity,
-                                declarations: style_rule.declarations.$priority.clone(),
-                                source_order: self.rules_source_order,
-                        });
+                        for (declarations, ignores_link_history) in rules.move_iter() {
+                            if declarations.get().len() == 0 {
+                                continue
+                            }
+                            map.$priority.insert(Rule {
+                                    selector: selector.compound_selectors.clone(),
+                                    specificity: selector.specificity,
+                                    declarations: declarations,
+                                    source_order: self.rules_source_order,
+                                    ignores_link_history: ignores_link_history,
+                            });
+                        }
                     }
                 }
             };
         );
 
-        let device = &Device { media_type: Screen };  // TODO, use Print when printing
-        iter_style_rules(stylesheet.rules.as_slice(), device, |style_rule| {
+        let device = self.device.clone();
+        iter_style_rules(stylesheet.rules.as_slice(), &device, |style_rule| {
             append!(normal);
             append!(important);
             self.rules_source_order += 1;
@@ -374,6 +507,8 @@ impl Stylist {
             None => &self.element_map,
             Some(Before) => &self.before_map,
             Some(After) => &self.after_map,
+            Some(FirstLine) => &self.first_line_map,
+            Some(FirstLetter) => &self.first_letter_map,
         };
         // In cascading order:
         let rule_map_list = [
@@ -393,7 +528,7 @@ impl Stylist {
 
         for (i, rule_map) in rule_map_list.iter().enumerate() {
             rule_map_indices[i] = matching_rules_list.len();
-            rule_map.get_all_matching_rules(element, &mut matching_rules_list);
+            rule_map.get_all_matching_rules(element, &self.link_history, &mut matching_rules_list);
         }
 
         let count = matching_rules_list.len();
@@ -477,6 +612,9 @@ struct Rule {
     // Precedence among rules of equal specificity
     source_order: uint,
     specificity: u32,
+    /// Whether `:link` and `:visited` match as though no link had been visited, for the
+    /// declarations of `:link` rules that may not depend on whether a link is visited.
+    ignores_link_history: bool,
 }
 
 impl Ord for Rule {
@@ -497,10 +635,62 @@ impl Eq for Rule {
     }
 }
 
-fn matches_compound_selector<E:TElement,N:TNode<E>>(selector: &CompoundSelector, element: &N)
-                             -> bool {
+/// Returns true if `:visited` appears anywhere in the selector.
+fn uses_visited(selector: &CompoundSelector) -> bool {
+    fn is_visited(simple_selector: &SimpleSelector) -> bool {
+        match *simple_selector {
+            Visited => true,
+            _ => false,
+        }
+    }
+    selector_uses(selector, is_visited)
+}
+
+/// Returns true if `:link` appears anywhere in the selector.
+fn uses_link(selector: &CompoundSelector) -> bool {
+    fn is_link(simple_selector: &SimpleSelector) -> bool {
+        match *simple_selector {
+            Link => true,
+            _ => false,
+        }
+    }
+    selector_uses(selector, is_link)
+}
+
+/// Returns true if a simple selector for which `is_wanted` is true appears anywhere in the
+/// selector, including inside negations.
+fn selector_uses(selector: &CompoundSelector, is_wanted: fn(&SimpleSelector) -> bool) -> bool {
+    fn simple_selectors_use(simple_selectors: &[SimpleSelector],
+                            is_wanted: fn(&SimpleSelector) -> bool)
+                            -> bool {
+        simple_selectors.iter().any(|simple_selector| {
+            is_wanted(simple_selector) || match *simple_selector {
+                Negation(ref negated) => simple_selectors_use(negated.as_slice(), is_wanted),
+                _ => false,
+            }
+        })
+    }
+    simple_selectors_use(selector.simple_selectors.as_slice(), is_wanted) ||
+        selector.next.as_ref().map_default(false, |&(ref next, _)| {
+            selector_uses(&**next, is_wanted)
+        })
+}
+
+/// Returns the declarations that may depend on whether a link is visited, or, if
+/// `visited_safe` is false, the other declarations.
+fn visited_safe_declarations(declarations: &[PropertyDeclaration], visited_safe: bool)
+                             -> Arc<~[PropertyDeclaration]> {
+    Arc::new(declarations.iter().filter(|declaration| {
+        declaration.applies_to_visited_links() == visited_safe
+    }).map(|declaration| declaration.clone()).collect())
+}
+
+fn matches_compound_selector<E:TElement,N:TNode<E>>(selector: &CompoundSelector,
+                                                    element: &N,
+                                                    link_history: &LinkHistory)
+                                                    -> bool {
     if !selector.simple_selectors.iter().all(|simple_selector| {
-            matches_simple_selector(simple_selector, element)
+            matches_simple_selector(simple_selector, element, link_history)
     }) {
         return false
     }
@@ -525,7 +715,7 @@ fn matches_compound_selector<E:TElement,N:TNode<E>>(selector: &CompoundSelector,
                     Some(next_node) => node = next_node,
                 }
                 if node.is_element() {
-                    if matches_compound_selector(&**next_selector, &node) {
+                    if matches_compound_selector(&**next_selector, &node, link_history) {
                         return true
                     } else if just_one {
                         return false
@@ -537,7 +727,10 @@ fn matches_compound_selector<E:TElement,N:TNode<E>>(selector: &CompoundSelector,
 }
 
 #[inline]
-fn matches_simple_selector<E:TElement,N:TNode<E>>(selector: &SimpleSelector, element: &N) -> bool {
+fn matches_simple_selector<E:TElement,N:TNode<E>>(selector: &SimpleSelector,
+                                                  element: &N,
+                                                  link_history: &LinkHistory)
+                                                  -> bool {
     match *selector {
         // TODO: case-sensitivity depends on the document type
         // TODO: intern element names
@@ -601,7 +794,7 @@ fn matches_simple_selector<E:TElement,N:TNode<E>>(selector: &SimpleSelector, ele
         Link => {
             element.with_element(|element: &E| {
                 match element.get_link() {
-                    Some(url) => !url_is_visited(url),
+                    Some(url) => !link_history.is_visited(url),
                     None => false,
                 }
             })
@@ -609,7 +802,7 @@ fn matches_simple_selector<E:TElement,N:TNode<E>>(selector: &SimpleSelector, ele
         Visited => {
             element.with_element(|element: &E| {
                 match element.get_link() {
-                    Some(url) => url_is_visited(url),
+                    Some(url) => link_history.is_visited(url),
                     None => false,
                 }
             })
@@ -620,6 +813,28 @@ fn matches_simple_selector<E:TElement,N:TNode<E>>(selector: &SimpleSelector, ele
                 element.get_hover_state()
             })
         },
+        Focus => {
+            element.with_element(|element: &E| {
+                element.get_focus_state()
+            })
+        }
+        Checked => {
+            element.with_element(|element: &E| {
+                element.get_checked_state()
+            })
+        }
+        Disabled => {
+            element.with_element(|element: &E| {
+                element.get_disabled_state() == Some(true)
+            })
+        }
+        Enabled => {
+            element.with_element(|element: &E| {
+                element.get_disabled_state() == Some(false)
+            })
+        }
+        Empty => element.is_empty(),
+        Lang(ref lang) => matches_lang(element, lang.as_slice()),
         FirstChild => matches_first_child(element),
         LastChild  => matches_last_child(element),
         OnlyChild  => matches_first_child(element) &&
@@ -638,16 +853,37 @@ fn matches_simple_selector<E:TElement,N:TNode<E>>(selector: &SimpleSelector, ele
                        matches_generic_nth_child(element, 0, 1, true, true),
 
         Negation(ref negated) => {
-            !negated.iter().all(|s| matches_simple_selector(s, element))
+            !negated.iter().all(|s| matches_simple_selector(s, element, link_history))
         },
     }
 }
 
-fn url_is_visited(_url: &str) -> bool {
-    // FIXME: implement this.
-    // This function will probably need to take a "session"
-    // or something containing browsing history as an additional parameter.
-    false
+/// The language of an element is that of its nearest `lang` or `xml:lang` attribute, and matches
+/// if it is the given language or a sub-language of it (Selectors Level 3 § 6.6.3).
+fn matches_lang<E:TElement, N:TNode<E>>(element: &N, lang: &str) -> bool {
+    let mut node = element.clone();
+    loop {
+        if node.is_element() {
+            let element_lang = node.with_element(|element: &E| {
+                element.get_attr(&namespace::XML, "lang").or_else(|| {
+                    element.get_attr(&namespace::Null, "lang")
+                })
+            });
+            match element_lang {
+                Some(element_lang) => {
+                    return element_lang.eq_ignore_ascii_case(lang) ||
+                        (element_lang.len() > lang.len() &&
+                         element_lang.slice_to(lang.len()).eq_ignore_ascii_case(lang) &&
+                         element_lang[lang.len()] == '-' as u8)
+                }
+                None => {}
+            }
+        }
+        match node.parent_node() {
+            None => return false,
+            Some(parent) => node = parent,
+        }
+    }
 }
 
 #[inline]
@@ -765,7 +1001,7 @@ fn matches_last_child<E:TElement,N:TNode<E>>(element: &N) -> bool {
 #[cfg(test)]
 mod tests {
     use extra::arc::Arc;
-    use super::{Rule, SelectorMap};
+    use super::{Rule, SelectorMap, uses_link, uses_visited};
 
     /// Helper method to get some Rules from selector strings.
     /// Each sublist of the result contains the Rules for one StyleRule.
@@ -783,6 +1019,7 @@ mod tests {
                     selector: s.compound_selectors,
                     declarations: Arc::new(~[]),
                     source_order: i,
+                    ignores_link_history: false,
                 }
             }).to_owned_vec()
         }).to_owned_vec()
@@ -829,4 +1066,23 @@ mod tests {
         assert_eq!(0, selector_map.class_hash.find(&~"intro").unwrap()[0].source_order);
         assert!(selector_map.class_hash.find(&~"foo").is_none());
     }
+
+    #[test]
+    fn test_uses_visited() {
+        let rules_list = get_mock_rules(["a:visited", "a:not(:visited) span", "a:link", "a"]);
+        assert!(uses_visited(rules_list[0][0].selector.get()));
+        assert!(uses_visited(rules_list[1][0].selector.get()));
+        assert!(!uses_visited(rules_list[2][0].selector.get()));
+        assert!(!uses_visited(rules_list[3][0].selector.get()));
+    }
+
+    #[test]
+    fn test_uses_link() {
+        let rules_list = get_mock_rules(["a:link", "a:not(:link) span", "a:visited",
+                                         "a:any-link"]);
+        assert!(uses_link(rules_list[0][0].selector.get()));
+        assert!(uses_link(rules_list[1][0].selector.get()));
+        assert!(!uses_link(rules_list[2][0].selector.get()));
+        assert!(!uses_link(rules_list[3][0].selector.get()));
+    }
 }
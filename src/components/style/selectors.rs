@@ -34,8 +34,8 @@ pub struct Selector {
 pub enum PseudoElement {
     Before,
     After,
-//    FirstLine,
-//    FirstLetter,
+    FirstLine,
+    FirstLetter,
 }
 
 
@@ -75,10 +75,14 @@ pub enum SimpleSelector {
     Link,
     Visited,
     Hover,
+    Focus,
+    Checked,
+    Disabled,
+    Enabled,
     FirstChild, LastChild, OnlyChild,
-//    Empty,
+    Empty,
     Root,
-//    Lang(~str),
+    Lang(~str),
     NthChild(i32, i32),
     NthLastChild(i32, i32),
     NthOfType(i32, i32),
@@ -212,8 +216,9 @@ fn compute_specificity(mut selector: &CompoundSelector,
                 | &AttrExists(..) | &AttrEqual(..) | &AttrIncludes(..) | &AttrDashMatch(..)
                 | &AttrPrefixMatch(..) | &AttrSubstringMatch(..) | &AttrSuffixMatch(..)
                 | &AnyLink | &Link | &Visited | &Hover
+                | &Focus | &Checked | &Disabled | &Enabled
                 | &FirstChild | &LastChild | &OnlyChild | &Root
-//                | &Empty | &Lang(*)
+                | &Empty | &Lang(..)
                 | &NthChild(..) | &NthLastChild(..)
                 | &NthOfType(..) | &NthLastOfType(..)
                 | &FirstOfType | &LastOfType | &OnlyOfType
@@ -330,8 +335,8 @@ fn parse_one_simple_selector(iter: &mut Iter, namespaces: &NamespaceMap, inside_
                             // ** Do not add to this list! **
                             "before" => PseudoElementResult(Before),
                             "after" => PseudoElementResult(After),
-//                            "first-line" => PseudoElementResult(FirstLine),
-//                            "first-letter" => PseudoElementResult(FirstLetter),
+                            "first-line" => PseudoElementResult(FirstLine),
+                            "first-letter" => PseudoElementResult(FirstLetter),
                             _ => InvalidSimpleSelector
                         }
                     },
@@ -471,6 +476,10 @@ fn parse_simple_pseudo_class(name: &str) -> Option<SimpleSelector> {
         "link" => Some(Link),
         "visited" => Some(Visited),
         "hover" => Some(Hover),
+        "focus" => Some(Focus),
+        "checked" => Some(Checked),
+        "disabled" => Some(Disabled),
+        "enabled" => Some(Enabled),
         "first-child" => Some(FirstChild),
         "last-child"  => Some(LastChild),
         "only-child"  => Some(OnlyChild),
@@ -478,7 +487,7 @@ fn parse_simple_pseudo_class(name: &str) -> Option<SimpleSelector> {
         "first-of-type" => Some(FirstOfType),
         "last-of-type"  => Some(LastOfType),
         "only-of-type"  => Some(OnlyOfType),
-//        "empty" => Some(Empty),
+        "empty" => Some(Empty),
         _ => None
     }
 }
@@ -490,7 +499,7 @@ fn parse_functional_pseudo_class(name: ~str, arguments: ~[ComponentValue],
     // FIXME: Workaround for https://github.com/mozilla/rust/issues/10683
     let name_lower = name.to_ascii_lower();
     match name_lower.as_slice() {
-//        "lang" => parse_lang(arguments),
+        "lang" => parse_lang(arguments),
         "nth-child"        => parse_nth(arguments).map(|(a, b)| NthChild(a, b)),
         "nth-last-child"   => parse_nth(arguments).map(|(a, b)| NthLastChild(a, b)),
         "nth-of-type"      => parse_nth(arguments).map(|(a, b)| NthOfType(a, b)),
@@ -508,23 +517,23 @@ fn parse_pseudo_element(name: ~str) -> Option<PseudoElement> {
         // All supported pseudo-elements
         "before" => Some(Before),
         "after" => Some(After),
-//        "first-line" => Some(FirstLine),
-//        "first-letter" => Some(FirstLetter),
+        "first-line" => Some(FirstLine),
+        "first-letter" => Some(FirstLetter),
         _ => None
     }
 }
 
 
-//fn parse_lang(arguments: ~[ComponentValue]) -> Option<SimpleSelector> {
-//    let mut iter = arguments.move_skip_whitespace();
-//    match iter.next() {
-//        Some(Ident(value)) => {
-//            if "" == value || iter.next().is_some() { None }
-//            else { Some(Lang(value)) }
-//        },
-//        _ => None,
-//    }
-//}
+fn parse_lang(arguments: ~[ComponentValue]) -> Option<SimpleSelector> {
+    let mut iter = arguments.move_skip_whitespace();
+    match iter.next() {
+        Some(Ident(value)) => {
+            if "" == value || iter.next().is_some() { None }
+            else { Some(Lang(value)) }
+        },
+        _ => None,
+    }
+}
 
 
 // Level 3: Parse ONE simple_selector
@@ -676,6 +685,43 @@ mod tests {
             pseudo_element: None,
             specificity: specificity(0, 0, 1),
         }]))
+        assert_eq!(parse("p::first-line"), Some(~[Selector{
+            compound_selectors: Arc::new(CompoundSelector {
+                simple_selectors: ~[LocalNameSelector(~"p")],
+                next: None,
+            }),
+            pseudo_element: Some(FirstLine),
+            specificity: specificity(0, 0, 2),
+        }]))
+        assert_eq!(parse("div:first-letter"), Some(~[Selector{
+            compound_selectors: Arc::new(CompoundSelector {
+                simple_selectors: ~[LocalNameSelector(~"div")],
+                next: None,
+            }),
+            pseudo_element: Some(FirstLetter),
+            specificity: specificity(0, 0, 2),
+        }]))
+        assert_eq!(parse("input:disabled:not(:focus)"), Some(~[Selector{
+            compound_selectors: Arc::new(CompoundSelector {
+                simple_selectors: ~[
+                    LocalNameSelector(~"input"),
+                    Disabled,
+                    Negation(~[Focus]),
+                ],
+                next: None,
+            }),
+            pseudo_element: None,
+            specificity: specificity(0, 2, 1),
+        }]))
+        assert_eq!(parse(":lang(fr)"), Some(~[Selector{
+            compound_selectors: Arc::new(CompoundSelector {
+                simple_selectors: ~[Lang(~"fr")],
+                next: None,
+            }),
+            pseudo_element: None,
+            specificity: specificity(0, 1, 0),
+        }]))
+        assert_eq!(parse(":lang()"), None)
 
     }
 }
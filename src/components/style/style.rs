@@ -19,11 +19,13 @@ extern mod servo_util = "util";
 // Public API
 pub use stylesheets::Stylesheet;
 pub use selector_matching::{Stylist, StylesheetOrigin, UserAgentOrigin, AuthorOrigin, UserOrigin};
-pub use properties::{cascade, PropertyDeclaration, ComputedValues, computed_values};
+pub use properties::{cascade, cascade_anonymous, PropertyDeclaration, ComputedValues};
+pub use properties::computed_values;
 pub use properties::{PropertyDeclarationBlock, parse_style_attribute};  // Style attributes
 pub use errors::with_errors_silenced;
+pub use media_queries::{Device, MediaType, Screen, Print};
 pub use node::{TElement, TNode};
-pub use selectors::{PseudoElement, Before, After, AttrSelector};
+pub use selectors::{PseudoElement, Before, After, FirstLine, FirstLetter, AttrSelector};
 
 mod stylesheets;
 mod errors;
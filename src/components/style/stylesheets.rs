@@ -4,17 +4,20 @@
 
 use std::iter::Iterator;
 use std::ascii::StrAsciiExt;
+use std::str::CharRange;
+use std::util::replace;
 use extra::url::Url;
 
-use encoding::EncodingRef;
+use encoding::{Decoder, EncodingRef, StringWriter};
 
 use cssparser::{decode_stylesheet_bytes, tokenize, parse_stylesheet_rules, ToCss};
 use cssparser::ast::*;
+use servo_util::url::parse_url;
 use selectors;
 use properties;
 use errors::{ErrorLoggerIterator, log_css_error};
 use namespaces::{NamespaceMap, parse_namespace_rule};
-use media_queries::{MediaRule, parse_media_rule};
+use media_queries::{MediaRule, MediaQueryList, parse_media_rule, parse_media_query_list};
 use media_queries;
 
 
@@ -31,6 +34,7 @@ pub struct Stylesheet {
 pub enum CSSRule {
     CSSStyleRule(StyleRule),
     CSSMediaRule(MediaRule),
+    CSSImportRule(ImportRule),
 }
 
 
@@ -40,16 +44,25 @@ pub struct StyleRule {
 }
 
 
+pub struct ImportRule {
+    url: Url,
+    media_queries: MediaQueryList,
+    /// The imported sheet, once it has been loaded. This stays `None` if it couldn't be fetched,
+    /// the server answered with an error status, or importing it would have created a cycle.
+    stylesheet: Option<Stylesheet>,
+}
+
+
 impl Stylesheet {
     pub fn from_bytes_iter<I: Iterator<~[u8]>>(
             mut input: I, base_url: Url, protocol_encoding_label: Option<&str>,
             environment_encoding: Option<EncodingRef>) -> Stylesheet {
-        let mut bytes = ~[];
-        // TODO: incremental decoding and tokinization/parsing
+        let mut builder = StylesheetBuilder::new(
+            base_url, protocol_encoding_label, environment_encoding);
         for chunk in input {
-            bytes.push_all(chunk)
+            builder.push_bytes(chunk)
         }
-        Stylesheet::from_bytes(bytes, base_url, protocol_encoding_label, environment_encoding)
+        builder.finish()
     }
 
     pub fn from_bytes(
@@ -61,78 +74,351 @@ impl Stylesheet {
     }
 
     pub fn from_str(css: &str, base_url: Url, encoding: EncodingRef) -> Stylesheet {
-        static STATE_CHARSET: uint = 1;
-        static STATE_IMPORTS: uint = 2;
-        static STATE_NAMESPACES: uint = 3;
-        static STATE_BODY: uint = 4;
-        let mut state: uint = STATE_CHARSET;
+        let mut parser = RuleParser::new(base_url);
+        parser.parse(css);
+        parser.finish(encoding)
+    }
 
-        let mut rules = ~[];
-        let mut namespaces = NamespaceMap::new();
+    /// Calls `load` for each `@import` rule, in order, and keeps the sheets it returns. Loading
+    /// is left to the caller, since fetching URLs is beyond this crate.
+    pub fn load_imports(&mut self, load: |&Url| -> Option<Stylesheet>) {
+        // @import rules are only valid at the top level, before any other rule.
+        for rule in self.rules.mut_iter() {
+            match *rule {
+                CSSImportRule(ref mut rule) => rule.stylesheet = load(&rule.url),
+                _ => (),
+            }
+        }
+    }
+}
+
+
+static STATE_CHARSET: uint = 1;
+static STATE_IMPORTS: uint = 2;
+static STATE_NAMESPACES: uint = 3;
+static STATE_BODY: uint = 4;
+
+/// Parses the top-level rules of a style sheet, possibly a few at a time.
+struct RuleParser {
+    state: uint,
+    rules: ~[CSSRule],
+    namespaces: NamespaceMap,
+    base_url: Url,
+}
+
+impl RuleParser {
+    fn new(base_url: Url) -> RuleParser {
+        RuleParser {
+            state: STATE_CHARSET,
+            rules: ~[],
+            namespaces: NamespaceMap::new(),
+            base_url: base_url,
+        }
+    }
 
+    fn finish(self, encoding: EncodingRef) -> Stylesheet {
+        let RuleParser { rules: rules, namespaces: namespaces, base_url: base_url, .. } = self;
+        Stylesheet { rules: rules, namespaces: namespaces, encoding: encoding, base_url: base_url }
+    }
+
+    fn parse(&mut self, css: &str) {
         for rule in ErrorLoggerIterator(parse_stylesheet_rules(tokenize(css))) {
             let next_state;  // Unitialized to force each branch to set it.
             match rule {
                 QualifiedRule(rule) => {
                     next_state = STATE_BODY;
-                    parse_style_rule(rule, &mut rules, &namespaces)
+                    parse_style_rule(rule, &mut self.rules, &self.namespaces, &self.base_url)
                 },
                 AtRule(rule) => {
                     let lower_name = rule.name.to_ascii_lower();
                     match lower_name.as_slice() {
                         "charset" => {
-                            if state > STATE_CHARSET {
+                            if self.state > STATE_CHARSET {
                                 log_css_error(rule.location, "@charset must be the first rule")
                             }
                             // Valid @charset rules are just ignored
                             next_state = STATE_IMPORTS;
                         },
                         "import" => {
-                            if state > STATE_IMPORTS {
-                                next_state = state;
+                            if self.state > STATE_IMPORTS {
+                                next_state = self.state;
                                 log_css_error(rule.location,
                                               "@import must be before any rule but @charset")
                             } else {
                                 next_state = STATE_IMPORTS;
-                                // TODO: support @import
-                                log_css_error(rule.location, "@import is not supported yet")
+                                parse_import_rule(rule, &mut self.rules, &self.base_url)
                             }
                         },
                         "namespace" => {
-                            if state > STATE_NAMESPACES {
-                                next_state = state;
+                            if self.state > STATE_NAMESPACES {
+                                next_state = self.state;
                                 log_css_error(
                                     rule.location,
                                     "@namespace must be before any rule but @charset and @import"
                                 )
                             } else {
                                 next_state = STATE_NAMESPACES;
-                                parse_namespace_rule(rule, &mut namespaces)
+                                parse_namespace_rule(rule, &mut self.namespaces)
                             }
                         },
                         _ => {
                             next_state = STATE_BODY;
-                            parse_nested_at_rule(lower_name, rule, &mut rules, &namespaces)
+                            parse_nested_at_rule(lower_name, rule, &mut self.rules,
+                                                 &self.namespaces, &self.base_url)
                         },
                     }
                 },
             }
-            state = next_state;
+            self.state = next_state;
+        }
+    }
+}
+
+
+/// How many bytes to wait for before picking an encoding, so that an `@charset` rule has a
+/// chance to arrive.
+static ENCODING_SNIFF_LENGTH: uint = 1024;
+
+/// Builds a style sheet from chunks of bytes as they arrive. Chunks are decoded as soon as the
+/// encoding is known, and each top-level rule is parsed as soon as it is complete, so the whole
+/// sheet is never buffered.
+pub struct StylesheetBuilder {
+    protocol_encoding_label: Option<~str>,
+    environment_encoding: Option<EncodingRef>,
+    /// Bytes received before the encoding was picked.
+    prefix: ~[u8],
+    decoder: Option<(EncodingRef, ~Decoder)>,
+    /// Decoded text that doesn't contain a complete rule yet.
+    pending: ~str,
+    splitter: RuleSplitter,
+    parser: RuleParser,
+}
+
+impl StylesheetBuilder {
+    pub fn new(base_url: Url, protocol_encoding_label: Option<&str>,
+               environment_encoding: Option<EncodingRef>) -> StylesheetBuilder {
+        StylesheetBuilder {
+            protocol_encoding_label: protocol_encoding_label.map(|label| label.to_owned()),
+            environment_encoding: environment_encoding,
+            prefix: ~[],
+            decoder: None,
+            pending: ~"",
+            splitter: RuleSplitter::new(),
+            parser: RuleParser::new(base_url),
+        }
+    }
+
+    pub fn push_bytes(&mut self, bytes: &[u8]) {
+        if self.decoder.is_some() {
+            self.decode(bytes)
+        } else {
+            self.prefix.push_all(bytes);
+            if self.prefix.len() < ENCODING_SNIFF_LENGTH {
+                return
+            }
+            self.start_decoding()
+        }
+
+        let end = self.splitter.scan(self.pending);
+        if end > 0 {
+            self.parser.parse(self.pending.slice_to(end));
+            self.pending = self.pending.slice_from(end).to_owned();
+        }
+    }
+
+    pub fn finish(mut self) -> Stylesheet {
+        if self.decoder.is_none() {
+            self.start_decoding()
+        }
+        let encoding = match self.decoder {
+            Some((ref encoding, ref mut decoder)) => {
+                match decoder.raw_finish(&mut self.pending as &mut StringWriter) {
+                    Some(_) => self.pending.push_char('\uFFFD'),
+                    None => (),
+                }
+                *encoding
+            }
+            None => fail!("no decoder after start_decoding"),
+        };
+
+        self.parser.parse(self.pending);
+        let StylesheetBuilder { parser: parser, .. } = self;
+        parser.finish(encoding)
+    }
+
+    fn start_decoding(&mut self) {
+        let prefix = replace(&mut self.prefix, ~[]);
+        let (_, encoding) = decode_stylesheet_bytes(
+            prefix, self.protocol_encoding_label.as_ref().map(|label| label.as_slice()),
+            self.environment_encoding);
+        self.decoder = Some((encoding, encoding.decoder()));
+
+        // `decode_stylesheet_bytes` picks the encoding of a byte order mark if there is one,
+        // but the decoder itself doesn't skip it.
+        let bom_length = match encoding.name() {
+            "utf-8" if prefix.starts_with([0xEF, 0xBB, 0xBF]) => 3,
+            "utf-16le" if prefix.starts_with([0xFF, 0xFE]) => 2,
+            "utf-16be" if prefix.starts_with([0xFE, 0xFF]) => 2,
+            _ => 0,
+        };
+        self.decode(prefix.slice_from(bom_length))
+    }
+
+    fn decode(&mut self, bytes: &[u8]) {
+        let decoder = match self.decoder {
+            Some((_, ref mut decoder)) => decoder,
+            None => fail!("decoding before the encoding is known"),
+        };
+        let mut input = bytes;
+        loop {
+            match decoder.raw_feed(input, &mut self.pending as &mut StringWriter) {
+                (_, Some(error)) => {
+                    self.pending.push_char('\uFFFD');
+                    input = input.slice_from(error.upto);
+                }
+                (_, None) => break,
+            }
+        }
+    }
+}
+
+
+/// What `RuleSplitter` is in the middle of.
+enum SplitterState {
+    SplitterNormal,
+    SplitterInString(char),
+    SplitterInComment,
+    /// Just after `url(`, where it isn't known yet whether the URL is quoted.
+    SplitterUrlStart,
+    SplitterInUrl,
+}
+
+/// Finds where top-level rules end in partially received style sheet text, so that they can be
+/// parsed before the rest of the sheet arrives. This only needs to know as much of the tokenizer
+/// as it takes to match up blocks: strings, comments, escapes and unquoted `url()`s.
+struct RuleSplitter {
+    state: SplitterState,
+    /// The characters closing the blocks we're in, innermost last.
+    block_ends: ~[char],
+    /// How much of the text has already been scanned.
+    position: uint,
+}
+
+impl RuleSplitter {
+    fn new() -> RuleSplitter {
+        RuleSplitter {
+            state: SplitterNormal,
+            block_ends: ~[],
+            position: 0,
+        }
+    }
+
+    /// Scans the text received so far and returns the length of its complete rules, or 0 if
+    /// there are none. The caller must then remove that many bytes from the front of the text.
+    fn scan(&mut self, text: &str) -> uint {
+        let mut end = 0;
+        let mut i = self.position;
+        while i < text.len() {
+            let CharRange { ch: ch, next: next_i } = text.char_range_at(i);
+            let next = if next_i < text.len() { Some(text.char_at(next_i)) } else { None };
+            match self.state {
+                SplitterNormal => match ch {
+                    // Escapes and comments need the next character; wait until it arrives.
+                    '\\' | '/' if next.is_none() => break,
+                    '\\' => i = next_i,
+                    '/' if next == Some('*') => {
+                        self.state = SplitterInComment;
+                        i = next_i;
+                    }
+                    '"' | '\'' => self.state = SplitterInString(ch),
+                    '{' => self.block_ends.push('}'),
+                    '[' => self.block_ends.push(']'),
+                    '(' => {
+                        if is_url_function(text.slice_to(i)) {
+                            self.state = SplitterUrlStart
+                        } else {
+                            self.block_ends.push(')')
+                        }
+                    }
+                    '}' | ']' | ')' => {
+                        // Unmatched closing characters are just tokens.
+                        let length = self.block_ends.len();
+                        if length > 0 && self.block_ends[length - 1] == ch {
+                            self.block_ends.pop();
+                            if ch == '}' && length == 1 {
+                                end = next_i;
+                            }
+                        }
+                    }
+                    _ => (),
+                },
+                SplitterInString(quote) => match ch {
+                    '\\' if next.is_none() => break,
+                    '\\' => i = next_i,
+                    '\n' | '\r' | '\x0C' => self.state = SplitterNormal,
+                    _ if ch == quote => self.state = SplitterNormal,
+                    _ => (),
+                },
+                SplitterInComment => match ch {
+                    '*' if next.is_none() => break,
+                    '*' if next == Some('/') => {
+                        self.state = SplitterNormal;
+                        i = next_i;
+                    }
+                    _ => (),
+                },
+                SplitterUrlStart => match ch {
+                    ' ' | '\t' | '\n' | '\r' | '\x0C' => (),
+                    '"' | '\'' => {
+                        // A quoted URL is an ordinary function.
+                        self.block_ends.push(')');
+                        self.state = SplitterInString(ch);
+                    }
+                    _ => {
+                        self.state = SplitterInUrl;
+                        continue
+                    }
+                },
+                SplitterInUrl => match ch {
+                    '\\' if next.is_none() => break,
+                    '\\' => i = next_i,
+                    ')' => self.state = SplitterNormal,
+                    _ => (),
+                },
+            }
+            i = text.char_range_at(i).next;
         }
-        Stylesheet{ rules: rules, namespaces: namespaces, encoding: encoding, base_url: base_url }
+        self.position = i - end;
+        end
+    }
+}
+
+/// Whether a `(` following `text` starts a `url()`.
+fn is_url_function(text: &str) -> bool {
+    let length = text.len();
+    if length < 3 || !text.slice_from(length - 3).eq_ignore_ascii_case("url") {
+        return false
+    }
+    if length == 3 {
+        return true
+    }
+    match text.char_at_reverse(length - 3) {
+        'a'..'z' | 'A'..'Z' | '0'..'9' | '_' | '-' | '\\' => false,
+        ch => (ch as u32) < 0x80,
     }
 }
 
 
 pub fn parse_style_rule(rule: QualifiedRule, parent_rules: &mut ~[CSSRule],
-                        namespaces: &NamespaceMap) {
+                        namespaces: &NamespaceMap, base_url: &Url) {
     let QualifiedRule{location: location, prelude: prelude, block: block} = rule;
     // FIXME: avoid doing this for valid selectors
     let serialized = prelude.iter().to_css();
     match selectors::parse_selector_list(prelude, namespaces) {
         Some(selectors) => parent_rules.push(CSSStyleRule(StyleRule{
             selectors: selectors,
-            declarations: properties::parse_property_declaration_list(block.move_iter())
+            declarations: properties::parse_property_declaration_list(block.move_iter(),
+                                                                      base_url)
         })),
         None => log_css_error(location, format!(
             "Invalid/unsupported selector: {}", serialized)),
@@ -140,16 +426,66 @@ pub fn parse_style_rule(rule: QualifiedRule, parent_rules: &mut ~[CSSRule],
 }
 
 
+pub fn parse_import_rule(rule: AtRule, parent_rules: &mut ~[CSSRule], base_url: &Url) {
+    let AtRule { location: location, prelude: prelude, block: block, .. } = rule;
+    if block.is_some() {
+        log_css_error(location, "Invalid @import rule");
+        return
+    }
+    let start = match prelude.iter().position(|value| *value != WhiteSpace) {
+        Some(start) => start,
+        None => {
+            log_css_error(location, "Invalid @import rule");
+            return
+        }
+    };
+    let url = match prelude[start] {
+        URL(ref value) | String(ref value) => parse_url(value.as_slice(), Some(base_url.clone())),
+        _ => {
+            log_css_error(location, "Invalid @import rule");
+            return
+        }
+    };
+    parent_rules.push(CSSImportRule(ImportRule {
+        url: url,
+        media_queries: parse_media_query_list(prelude.slice_from(start + 1)),
+        stylesheet: None,
+    }))
+}
+
+
 // lower_name is passed explicitly to avoid computing it twice.
 pub fn parse_nested_at_rule(lower_name: &str, rule: AtRule,
-                            parent_rules: &mut ~[CSSRule], namespaces: &NamespaceMap) {
+                            parent_rules: &mut ~[CSSRule], namespaces: &NamespaceMap,
+                            base_url: &Url) {
     match lower_name {
-        "media" => parse_media_rule(rule, parent_rules, namespaces),
+        "media" => parse_media_rule(rule, parent_rules, namespaces, base_url),
         _ => log_css_error(rule.location, format!("Unsupported at-rule: @{:s}", lower_name))
     }
 }
 
 
+/// Whether any of the media queries in `rules` evaluate differently on the two devices.
+pub fn media_queries_changed(rules: &[CSSRule], before: &media_queries::Device,
+                             after: &media_queries::Device) -> bool {
+    rules.iter().any(|rule| {
+        match *rule {
+            CSSStyleRule(_) => false,
+            CSSMediaRule(ref rule) => {
+                rule.media_queries.evaluate(before) != rule.media_queries.evaluate(after) ||
+                    media_queries_changed(rule.rules.as_slice(), before, after)
+            }
+            CSSImportRule(ref rule) => {
+                rule.media_queries.evaluate(before) != rule.media_queries.evaluate(after) ||
+                    rule.stylesheet.iter().any(|sheet| {
+                        media_queries_changed(sheet.rules.as_slice(), before, after)
+                    })
+            }
+        }
+    })
+}
+
+
 pub fn iter_style_rules<'a>(rules: &[CSSRule], device: &media_queries::Device,
                             callback: |&StyleRule|) {
     for rule in rules.iter() {
@@ -157,7 +493,239 @@ pub fn iter_style_rules<'a>(rules: &[CSSRule], device: &media_queries::Device,
             CSSStyleRule(ref rule) => callback(rule),
             CSSMediaRule(ref rule) => if rule.media_queries.evaluate(device) {
                 iter_style_rules(rule.rules.as_slice(), device, |s| callback(s))
+            },
+            // Imported rules cascade as if they were where the @import is.
+            CSSImportRule(ref rule) => if rule.media_queries.evaluate(device) {
+                for sheet in rule.stylesheet.iter() {
+                    iter_style_rules(sheet.rules.as_slice(), device, |s| callback(s))
+                }
+            },
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::from_str::FromStr;
+    use encoding::EncodingRef;
+    use encoding::all::UTF_8;
+    use super::{Stylesheet, StylesheetBuilder, RuleSplitter, CSSMediaRule, CSSImportRule};
+    use super::CSSStyleRule;
+    use properties::{CSSWideKeyword, Initial, SpecifiedValue};
+    use properties::{background_attachment_declaration, background_color_declaration};
+    use properties::{background_image_declaration, background_position_declaration};
+    use properties::{background_repeat_declaration, content_declaration};
+    use properties::{counter_increment_declaration, counter_reset_declaration, quotes_declaration};
+    use properties::{PropertyDeclaration, parse_style_attribute};
+    use properties::longhands::{background_attachment, background_repeat, list_style_type};
+    use properties::longhands::content::computed_value::{AttrContent, CloseQuote, Content};
+    use properties::longhands::content::computed_value::{CounterContent, CountersContent};
+    use properties::longhands::content::computed_value::{NoCloseQuote, OpenQuote, StringContent};
+    use properties::longhands::content::computed_value::none;
+    use properties::specified::LP_Percentage;
+
+    fn scan_all(text: &str) -> ~[~str] {
+        let mut splitter = RuleSplitter::new();
+        let mut rules = ~[];
+        let mut pending = ~"";
+        for ch in text.chars() {
+            pending.push_char(ch);
+            let end = splitter.scan(pending);
+            if end > 0 {
+                rules.push(pending.slice_to(end).to_owned());
+                pending = pending.slice_from(end).to_owned();
+            }
+        }
+        rules
+    }
+
+    #[test]
+    fn test_rule_splitter() {
+        assert_eq!(scan_all("a { color: red } b { }"), ~[~"a { color: red }", ~" b { }"]);
+        assert_eq!(scan_all("@import 'a.css'; @media print { a { } b { } } c"),
+                   ~[~"@import 'a.css'; @media print { a { } b { } }"]);
+        assert_eq!(scan_all("a { content: \"}\" } /* } */ b { }"),
+                   ~[~"a { content: \"}\" }", ~" /* } */ b { }"]);
+        assert_eq!(scan_all("a { background: url(x}y) } b { background: url( \"}\" ) }"),
+                   ~[~"a { background: url(x}y) }", ~" b { background: url( \"}\" ) }"]);
+        assert_eq!(scan_all("a\\{ } b { ( } ) }"), ~[~"a\\{ } b { ( } ) }"]);
+    }
+
+    #[test]
+    fn test_incremental_parse() {
+        // Long enough for the encoding to be picked before the first rule arrives.
+        let css = format!("/*{:s}*/", " ".repeat(2000)) +
+            " a { color: red } @media screen { b {} i {} } p";
+        let url = FromStr::from_str("http://example.com/").unwrap();
+        let mut builder = StylesheetBuilder::new(url, None, Some(UTF_8 as EncodingRef));
+        for byte in css.as_bytes().iter() {
+            builder.push_bytes([*byte]);
+        }
+        assert_eq!(builder.parser.rules.len(), 2);
+        builder.push_bytes(bytes!(" { }"));
+        assert_eq!(builder.parser.rules.len(), 3);
+        let sheet = builder.finish();
+
+        let url = FromStr::from_str("http://example.com/").unwrap();
+        let expected = Stylesheet::from_str(css + " { }", url, UTF_8 as EncodingRef);
+        assert_eq!(sheet.rules.len(), expected.rules.len());
+        match sheet.rules[1] {
+            CSSMediaRule(ref rule) => assert_eq!(rule.rules.len(), 2),
+            _ => fail!("expected a media rule"),
+        }
+    }
+
+    #[test]
+    fn test_import_rules() {
+        let css = "@import url(print.css) print; @import 'base.css'; a { } @import 'late.css';";
+        let url = FromStr::from_str("http://example.com/css/main.css").unwrap();
+        let mut sheet = Stylesheet::from_str(css, url, UTF_8 as EncodingRef);
+        assert_eq!(sheet.rules.len(), 3);
+
+        let mut loaded = ~[];
+        sheet.load_imports(|url| {
+            loaded.push(url.to_str());
+            let url = url.clone();
+            Some(Stylesheet::from_str("b { }", url, UTF_8 as EncodingRef))
+        });
+        assert_eq!(loaded, ~[~"http://example.com/css/print.css",
+                             ~"http://example.com/css/base.css"]);
+        match sheet.rules[1] {
+            CSSImportRule(ref rule) => {
+                assert_eq!(rule.stylesheet.get_ref().rules.len(), 1);
+                assert_eq!(rule.media_queries.media_queries.len(), 1);
+            }
+            _ => fail!("expected an import rule"),
+        }
+    }
+
+    #[test]
+    fn test_background_attachment_fixed() {
+        let css = "a { background: url(img/bg.png) fixed red }";
+        let url = FromStr::from_str("http://example.com/css/main.css").unwrap();
+        let sheet = Stylesheet::from_str(css, url, UTF_8 as EncodingRef);
+        let declarations = match sheet.rules[0] {
+            CSSStyleRule(ref rule) => rule.declarations.normal.clone(),
+            _ => fail!("expected a style rule"),
+        };
+        assert_eq!(declarations.get().len(), 5);
+        for declaration in declarations.get().iter() {
+            match *declaration {
+                background_color_declaration(SpecifiedValue(_)) => (),
+                background_image_declaration(SpecifiedValue(Some(ref url))) => {
+                    assert_eq!(url.to_str(), ~"http://example.com/css/img/bg.png")
+                }
+                background_attachment_declaration(SpecifiedValue(attachment)) => {
+                    assert!(attachment == background_attachment::fixed)
+                }
+                background_repeat_declaration(CSSWideKeyword(Initial)) => (),
+                background_position_declaration(CSSWideKeyword(Initial)) => (),
+                _ => fail!("unexpected declaration"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_background_shorthand() {
+        let css = "a { background: url(img/bg.png) no-repeat right top red }";
+        let url = FromStr::from_str("http://example.com/css/main.css").unwrap();
+        let sheet = Stylesheet::from_str(css, url, UTF_8 as EncodingRef);
+        let declarations = match sheet.rules[0] {
+            CSSStyleRule(ref rule) => rule.declarations.normal.clone(),
+            _ => fail!("expected a style rule"),
+        };
+        assert_eq!(declarations.get().len(), 5);
+        for declaration in declarations.get().iter() {
+            match *declaration {
+                background_color_declaration(SpecifiedValue(_)) => (),
+                background_image_declaration(SpecifiedValue(Some(ref url))) => {
+                    assert_eq!(url.to_str(), ~"http://example.com/css/img/bg.png")
+                }
+                background_repeat_declaration(SpecifiedValue(repeat)) => {
+                    assert!(repeat == background_repeat::no_repeat)
+                }
+                background_attachment_declaration(CSSWideKeyword(Initial)) => (),
+                background_position_declaration(SpecifiedValue(ref position)) => {
+                    match (position.horizontal, position.vertical) {
+                        (LP_Percentage(horizontal), LP_Percentage(vertical)) => {
+                            assert_eq!(horizontal, 1.0);
+                            assert_eq!(vertical, 0.0);
+                        }
+                        _ => fail!("expected percentages"),
+                    }
+                }
+                _ => fail!("unexpected declaration"),
+            }
+        }
+    }
+
+    fn parse_declarations(css: &str) -> ~[PropertyDeclaration] {
+        let url = FromStr::from_str("http://example.com/css/main.css").unwrap();
+        parse_style_attribute(css, &url).normal.get().clone()
+    }
+
+    #[test]
+    fn test_content_parsing() {
+        let css = "content: 'a' counter(item) counters(item, '.', upper-roman) attr(title) \
+                   open-quote close-quote no-close-quote";
+        match parse_declarations(css).as_slice() {
+            [content_declaration(SpecifiedValue(Content(ref items)))] => {
+                assert_eq!(*items, ~[StringContent(~"a"),
+                                     CounterContent(~"item", list_style_type::decimal),
+                                     CountersContent(~"item", ~".", list_style_type::upper_roman),
+                                     AttrContent(~"title"),
+                                     OpenQuote,
+                                     CloseQuote,
+                                     NoCloseQuote]);
             }
+            _ => fail!("expected a content declaration"),
+        }
+        match parse_declarations("content: none").as_slice() {
+            [content_declaration(SpecifiedValue(none))] => (),
+            _ => fail!("expected content: none"),
+        }
+        // Invalid counter styles, missing separators and unknown keywords drop the declaration.
+        assert_eq!(parse_declarations("content: counter(item, 'x')").len(), 0);
+        assert_eq!(parse_declarations("content: counters(item)").len(), 0);
+        assert_eq!(parse_declarations("content: 'a' none").len(), 0);
+    }
+
+    #[test]
+    fn test_counter_parsing() {
+        match parse_declarations("counter-reset: a b 3; counter-increment: a -2 b").as_slice() {
+            [counter_reset_declaration(SpecifiedValue(ref reset)),
+             counter_increment_declaration(SpecifiedValue(ref increment))] => {
+                assert_eq!(*reset, ~[(~"a", 0), (~"b", 3)]);
+                assert_eq!(*increment, ~[(~"a", -2), (~"b", 1)]);
+            }
+            _ => fail!("expected counter declarations"),
+        }
+        match parse_declarations("counter-increment: none").as_slice() {
+            [counter_increment_declaration(SpecifiedValue(ref increment))] => {
+                assert!(increment.is_empty())
+            }
+            _ => fail!("expected counter-increment: none"),
+        }
+        // An integer has to follow a counter name, and may only follow it once.
+        assert_eq!(parse_declarations("counter-reset: 3").len(), 0);
+        assert_eq!(parse_declarations("counter-reset: a 1 2").len(), 0);
+        assert_eq!(parse_declarations("counter-reset: a 1.5").len(), 0);
+    }
+
+    #[test]
+    fn test_quotes_parsing() {
+        match parse_declarations("quotes: '<' '>' '(' ')'").as_slice() {
+            [quotes_declaration(SpecifiedValue(ref quotes))] => {
+                assert_eq!(*quotes, ~[(~"<", ~">"), (~"(", ~")")]);
+            }
+            _ => fail!("expected a quotes declaration"),
+        }
+        match parse_declarations("quotes: none").as_slice() {
+            [quotes_declaration(SpecifiedValue(ref quotes))] => assert!(quotes.is_empty()),
+            _ => fail!("expected quotes: none"),
         }
+        assert_eq!(parse_declarations("quotes: '<'").len(), 0);
+        assert_eq!(parse_declarations("quotes: '<' '>' none").len(), 0);
     }
 }
@@ -25,4 +25,4 @@ pub mod namespace;
 pub mod sort;
 pub mod smallvec;
 pub mod concurrentmap;
-
+pub mod visited;
@@ -0,0 +1,65 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The URLs that have been visited in this session. The constellation records each page it
+//! navigates to, and layout tasks look links up here to match `:visited`.
+
+use extra::arc::RWArc;
+use extra::url::Url;
+use std::hashmap::HashSet;
+
+/// A handle to the set of visited URLs. Clones share the same set.
+#[deriving(Clone)]
+pub struct VisitedUrls {
+    priv urls: RWArc<HashSet<~str>>,
+}
+
+impl VisitedUrls {
+    pub fn new() -> VisitedUrls {
+        VisitedUrls {
+            urls: RWArc::new(HashSet::new()),
+        }
+    }
+
+    /// Records a visit to `url`.
+    pub fn add(&self, url: &Url) {
+        let key = key_for_url(url);
+        self.urls.write(|urls| {
+            urls.insert(key.clone());
+        })
+    }
+
+    /// Returns true if `url` has been visited. Fragments are ignored, so a link to any part of a
+    /// visited page counts as visited.
+    pub fn contains(&self, url: &Url) -> bool {
+        let key = key_for_url(url);
+        self.urls.read(|urls| urls.contains(&key))
+    }
+}
+
+fn key_for_url(url: &Url) -> ~str {
+    let mut url = url.clone();
+    url.fragment = None;
+    url.to_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VisitedUrls;
+    use extra::url;
+
+    #[test]
+    fn test_visited_urls() {
+        let visited_urls = VisitedUrls::new();
+        let page = url::from_str("http://example.com/index.html").unwrap();
+        let anchor = url::from_str("http://example.com/index.html#top").unwrap();
+        let other = url::from_str("http://example.com/other.html").unwrap();
+        assert!(!visited_urls.contains(&page));
+
+        visited_urls.clone().add(&page);
+        assert!(visited_urls.contains(&page));
+        assert!(visited_urls.contains(&anchor));
+        assert!(!visited_urls.contains(&other));
+    }
+}